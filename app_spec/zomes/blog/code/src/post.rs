@@ -159,6 +159,7 @@ mod tests {
             ],
             links_to: Vec::new(),
             sharing: Sharing::Public,
+            schema: None,
         };
         assert_eq!(
             expected_definition,
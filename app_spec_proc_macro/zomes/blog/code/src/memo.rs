@@ -100,6 +100,7 @@ mod tests {
             linked_from: vec![],
             links_to: Vec::new(),
             sharing: Sharing::Private,
+            schema: None,
         };
         assert_eq!(
             expected_definition,
@@ -158,6 +158,7 @@ mod tests {
             ],
             links_to: Vec::new(),
             sharing: Sharing::Public,
+            schema: None,
         };
         assert_eq!(
             expected_definition,
@@ -1,47 +1,76 @@
-use crate::error::DefaultResult;
+use crate::{error::DefaultResult, ChainExportFormat, ChainStorage};
 use colored::*;
+use holochain_conductor_lib::{
+    conductor::{Conductor, ConductorAdmin},
+    config::{default_persistence_dir, load_configuration, Configuration},
+    encrypted_storage::STORAGE_KEYS_FILE,
+};
 use holochain_core::{
     agent::{
+        chain_export::{ChainElement, ChainExport},
         chain_store::ChainStore,
-        state::{AgentState, AgentStateSnapshot},
+        state::AgentStateSnapshot,
     },
     content_store::GetContent,
 };
-use holochain_core_types::{chain_header::ChainHeader, entry::Entry};
+use holochain_core_types::{chain_header::ChainHeader, entry::Entry, error::HolochainError};
 use holochain_locksmith::RwLock;
-use holochain_persistence_api::cas::content::Address;
+use holochain_persistence_api::cas::{content::Address, storage::ContentAddressableStorage};
 use holochain_persistence_file::cas::file::FilesystemStorage;
-use std::{fs, path::PathBuf};
+use holochain_persistence_lmdb::cas::lmdb::LmdbStorage;
+use holochain_persistence_pickle::cas::pickle::PickleStorage;
+use serde_cbor;
+use serde_json;
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+};
 
-// TODO: use system-agnostic default path
-const DEFAULT_CHAIN_PATH: &str = "TODO";
+/// Where a conductor with the default `persistence_dir` keeps the storage of its instances
+fn default_chain_path() -> PathBuf {
+    default_persistence_dir().join("storage")
+}
 
-#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
-#[allow(clippy::toplevel_ref_arg)]
-pub fn chain_log(storage_path: Option<PathBuf>, instance_id: String) -> DefaultResult<()> {
-    let storage_path = storage_path.ok_or_else(|| {
-        format_err!("Please specify the path to CAS storage with the --path option.")
-    })?;
+/// Opens the chain CAS of an instance, returning it, the top chain header and the CAS path
+fn open_chain(
+    storage_path: Option<PathBuf>,
+    storage: ChainStorage,
+    instance_id: &str,
+) -> DefaultResult<(ChainStore, Option<ChainHeader>, PathBuf)> {
+    let storage_path = storage_path.unwrap_or_else(default_chain_path);
     let cas_path = storage_path.join(instance_id).join("cas");
-    let chain_store = ChainStore::new(std::sync::Arc::new(RwLock::new(
-        FilesystemStorage::new(cas_path.clone()).expect("Could not create chain store"),
-    )));
+    ensure!(
+        cas_path.is_dir(),
+        "Could not find chain storage at '{}'",
+        cas_path.to_string_lossy()
+    );
+    ensure!(
+        !storage_path
+            .join(instance_id)
+            .join(STORAGE_KEYS_FILE)
+            .exists(),
+        "Chain storage at '{}' is encrypted and can only be read by its conductor",
+        cas_path.to_string_lossy()
+    );
+    let cas: Arc<RwLock<dyn ContentAddressableStorage>> = match storage {
+        ChainStorage::File => Arc::new(RwLock::new(
+            FilesystemStorage::new(cas_path.clone()).map_err(HolochainError::from)?,
+        )),
+        ChainStorage::Pickle => Arc::new(RwLock::new(PickleStorage::new(&cas_path))),
+        ChainStorage::Lmdb => Arc::new(RwLock::new(LmdbStorage::new(&cas_path, None))),
+    };
+    let chain_store = ChainStore::new(cas);
 
-    let agent = chain_store
+    let top_header = chain_store
         .get_raw(&Address::from("AgentState"))?
         .ok_or("Chain does not exist or has not been initialized")
         .and_then(|snapshot_json| {
             AgentStateSnapshot::from_json_str(&snapshot_json.to_string())
                 .map_err(|_| "AgentState is malformed")
         })
-        .map(|snapshot| {
-            let top_header = snapshot.top_chain_header().to_owned();
-            AgentState::new_with_top_chain_header(
-                chain_store.clone(),
-                top_header.cloned(),
-                Address::new(),
-            )
-        })
+        .map(|snapshot| snapshot.top_chain_header().cloned())
         .map_err(|err| {
             format_err!(
                 "Could not display chain for '{}': {}",
@@ -49,12 +78,32 @@ pub fn chain_log(storage_path: Option<PathBuf>, instance_id: String) -> DefaultR
                 err.to_string()
             )
         })?;
+    Ok((chain_store, top_header, cas_path))
+}
+
+fn read_chain(
+    storage_path: Option<PathBuf>,
+    storage: ChainStorage,
+    instance_id: &str,
+) -> DefaultResult<ChainExport> {
+    let (chain_store, top_header, _) = open_chain(storage_path, storage, instance_id)?;
+    Ok(ChainExport::from_chain_store(&chain_store, &top_header)?)
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+#[allow(clippy::toplevel_ref_arg)]
+pub fn chain_log(
+    storage_path: Option<PathBuf>,
+    storage: ChainStorage,
+    instance_id: String,
+) -> DefaultResult<()> {
+    let (chain_store, top_header, cas_path) = open_chain(storage_path, storage, &instance_id)?;
 
     println!(
         "\nChain entries for '{}' (latest on top):\n",
         cas_path.to_string_lossy()
     );
-    for ref header in agent.iter_chain() {
+    for ref header in chain_store.iter(&top_header) {
         let entry = chain_store
             .get(header.entry_address())
             .expect("Panic while fetching from CAS!")
@@ -71,15 +120,150 @@ pub fn chain_log(storage_path: Option<PathBuf>, instance_id: String) -> DefaultR
     Ok(())
 }
 
+/// Writes all headers and entries of a chain, oldest first, to `output` or stdout
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn chain_export(
+    storage_path: Option<PathBuf>,
+    storage: ChainStorage,
+    instance_id: String,
+    output: Option<PathBuf>,
+    format: ChainExportFormat,
+) -> DefaultResult<()> {
+    let export = read_chain(storage_path, storage, &instance_id)?;
+    let bytes = encode_export(&export, format)?;
+    match output {
+        Some(path) => {
+            fs::write(&path, bytes)?;
+            eprintln!(
+                "Exported {} headers of '{}' to {}",
+                export.elements.len(),
+                instance_id,
+                path.to_string_lossy()
+            );
+        }
+        None => io::stdout().write_all(&bytes)?,
+    }
+    Ok(())
+}
+
+fn encode_export(export: &ChainExport, format: ChainExportFormat) -> DefaultResult<Vec<u8>> {
+    Ok(match format {
+        ChainExportFormat::Json => (serde_json::to_string_pretty(export)? + "\n").into_bytes(),
+        ChainExportFormat::Cbor => serde_cbor::to_vec(export)?,
+    })
+}
+
+fn decode_export(bytes: &[u8], format: ChainExportFormat) -> DefaultResult<ChainExport> {
+    Ok(match format {
+        ChainExportFormat::Json => serde_json::from_slice(bytes)?,
+        ChainExportFormat::Cbor => serde_cbor::from_slice(bytes)?,
+    })
+}
+
+/// Boots the conductor configured in `config_path` and imports an exported chain into one
+/// of its instances, which has to be fresh and of the same DNA and agent
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn chain_import(
+    config_path: PathBuf,
+    instance_id: String,
+    input: PathBuf,
+    format: ChainExportFormat,
+) -> DefaultResult<()> {
+    let chain = decode_export(&fs::read(&input)?, format)?;
+    let config = load_configuration::<Configuration>(&fs::read_to_string(&config_path)?)?;
+    config
+        .check_consistency(&mut Arc::new(Box::new(Conductor::load_dna)))
+        .map_err(HolochainError::ConfigError)?;
+
+    let mut conductor = Conductor::from_config(config);
+    conductor
+        .boot_from_config()
+        .map_err(|err| format_err!("{}", err))?;
+    conductor.import_chain(&instance_id, chain)?;
+
+    println!(
+        "{} chain of '{}' from {}",
+        "Imported".green().bold(),
+        instance_id,
+        input.to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Checks header links, entry hashes and signatures of a chain and fails if any are broken
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn chain_verify(
+    storage_path: Option<PathBuf>,
+    storage: ChainStorage,
+    instance_id: String,
+) -> DefaultResult<()> {
+    let export = read_chain(storage_path, storage, &instance_id)?;
+    let problems = export.verify();
+    for problem in &problems {
+        println!("{} {}", "Problem:".red().bold(), problem);
+    }
+    ensure!(
+        problems.is_empty(),
+        "Chain of '{}' has {} problem(s)",
+        instance_id,
+        problems.len()
+    );
+    println!(
+        "{} chain of '{}' with {} headers is intact",
+        "Verified:".green().bold(),
+        instance_id,
+        export.elements.len()
+    );
+    Ok(())
+}
+
+/// Prints the headers two chains have in common and those that only one of them has
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn chain_diff(
+    storage_path: Option<PathBuf>,
+    storage: ChainStorage,
+    instance_id: String,
+    other_storage_path: Option<PathBuf>,
+    other_instance_id: String,
+) -> DefaultResult<()> {
+    let other_storage_path = other_storage_path.or_else(|| storage_path.clone());
+    let chain = read_chain(storage_path, storage, &instance_id)?;
+    let other_chain = read_chain(other_storage_path, storage, &other_instance_id)?;
+    let common = chain.common_length(&other_chain);
+
+    println!(
+        "'{}' and '{}' have the first {} headers in common",
+        instance_id, other_instance_id, common
+    );
+    for (id, elements) in &[
+        (&instance_id, &chain.elements[common..]),
+        (&other_instance_id, &other_chain.elements[common..]),
+    ] {
+        if !elements.is_empty() {
+            println!("\nOnly in '{}':", id);
+            elements.iter().for_each(display_element);
+        }
+    }
+    Ok(())
+}
+
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
-pub fn chain_list(path: Option<PathBuf>) {
-    let path = path.unwrap_or_else(|| PathBuf::from(DEFAULT_CHAIN_PATH));
+pub fn chain_list(path: Option<PathBuf>) -> DefaultResult<()> {
+    let path = path.unwrap_or_else(default_chain_path);
+    let entries = fs::read_dir(&path).map_err(|e| {
+        format_err!(
+            "Could not list instances in '{}': {}. Specify the storage directory with --path.",
+            path.to_string_lossy(),
+            e
+        )
+    })?;
     println!("Please specify an instance ID to view its chain.");
     println!("Available instances for '{}':\n", path.to_string_lossy());
-    for entry in fs::read_dir(path).unwrap() {
-        let name = entry.unwrap().file_name();
+    for entry in entries {
+        let name = entry?.file_name();
         println!("- {}", name.to_string_lossy());
     }
+    Ok(())
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
@@ -92,3 +276,78 @@ fn display_header(header: &ChainHeader, entry: &Entry) {
     );
     println!("{:#?}", entry);
 }
+
+fn display_element(element: &ChainElement) {
+    println!(
+        "{} {} {:?}",
+        element.header.timestamp().to_string().bright_black(),
+        element.header_address.to_string().yellow(),
+        element.header.entry_type(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_core_types::{chain_header::test_chain_header, entry::test_entry};
+    use holochain_persistence_api::cas::content::AddressableContent;
+    use tempfile;
+
+    #[test]
+    fn encodes_and_decodes_exports_as_json_and_cbor() {
+        let header = test_chain_header();
+        let export = ChainExport {
+            elements: vec![ChainElement {
+                header_address: header.address(),
+                header,
+                entry: Some(test_entry()),
+            }],
+        };
+
+        let json = encode_export(&export, ChainExportFormat::Json).unwrap();
+        assert_eq!(
+            decode_export(&json, ChainExportFormat::Json).unwrap(),
+            export
+        );
+
+        let cbor = encode_export(&export, ChainExportFormat::Cbor).unwrap();
+        assert_eq!(
+            decode_export(&cbor, ChainExportFormat::Cbor).unwrap(),
+            export
+        );
+        assert!(cbor.len() < json.len());
+    }
+
+    #[test]
+    fn fails_without_chain_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_chain(None, ChainStorage::File, "instance").is_err());
+        assert!(read_chain(
+            Some(dir.path().to_path_buf()),
+            ChainStorage::Lmdb,
+            "instance"
+        )
+        .is_err());
+
+        fs::create_dir_all(dir.path().join("instance").join("cas")).unwrap();
+        fs::write(dir.path().join("instance").join(STORAGE_KEYS_FILE), "{}").unwrap();
+        let result = read_chain(
+            Some(dir.path().to_path_buf()),
+            ChainStorage::File,
+            "instance",
+        );
+        assert!(format!("{:?}", result).contains("encrypted"));
+    }
+
+    #[test]
+    fn defaults_to_the_conductor_storage_directory() {
+        assert_eq!(
+            default_chain_path(),
+            default_persistence_dir().join("storage")
+        );
+        let dir = tempfile::tempdir().unwrap();
+        assert!(chain_list(Some(dir.path().join("missing"))).is_err());
+        fs::create_dir_all(dir.path().join("instance")).unwrap();
+        assert!(chain_list(Some(dir.path().to_path_buf())).is_ok());
+    }
+}
@@ -1,15 +1,39 @@
 use error::DefaultResult;
 use holochain_common::paths::keys_directory;
 use holochain_conductor_lib::{key_loaders::mock_passphrase_manager, keystore::Keystore};
+use holochain_dpki::{
+    seed::{MnemonicableSeed, RootSeed, SeedTrait},
+    shamir::SeedShare,
+    utils::zero_string,
+};
 use rpassword;
 use std::{
     fs::create_dir_all,
-    io::{self, Write},
+    io::{self, BufRead, Write},
     path::PathBuf,
 };
 
+/// reads the 24 word mnemonic of a root seed from stdin without echoing it, to
+/// restore a keystore from
+pub fn read_mnemonic(quiet: bool) -> DefaultResult<String> {
+    if !quiet {
+        print!("Please enter the 24 word mnemonic of the keystore to restore: ");
+        io::stdout().flush()?;
+    }
+    Ok(rpassword::read_password()?)
+}
+
+/// creates a keystore, or recreates one from the mnemonic of its root seed when
+/// `restore_mnemonic` is given; with `show_mnemonic` the root seed's mnemonic is
+/// printed so the keystore can be restored later
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
-pub fn keygen(path: Option<PathBuf>, passphrase: Option<String>, quiet: bool) -> DefaultResult<()> {
+pub fn keygen(
+    path: Option<PathBuf>,
+    passphrase: Option<String>,
+    quiet: bool,
+    show_mnemonic: bool,
+    restore_mnemonic: Option<String>,
+) -> DefaultResult<()> {
     let passphrase = passphrase.unwrap_or_else(|| {
         if !quiet {
             println!(
@@ -41,7 +65,23 @@ when unlocking the keybundle to use within a Holochain conductor."
     if !quiet {
         println!("Generating keystore (this will take a few moments)...");
     }
-    let (keystore, pub_key) = Keystore::new_standalone(mock_passphrase_manager(passphrase), None)?;
+    let (mut keystore, pub_key) = match restore_mnemonic {
+        Some(mut mnemonic) => {
+            let restored = Keystore::new_standalone_from_mnemonic(
+                mock_passphrase_manager(passphrase),
+                &mnemonic,
+                None,
+            );
+            zero_string(&mut mnemonic);
+            restored?
+        }
+        None => Keystore::new_standalone(mock_passphrase_manager(passphrase), None)?,
+    };
+    let mnemonic = if show_mnemonic {
+        Some(keystore.get_root_seed_mnemonic()?)
+    } else {
+        None
+    };
 
     let path = if None == path {
         let p = keys_directory();
@@ -57,6 +97,9 @@ when unlocking the keybundle to use within a Holochain conductor."
     if quiet {
         println!("{}", pub_key);
         println!("{}", path_str);
+        if let Some(mnemonic) = &mnemonic {
+            println!("{}", mnemonic);
+        }
     } else {
         println!();
         println!("Succesfully created new agent keystore.");
@@ -65,10 +108,130 @@ when unlocking the keybundle to use within a Holochain conductor."
         println!("Keystore written to: {}", path_str);
         println!();
         println!("You can set this file in a conductor config as keystore_file for an agent.");
+        if let Some(mnemonic) = &mnemonic {
+            println!();
+            println!("Root seed mnemonic: {}", mnemonic);
+            println!();
+            println!("Anyone with these 24 words can recreate this agent's keys, write them down");
+            println!("and keep them safe. Restore the keystore with `hc keygen --restore`.");
+        }
+    }
+    if let Some(mut mnemonic) = mnemonic {
+        zero_string(&mut mnemonic);
     }
     Ok(())
 }
 
+/// splits the root seed of the keystore at `path` into `shares` mnemonics,
+/// any `threshold` of which recreate it with [keygen_combine]
+pub fn split_keystore(
+    path: PathBuf,
+    passphrase: String,
+    threshold: u8,
+    shares: u8,
+) -> DefaultResult<Vec<String>> {
+    let mut keystore = Keystore::new_from_file(path, mock_passphrase_manager(passphrase), None)?;
+    let mnemonics = keystore
+        .get_root_seed()?
+        .split(threshold, shares)?
+        .into_iter()
+        .map(|mut share| share.get_mnemonic())
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(mnemonics)
+}
+
+/// prints the shares [split_keystore] splits the keystore at `path` into
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn keygen_split(
+    path: PathBuf,
+    passphrase: Option<String>,
+    threshold: u8,
+    shares: u8,
+    quiet: bool,
+) -> DefaultResult<()> {
+    let passphrase = passphrase.unwrap_or_else(|| {
+        if !quiet {
+            print!("Passphrase of {}: ", path.display());
+            io::stdout().flush().expect("Could not flush stdout");
+        }
+        rpassword::read_password().unwrap()
+    });
+    let mut mnemonics = split_keystore(path, passphrase, threshold, shares)?;
+
+    if quiet {
+        for mnemonic in &mnemonics {
+            println!("{}", mnemonic);
+        }
+    } else {
+        println!();
+        println!(
+            "Split the keystore's root seed into {} shares, any {} of which recreate it.",
+            shares, threshold
+        );
+        println!("Give each share to a different person and keep them apart.");
+        println!("Recreate the keystore with `hc keygen combine`.");
+        for (index, mnemonic) in mnemonics.iter().enumerate() {
+            println!();
+            println!("Share {}: {}", index + 1, mnemonic);
+        }
+    }
+    mnemonics.iter_mut().for_each(zero_string);
+    Ok(())
+}
+
+/// reads seed share mnemonics from stdin without echoing them, one per line, until
+/// there are as many as the shares' threshold or an empty line is entered
+pub fn read_seed_shares(quiet: bool) -> DefaultResult<Vec<String>> {
+    if !quiet {
+        println!("Please enter the seed shares, one per line:");
+    }
+    let mut mnemonics = Vec::new();
+    let mut threshold = None;
+    while threshold.map_or(true, |needed| mnemonics.len() < needed as usize) {
+        if !quiet {
+            print!("Share {}: ", mnemonics.len() + 1);
+            io::stdout().flush()?;
+        }
+        let mnemonic = rpassword::read_password()?;
+        if mnemonic.trim().is_empty() {
+            break;
+        }
+        let share = SeedShare::new_with_mnemonic(&mnemonic);
+        mnemonics.push(mnemonic);
+        match share {
+            Ok(share) => {
+                threshold.get_or_insert(share.threshold);
+            }
+            Err(error) => {
+                mnemonics.iter_mut().for_each(zero_string);
+                return Err(error.into());
+            }
+        }
+    }
+    Ok(mnemonics)
+}
+
+/// recreates a keystore from the mnemonics of enough of its root seed's shares,
+/// as [keygen] does from the mnemonic of the root seed itself. The share mnemonics
+/// are zeroed once they are read, and [keygen] zeroes the recombined mnemonic
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn keygen_combine(
+    path: Option<PathBuf>,
+    passphrase: Option<String>,
+    quiet: bool,
+    mut share_mnemonics: Vec<String>,
+) -> DefaultResult<()> {
+    let shares = share_mnemonics
+        .iter()
+        .map(|mnemonic| SeedShare::new_with_mnemonic(mnemonic.as_str()))
+        .collect::<Result<Vec<SeedShare>, _>>();
+    share_mnemonics.iter_mut().for_each(zero_string);
+    let mnemonic = RootSeed::new_from_shares(&mut shares?)?
+        .seed_mut()
+        .get_mnemonic()?;
+    keygen(path, passphrase, quiet, false, Some(mnemonic))
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -83,7 +246,14 @@ pub mod test {
         let path = PathBuf::new().join("test.key");
         let passphrase = String::from("secret");
 
-        keygen(Some(path.clone()), Some(passphrase.clone()), true).expect("Keygen should work");
+        keygen(
+            Some(path.clone()),
+            Some(passphrase.clone()),
+            true,
+            false,
+            None,
+        )
+        .expect("Keygen should work");
 
         let mut keystore =
             Keystore::new_from_file(path.clone(), mock_passphrase_manager(passphrase), None)
@@ -95,4 +265,104 @@ pub mod test {
 
         let _ = remove_file(path);
     }
+
+    #[test]
+    fn keygen_restore_roundtrip() {
+        let path = PathBuf::new().join("test_restore.key");
+        let passphrase = String::from("secret");
+        keygen(
+            Some(path.clone()),
+            Some(passphrase.clone()),
+            true,
+            false,
+            None,
+        )
+        .expect("Keygen should work");
+        let mut keystore =
+            Keystore::new_from_file(path.clone(), mock_passphrase_manager(passphrase), None)
+                .unwrap();
+        let mnemonic = keystore.get_root_seed_mnemonic().unwrap();
+        let _ = remove_file(path.clone());
+
+        let passphrase = String::from("another secret");
+        keygen(
+            Some(path.clone()),
+            Some(passphrase.clone()),
+            true,
+            true,
+            Some(mnemonic.clone()),
+        )
+        .expect("Restoring should work");
+        let mut restored =
+            Keystore::new_from_file(path.clone(), mock_passphrase_manager(passphrase), None)
+                .unwrap();
+        assert_eq!(mnemonic, restored.get_root_seed_mnemonic().unwrap());
+        let mut keybundle = keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID).unwrap();
+        let mut restored_keybundle = restored.get_keybundle(PRIMARY_KEYBUNDLE_ID).unwrap();
+        assert_eq!(keybundle.get_id(), restored_keybundle.get_id());
+        assert!(keybundle
+            .sign_keys
+            .is_same(&mut restored_keybundle.sign_keys));
+
+        let _ = remove_file(path);
+    }
+
+    #[test]
+    fn keygen_split_and_combine_roundtrip() {
+        let path = PathBuf::new().join("test_split.key");
+        let passphrase = String::from("secret");
+        keygen(
+            Some(path.clone()),
+            Some(passphrase.clone()),
+            true,
+            false,
+            None,
+        )
+        .expect("Keygen should work");
+        let mut keystore = Keystore::new_from_file(
+            path.clone(),
+            mock_passphrase_manager(passphrase.clone()),
+            None,
+        )
+        .unwrap();
+        let mnemonic = keystore.get_root_seed_mnemonic().unwrap();
+        let shares =
+            split_keystore(path.clone(), passphrase.clone(), 2, 3).expect("Splitting should work");
+        assert_eq!(3, shares.len());
+        let _ = remove_file(path.clone());
+
+        // any two of the shares recreate the keystore, one alone doesn't
+        assert!(keygen_combine(
+            Some(path.clone()),
+            Some(passphrase.clone()),
+            true,
+            shares[..1].to_vec()
+        )
+        .is_err());
+        keygen_combine(
+            Some(path.clone()),
+            Some(passphrase.clone()),
+            true,
+            vec![shares[0].clone(), shares[2].clone()],
+        )
+        .expect("Combining should work");
+        let mut combined = Keystore::new_from_file(
+            path.clone(),
+            mock_passphrase_manager(passphrase.clone()),
+            None,
+        )
+        .unwrap();
+        assert_eq!(mnemonic, combined.get_root_seed_mnemonic().unwrap());
+        let _ = remove_file(path.clone());
+
+        let shares = shares[1..].to_vec();
+        keygen_combine(Some(path.clone()), Some(passphrase.clone()), true, shares)
+            .expect("Combining should work");
+        let mut combined =
+            Keystore::new_from_file(path.clone(), mock_passphrase_manager(passphrase), None)
+                .unwrap();
+        assert_eq!(mnemonic, combined.get_root_seed_mnemonic().unwrap());
+
+        let _ = remove_file(path);
+    }
 }
@@ -4,18 +4,21 @@ mod hash_dna;
 pub mod init;
 mod keygen;
 pub mod package;
+mod reproducible;
 pub mod run;
 mod sim2h_client;
+mod storage;
 pub mod test;
 
 pub use self::{
-    chain_log::{chain_list, chain_log},
+    chain_log::{chain_diff, chain_export, chain_import, chain_list, chain_log, chain_verify},
     generate::generate,
     hash_dna::hash_dna,
     init::init,
-    keygen::keygen,
-    package::package,
+    keygen::{keygen, keygen_combine, keygen_split, read_mnemonic, read_seed_shares},
+    package::{package, verify_package},
     run::{get_interface_type_string, hc_run_bundle_configuration, hc_run_configuration, run},
     sim2h_client::sim2h_client,
+    storage::storage_rekey,
     test::{test, TEST_DIR_NAME},
 };
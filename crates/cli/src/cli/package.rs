@@ -1,17 +1,31 @@
-use crate::{config_files::Build, error::DefaultResult, util};
+use crate::{
+    cli::reproducible::{
+        differences, strip_custom_sections, toolchain_versions, zome_hashes, DnaLock,
+        DNA_LOCK_FILE_NAME,
+    },
+    config_files::Build,
+    error::DefaultResult,
+    util,
+};
 use base64;
 use colored::*;
+use holochain_conductor_lib::{
+    key_loaders::mock_passphrase_manager,
+    keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
+};
 use holochain_core::wasm_engine::{run_dna, WasmCallData};
 use holochain_core_types::dna::Dna;
+use holochain_dpki::{dna_signature::DnaAuthor, key_bundle::KeyBundle};
 use holochain_json_api::json::JsonString;
 use holochain_persistence_api::cas::content::AddressableContent;
 use ignore::WalkBuilder;
 use json_patch::merge;
+use rpassword;
 use serde_json::{self, Map, Value};
 use std::{
     convert::TryFrom,
     fs::{self, File},
-    io::Read,
+    io::{self, Read, Write},
     path::PathBuf,
     sync::Arc,
 };
@@ -46,35 +60,123 @@ fn hdk_version_compare(hdk_version: &HDKVersion, cargo_toml: &str) -> DefaultRes
     Ok(hdk_version == &hdk_version_from_toml)
 }
 
-struct Packager {}
+struct Packager {
+    /// sort files and strip wasm custom sections so builds of the same sources match
+    reproducible: bool,
+}
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
 impl Packager {
-    fn new() -> Packager {
-        Packager {}
+    fn new(reproducible: bool) -> Packager {
+        Packager { reproducible }
     }
 
-    pub fn package(output: PathBuf, properties: Value) -> DefaultResult<()> {
+    fn check_for_cargo() -> DefaultResult<bool> {
         // First, check whether they have `cargo` installed, since it will be needed for packaging
         // TODO: in the future, don't check for this here, since other build tools and languages
         // could be used
-        let should_continue = util::check_for_cargo(
+        util::check_for_cargo(
             "Compiling a Rust based Zome to WASM depends on having Rust installed.",
             Some(vec![
                 "Compiling to WASM also requires adding WASM as a compile target.",
                 "Make sure to be running inside a nix-shell or from a nix-env installation.",
                 "See https://docs.holochain.love for more information.",
             ]),
-        )?;
-        if !should_continue {
+        )
+    }
+
+    pub fn package(
+        output: PathBuf,
+        properties: Value,
+        signer: Option<KeyBundle>,
+        reproducible: bool,
+    ) -> DefaultResult<()> {
+        if !Packager::check_for_cargo()? {
             // early exit, but user will have received feedback within check_for_cargo about why
             return Ok(());
         }
 
-        Packager::new().run(&output, properties)
+        Packager::new(reproducible).run(&output, properties, signer)
+    }
+
+    pub fn verify(dna_file: PathBuf, properties: Value) -> DefaultResult<()> {
+        if !Packager::check_for_cargo()? {
+            return Ok(());
+        }
+
+        let packaged = Dna::try_from(JsonString::from_json(&fs::read_to_string(&dna_file)?))
+            .map_err(|e| format_err!("Couldn't read DNA from {:?}: {}", dna_file, e))?;
+        let (_, rebuilt) = Packager::new(true).build(properties)?;
+
+        let lock_path = std::env::current_dir()?.join(DNA_LOCK_FILE_NAME);
+        if lock_path.exists() {
+            let lock = DnaLock::from_file(&lock_path)?;
+            for difference in differences(&lock.toolchain, &toolchain_versions()) {
+                eprintln!(
+                    "{} toolchain differs from {}: {}",
+                    "Warning:".yellow().bold(),
+                    DNA_LOCK_FILE_NAME,
+                    difference
+                );
+            }
+        }
+
+        if packaged.address() != rebuilt.address() {
+            bail!(
+                "{:?} does not match its sources: its DNA hash is {} but the sources build {}\n{}",
+                dna_file,
+                packaged.address(),
+                rebuilt.address(),
+                differences(&zome_hashes(&packaged)?, &zome_hashes(&rebuilt)?).join("\n")
+            );
+        }
+
+        println!(
+            "{} {:?} matches its sources",
+            "Verified".green().bold(),
+            dna_file
+        );
+        println!("DNA hash: {}", rebuilt.address());
+        Ok(())
     }
 
-    fn run(&self, output: &PathBuf, mut properties: Value) -> DefaultResult<()> {
+    fn run(
+        &self,
+        output: &PathBuf,
+        properties: Value,
+        signer: Option<KeyBundle>,
+    ) -> DefaultResult<()> {
+        let (mut dir_obj_bundle, dna) = self.build(properties)?;
+
+        if let Some(mut keybundle) = signer {
+            add_author_signature(&mut dir_obj_bundle, &dna, &mut keybundle)?;
+            println!("DNA signed by: {}", keybundle.get_id());
+        }
+
+        let out_file = File::create(&output)
+            .map_err(|e| format_err!("Couldn't create DNA output file {:?}; {}", output, e))?;
+
+        serde_json::to_writer_pretty(&out_file, &(dir_obj_bundle))?;
+
+        // CLI feedback
+        println!(
+            "{} DNA package file at {:?}",
+            "Created".green().bold(),
+            output
+        );
+        println!("DNA hash: {}", dna.address());
+
+        if self.reproducible {
+            let lock_path = std::env::current_dir()?.join(DNA_LOCK_FILE_NAME);
+            DnaLock::new(dna.address().to_string(), &dna)?.write(&lock_path)?;
+            println!("{} {:?}", "Wrote".green().bold(), lock_path);
+        }
+
+        Ok(())
+    }
+
+    /// bundles the sources in the current directory into the JSON of the package and its DNA
+    fn build(&self, mut properties: Value) -> DefaultResult<(Value, Dna)> {
         let current_dir = std::env::current_dir()?;
         let dir_obj_bundle = Value::from(
             self.bundle_recurse(&current_dir)
@@ -106,20 +208,20 @@ impl Packager {
             )
         })?;
 
-        let out_file = File::create(&output)
-            .map_err(|e| format_err!("Couldn't create DNA output file {:?}; {}", output, e))?;
-
-        serde_json::to_writer_pretty(&out_file, &(dir_obj_bundle))?;
-
-        // CLI feedback
-        println!(
-            "{} DNA package file at {:?}",
-            "Created".green().bold(),
-            output
-        );
-        println!("DNA hash: {}", dna.address());
+        let schema_errors: Vec<String> = dna
+            .verify_entry_schemas()
+            .into_iter()
+            .filter_map(|result| result.err())
+            .map(|e| e.to_string())
+            .collect();
+        if !schema_errors.is_empty() {
+            return Err(format_err!(
+                "Invalid entry schemas in DNA:\n{}",
+                schema_errors.join("\n")
+            ));
+        }
 
-        Ok(())
+        Ok((dir_obj_bundle, dna))
     }
 
     fn bundle_recurse(&self, path: &PathBuf) -> DefaultResult<Object> {
@@ -129,10 +231,13 @@ impl Packager {
             .build()
             .skip(1);
 
-        let root: Vec<_> = root_dir
+        let mut root: Vec<_> = root_dir
             .filter_map(|e| e.ok())
             .map(|e| e.path().to_path_buf())
             .collect();
+        if self.reproducible {
+            root.sort();
+        }
 
         let root_json_files: Vec<&PathBuf> = root
             .iter()
@@ -177,11 +282,17 @@ impl Packager {
             if node.is_dir() {
                 // a folder within this folder has a .hcbuild in it, meaning this node
                 // should build the json and insert it for this zome
-                if let Some(dir_with_code) = node
+                let mut sub_dirs: Vec<PathBuf> = node
                     .read_dir()?
                     .filter_map(|e| e.ok())
                     .map(|e| e.path())
                     .filter(|path| path.is_dir())
+                    .collect();
+                if self.reproducible {
+                    sub_dirs.sort();
+                }
+                if let Some(dir_with_code) = sub_dirs
+                    .into_iter()
                     .find(|path| path.join(BUILD_CONFIG_FILE_NAME).exists())
                 {
                     let build = Build::from_file(dir_with_code.join(BUILD_CONFIG_FILE_NAME))?;
@@ -246,7 +357,10 @@ impl Packager {
                         });
                     }
 
-                    let wasm = build.run(&node)?;
+                    let mut wasm = build.run(&node)?;
+                    if self.reproducible {
+                        wasm = base64::encode(&strip_custom_sections(&base64::decode(&wasm)?)?);
+                    }
 
                     // here insert the wasm itself
                     main_tree.insert(file_name.clone(), json!({ "code": wasm }));
@@ -261,9 +375,65 @@ impl Packager {
     }
 }
 
+/// Signs the DNA in `bundle` with `keybundle`, filling in the signature of the author with
+/// that key in the bundle's `authors`, or of the first author without a key if there is
+/// none, or adding a new author named after the key.
+fn add_author_signature(
+    bundle: &mut Value,
+    dna: &Dna,
+    keybundle: &mut KeyBundle,
+) -> DefaultResult<()> {
+    let mut authors: Vec<DnaAuthor> = match bundle.get("authors") {
+        Some(authors) => serde_json::from_value(authors.clone())
+            .map_err(|e| format_err!("Invalid authors in DNA: {}", e))?,
+        None => Vec::new(),
+    };
+    let key = keybundle.get_id();
+    let position = authors
+        .iter()
+        .position(|author| author.public_key_source == key)
+        .or_else(|| {
+            authors
+                .iter()
+                .position(|author| author.public_key_source.is_empty())
+        });
+    let identifier = position
+        .map(|i| authors[i].identifier.clone())
+        .unwrap_or_else(|| key.clone());
+    let author = DnaAuthor::sign(identifier, dna, keybundle)?;
+    match position {
+        Some(i) => authors[i] = author,
+        None => authors.push(author),
+    }
+    bundle["authors"] = serde_json::to_value(authors)?;
+    Ok(())
+}
+
+/// Prompts for the passphrase of the keystore at `path` and unlocks its primary keybundle,
+/// to sign DNA packages with
+pub fn signing_keybundle(path: PathBuf) -> DefaultResult<KeyBundle> {
+    print!("Passphrase of {}: ", path.display());
+    io::stdout().flush()?;
+    let passphrase = rpassword::read_password()?;
+    let mut keystore = Keystore::new_from_file(path, mock_passphrase_manager(passphrase), None)?;
+    Ok(keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID)?)
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn package(
+    output: PathBuf,
+    properties: serde_json::Value,
+    signer: Option<KeyBundle>,
+    reproducible: bool,
+) -> DefaultResult<()> {
+    Packager::package(output, properties, signer, reproducible)
+}
+
+/// Rebuilds the DNA from the sources in the current directory, reproducibly, and checks
+/// that `dna_file` is what they build
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
-pub fn package(output: PathBuf, properties: serde_json::Value) -> DefaultResult<()> {
-    Packager::package(output, properties)
+pub fn verify_package(dna_file: PathBuf, properties: serde_json::Value) -> DefaultResult<()> {
+    Packager::verify(dna_file, properties)
 }
 
 #[cfg(test)]
@@ -303,6 +473,34 @@ mod tests {
         shared_space.close().unwrap();
     }
 
+    #[test]
+    fn add_author_signature_fills_in_authors() {
+        use holochain_dpki::utils::generate_random_seed_buf;
+
+        let dna = Dna::new();
+        let mut bundle = json!({
+            "name": "",
+            "authors": [{
+                "identifier": "Author Name <author@name.com>",
+                "public_key_source": "",
+                "signature": "",
+            }],
+        });
+        let mut keybundle = KeyBundle::new_from_seed_buf(&mut generate_random_seed_buf()).unwrap();
+        let mut other = KeyBundle::new_from_seed_buf(&mut generate_random_seed_buf()).unwrap();
+
+        add_author_signature(&mut bundle, &dna, &mut keybundle).unwrap();
+        add_author_signature(&mut bundle, &dna, &mut keybundle).unwrap();
+        add_author_signature(&mut bundle, &dna, &mut other).unwrap();
+
+        let authors: Vec<DnaAuthor> = serde_json::from_value(bundle["authors"].clone()).unwrap();
+        assert_eq!(authors.len(), 2);
+        assert_eq!(authors[0].identifier, "Author Name <author@name.com>");
+        assert_eq!(authors[0].public_key_source, keybundle.get_id());
+        assert_eq!(authors[1].identifier, other.get_id());
+        assert!(authors.iter().all(|author| author.verify(&dna).unwrap()));
+    }
+
     #[test]
     fn hdk_version_compare_test() {
         //compare same
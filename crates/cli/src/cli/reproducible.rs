@@ -0,0 +1,213 @@
+use error::DefaultResult;
+use holochain_core_types::{dna::Dna, error::HolochainError, hdk_version::HDK_VERSION};
+use lib3h_sodium::{hash::sha256, secbuf::SecBuf};
+use serde_json;
+use std::{collections::BTreeMap, fs, path::Path, process::Command};
+
+/// File written next to the sources by `hc package --reproducible`
+pub const DNA_LOCK_FILE_NAME: &str = "dna.lock";
+
+const WASM_HEADER_SIZE: usize = 8;
+const WASM_MAGIC: &[u8] = b"\0asm";
+const WASM_CUSTOM_SECTION_ID: u8 = 0;
+const SHA256_BYTES: usize = 32;
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// What a reproducible build of a DNA depends on, to check later builds against
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DnaLock {
+    pub dna_hash: String,
+    /// versions of the tools the wasm was built with
+    pub toolchain: BTreeMap<String, String>,
+    /// sha256 of the wasm of each zome
+    pub zomes: BTreeMap<String, String>,
+}
+
+impl DnaLock {
+    pub fn new(dna_hash: String, dna: &Dna) -> DefaultResult<Self> {
+        Ok(DnaLock {
+            dna_hash,
+            toolchain: toolchain_versions(),
+            zomes: zome_hashes(dna)?,
+        })
+    }
+
+    pub fn from_file(path: &Path) -> DefaultResult<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn write(&self, path: &Path) -> DefaultResult<()> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+}
+
+/// Removes all custom sections, such as `producers` and debug info, from a wasm module.
+/// They don't change what the code does but differ between toolchains and build paths.
+pub fn strip_custom_sections(wasm: &[u8]) -> DefaultResult<Vec<u8>> {
+    ensure!(
+        wasm.len() >= WASM_HEADER_SIZE && &wasm[0..4] == WASM_MAGIC,
+        "not a WebAssembly module"
+    );
+    let mut stripped = wasm[0..WASM_HEADER_SIZE].to_vec();
+    let mut position = WASM_HEADER_SIZE;
+    while position < wasm.len() {
+        let section_start = position;
+        let section_id = wasm[position];
+        let (size, size_length) = read_leb128_u32(&wasm[position + 1..])?;
+        let section_end = position + 1 + size_length + size as usize;
+        ensure!(section_end <= wasm.len(), "truncated WebAssembly section");
+        if section_id != WASM_CUSTOM_SECTION_ID {
+            stripped.extend_from_slice(&wasm[section_start..section_end]);
+        }
+        position = section_end;
+    }
+    Ok(stripped)
+}
+
+/// reads an unsigned LEB128 number, returning it and the number of bytes it took
+fn read_leb128_u32(bytes: &[u8]) -> DefaultResult<(u32, usize)> {
+    let mut value: u32 = 0;
+    for (i, byte) in bytes.iter().take(5).enumerate() {
+        value |= u32::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    bail!("invalid LEB128 number in WebAssembly module")
+}
+
+/// sha256 of the wasm of every zome, by zome name
+pub fn zome_hashes(dna: &Dna) -> DefaultResult<BTreeMap<String, String>> {
+    dna.zomes
+        .iter()
+        .map(|(name, zome)| Ok((name.clone(), sha256_hex(&zome.code.code)?)))
+        .collect()
+}
+
+fn sha256_hex(data: &[u8]) -> DefaultResult<String> {
+    if data.is_empty() {
+        return Ok(EMPTY_SHA256.to_string());
+    }
+    let mut input = SecBuf::with_insecure(data.len());
+    input.write(0, data).map_err(HolochainError::from)?;
+    let mut hash = SecBuf::with_insecure(SHA256_BYTES);
+    sha256(&mut input, &mut hash).map_err(HolochainError::from)?;
+    let hash = hash.read_lock();
+    Ok(hash.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// versions of hc, the HDK it was built for and the rust toolchain on this machine
+pub fn toolchain_versions() -> BTreeMap<String, String> {
+    let mut versions = BTreeMap::new();
+    versions.insert("hc".to_string(), env!("CARGO_PKG_VERSION").to_string());
+    versions.insert("hdk".to_string(), HDK_VERSION.to_string());
+    for tool in &["rustc", "cargo"] {
+        if let Some(version) = tool_version(tool) {
+            versions.insert(tool.to_string(), version);
+        }
+    }
+    versions
+}
+
+fn tool_version(tool: &str) -> Option<String> {
+    let output = Command::new(tool).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// describes every entry that is missing or different between `expected` and `actual`
+pub fn differences(
+    expected: &BTreeMap<String, String>,
+    actual: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut differences = Vec::new();
+    for (name, expected_value) in expected {
+        match actual.get(name) {
+            Some(actual_value) if actual_value == expected_value => {}
+            Some(actual_value) => differences.push(format!(
+                "{}: expected {}, found {}",
+                name, expected_value, actual_value
+            )),
+            None => differences.push(format!("{}: missing", name)),
+        }
+    }
+    for name in actual.keys().filter(|name| !expected.contains_key(*name)) {
+        differences.push(format!("{}: not expected", name));
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut section = vec![id, payload.len() as u8];
+        section.extend_from_slice(payload);
+        section
+    }
+
+    #[test]
+    fn strips_custom_sections_only() {
+        let header = b"\0asm\x01\0\0\0".to_vec();
+        let type_section = section(1, &[1, 0x60, 0, 0]);
+        let function_section = section(3, &[1, 0]);
+        let mut producers = vec![9];
+        producers.extend_from_slice(b"producers");
+        producers.extend_from_slice(b"rustc 1.2.3");
+
+        let mut wasm = header.clone();
+        wasm.extend(type_section.clone());
+        wasm.extend(section(0, &producers));
+        wasm.extend(function_section.clone());
+
+        let mut expected = header;
+        expected.extend(type_section);
+        expected.extend(function_section);
+        assert_eq!(strip_custom_sections(&wasm).unwrap(), expected);
+        assert_eq!(strip_custom_sections(&expected).unwrap(), expected);
+
+        assert!(strip_custom_sections(b"not wasm").is_err());
+        let mut truncated = expected.clone();
+        truncated.extend(&[1, 10, 0]);
+        assert!(strip_custom_sections(&truncated).is_err());
+    }
+
+    #[test]
+    fn reads_leb128() {
+        assert_eq!(read_leb128_u32(&[0x02]).unwrap(), (2, 1));
+        assert_eq!(
+            read_leb128_u32(&[0xe5, 0x8e, 0x26, 0xff]).unwrap(),
+            (624_485, 3)
+        );
+        assert!(read_leb128_u32(&[0x80, 0x80]).is_err());
+    }
+
+    #[test]
+    fn hashes_zomes_and_lists_differences() {
+        assert_eq!(
+            sha256_hex(b"abc").unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(sha256_hex(b"").unwrap(), EMPTY_SHA256);
+
+        let mut expected = BTreeMap::new();
+        expected.insert("a".to_string(), "1".to_string());
+        expected.insert("b".to_string(), "2".to_string());
+        let mut actual = BTreeMap::new();
+        actual.insert("a".to_string(), "1".to_string());
+        actual.insert("b".to_string(), "3".to_string());
+        actual.insert("c".to_string(), "4".to_string());
+        assert_eq!(differences(&expected, &expected), Vec::<String>::new());
+        assert_eq!(
+            differences(&expected, &actual),
+            vec![
+                "b: expected 2, found 3".to_string(),
+                "c: not expected".to_string()
+            ]
+        );
+    }
+}
@@ -30,7 +30,7 @@ pub fn run(
     conductor_config: Configuration,
 ) -> DefaultResult<()> {
     if package {
-        cli::package(dna_path, json!({}))?;
+        cli::package(dna_path, json!({}), None, false)?;
     }
 
     mount_conductor_from_config(conductor_config);
@@ -146,6 +146,9 @@ fn agent_configuration(agent_name: String) -> AgentConfiguration {
         keystore_file: agent_name,
         holo_remote_key: None,
         test_agent: Some(true),
+        revocation_key: None,
+        device_key: None,
+        keystore_socket: None,
     }
 }
 
@@ -181,6 +184,7 @@ fn storage_configuration(persist: bool) -> DefaultResult<StorageConfiguration> {
 
         Ok(StorageConfiguration::Pickle {
             path: LOCAL_STORAGE_PATH.into(),
+            encrypted: None,
         })
     } else {
         Ok(StorageConfiguration::Memory)
@@ -323,6 +327,9 @@ mod tests {
                 keystore_file: "testAgent".to_string(),
                 holo_remote_key: None,
                 test_agent: Some(true),
+                revocation_key: None,
+                device_key: None,
+                keystore_socket: None,
             },
         );
     }
@@ -359,7 +366,8 @@ mod tests {
         assert_eq!(
             persist_store,
             StorageConfiguration::Pickle {
-                path: ".hc".to_string()
+                path: ".hc".to_string(),
+                encrypted: None,
             }
         );
     }
@@ -0,0 +1,64 @@
+use crate::error::DefaultResult;
+use colored::*;
+use holochain_conductor_lib::{
+    conductor::Conductor,
+    config::{load_configuration, Configuration},
+};
+use std::{fs, path::PathBuf};
+
+/// Re-keys the encrypted storage of an instance configured in the conductor config at
+/// `config_path`. Unlocking the agent's keystore uses the config's passphrase service.
+/// Everything the storage holds gets re-encrypted with a new content key, so the
+/// instance must not be running.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
+pub fn storage_rekey(config_path: PathBuf, instance_id: String) -> DefaultResult<()> {
+    let config = load_configuration::<Configuration>(&fs::read_to_string(&config_path)?)?;
+    let mut conductor = Conductor::from_config(config);
+    let generation = conductor.rekey_instance_storage(&instance_id)?;
+    println!(
+        "{} storage of '{}', its keys are now locked with storage key generation {}",
+        "Re-keyed".green().bold(),
+        instance_id,
+        generation
+    );
+    println!("All content got re-encrypted with a new content key, the old keys are dropped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile;
+
+    #[test]
+    fn fails_for_unencrypted_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("conductor-config.toml");
+        fs::write(
+            &config_path,
+            r#"
+    persistence_dir = "."
+
+    [[agents]]
+    id = "test-agent"
+    name = "test agent"
+    public_address = "HcScic3VAmEP9ucmrw4MMFKVARIvvdn43k6MI5Ve3qgkbx4jbW3wJtN3ywgb4tz"
+    keystore_file = "test-agent.keystore"
+    test_agent = true
+
+    [[instances]]
+    id = "test-instance"
+    dna = "test-dna"
+    agent = "test-agent"
+        [instances.storage]
+        type = "memory"
+    "#,
+        )
+        .unwrap();
+
+        let result = storage_rekey(config_path.clone(), "test-instance".to_string());
+        assert!(format!("{:?}", result).contains("is not encrypted"));
+        let result = storage_rekey(config_path, "other-instance".to_string());
+        assert!(format!("{:?}", result).contains("not found"));
+    }
+}
@@ -53,7 +53,7 @@ pub fn test(
             "Packaging".green().bold(),
             &file_path
         );
-        package(file_path, json!({}))?;
+        package(file_path, json!({}), None, false)?;
     }
 
     // build tests
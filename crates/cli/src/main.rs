@@ -4,11 +4,14 @@ extern crate holochain_common;
 extern crate holochain_conductor_lib;
 extern crate holochain_core;
 extern crate holochain_core_types;
+extern crate holochain_dpki;
 extern crate holochain_json_api;
 extern crate holochain_locksmith;
 extern crate holochain_net;
 extern crate holochain_persistence_api;
 extern crate holochain_persistence_file;
+extern crate holochain_persistence_lmdb;
+extern crate holochain_persistence_pickle;
 extern crate json_patch;
 extern crate lib3h_crypto_api;
 extern crate lib3h_protocol;
@@ -22,6 +25,7 @@ extern crate serde_derive;
 extern crate base64;
 extern crate colored;
 extern crate semver;
+extern crate serde_cbor;
 #[macro_use]
 extern crate serde_json;
 extern crate dns_lookup;
@@ -56,6 +60,17 @@ enum Cli {
         output: Option<PathBuf>,
         #[structopt(long, short)]
         properties: Option<String>,
+        #[structopt(long, parse(from_os_str))]
+        /// Signs the DNA with the primary key of this keystore file, as one of its authors
+        sign: Option<PathBuf>,
+        #[structopt(long)]
+        /// Builds the same package from the same sources on any machine, and records the
+        /// zome hashes and toolchain versions in dna.lock
+        reproducible: bool,
+        #[structopt(long, parse(from_os_str))]
+        /// Instead of packaging, checks that this .dna.json is what the sources build
+        /// reproducibly
+        verify: Option<PathBuf>,
     },
     #[structopt(alias = "i")]
     /// Initializes a new Holochain app at the given directory
@@ -132,6 +147,14 @@ enum Cli {
         #[structopt(long, short)]
         /// Don't ask for passphrase
         nullpass: bool,
+        #[structopt(long = "show-mnemonic")]
+        /// Print the 24 word mnemonic of the keystore's root seed, to back it up with
+        show_mnemonic: bool,
+        #[structopt(long)]
+        /// Recreate a keystore from the 24 word mnemonic of its root seed, read from stdin
+        restore: bool,
+        #[structopt(subcommand)]
+        cmd: Option<KeyGenCommand>,
     },
     #[structopt(name = "chain")]
     /// View the contents of a source chain
@@ -140,11 +163,22 @@ enum Cli {
         /// Instance ID to view
         instance_id: Option<String>,
         #[structopt(long, short, parse(from_os_str))]
-        /// Location of chain storage
+        /// Location of chain storage [default: the storage directory in the default conductor persistence_dir]
         path: Option<PathBuf>,
         #[structopt(long, short)]
         /// List available instances
         list: bool,
+        #[structopt(long, short, default_value = "file", possible_values = &ChainStorage::variants(), case_insensitive = true)]
+        /// Type of the chain storage: file, pickle or lmdb
+        storage: ChainStorage,
+        #[structopt(subcommand)]
+        cmd: Option<ChainCommand>,
+    },
+    #[structopt(name = "storage")]
+    /// Manage the storage of conductor instances
+    Storage {
+        #[structopt(subcommand)]
+        cmd: StorageCommand,
     },
     #[structopt(name = "hash")]
     /// Parse and hash a DNA file to determine its unique network hash
@@ -172,6 +206,114 @@ enum Cli {
         files: bool,
     },
 }
+
+#[derive(StructOpt)]
+/// Shamir secret sharing of a keystore's root seed
+enum KeyGenCommand {
+    #[structopt(name = "split")]
+    /// Splits the root seed of a keystore into mnemonic shares, any threshold of which recreate it
+    Split {
+        #[structopt(long, short, parse(from_os_str))]
+        /// Path of the keystore file to split
+        path: PathBuf,
+        #[structopt(long, short)]
+        /// How many shares are needed to recreate the keystore
+        threshold: u8,
+        #[structopt(long, short)]
+        /// How many shares to make
+        shares: u8,
+        #[structopt(long, short)]
+        /// Only print the shares, one per line
+        quiet: bool,
+        #[structopt(long, short)]
+        /// Don't ask for passphrase
+        nullpass: bool,
+    },
+    #[structopt(name = "combine")]
+    /// Recreates a keystore from enough of its root seed's shares, read from stdin one per line
+    Combine {
+        #[structopt(long, short, parse(from_os_str))]
+        /// Specify path of file
+        path: Option<PathBuf>,
+        #[structopt(long, short)]
+        /// Only print machine-readable output; intended for use by programs and scripts
+        quiet: bool,
+        #[structopt(long, short)]
+        /// Don't ask for passphrase
+        nullpass: bool,
+    },
+}
+
+#[derive(StructOpt)]
+/// Exports, verifies, compares and imports source chains. All but import read the chain at the --path and --storage of `hc chain`
+enum ChainCommand {
+    #[structopt(name = "export")]
+    /// Exports all headers and entries of a source chain, with their signatures
+    Export {
+        #[structopt(name = "INSTANCE")]
+        /// Instance ID of the chain to export
+        instance_id: String,
+        #[structopt(long, short, parse(from_os_str))]
+        /// File to write the export to [default: stdout]
+        output: Option<PathBuf>,
+        #[structopt(long, short, default_value = "json", possible_values = &ChainExportFormat::variants(), case_insensitive = true)]
+        /// Format of the export: json or cbor
+        format: ChainExportFormat,
+    },
+    #[structopt(name = "verify")]
+    /// Checks the header links, entry hashes and signatures of a source chain
+    Verify {
+        #[structopt(name = "INSTANCE")]
+        /// Instance ID of the chain to verify
+        instance_id: String,
+    },
+    #[structopt(name = "import")]
+    /// Imports an exported chain into a fresh instance of the same DNA and agent, validating and publishing its entries
+    Import {
+        #[structopt(name = "INSTANCE")]
+        /// Instance ID to import the chain into
+        instance_id: String,
+        #[structopt(name = "FILE", parse(from_os_str))]
+        /// Exported chain to import
+        input: PathBuf,
+        #[structopt(long, short, parse(from_os_str))]
+        /// Conductor config file that configures the instance
+        config: PathBuf,
+        #[structopt(long, short, default_value = "json", possible_values = &ChainExportFormat::variants(), case_insensitive = true)]
+        /// Format of the exported chain: json or cbor
+        format: ChainExportFormat,
+    },
+    #[structopt(name = "diff")]
+    /// Shows where the source chains of two instances diverge
+    Diff {
+        #[structopt(name = "INSTANCE")]
+        /// Instance ID of the first chain
+        instance_id: String,
+        #[structopt(name = "OTHER_INSTANCE")]
+        /// Instance ID of the second chain
+        other_instance_id: String,
+        #[structopt(long, parse(from_os_str))]
+        /// Location of the second chain's storage [default: the --path of the first]
+        other_path: Option<PathBuf>,
+    },
+}
+
+#[derive(StructOpt)]
+/// Manage the storage of conductor instances
+enum StorageCommand {
+    #[structopt(name = "rekey")]
+    /// Re-encrypts an instance's encrypted storage with a new content key, unlocking the agent's
+    /// keystore. The instance must not be running
+    Rekey {
+        #[structopt(name = "INSTANCE")]
+        /// Instance ID of the storage to re-key
+        instance_id: String,
+        #[structopt(long, short, parse(from_os_str))]
+        /// Conductor config file that configures the instance
+        config: PathBuf,
+    },
+}
+
 arg_enum! {
     #[derive(Debug)]
     pub enum NetworkingType {
@@ -179,6 +321,23 @@ arg_enum! {
     }
 }
 
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ChainStorage {
+        File,
+        Pickle,
+        Lmdb,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ChainExportFormat {
+        Json,
+        Cbor,
+    }
+}
+
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CLI)]
 fn main() {
     lib3h_sodium::check_init();
@@ -200,6 +359,9 @@ fn run() -> HolochainResult<()> {
         Cli::Package {
             output,
             properties: properties_string,
+            sign,
+            reproducible,
+            verify,
         } => {
             let output = if let Some(output_inner) = output {
                 output_inner
@@ -211,11 +373,21 @@ fn run() -> HolochainResult<()> {
                 .map(|s| serde_json::Value::from_str(&s))
                 .unwrap_or_else(|| Ok(json!({})));
 
-            match properties {
-                Ok(properties) => {
-                    cli::package(output, properties).map_err(HolochainError::Default)?
+            let signer = match sign {
+                Some(keystore_path) => Some(
+                    cli::package::signing_keybundle(keystore_path)
+                        .map_err(HolochainError::Default)?,
+                ),
+                None => None,
+            };
+
+            match (properties, verify) {
+                (Ok(properties), Some(dna_file)) => {
+                    cli::verify_package(dna_file, properties).map_err(HolochainError::Default)?
                 }
-                Err(e) => {
+                (Ok(properties), None) => cli::package(output, properties, signer, reproducible)
+                    .map_err(HolochainError::Default)?,
+                (Err(e), _) => {
                     return Err(HolochainError::Default(format_err!(
                         "Failed to parse properties argument as JSON: {:?}",
                         e
@@ -297,35 +469,137 @@ fn run() -> HolochainResult<()> {
         }
         .map_err(HolochainError::Default)?,
 
+        Cli::KeyGen {
+            cmd:
+                Some(KeyGenCommand::Split {
+                    path,
+                    threshold,
+                    shares,
+                    quiet,
+                    nullpass,
+                }),
+            ..
+        } => {
+            let passphrase = if nullpass {
+                Some(String::from(holochain_common::DEFAULT_PASSPHRASE))
+            } else {
+                None
+            };
+            cli::keygen_split(path, passphrase, threshold, shares, quiet)
+                .map_err(|e| HolochainError::Default(format_err!("{}", e)))?
+        }
+        Cli::KeyGen {
+            cmd:
+                Some(KeyGenCommand::Combine {
+                    path,
+                    quiet,
+                    nullpass,
+                }),
+            ..
+        } => {
+            let shares = cli::read_seed_shares(quiet)
+                .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
+            let passphrase = if nullpass {
+                Some(String::from(holochain_common::DEFAULT_PASSPHRASE))
+            } else {
+                None
+            };
+            cli::keygen_combine(path, passphrase, quiet, shares)
+                .map_err(|e| HolochainError::Default(format_err!("{}", e)))?
+        }
         Cli::KeyGen {
             path,
             quiet,
             nullpass,
+            show_mnemonic,
+            restore,
+            cmd: None,
         } => {
+            let restore_mnemonic = if restore {
+                Some(
+                    cli::read_mnemonic(quiet)
+                        .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
+                )
+            } else {
+                None
+            };
             let passphrase = if nullpass {
                 Some(String::from(holochain_common::DEFAULT_PASSPHRASE))
             } else {
                 None
             };
-            cli::keygen(path, passphrase, quiet)
+            cli::keygen(path, passphrase, quiet, show_mnemonic, restore_mnemonic)
                 .map_err(|e| HolochainError::Default(format_err!("{}", e)))?
         }
 
+        Cli::ChainLog {
+            path,
+            storage,
+            cmd:
+                Some(ChainCommand::Export {
+                    instance_id,
+                    output,
+                    format,
+                }),
+            ..
+        } => cli::chain_export(path, storage, instance_id, output, format)
+            .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
+        Cli::ChainLog {
+            path,
+            storage,
+            cmd: Some(ChainCommand::Verify { instance_id }),
+            ..
+        } => cli::chain_verify(path, storage, instance_id)
+            .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
+        Cli::ChainLog {
+            cmd:
+                Some(ChainCommand::Import {
+                    instance_id,
+                    input,
+                    config,
+                    format,
+                }),
+            ..
+        } => cli::chain_import(config, instance_id, input, format)
+            .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
+        Cli::ChainLog {
+            path,
+            storage,
+            cmd:
+                Some(ChainCommand::Diff {
+                    instance_id,
+                    other_instance_id,
+                    other_path,
+                }),
+            ..
+        } => cli::chain_diff(path, storage, instance_id, other_path, other_instance_id)
+            .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
         Cli::ChainLog {
             instance_id,
             list,
             path,
+            storage,
+            cmd: None,
         } => match (list, instance_id) {
-            (true, _) => cli::chain_list(path),
+            (true, _) => cli::chain_list(path)
+                .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
             (false, None) => {
                 Cli::clap().print_help().expect("Couldn't print help!");
                 println!("\n\nTry `hc help chain` for more info");
             }
             (false, Some(instance_id)) => {
-                cli::chain_log(path, instance_id)
+                cli::chain_log(path, storage, instance_id)
                     .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
             }
         },
+        Cli::Storage {
+            cmd:
+                StorageCommand::Rekey {
+                    instance_id,
+                    config,
+                },
+        } => cli::storage_rekey(config, instance_id)
+            .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
         Cli::HashDna {
             path,
             property,
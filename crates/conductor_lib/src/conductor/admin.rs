@@ -7,7 +7,9 @@ use crate::{
     dpki_instance::DpkiInstance,
     keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
 };
+use holochain_core::{agent::chain_export::ChainExport, workflows::import_chain::import_chain};
 use holochain_core_types::error::HolochainError;
+use holochain_dpki::dna_signature::{find_trusted_author, read_dna_authors};
 use holochain_locksmith::RwLock;
 use holochain_persistence_api::{cas::content::AddressableContent, hash::HashString};
 
@@ -43,6 +45,11 @@ pub trait ConductorAdmin {
         storage: Option<&str>,
     ) -> Result<(), HolochainError>;
     fn remove_instance(&mut self, id: &String) -> Result<(), HolochainError>;
+    fn import_chain(
+        &mut self,
+        instance_id: &String,
+        chain: ChainExport,
+    ) -> Result<(), HolochainError>;
     fn add_interface(&mut self, new_instance: InterfaceConfiguration)
         -> Result<(), HolochainError>;
     fn remove_interface(&mut self, id: &String) -> Result<(), HolochainError>;
@@ -111,6 +118,22 @@ impl ConductorAdmin for Conductor {
             }
         }
 
+        if let Some(trusted_authors) = &self.config.trusted_dna_authors {
+            let authors = read_dna_authors(&fs::read_to_string(&path)?)?;
+            match find_trusted_author(&dna, &authors, trusted_authors)? {
+                Some(author) => notify(format!(
+                    "DNA {} is signed by trusted author {} ({})",
+                    path_string, author.identifier, author.public_key_source
+                )),
+                None => {
+                    return Err(HolochainError::ConfigError(format!(
+                        "DNA {} is not signed by a trusted author",
+                        path_string
+                    )))
+                }
+            }
+        }
+
         if let Some(props) = properties {
             if !copy {
                 return Err(HolochainError::ConfigError(
@@ -215,11 +238,18 @@ impl ConductorAdmin for Conductor {
         fs::create_dir_all(&storage_path)?;
         let storage_config = match storage {
             Some("memory") => StorageConfiguration::Memory,
-            Some("file") => StorageConfiguration::File { path: storage_path },
-            Some("pickle") => StorageConfiguration::Pickle { path: storage_path },
+            Some("file") => StorageConfiguration::File {
+                path: storage_path,
+                encrypted: None,
+            },
+            Some("pickle") => StorageConfiguration::Pickle {
+                path: storage_path,
+                encrypted: None,
+            },
             None | Some("lmdb") => StorageConfiguration::Lmdb {
                 path: storage_path,
                 initial_mmap_bytes: None,
+                encrypted: None,
             },
             Some(s) => {
                 return Err(HolochainError::ConfigError(format!(
@@ -276,6 +306,54 @@ impl ConductorAdmin for Conductor {
         Ok(())
     }
 
+    /// Replaces the genesis chain of the instance given by id with an exported chain of the
+    /// same DNA and agent, validating every entry and publishing them again.
+    /// The instance must not be serving zome calls. Importing needs its action loop and
+    /// network though, so an instance that got stopped is instantiated again first.
+    /// The instance is not started afterwards.
+    fn import_chain(
+        &mut self,
+        instance_id: &String,
+        chain: ChainExport,
+    ) -> Result<(), HolochainError> {
+        let instance = self.instances.get(instance_id).cloned().ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!(
+                "Instance with ID '{}' does not exist",
+                instance_id
+            ))
+        })?;
+        let running = {
+            let instance = instance.read().unwrap();
+            if instance.active() {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Instance '{}' has to be stopped to import a chain",
+                    instance_id
+                )));
+            }
+            instance.running()
+        };
+        if !running {
+            notify(format!(
+                "Instantiating the stopped instance \"{}\" again to import a chain...",
+                instance_id
+            ));
+            // release the storage of the stopped instance before opening it again,
+            // and keep the lock shared with the interfaces
+            instance.write().unwrap().kill();
+            let new_instance = self.instantiate_from_config(instance_id)?;
+            *instance.write().unwrap() = new_instance;
+        }
+        let context = instance.read().unwrap().context()?;
+        context.block_on(import_chain(&chain, &context))?;
+
+        notify(format!(
+            "Imported {} headers into the chain of instance \"{}\".",
+            chain.elements.len(),
+            instance_id
+        ));
+        Ok(())
+    }
+
     fn add_interface(&mut self, interface: InterfaceConfiguration) -> Result<(), HolochainError> {
         let mut new_config = self.config.clone();
         if new_config.interfaces.iter().any(|i| i.id == interface.id) {
@@ -490,6 +568,9 @@ impl ConductorAdmin for Conductor {
             keystore_file,
             holo_remote_key: holo_remote_key.map(|_| true),
             test_agent: None,
+            revocation_key: None,
+            device_key: None,
+            keystore_socket: None,
         };
 
         new_config.agents.push(new_agent);
@@ -630,6 +711,7 @@ pub mod tests {
     };
     use holochain_common::paths::DNA_EXTENSION;
     use holochain_core_types::dna::Dna;
+    use holochain_dpki::dna_signature::DnaAuthor;
     use holochain_json_api::json::JsonString;
     use std::{
         convert::TryFrom,
@@ -637,6 +719,7 @@ pub mod tests {
         fs::{remove_dir_all, File},
         io::Read,
     };
+    use tempfile::tempdir;
 
     pub fn test_dna_loader() -> DnaLoader {
         let loader = Box::new(|_: &PathBuf| {
@@ -981,6 +1064,64 @@ id = 'new-dna'"#,
         );
     }
 
+    #[test]
+    fn test_install_dna_requires_trusted_author() {
+        let test_name = "test_install_dna_requires_trusted_author";
+        let mut conductor = create_test_conductor(test_name, 3000);
+        let dna = Dna::try_from(JsonString::from_json(&example_dna_string())).unwrap();
+        let author = DnaAuthor::sign("Tester".to_string(), &dna, &mut test_keybundle(1)).unwrap();
+        let dir = tempdir().unwrap();
+        let write_package = |name: &str, authors: Vec<DnaAuthor>| {
+            let mut package: serde_json::Value =
+                serde_json::from_str(&example_dna_string()).unwrap();
+            package["authors"] = json!(authors);
+            let path = dir.path().join(name);
+            fs::write(&path, package.to_string()).unwrap();
+            path
+        };
+        let signed = write_package("signed.dna.json", vec![author]);
+        let unsigned = write_package("unsigned.dna.json", Vec::new());
+
+        conductor.config.trusted_dna_authors = Some(vec![test_keybundle(2).get_id()]);
+        assert_eq!(
+            conductor.install_dna_from_file(
+                signed.clone(),
+                String::from("signed-dna"),
+                false,
+                None,
+                None,
+                None,
+            ),
+            Err(HolochainError::ConfigError(format!(
+                "DNA {} is not signed by a trusted author",
+                signed.display()
+            ))),
+        );
+
+        conductor.config.trusted_dna_authors = Some(vec![test_keybundle(1).get_id()]);
+        assert!(conductor
+            .install_dna_from_file(
+                unsigned,
+                String::from("unsigned-dna"),
+                false,
+                None,
+                None,
+                None
+            )
+            .is_err());
+        assert_eq!(
+            conductor.install_dna_from_file(
+                signed,
+                String::from("signed-dna"),
+                false,
+                None,
+                None,
+                None,
+            ),
+            Ok(dna.address()),
+        );
+    }
+
     #[test]
     fn test_install_dna_from_file_with_properties() {
         let test_name = "test_install_dna_from_file_with_properties";
@@ -1629,6 +1770,71 @@ public_address = '{}'"#,
         assert_eq!(config_contents, toml,);
     }
 
+    #[test]
+    fn test_import_chain() {
+        let test_name = "test_import_chain";
+        let mut conductor = create_test_conductor(test_name, 3012);
+
+        let chain = {
+            let instance = conductor.instances.get("test-instance-1").unwrap();
+            let state = instance.read().unwrap().context().unwrap().state().unwrap();
+            ChainExport::from_chain_store(
+                &state.agent().chain_store(),
+                &state.agent().top_chain_header(),
+            )
+            .unwrap()
+        };
+        assert!(!chain.elements.is_empty());
+
+        let result = conductor.import_chain(&String::from("test-instance-2"), chain.clone());
+        assert!(format!("{:?}", result).contains("does not belong to agent"));
+
+        let result = conductor.import_chain(&String::from("no-such-instance"), chain.clone());
+        assert!(format!("{:?}", result).contains("does not exist"));
+
+        conductor
+            .start_instance(&String::from("test-instance-1"))
+            .unwrap();
+        let result = conductor.import_chain(&String::from("test-instance-1"), chain);
+        assert!(format!("{:?}", result).contains("has to be stopped"));
+    }
+
+    #[test]
+    fn test_import_chain_into_a_stopped_instance() {
+        let test_name = "test_import_chain_into_a_stopped_instance";
+        let mut conductor = create_test_conductor(test_name, 3013);
+        let instance_id = String::from("test-instance-1");
+        let chain_of = |conductor: &Conductor| {
+            let instance = conductor.instances.get(&instance_id).unwrap();
+            let state = instance.read().unwrap().context().unwrap().state().unwrap();
+            ChainExport::from_chain_store(
+                &state.agent().chain_store(),
+                &state.agent().top_chain_header(),
+            )
+            .unwrap()
+        };
+        let chain = chain_of(&conductor);
+
+        conductor.start_instance(&instance_id).unwrap();
+        conductor.stop_instance(&instance_id).unwrap();
+        assert!(!conductor
+            .instances
+            .get(&instance_id)
+            .unwrap()
+            .read()
+            .unwrap()
+            .running());
+
+        // the genesis chain of an agent can be imported into its own fresh chain
+        conductor.import_chain(&instance_id, chain.clone()).unwrap();
+        assert_eq!(chain_of(&conductor), chain);
+        let instance = conductor.instances.get(&instance_id).unwrap();
+        assert!(instance.read().unwrap().running());
+        assert!(!instance.read().unwrap().active());
+
+        conductor.start_instance(&instance_id).unwrap();
+    }
+
     #[test]
     fn test_remove_agent() {
         let test_name = "test_remove_agent";
@@ -4,11 +4,15 @@ use crate::{
         serialize_configuration, Configuration, InterfaceConfiguration, InterfaceDriver,
         NetworkConfig, StorageConfiguration,
     },
-    context_builder::ContextBuilder,
+    context_builder::{
+        open_file_storages, open_lmdb_storages, open_pickle_storages, ContextBuilder,
+    },
     dpki_instance::DpkiInstance,
+    encrypted_storage::{open_storage_keys, rekey_storage},
     error::HolochainInstanceError,
     key_loaders::test_keystore,
     keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
+    keystore_service::RemoteKeystore,
     port_utils::{try_with_port, INTERFACE_CONNECT_ATTEMPTS_MAX},
     Holochain,
 };
@@ -16,11 +20,15 @@ use crossbeam_channel::{unbounded, Receiver, Sender};
 use holochain_common::paths::DNA_EXTENSION;
 use holochain_core::{logger::Logger, signal::Signal};
 use holochain_core_types::{
-    agent::AgentId,
+    agent::{AgentId, Base32},
     dna::Dna,
     error::{HcResult, HolochainError},
 };
-use holochain_dpki::{key_bundle::KeyBundle, password_encryption::PwHashConfig};
+use holochain_dpki::{
+    password_encryption::PwHashConfig,
+    revocation::RevokedKey,
+    utils::{find_revocation, verify_revocation_certificate},
+};
 use holochain_json_api::json::JsonString;
 use holochain_locksmith::{Mutex, RwLock};
 use holochain_logging::{rule::RuleFilter, FastLogger, FastLoggerBuilder};
@@ -45,10 +53,11 @@ use std::{
 use crate::conductor::passphrase_manager::PassphraseServiceUnixSocket;
 use crate::{
     conductor::passphrase_manager::{
-        PassphraseManager, PassphraseService, PassphraseServiceCmd, PassphraseServiceMock,
+        PassphraseManager, PassphraseProvider, PassphraseService, PassphraseServiceAdmin,
+        PassphraseServiceCmd, PassphraseServiceMock,
     },
     config::{AgentConfiguration, PassphraseServiceConfig, TracingConfiguration},
-    interface::{ConductorApiBuilder, InstanceMap, Interface},
+    interface::{ConductorApiBuilder, InstanceMap, Interface, InterfaceMetadata},
     port_utils::get_free_port,
     signal_wrapper::SignalWrapper,
     static_file_server::ConductorStaticFileServer,
@@ -117,6 +126,7 @@ pub struct Conductor {
     instance_signal_receivers: Arc<RwLock<HashMap<String, Receiver<Signal>>>>,
     trace_reporters: Arc<RwLock<TraceReporterMap>>,
     agent_keys: HashMap<String, Arc<Mutex<Keystore>>>,
+    remote_keystores: HashMap<String, Arc<RemoteKeystore>>,
     pub(in crate::conductor) config: Configuration,
     pub(in crate::conductor) static_servers: HashMap<String, StaticServer>,
     pub(in crate::conductor) interface_threads: HashMap<String, Sender<()>>,
@@ -131,6 +141,7 @@ pub struct Conductor {
     logger: FastLogger,
     p2p_config: Option<P2pConfig>,
     pub passphrase_manager: Arc<PassphraseManager>,
+    passphrase_provider: Option<PassphraseProvider>,
     pub hash_config: Option<PwHashConfig>, // currently this has to be pub for testing.  would like to remove
 }
 
@@ -198,6 +209,8 @@ impl Conductor {
             println!();
         }
 
+        let interface_broadcasters = Arc::new(RwLock::new(HashMap::new()));
+        let mut passphrase_provider = None;
         let passphrase_service: Arc<Mutex<dyn PassphraseService + Send>> =
             if let PassphraseServiceConfig::UnixSocket { path } = config.passphrase_service.clone()
             {
@@ -214,18 +227,41 @@ impl Conductor {
                     PassphraseServiceConfig::Mock { passphrase } => {
                         Arc::new(Mutex::new(PassphraseServiceMock { passphrase }))
                     }
+                    PassphraseServiceConfig::Admin => {
+                        let admin_interface_ids = config
+                            .interfaces
+                            .iter()
+                            .filter(|interface_config| interface_config.admin)
+                            .map(|interface_config| interface_config.id.clone())
+                            .collect();
+                        let service = PassphraseServiceAdmin::new(
+                            interface_broadcasters.clone(),
+                            admin_interface_ids,
+                        );
+                        passphrase_provider = Some(service.provider());
+                        Arc::new(Mutex::new(service))
+                    }
                     _ => unreachable!(),
                 }
             };
 
+        // Decrypted secrets only get wiped if an idle timeout is configured explicitly.
+        let passphrase_manager = match config.passphrase_idle_timeout {
+            Some(secs) => {
+                PassphraseManager::with_idle_timeout(passphrase_service, Duration::from_secs(secs))
+            }
+            None => PassphraseManager::new(passphrase_service),
+        };
+
         Conductor {
             instances: HashMap::new(),
             instance_signal_receivers: Arc::new(RwLock::new(HashMap::new())),
             trace_reporters: Arc::new(RwLock::new(HashMap::new())),
             agent_keys: HashMap::new(),
+            remote_keystores: HashMap::new(),
             interface_threads: HashMap::new(),
             static_servers: HashMap::new(),
-            interface_broadcasters: Arc::new(RwLock::new(HashMap::new())),
+            interface_broadcasters,
             signal_multiplexer_kill_switch: None,
             stats_thread_kill_switch: None,
             stats_signal_receiver: None,
@@ -236,7 +272,8 @@ impl Conductor {
             signal_tx: None,
             logger,
             p2p_config: None,
-            passphrase_manager: Arc::new(PassphraseManager::new(passphrase_service)),
+            passphrase_manager: Arc::new(passphrase_manager),
+            passphrase_provider,
             hash_config: None,
         }
     }
@@ -453,19 +490,34 @@ impl Conductor {
             .map(|kill_switch| kill_switch.send(()));
     }
 
+    /// Starts all interfaces that are not running yet.
     pub fn start_all_interfaces(&mut self) {
-        self.interface_threads = self
-            .config
-            .interfaces
-            .iter()
-            .map(|ic| (ic.id.clone(), self.spawn_interface_thread(ic.clone())))
-            .collect();
+        for interface_config in self.config.interfaces.clone() {
+            if !self.interface_threads.contains_key(&interface_config.id) {
+                let kill_switch = self.spawn_interface_thread(interface_config.clone());
+                self.interface_threads
+                    .insert(interface_config.id, kill_switch);
+            }
+        }
 
         self.start_signal_multiplexer();
     }
 
+    /// Starts only the admin interfaces that don't expose any instances, e.g. so that the
+    /// "admin" passphrase service can receive passphrases before the instances get booted.
+    /// They keep running when all other interfaces get started.
+    pub fn start_admin_interfaces(&mut self) {
+        for interface_config in self.config.interfaces.clone() {
+            if interface_config.admin && interface_config.instances.is_empty() {
+                let kill_switch = self.spawn_interface_thread(interface_config.clone());
+                self.interface_threads
+                    .insert(interface_config.id, kill_switch);
+            }
+        }
+    }
+
     pub fn stop_all_interfaces(&mut self) {
-        for (id, kill_switch) in self.interface_threads.iter() {
+        for (id, kill_switch) in self.interface_threads.drain() {
             notify(format!("Stopping interface {}", id));
             kill_switch.send(()).unwrap_or_else(|err| {
                 let message = format!("Error stopping interface: {}", err);
@@ -849,8 +901,17 @@ impl Conductor {
                 }
 
                 // Storage:
+                // Keys get opened before the storage, which creates files in its directory
+                if let Some(path) = instance_config.storage.encrypted_path() {
+                    let keystore = self.get_keystore_for_agent(&instance_config.agent)?;
+                    let mut keystore = keystore.lock().unwrap();
+                    let storage_keys = open_storage_keys(path, &mut keystore).map_err(|hc_err| {
+                        format!("Error opening encrypted storage: {}", hc_err.to_string())
+                    })?;
+                    context_builder = context_builder.with_storage_keys(storage_keys);
+                }
                 match instance_config.storage {
-                    StorageConfiguration::File { path } => {
+                    StorageConfiguration::File { path, .. } => {
                         context_builder =
                             context_builder.with_file_storage(path).map_err(|hc_err| {
                                 format!("Error creating context: {}", hc_err.to_string())
@@ -859,7 +920,7 @@ impl Conductor {
                     StorageConfiguration::Memory => {
                         context_builder = context_builder.with_memory_storage()
                     }
-                    StorageConfiguration::Pickle { path } => {
+                    StorageConfiguration::Pickle { path, .. } => {
                         context_builder =
                             context_builder
                                 .with_pickle_storage(path)
@@ -867,7 +928,7 @@ impl Conductor {
                                     format!("Error creating context: {}", hc_err.to_string())
                                 })?
                     }
-                    StorageConfiguration::Lmdb { path, initial_mmap_bytes } => {
+                    StorageConfiguration::Lmdb { path, initial_mmap_bytes, .. } => {
                         context_builder =
                             context_builder
                                 .with_lmdb_storage(path, initial_mmap_bytes)
@@ -1032,17 +1093,10 @@ impl Conductor {
                     .clone()
                     .expect("holo_remote_key needs decryption_service_uri set"),
             );
+        } else if agent_config.keystore_socket.is_some() {
+            let remote = self.get_remote_keystore_for_agent(&instance_config.agent)?;
+            api_builder = api_builder.with_remote_keystore_functions(remote);
         } else {
-            api_builder = api_builder.with_agent_signature_callback(
-                self.get_keybundle_for_agent(&instance_config.agent)?,
-            );
-
-            api_builder = api_builder.with_agent_encryption_callback(
-                self.get_keybundle_for_agent(&instance_config.agent)?,
-            );
-            api_builder = api_builder.with_agent_decryption_callback(
-                self.get_keybundle_for_agent(&instance_config.agent)?,
-            );
             let keystore = self.get_keystore_for_agent(&instance_config.agent)?;
             api_builder = api_builder.with_agent_keystore_functions(keystore);
         }
@@ -1080,9 +1134,17 @@ impl Conductor {
             // Holo closed-alpha hack:
             // !!!!!!!!!!!!!!!!!!!!!!!
             AgentId::new(&agent_config.name, agent_config.public_address.clone())
+        } else if agent_config.keystore_socket.is_some() {
+            // get_remote_keystore_for_agent() checked it matches the daemon's key
+            self.get_remote_keystore_for_agent(&agent_config.id)?;
+            AgentId::new(&agent_config.name, agent_config.public_address.clone())
         } else {
-            let keybundle_arc = self.get_keybundle_for_agent(&agent_config.id)?;
-            let keybundle = keybundle_arc.lock().unwrap();
+            let keystore = self.get_keystore_for_agent(&agent_config.id)?;
+            let keybundle = keystore
+                .lock()
+                .unwrap()
+                .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+                .map_err(|err| format!("{}", err))?;
             AgentId::new(&agent_config.name, keybundle.get_id())
         })
     }
@@ -1103,6 +1165,15 @@ impl Conductor {
             // !!!!!!!!!!!!!!!!!!!!!!!
             return Ok(());
         }
+        if self
+            .config
+            .agent_by_id(agent_id)
+            .and_then(|a| a.keystore_socket)
+            .is_some()
+        {
+            self.get_remote_keystore_for_agent(agent_id)?;
+            return Ok(());
+        }
         self.get_keystore_for_agent(agent_id)?;
         Ok(())
     }
@@ -1202,6 +1273,12 @@ impl Conductor {
             if let Some(true) = agent_config.holo_remote_key {
                 return Err("agent is holo_remote, no keystore".to_string());
             }
+            if agent_config.keystore_socket.is_some() {
+                return Err(format!(
+                    "Agent '{}' uses a keystore daemon, no keystore in the conductor",
+                    agent_id
+                ));
+            }
 
             let mut keystore = match agent_config.test_agent {
                 Some(true) => test_keystore(&agent_config.name),
@@ -1239,26 +1316,144 @@ impl Conductor {
                 }
             }
 
-            self.agent_keys
-                .insert(agent_id.clone(), Arc::new(Mutex::new(keystore)));
+            self.check_revocation(&agent_config, &keybundle.get_id())?;
+
+            let keystore = Arc::new(Mutex::new(keystore));
+            // Wipe the decrypted secrets together with the passphrase after the idle timeout.
+            // A keystore that is locked right then is in use and gets wiped on a later retry.
+            let weak_keystore = Arc::downgrade(&keystore);
+            self.passphrase_manager
+                .on_wipe(move || match weak_keystore.upgrade() {
+                    Some(keystore) => match keystore.try_lock() {
+                        Some(mut keystore) => {
+                            keystore.wipe_cache();
+                            true
+                        }
+                        None => false,
+                    },
+                    None => true,
+                });
+            self.agent_keys.insert(agent_id.clone(), keystore);
         }
         let keystore_ref = self.agent_keys.get(agent_id).unwrap();
         Ok(keystore_ref.clone())
     }
 
-    /// Get reference to the keybundle stored in the keystore for given agent ID.
-    /// If the key was not loaded (into secure memory) yet, this will use the KeyLoader
-    /// to do so.
-    pub fn get_keybundle_for_agent(
+    /// Re-keys the encrypted storage of the given instance with the keystore of its agent
+    /// (see [rekey_storage]) and returns the new generation of the agent's storage key.
+    /// All content gets re-encrypted with a new content key and the old keys get dropped,
+    /// so the storage must not be in use by a loaded instance.
+    pub fn rekey_instance_storage(&mut self, instance_id: &String) -> HcResult<u64> {
+        let instance_config = self.config.instance_by_id(instance_id).ok_or_else(|| {
+            HolochainError::ConfigError(format!("Instance '{}' not found", instance_id))
+        })?;
+        if self.instances.contains_key(instance_id) {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Storage of instance '{}' is in use, it can only be re-keyed while the instance is not loaded",
+                instance_id
+            )));
+        }
+        let path = instance_config.storage.encrypted_path().ok_or_else(|| {
+            HolochainError::ConfigError(format!(
+                "Storage of instance '{}' is not encrypted",
+                instance_id
+            ))
+        })?;
+        let keystore = self
+            .get_keystore_for_agent(&instance_config.agent)
+            .map_err(HolochainError::ConfigError)?;
+        let mut keystore = keystore.lock().unwrap();
+        let storage = instance_config.storage.clone();
+        rekey_storage(path, &mut keystore, |cas_path, eav_path| match &storage {
+            StorageConfiguration::File { .. } => open_file_storages(cas_path, eav_path),
+            StorageConfiguration::Pickle { .. } => open_pickle_storages(cas_path, eav_path),
+            StorageConfiguration::Lmdb {
+                initial_mmap_bytes, ..
+            } => open_lmdb_storages(cas_path, eav_path, *initial_mmap_bytes),
+            StorageConfiguration::Memory => Err(HolochainError::ConfigError(
+                "Memory storages are not persisted and can't be re-keyed".to_string(),
+            )),
+        })
+    }
+
+    /// Get the connection to the keystore daemon holding the keys of given agent ID.
+    /// The first time, this asks the daemon for the agent's key and checks it
+    /// against the config.
+    pub fn get_remote_keystore_for_agent(
         &mut self,
         agent_id: &String,
-    ) -> Result<Arc<Mutex<KeyBundle>>, String> {
-        let keystore = self.get_keystore_for_agent(agent_id)?;
-        let mut keystore = keystore.lock().unwrap();
-        let keybundle = keystore
-            .get_keybundle(PRIMARY_KEYBUNDLE_ID)
-            .map_err(|err| format!("{}", err))?;
-        Ok(Arc::new(Mutex::new(keybundle)))
+    ) -> Result<Arc<RemoteKeystore>, String> {
+        if !self.remote_keystores.contains_key(agent_id) {
+            let agent_config = self
+                .config
+                .agent_by_id(agent_id)
+                .ok_or_else(|| format!("Agent '{}' not found", agent_id))?;
+            let socket = agent_config
+                .keystore_socket
+                .clone()
+                .ok_or_else(|| format!("Agent '{}' has no keystore_socket", agent_id))?;
+            let remote = RemoteKeystore::new(PathBuf::from(socket.clone()));
+            let public_key = remote.get_keybundle_id(PRIMARY_KEYBUNDLE_ID)?;
+            if agent_config.public_address != public_key {
+                return Err(format!(
+                    "Key from keystore at '{}' ('{}') does not match public address {} mentioned in config!",
+                    socket, public_key, agent_config.public_address,
+                ));
+            }
+            self.check_revocation(&agent_config, &public_key)?;
+            self.remote_keystores
+                .insert(agent_id.clone(), Arc::new(remote));
+        }
+        Ok(self.remote_keystores.get(agent_id).unwrap().clone())
+    }
+
+    /// Fails if a revocation certificate signed with the agent's revocation key revokes `key`
+    /// or the agent's device key.
+    /// Also fails if such a certificate revokes a device while the agent has no `device_key`
+    /// configured, as it can't be told whether that device is the agent's.
+    fn check_revocation(
+        &self,
+        agent_config: &AgentConfiguration,
+        key: &Base32,
+    ) -> Result<(), String> {
+        let revocation_key = match &agent_config.revocation_key {
+            Some(revocation_key) => revocation_key,
+            None => return Ok(()),
+        };
+        let certificates = self.config.revocation_certificates()?;
+        if let Some(certificate) = find_revocation(key, revocation_key, &certificates)? {
+            return Err(format!(
+                "Key '{}' of agent '{}' has been revoked: {}",
+                key, agent_config.id, certificate.reason,
+            ));
+        }
+        match &agent_config.device_key {
+            Some(device_key) => {
+                if let Some(certificate) =
+                    find_revocation(device_key, revocation_key, &certificates)?
+                {
+                    return Err(format!(
+                        "Device '{}' of agent '{}' has been revoked: {}",
+                        device_key, agent_config.id, certificate.reason,
+                    ));
+                }
+            }
+            None => {
+                for certificate in certificates.iter() {
+                    if let RevokedKey::Device(device) = &certificate.revoked {
+                        if &certificate.revocation_key == revocation_key
+                            && verify_revocation_certificate(certificate)?
+                        {
+                            return Err(format!(
+                                "Device '{}' has been revoked with the revocation key of agent '{}', which has no device_key configured to check it against",
+                                device, agent_config.id,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 
     fn start_interface(&mut self, config: &InterfaceConfiguration) -> Result<(), String> {
@@ -1308,8 +1503,11 @@ impl Conductor {
         Ok(())
     }
 
-    fn make_interface_handler(&self, interface_config: &InterfaceConfiguration) -> IoHandler {
-        let mut conductor_api_builder = ConductorApiBuilder::new();
+    fn make_interface_handler(
+        &self,
+        interface_config: &InterfaceConfiguration,
+    ) -> IoHandler<InterfaceMetadata> {
+        let mut conductor_api_builder = ConductorApiBuilder::with_metadata();
         for instance_ref_config in interface_config.instances.iter() {
             let id = &instance_ref_config.id;
             let name = instance_ref_config.alias.as_ref().unwrap_or(id).clone();
@@ -1334,6 +1532,10 @@ impl Conductor {
                 .with_admin_ui_functions()
                 .with_test_admin_functions()
                 .with_debug_functions();
+            if let Some(ref passphrase_provider) = self.passphrase_provider {
+                conductor_api_builder =
+                    conductor_api_builder.with_passphrase_provider(passphrase_provider.clone());
+            }
         }
 
         conductor_api_builder.spawn()
@@ -1506,7 +1708,7 @@ fn with_port_heuristic<T, F: FnOnce() -> T>(
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
 fn run_interface(
     interface_config: &InterfaceConfiguration,
-    handler: IoHandler,
+    handler: IoHandler<InterfaceMetadata>,
     kill_switch: Receiver<()>,
 ) -> Result<(Broadcaster, thread::JoinHandle<()>), String> {
     use crate::interface_impls::{http::HttpInterface, websocket::WebsocketInterface};
@@ -1571,7 +1773,10 @@ pub mod tests {
         signal::signal_channel,
     };
     use holochain_core_types::dna;
-    use holochain_dpki::{key_bundle::KeyBundle, password_encryption::PwHashConfig, SEED_SIZE};
+    use holochain_dpki::{
+        key_bundle::KeyBundle, keypair::KeyPair, password_encryption::PwHashConfig, seed::RootSeed,
+        utils::generate_random_seed_buf, SEED_SIZE,
+    };
     use holochain_persistence_api::cas::content::Address;
     use holochain_wasm_utils::wasm_target_dir;
     use lib3h_sodium::secbuf::SecBuf;
@@ -2539,6 +2744,182 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn refuses_agent_with_revoked_key() {
+        let mut revocation_seed = RootSeed::new(generate_random_seed_buf())
+            .generate_revocation_seed(1)
+            .unwrap();
+        let revocation_key = revocation_seed.generate_revocation_key().unwrap().public();
+        let certificate = revocation_seed
+            .revoke_key_bundle(&test_keybundle(1), "compromised".to_string())
+            .unwrap();
+        let certificates_dir = tempdir().unwrap();
+        let certificate_toml = |agent_revocation_key: &str| {
+            format!(
+                r#"
+                revocation_certificates_dir = "{dir}"
+
+                [[agents]]
+                id = "test-agent-1"
+                name = "Holo Tester 1"
+                public_address = "{tkb1}"
+                keystore_file = "holo_tester1.key"
+                revocation_key = "{revocation_key}"
+                "#,
+                dir = certificates_dir.path().display(),
+                tkb1 = test_keybundle(1).get_id(),
+                revocation_key = agent_revocation_key,
+            )
+        };
+        let keystore_for_agent = |toml: String| {
+            let mut conductor =
+                Conductor::from_config(load_configuration::<Configuration>(&toml).unwrap());
+            conductor.key_loader = test_key_loader();
+            conductor
+                .get_keystore_for_agent(&"test-agent-1".to_string())
+                .map(|_| ())
+        };
+
+        // no certificate yet
+        assert_eq!(
+            Ok(()),
+            keystore_for_agent(certificate_toml(&revocation_key))
+        );
+
+        let mut file = File::create(certificates_dir.path().join("revocation.json")).unwrap();
+        file.write_all(serde_json::to_string(&certificate).unwrap().as_bytes())
+            .unwrap();
+        assert_eq!(
+            Err(format!(
+                "Key '{}' of agent 'test-agent-1' has been revoked: compromised",
+                test_keybundle(1).get_id()
+            )),
+            keystore_for_agent(certificate_toml(&revocation_key)),
+        );
+
+        // a certificate signed by a key other than the agent's revocation key is ignored
+        let other_revocation_key = RootSeed::new(generate_random_seed_buf())
+            .generate_revocation_seed(1)
+            .unwrap()
+            .generate_revocation_key()
+            .unwrap()
+            .public();
+        assert_eq!(
+            Ok(()),
+            keystore_for_agent(certificate_toml(&other_revocation_key))
+        );
+    }
+
+    #[test]
+    fn refuses_agent_with_revoked_device() {
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        let mut revocation_seed = root_seed.generate_revocation_seed(1).unwrap();
+        let revocation_key = revocation_seed.generate_revocation_key().unwrap().public();
+        let context = holochain_dpki::utils::SeedContext::new(*b"HCDEVICE");
+        let mut device_seed = root_seed.generate_device_seed(&context, 1).unwrap();
+        let device_key = device_seed.generate_device_key().unwrap().public();
+        let mut other_device_seed = root_seed.generate_device_seed(&context, 2).unwrap();
+        let other_device_key = other_device_seed.generate_device_key().unwrap().public();
+        let certificate = revocation_seed
+            .revoke_device(&mut device_seed, "lost phone".to_string())
+            .unwrap();
+        let certificates_dir = tempdir().unwrap();
+        let mut file = File::create(certificates_dir.path().join("revocation.json")).unwrap();
+        file.write_all(serde_json::to_string(&certificate).unwrap().as_bytes())
+            .unwrap();
+
+        let keystore_for_agent = |device_key_line: String| {
+            let toml = format!(
+                r#"
+                revocation_certificates_dir = "{dir}"
+
+                [[agents]]
+                id = "test-agent-1"
+                name = "Holo Tester 1"
+                public_address = "{tkb1}"
+                keystore_file = "holo_tester1.key"
+                revocation_key = "{revocation_key}"
+                {device_key_line}
+                "#,
+                dir = certificates_dir.path().display(),
+                tkb1 = test_keybundle(1).get_id(),
+                revocation_key = revocation_key,
+                device_key_line = device_key_line,
+            );
+            let mut conductor =
+                Conductor::from_config(load_configuration::<Configuration>(&toml).unwrap());
+            conductor.key_loader = test_key_loader();
+            conductor
+                .get_keystore_for_agent(&"test-agent-1".to_string())
+                .map(|_| ())
+        };
+
+        assert_eq!(
+            Err(format!(
+                "Device '{}' of agent 'test-agent-1' has been revoked: lost phone",
+                device_key
+            )),
+            keystore_for_agent(format!("device_key = \"{}\"", device_key)),
+        );
+        assert_eq!(
+            Ok(()),
+            keystore_for_agent(format!("device_key = \"{}\"", other_device_key)),
+        );
+        // without a device key, a device revocation can't be ruled out
+        assert!(keystore_for_agent(String::new())
+            .unwrap_err()
+            .contains("has no device_key configured"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn loads_agent_key_from_keystore_daemon() {
+        use crate::keystore_service::KeystoreServer;
+        let dir = tempdir().unwrap();
+        let socket = dir.path().join("keystore.sock");
+        let server =
+            KeystoreServer::bind(socket.clone(), Arc::new(Mutex::new(test_keystore(1)))).unwrap();
+        thread::spawn(move || server.run());
+
+        let remote_keystore_for_agent = |public_address: String| -> Result<(), String> {
+            let toml = format!(
+                r#"
+                [[agents]]
+                id = "test-agent-1"
+                name = "Holo Tester 1"
+                public_address = "{public_address}"
+                keystore_file = "holo_tester1.key"
+                keystore_socket = "{socket}"
+                "#,
+                public_address = public_address,
+                socket = socket.display(),
+            );
+            let mut conductor =
+                Conductor::from_config(load_configuration::<Configuration>(&toml).unwrap());
+            conductor.key_loader = test_key_loader();
+            conductor.check_load_key_for_agent(&"test-agent-1".to_string())?;
+            // the keys stay in the daemon
+            assert!(conductor
+                .get_keystore_for_agent(&"test-agent-1".to_string())
+                .is_err());
+            Ok(())
+        };
+
+        assert_eq!(
+            Ok(()),
+            remote_keystore_for_agent(test_keybundle(1).get_id())
+        );
+        assert_eq!(
+            Err(format!(
+                "Key from keystore at '{}' ('{}') does not match public address {} mentioned in config!",
+                socket.display(),
+                test_keybundle(1).get_id(),
+                test_keybundle(2).get_id(),
+            )),
+            remote_keystore_for_agent(test_keybundle(2).get_id())
+        );
+    }
+
     #[test]
     // flaky test
     // signal ordering is not deterministic nor is timing
@@ -1,12 +1,16 @@
-use crossbeam_channel::{unbounded, Sender};
+use crate::{conductor::broadcaster::Broadcaster, signal_wrapper::SignalWrapper};
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
 use holochain_core_types::error::HolochainError;
-use holochain_locksmith::Mutex;
+use holochain_locksmith::{Mutex, RwLock};
 use lib3h_sodium::secbuf::SecBuf;
-#[cfg(unix)]
 use log::Level;
 use std::{
+    collections::HashMap,
     io::{self, Write},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     thread,
     time::{Duration, Instant},
 };
@@ -16,29 +20,66 @@ use std::io::{BufRead, BufReader};
 #[cfg(unix)]
 use std::os::unix::net::{UnixListener, UnixStream};
 
-/// We are caching the passphrase for 10 minutes.
-const PASSPHRASE_CACHE_DURATION_SECS: u64 = 600;
+/// We are caching the passphrase for 10 minutes unless configured otherwise.
+pub const PASSPHRASE_CACHE_DURATION_SECS: u64 = 600;
+
+/// The admin passphrase service repeats its signal this often until a passphrase gets provided,
+/// so that clients connecting after the request went out get to see it.
+const PASSPHRASE_REQUEST_REPEAT_SECS: u64 = 5;
 
 pub trait PassphraseService {
     fn request_passphrase(&self) -> Result<SecBuf, HolochainError>;
 }
 
+/// Wipes secrets that were decrypted with the passphrase.
+/// Returns false if that is not possible right now and should be retried later.
+type WipeCallback = Box<dyn Fn() -> bool + Send>;
+
 #[derive(Clone)]
 pub struct PassphraseManager {
     passphrase_cache: Arc<Mutex<Option<SecBuf>>>,
     passphrase_service: Arc<Mutex<dyn PassphraseService + Send>>,
     last_read: Arc<Mutex<Instant>>,
+    wipe_callbacks: Arc<Mutex<Vec<WipeCallback>>>,
+    pending_wipes: Arc<Mutex<Vec<usize>>>,
+    wipe_secrets: bool,
     timeout_kill_switch: Sender<()>,
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
 impl PassphraseManager {
+    /// Creates a manager that forgets the passphrase after it has not been used for
+    /// [PASSPHRASE_CACHE_DURATION_SECS]. Secrets decrypted with it stay in memory.
     pub fn new(passphrase_service: Arc<Mutex<dyn PassphraseService + Send>>) -> Self {
+        Self::spawn(
+            passphrase_service,
+            Duration::from_secs(PASSPHRASE_CACHE_DURATION_SECS),
+            false,
+        )
+    }
+
+    /// Creates a manager that forgets the passphrase once it has not been used for
+    /// `idle_timeout` and then calls all callbacks registered with [on_wipe].
+    pub fn with_idle_timeout(
+        passphrase_service: Arc<Mutex<dyn PassphraseService + Send>>,
+        idle_timeout: Duration,
+    ) -> Self {
+        Self::spawn(passphrase_service, idle_timeout, true)
+    }
+
+    fn spawn(
+        passphrase_service: Arc<Mutex<dyn PassphraseService + Send>>,
+        idle_timeout: Duration,
+        wipe_secrets: bool,
+    ) -> Self {
         let (kill_switch_tx, kill_switch_rx) = unbounded::<()>();
         let pm = PassphraseManager {
             passphrase_cache: Arc::new(Mutex::new(None)),
             passphrase_service,
             last_read: Arc::new(Mutex::new(Instant::now())),
+            wipe_callbacks: Arc::new(Mutex::new(Vec::new())),
+            pending_wipes: Arc::new(Mutex::new(Vec::new())),
+            wipe_secrets,
             timeout_kill_switch: kill_switch_tx,
         };
 
@@ -51,15 +92,23 @@ impl PassphraseManager {
                     return;
                 }
 
-                if pm_clone.passphrase_cache.lock().unwrap().is_some() {
+                // The cache stays locked while a passphrase is being requested,
+                // which might take a while. There is nothing to forget in that case.
+                let is_cached = pm_clone
+                    .passphrase_cache
+                    .try_lock()
+                    .map(|passphrase| passphrase.is_some())
+                    .unwrap_or(false);
+
+                if is_cached {
                     let duration_since_last_read =
                         Instant::now().duration_since(*pm_clone.last_read.lock().unwrap());
 
-                    if duration_since_last_read
-                        > Duration::from_secs(PASSPHRASE_CACHE_DURATION_SECS)
-                    {
+                    if duration_since_last_read > idle_timeout {
                         pm_clone.forget_passphrase();
                     }
+                } else {
+                    pm_clone.run_pending_wipes();
                 }
 
                 thread::sleep(Duration::from_secs(1));
@@ -77,9 +126,12 @@ impl PassphraseManager {
                     .unwrap()
                     .request_passphrase()?,
             );
+            // Secrets that could not be wiped yet are going to be wiped
+            // together with the new passphrase after the next idle timeout.
+            self.pending_wipes.lock().unwrap().clear();
         }
 
-        *(self.last_read.lock().unwrap()) = Instant::now();
+        self.note_activity();
 
         match *passphrase {
             Some(ref mut passphrase_buf) => {
@@ -91,9 +143,43 @@ impl PassphraseManager {
         }
     }
 
+    /// Resets the idle timeout, e.g. when a secret that was decrypted with the passphrase
+    /// gets used.
+    pub fn note_activity(&self) {
+        *(self.last_read.lock().unwrap()) = Instant::now();
+    }
+
+    /// Registers a callback that wipes secrets decrypted with the passphrase.
+    /// It gets called whenever the passphrase is forgotten after the idle timeout,
+    /// and again every second for as long as it returns false.
+    pub fn on_wipe<F>(&self, callback: F)
+    where
+        F: Fn() -> bool + Send + 'static,
+    {
+        self.wipe_callbacks.lock().unwrap().push(Box::new(callback));
+    }
+
     fn forget_passphrase(&self) {
-        let mut passphrase = self.passphrase_cache.lock().unwrap();
-        *passphrase = None;
+        {
+            let mut passphrase = self.passphrase_cache.lock().unwrap();
+            *passphrase = None;
+        }
+        if self.wipe_secrets {
+            log_info!("Passphrase unused for too long. Wiping decrypted secrets...");
+            *self.pending_wipes.lock().unwrap() =
+                (0..self.wipe_callbacks.lock().unwrap().len()).collect();
+            self.run_pending_wipes();
+        } else {
+            log_info!("Passphrase unused for too long. Forgetting it...");
+        }
+    }
+
+    fn run_pending_wipes(&self) {
+        let wipe_callbacks = self.wipe_callbacks.lock().unwrap();
+        self.pending_wipes
+            .lock()
+            .unwrap()
+            .retain(|index| !wipe_callbacks[*index]());
     }
 }
 
@@ -218,3 +304,221 @@ impl PassphraseService for PassphraseServiceUnixSocket {
         Ok(passphrase_buf)
     }
 }
+
+/// Receives passphrases for a [PassphraseServiceAdmin] through the
+/// `admin/passphrase/provide` method of admin interfaces.
+#[derive(Clone)]
+pub struct PassphraseProvider {
+    requested: Arc<AtomicBool>,
+    passphrase_tx: Sender<SecBuf>,
+}
+
+impl PassphraseProvider {
+    pub fn provide(&self, mut passphrase_string: String) -> Result<(), HolochainError> {
+        // Move passphrase in secure memory
+        let passphrase_bytes = unsafe { passphrase_string.as_mut_vec() };
+        let mut passphrase_buf = SecBuf::with_insecure(passphrase_bytes.len());
+        let write_result = passphrase_buf.write(0, passphrase_bytes.as_slice());
+
+        // Overwrite the unsafe passphrase memory with zeros
+        for byte in passphrase_bytes.iter_mut() {
+            *byte = 0u8;
+        }
+        write_result?;
+
+        if !self.requested.swap(false, Ordering::SeqCst) {
+            return Err(HolochainError::ErrorGeneric(
+                "No passphrase has been requested".into(),
+            ));
+        }
+        self.passphrase_tx
+            .send(passphrase_buf)
+            .map_err(|_| HolochainError::ErrorGeneric("Passphrase service is not running".into()))
+    }
+}
+
+/// Requests passphrases by sending a `PassphraseRequest` signal over the given admin interfaces
+/// and waits until a client replies through the according [PassphraseProvider].
+pub struct PassphraseServiceAdmin {
+    broadcasters: Arc<RwLock<HashMap<String, Broadcaster>>>,
+    admin_interface_ids: Vec<String>,
+    requested: Arc<AtomicBool>,
+    passphrase_tx: Sender<SecBuf>,
+    passphrase_rx: Receiver<SecBuf>,
+}
+
+impl PassphraseServiceAdmin {
+    pub fn new(
+        broadcasters: Arc<RwLock<HashMap<String, Broadcaster>>>,
+        admin_interface_ids: Vec<String>,
+    ) -> Self {
+        let (passphrase_tx, passphrase_rx) = unbounded();
+        PassphraseServiceAdmin {
+            broadcasters,
+            admin_interface_ids,
+            requested: Arc::new(AtomicBool::new(false)),
+            passphrase_tx,
+            passphrase_rx,
+        }
+    }
+
+    pub fn provider(&self) -> PassphraseProvider {
+        PassphraseProvider {
+            requested: self.requested.clone(),
+            passphrase_tx: self.passphrase_tx.clone(),
+        }
+    }
+
+    fn send_request_signal(&self) -> Result<(), HolochainError> {
+        let broadcasters = self.broadcasters.read().unwrap();
+        let mut running_interfaces = 0;
+        for id in self.admin_interface_ids.iter() {
+            if let Some(broadcaster) = broadcasters.get(id) {
+                running_interfaces += 1;
+                if let Err(error) = broadcaster.send(SignalWrapper::PassphraseRequest) {
+                    warn!(
+                        "Could not send passphrase request to interface {}: {}",
+                        id, error
+                    );
+                }
+            }
+        }
+        if running_interfaces == 0 {
+            return Err(HolochainError::ErrorGeneric(
+                "No admin interface is running to request the passphrase through".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl PassphraseService for PassphraseServiceAdmin {
+    fn request_passphrase(&self) -> Result<SecBuf, HolochainError> {
+        log_debug!("Passphrase needed. Using admin interface passphrase service...");
+        // Drop passphrases that were provided for an earlier request that got answered already
+        while self.passphrase_rx.try_recv().is_ok() {}
+        self.requested.store(true, Ordering::SeqCst);
+        loop {
+            if let Err(error) = self.send_request_signal() {
+                self.requested.store(false, Ordering::SeqCst);
+                return Err(error);
+            }
+            match self
+                .passphrase_rx
+                .recv_timeout(Duration::from_secs(PASSPHRASE_REQUEST_REPEAT_SECS))
+            {
+                Ok(passphrase) => {
+                    log_debug!("Got passphrase through admin interface.");
+                    return Ok(passphrase);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    log_debug!("No passphrase provided yet. Requesting again...");
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(HolochainError::ErrorGeneric(
+                        "Passphrase channel disconnected".into(),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct PassphraseServiceCounting {
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl PassphraseService for PassphraseServiceCounting {
+        fn request_passphrase(&self) -> Result<SecBuf, HolochainError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Ok(SecBuf::with_insecure_from_string("passphrase".to_string()))
+        }
+    }
+
+    #[test]
+    fn forgets_passphrase_and_wipes_after_idle_timeout() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let manager = PassphraseManager::with_idle_timeout(
+            Arc::new(Mutex::new(PassphraseServiceCounting {
+                requests: requests.clone(),
+            })),
+            Duration::from_secs(1),
+        );
+        let wiped = Arc::new(AtomicBool::new(false));
+        let wiped_clone = wiped.clone();
+        manager.on_wipe(move || {
+            wiped_clone.store(true, Ordering::SeqCst);
+            true
+        });
+
+        manager.get_passphrase().unwrap();
+        manager.get_passphrase().unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(!wiped.load(Ordering::SeqCst));
+
+        thread::sleep(Duration::from_secs(3));
+        assert!(wiped.load(Ordering::SeqCst));
+        manager.get_passphrase().unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn retries_wipes_that_are_not_possible_yet() {
+        let manager = PassphraseManager::with_idle_timeout(
+            Arc::new(Mutex::new(PassphraseServiceCounting {
+                requests: Arc::new(AtomicUsize::new(0)),
+            })),
+            Duration::from_secs(1),
+        );
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = attempts.clone();
+        manager.on_wipe(move || attempts_clone.fetch_add(1, Ordering::SeqCst) >= 2);
+
+        manager.get_passphrase().unwrap();
+        thread::sleep(Duration::from_secs(6));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn admin_service_fails_without_running_admin_interface() {
+        let service = PassphraseServiceAdmin::new(
+            Arc::new(RwLock::new(HashMap::new())),
+            vec!["admin interface".to_string()],
+        );
+        let provider = service.provider();
+        assert!(service.request_passphrase().is_err());
+        assert!(provider.provide("passphrase".to_string()).is_err());
+    }
+
+    #[test]
+    fn admin_service_waits_for_provided_passphrase() {
+        let mut broadcasters = HashMap::new();
+        broadcasters.insert("admin interface".to_string(), Broadcaster::Noop);
+        let service = PassphraseServiceAdmin::new(
+            Arc::new(RwLock::new(broadcasters)),
+            vec!["admin interface".to_string()],
+        );
+        let provider = service.provider();
+        assert!(provider.provide("too early".to_string()).is_err());
+
+        let handle = thread::spawn(move || service.request_passphrase());
+        while !provider.requested.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        provider.provide("passphrase".to_string()).unwrap();
+
+        let mut passphrase = handle.join().unwrap().unwrap();
+        assert_eq!(
+            passphrase.compare(&mut SecBuf::with_insecure_from_string(
+                "passphrase".to_string()
+            )),
+            0
+        );
+        assert!(provider.provide("passphrase".to_string()).is_err());
+    }
+}
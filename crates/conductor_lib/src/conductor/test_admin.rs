@@ -31,6 +31,9 @@ impl ConductorTestAdmin for Conductor {
             keystore_file: name,
             holo_remote_key: None,
             test_agent: Some(true),
+            revocation_key: None,
+            device_key: None,
+            keystore_socket: None,
         };
         new_config.agents.push(new_agent);
         new_config.check_consistency(&mut self.dna_loader)?;
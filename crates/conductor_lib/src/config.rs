@@ -19,6 +19,7 @@ use holochain_core_types::{
     },
     error::{HcResult, HolochainError},
 };
+use holochain_dpki::revocation::RevocationCertificate;
 use holochain_json_api::json::JsonString;
 use holochain_metrics::MetricPublisherConfig;
 use holochain_net::sim2h_worker::Sim2hConfig;
@@ -30,7 +31,7 @@ use serde::Deserialize;
 use std::{
     collections::{HashMap, HashSet},
     convert::TryFrom,
-    fs::File,
+    fs::{self, File},
     io::prelude::*,
     net::Ipv4Addr,
     path::PathBuf,
@@ -108,6 +109,18 @@ pub struct Configuration {
     /// keys for new instances
     pub dpki: Option<DpkiConfiguration>,
 
+    /// Optional directory of JSON encoded revocation certificates.
+    /// Agents whose key is revoked by one of these, signed with the agent's `revocation_key`,
+    /// are not loaded.
+    #[serde(default)]
+    pub revocation_certificates_dir: Option<PathBuf>,
+
+    /// Optional public signing keys of trusted DNA authors.
+    /// If set, `admin/dna/install_from_file` only installs DNA packages
+    /// signed by one of them (see `hc package --sign`).
+    #[serde(default)]
+    pub trusted_dna_authors: Option<Vec<Base32>>,
+
     /// Which signals to emit
     #[serde(default)]
     pub signals: SignalConfig,
@@ -119,6 +132,15 @@ pub struct Configuration {
     #[serde(default)]
     pub passphrase_service: PassphraseServiceConfig,
 
+    /// Seconds after which an unused passphrase and all keystore secrets decrypted with it
+    /// are wiped from memory and have to be requested again from the passphrase service.
+    /// Optional. Without it, only the passphrase is forgotten after 600 seconds (10 minutes)
+    /// while decrypted secrets stay in memory.
+    #[serde(default)]
+    pub passphrase_idle_timeout: Option<u64>,
+
+    /// Where instances publish their metrics: the logger (default), CloudWatch logs,
+    /// or a Prometheus endpoint at `http://<bind_address>/metrics`.
     #[serde(default)]
     pub metric_publisher: Option<MetricPublisherConfig>,
 }
@@ -130,11 +152,15 @@ pub struct Configuration {
 /// arbitrary UIs to connect to the conductor and prompt the user for a passphrase.
 /// The according `PassphraseServiceUnixSocket` will send a request message over the socket
 /// then receives bytes as passphrase until a newline is sent.
+/// Setting the type to "admin" sends a `PassphraseRequest` signal over all admin interfaces
+/// whenever a passphrase is needed and waits for a client to reply by calling
+/// `admin/passphrase/provide` on one of them.
 #[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum PassphraseServiceConfig {
     Cmd,
     UnixSocket { path: String },
+    Admin,
     Mock { passphrase: String },
 }
 
@@ -250,12 +276,21 @@ impl Configuration {
         detect_dupes("interface", self.interfaces.iter().map(|c| &c.id))?;
 
         for ref instance in self.instances.iter() {
-            self.agent_by_id(&instance.agent).is_some().ok_or_else(|| {
+            let agent_config = self.agent_by_id(&instance.agent).ok_or_else(|| {
                 format!(
                     "Agent configuration {} not found, mentioned in instance {}",
                     instance.agent, instance.id
                 )
             })?;
+            if instance.storage.encrypted_path().is_some()
+                && (agent_config.holo_remote_key.unwrap_or_default()
+                    || agent_config.keystore_socket.is_some())
+            {
+                return Err(format!(
+                    "Instance \"{}\" has encrypted storage, which needs a keystore in the conductor, but agent \"{}\" keeps its keys elsewhere",
+                    instance.id, instance.agent
+                ));
+            }
             let dna_config = self.dna_by_id(&instance.dna);
             dna_config.is_some().ok_or_else(|| {
                 format!(
@@ -346,6 +381,18 @@ impl Configuration {
             }
         }
 
+        if let PassphraseServiceConfig::Admin = self.passphrase_service {
+            if !self
+                .interfaces
+                .iter()
+                .any(|interface| interface.admin && interface.instances.is_empty())
+            {
+                return Err(String::from(
+                    "Passphrase service type 'admin' needs at least one admin interface without instances",
+                ));
+            }
+        }
+
         Ok(())
     }
 
@@ -493,6 +540,33 @@ impl Configuration {
         self.agents.iter().find(|ac| ac.id == id).cloned()
     }
 
+    /// Reads every certificate in `revocation_certificates_dir`, if set.
+    /// Fails on a file that is not a revocation certificate rather than
+    /// risk missing a revocation.
+    pub fn revocation_certificates(&self) -> Result<Vec<RevocationCertificate>, HolochainError> {
+        let dir = match &self.revocation_certificates_dir {
+            Some(dir) => dir,
+            None => return Ok(Vec::new()),
+        };
+        let mut certificates = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let contents = fs::read_to_string(&path)?;
+            let certificate = serde_json::from_str(&contents).map_err(|e| {
+                HolochainError::ConfigError(format!(
+                    "Invalid revocation certificate {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            certificates.push(certificate);
+        }
+        Ok(certificates)
+    }
+
     /// Returns the agent configuration with the given ID if present
     pub fn update_agent_address_by_id(&mut self, id: &str, agent_id: &AgentId) {
         self.agents.iter_mut().for_each(|ac| {
@@ -645,9 +719,9 @@ impl Configuration {
             .instances
             .iter()
             .filter_map(|stg_config| match stg_config.storage {
-                StorageConfiguration::File { ref path }
+                StorageConfiguration::File { ref path, .. }
                 | StorageConfiguration::Lmdb { ref path, .. }
-                | StorageConfiguration::Pickle { ref path } => Some(path.as_str()),
+                | StorageConfiguration::Pickle { ref path, .. } => Some(path.as_str()),
                 _ => None,
             })
             .collect();
@@ -682,6 +756,21 @@ pub struct AgentConfiguration {
     pub holo_remote_key: Option<bool>,
     /// If true this agent will use dummy keys rather than a keystore file
     pub test_agent: Option<bool>,
+    /// Public key of the revocation keypair this agent derived from its root seed.
+    /// If set, the conductor refuses to load the agent's key when a certificate
+    /// in `revocation_certificates_dir` signed with this key revokes it.
+    #[serde(default)]
+    pub revocation_key: Option<Base32>,
+    /// Public key of the device seed this agent's keys were derived from.
+    /// If set, a certificate signed with `revocation_key` that revokes this device key
+    /// revokes the agent as well. Without it, such device revocations can't be checked
+    /// and the conductor refuses to load the agent while one is present.
+    #[serde(default)]
+    pub device_key: Option<Base32>,
+    /// Path of the Unix domain socket of an `hc-keystore` daemon holding this agent's keys.
+    /// If set, keystore_file is ignored and secrets never get decrypted in the conductor.
+    #[serde(default)]
+    pub keystore_socket: Option<String>,
 }
 
 impl From<AgentConfiguration> for AgentId {
@@ -729,22 +818,50 @@ pub struct InstanceConfiguration {
 /// * file
 ///
 /// Projected are various DB adapters.
+///
+/// Persistent storages can be set to `encrypted`, which keeps their content encrypted at rest
+/// with keys unlocked by the agent's keystore (see [encrypted_storage](../encrypted_storage/index.html)).
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum StorageConfiguration {
     Memory,
     File {
         path: String,
+        encrypted: Option<bool>,
     },
     Pickle {
         path: String,
+        encrypted: Option<bool>,
     },
     Lmdb {
         path: String,
         initial_mmap_bytes: Option<usize>,
+        encrypted: Option<bool>,
     },
 }
 
+impl StorageConfiguration {
+    /// The directory of the storage if it is to be encrypted at rest
+    pub fn encrypted_path(&self) -> Option<&str> {
+        match self {
+            StorageConfiguration::File {
+                path,
+                encrypted: Some(true),
+            }
+            | StorageConfiguration::Pickle {
+                path,
+                encrypted: Some(true),
+            }
+            | StorageConfiguration::Lmdb {
+                path,
+                encrypted: Some(true),
+                ..
+            } => Some(path.as_str()),
+            _ => None,
+        }
+    }
+}
+
 /// Here, interfaces are user facing and make available zome functions to
 /// GUIs, browser based web UIs, local native UIs, other local applications and scripts.
 /// We currently have:
@@ -1233,6 +1350,133 @@ pub mod tests {
         assert_eq!(config.check_consistency(&mut test_dna_loader()), Err("DNA configuration \"WRONG DNA ID\" not found, mentioned in instance \"app spec instance\"".to_string()));
     }
 
+    #[test]
+    fn test_encrypted_storage_config() {
+        let toml = r#"
+    [[agents]]
+    id = "test agent"
+    name = "Holo Tester 1"
+    public_address = "HoloTester1-------------------------------------------------------------------------AHi1"
+    keystore_file = "holo_tester.key"
+    keystore_socket = "/tmp/hc-keystore.sock"
+
+    [[dnas]]
+    id = "app spec rust"
+    file = "app_spec.dna.json"
+    hash = "Qm328wyq38924y"
+
+    [[instances]]
+    id = "app spec instance"
+    dna = "app spec rust"
+    agent = "test agent"
+        [instances.storage]
+        type = "lmdb"
+        path = "app_spec_storage"
+        encrypted = true
+    "#;
+
+        let mut config: Configuration =
+            load_configuration(toml).expect("Failed to load config from toml string");
+        assert_eq!(
+            config.instances[0].storage.encrypted_path(),
+            Some("app_spec_storage")
+        );
+        assert_eq!(config.check_consistency(&mut test_dna_loader()), Err("Instance \"app spec instance\" has encrypted storage, which needs a keystore in the conductor, but agent \"test agent\" keeps its keys elsewhere".to_string()));
+
+        config.agents[0].keystore_socket = None;
+        assert_eq!(config.check_consistency(&mut test_dna_loader()), Ok(()));
+
+        config.instances[0].storage = StorageConfiguration::Pickle {
+            path: "app_spec_storage".to_string(),
+            encrypted: None,
+        };
+        assert_eq!(config.instances[0].storage.encrypted_path(), None);
+    }
+
+    #[test]
+    fn test_admin_passphrase_service_config() {
+        let toml = r#"
+    passphrase_idle_timeout = 60
+
+    [passphrase_service]
+    type = "admin"
+
+    [[interfaces]]
+    id = "admin interface"
+    admin = true
+        [interfaces.driver]
+        type = "websocket"
+        port = 8888
+    "#;
+
+        let mut config: Configuration =
+            load_configuration(toml).expect("Failed to load config from toml string");
+        assert_eq!(config.passphrase_idle_timeout, Some(60));
+        match config.passphrase_service {
+            PassphraseServiceConfig::Admin => (),
+            _ => panic!("Expected the admin passphrase service"),
+        }
+        assert_eq!(config.check_consistency(&mut test_dna_loader()), Ok(()));
+
+        config.interfaces[0].admin = false;
+        assert_eq!(
+            config.check_consistency(&mut test_dna_loader()),
+            Err(
+                "Passphrase service type 'admin' needs at least one admin interface without instances"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_admin_passphrase_service_needs_an_interface_without_instances() {
+        let toml = r#"
+    [passphrase_service]
+    type = "admin"
+
+    [[agents]]
+    id = "test agent"
+    name = "Holo Tester 1"
+    public_address = "HoloTester1-------------------------------------------------------------------------AHi1"
+    keystore_file = "holo_tester.key"
+
+    [[dnas]]
+    id = "app spec rust"
+    file = "app_spec.dna.json"
+    hash = "Qm328wyq38924y"
+
+    [[instances]]
+    id = "app spec instance"
+    dna = "app spec rust"
+    agent = "test agent"
+        [instances.storage]
+        type = "memory"
+
+    [[interfaces]]
+    id = "admin interface"
+    admin = true
+        [interfaces.driver]
+        type = "websocket"
+        port = 8888
+        [[interfaces.instances]]
+        id = "app spec instance"
+    "#;
+
+        // only admin interfaces without instances run before the instances get booted
+        let mut config: Configuration =
+            load_configuration(toml).expect("Failed to load config from toml string");
+        assert_eq!(
+            config.check_consistency(&mut test_dna_loader()),
+            Err(
+                "Passphrase service type 'admin' needs at least one admin interface without instances"
+                    .to_string()
+            )
+        );
+
+        config.interfaces[0].instances.clear();
+        assert_eq!(config.check_consistency(&mut test_dna_loader()), Ok(()));
+    }
+
     #[test]
     fn test_inconsistent_config_interface_1() {
         let toml = r#"
@@ -1,3 +1,7 @@
+use crate::encrypted_storage::{
+    EncryptedContentAddressableStorage, EncryptedEntityAttributeValueStorage, InnerStorages,
+    StorageKeys,
+};
 use holochain_core::{context::Context, persister::SimplePersister, signal::SignalSender};
 use holochain_core_types::{agent::AgentId, eav::Attribute, error::HolochainError};
 use holochain_locksmith::RwLock;
@@ -43,6 +47,7 @@ pub struct ContextBuilder {
     tracer: Option<holochain_tracing::Tracer>,
     state_dump_logging: bool,
     metric_publisher: Option<Arc<RwLock<dyn MetricPublisher>>>,
+    storage_keys: Option<StorageKeys>,
 }
 
 impl ContextBuilder {
@@ -60,6 +65,7 @@ impl ContextBuilder {
             tracer: None,
             state_dump_logging: false,
             metric_publisher: None,
+            storage_keys: None,
         }
     }
 
@@ -84,63 +90,50 @@ impl ContextBuilder {
     /// Sets all three storages, chain, DHT and EAV storage, to persistent file based implementations.
     /// Chain and DHT storages get set to the same file CAS.
     /// Returns an error if no file storage could be spawned on the given path.
-    pub fn with_file_storage<P: AsRef<Path>>(mut self, path: P) -> Result<Self, HolochainError> {
+    pub fn with_file_storage<P: AsRef<Path>>(self, path: P) -> Result<Self, HolochainError> {
         let base_path: PathBuf = path.as_ref().into();
-        let cas_path = base_path.join("cas");
-        let eav_path = base_path.join("eav");
-        fs::create_dir_all(&cas_path)?;
-        fs::create_dir_all(&eav_path)?;
-
-        let file_storage = Arc::new(RwLock::new(FilesystemStorage::new(&cas_path)?));
-        let eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
-            Arc::new(RwLock::new(EavFileStorage::new(eav_path)?));
-        self.chain_storage = Some(file_storage.clone());
-        self.dht_storage = Some(file_storage);
-        self.eav_storage = Some(eav_storage);
-        Ok(self)
+        let storages = open_file_storages(&base_path.join("cas"), &base_path.join("eav"))?;
+        Ok(self.with_storages(storages))
     }
 
     /// Sets all three storages, chain, DHT and EAV storage, to persistent pikcle based implementations.
     /// Chain and DHT storages get set to the same pikcle CAS.
     /// Returns an error if no pickle storage could be spawned on the given path.
-    pub fn with_pickle_storage<P: AsRef<Path>>(mut self, path: P) -> Result<Self, HolochainError> {
+    pub fn with_pickle_storage<P: AsRef<Path>>(self, path: P) -> Result<Self, HolochainError> {
         let base_path: PathBuf = path.as_ref().into();
-        let cas_path = base_path.join("cas");
-        let eav_path = base_path.join("eav");
-        fs::create_dir_all(&cas_path)?;
-        fs::create_dir_all(&eav_path)?;
-
-        let file_storage = Arc::new(RwLock::new(PickleStorage::new(&cas_path)));
-        let eav_storage = Arc::new(RwLock::new(EavPickleStorage::new(eav_path)));
-        self.chain_storage = Some(file_storage.clone());
-        self.dht_storage = Some(file_storage);
-        self.eav_storage = Some(eav_storage);
-        Ok(self)
+        let storages = open_pickle_storages(&base_path.join("cas"), &base_path.join("eav"))?;
+        Ok(self.with_storages(storages))
     }
 
     /// Sets all three storages, chain, DHT and EAV storage, to persistent lmdb based implementations.
     /// Chain and DHT storages get set to the same pikcle CAS.
     /// Returns an error if no lmdb storage could be spawned on the given path.
     pub fn with_lmdb_storage<P: AsRef<Path>>(
-        mut self,
+        self,
         path: P,
         initial_mmap_bytes: Option<usize>,
     ) -> Result<Self, HolochainError> {
         let base_path: PathBuf = path.as_ref().into();
-        let cas_path = base_path.join("cas");
-        let eav_path = base_path.join("eav");
-        fs::create_dir_all(&cas_path)?;
-        fs::create_dir_all(&eav_path)?;
-
-        let cas_storage = Arc::new(RwLock::new(LmdbStorage::new(&cas_path, initial_mmap_bytes)));
-        let eav_storage = Arc::new(RwLock::new(EavLmdbStorage::new(
-            eav_path,
+        let storages = open_lmdb_storages(
+            &base_path.join("cas"),
+            &base_path.join("eav"),
             initial_mmap_bytes,
-        )));
-        self.chain_storage = Some(cas_storage.clone());
-        self.dht_storage = Some(cas_storage);
-        self.eav_storage = Some(eav_storage);
-        Ok(self)
+        )?;
+        Ok(self.with_storages(storages))
+    }
+
+    /// Chain and DHT storages get set to the same CAS.
+    fn with_storages(mut self, (cas, eav): InnerStorages) -> Self {
+        self.chain_storage = Some(cas.clone());
+        self.dht_storage = Some(cas);
+        self.eav_storage = Some(eav);
+        self
+    }
+
+    /// Encrypts everything the storages hold with the given keys, whichever storages are set.
+    pub fn with_storage_keys(mut self, storage_keys: StorageKeys) -> Self {
+        self.storage_keys = Some(storage_keys);
+        self
     }
 
     /// Sets the network config.
@@ -191,6 +184,9 @@ impl ContextBuilder {
                 )
             }
             MetricPublisherConfig::Logger => MetricPublisherConfig::Logger,
+            MetricPublisherConfig::Prometheus(config) => {
+                MetricPublisherConfig::Prometheus(config.clone())
+            }
         };
         self.metric_publisher = Some(config.create_metric_publisher());
         self
@@ -200,15 +196,29 @@ impl ContextBuilder {
     /// Defaults to memory storages, an in-memory network config and a fake agent called "alice".
     /// The persister gets set to SimplePersister based on the chain storage.
     pub fn spawn(self) -> Context {
-        let chain_storage = self
+        let mut chain_storage = self
             .chain_storage
             .unwrap_or_else(|| Arc::new(RwLock::new(MemoryStorage::new())));
-        let dht_storage = self
+        let mut dht_storage = self
             .dht_storage
             .unwrap_or_else(|| Arc::new(RwLock::new(MemoryStorage::new())));
-        let eav_storage = self
+        let mut eav_storage = self
             .eav_storage
             .unwrap_or_else(|| Arc::new(RwLock::new(EavMemoryStorage::new())));
+        if let Some(storage_keys) = self.storage_keys {
+            chain_storage = Arc::new(RwLock::new(EncryptedContentAddressableStorage::new(
+                chain_storage,
+                storage_keys.clone(),
+            )));
+            dht_storage = Arc::new(RwLock::new(EncryptedContentAddressableStorage::new(
+                dht_storage,
+                storage_keys.clone(),
+            )));
+            eav_storage = Arc::new(RwLock::new(EncryptedEntityAttributeValueStorage::new(
+                eav_storage,
+                storage_keys,
+            )));
+        }
         let metric_publisher = self
             .metric_publisher
             .unwrap_or_else(|| Arc::new(RwLock::new(DefaultMetricPublisher::default())));
@@ -238,11 +248,60 @@ impl ContextBuilder {
     }
 }
 
+/// Opens file based CAS and EAV storages in the given directories, creating them if needed.
+pub fn open_file_storages(
+    cas_path: &Path,
+    eav_path: &Path,
+) -> Result<InnerStorages, HolochainError> {
+    fs::create_dir_all(cas_path)?;
+    fs::create_dir_all(eav_path)?;
+    let cas_storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+        Arc::new(RwLock::new(FilesystemStorage::new(cas_path)?));
+    let eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+        Arc::new(RwLock::new(EavFileStorage::new(eav_path.to_path_buf())?));
+    Ok((cas_storage, eav_storage))
+}
+
+/// Opens pickle based CAS and EAV storages in the given directories, creating them if needed.
+pub fn open_pickle_storages(
+    cas_path: &Path,
+    eav_path: &Path,
+) -> Result<InnerStorages, HolochainError> {
+    fs::create_dir_all(cas_path)?;
+    fs::create_dir_all(eav_path)?;
+    let cas_storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+        Arc::new(RwLock::new(PickleStorage::new(cas_path)));
+    let eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+        Arc::new(RwLock::new(EavPickleStorage::new(eav_path.to_path_buf())));
+    Ok((cas_storage, eav_storage))
+}
+
+/// Opens lmdb based CAS and EAV storages in the given directories, creating them if needed.
+pub fn open_lmdb_storages(
+    cas_path: &Path,
+    eav_path: &Path,
+    initial_mmap_bytes: Option<usize>,
+) -> Result<InnerStorages, HolochainError> {
+    fs::create_dir_all(cas_path)?;
+    fs::create_dir_all(eav_path)?;
+    let cas_storage: Arc<RwLock<dyn ContentAddressableStorage>> =
+        Arc::new(RwLock::new(LmdbStorage::new(cas_path, initial_mmap_bytes)));
+    let eav_storage: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+        Arc::new(RwLock::new(EavLmdbStorage::new(
+            eav_path.to_path_buf(),
+            initial_mmap_bytes,
+        )));
+    Ok((cas_storage, eav_storage))
+}
+
 #[cfg(test)]
 mod tests {
     use self::tempfile::tempdir;
     use super::*;
+    use crate::{encrypted_storage::open_storage_keys, key_loaders::test_keystore};
+    use holochain_core_types::entry::test_entry;
     use holochain_net::p2p_config::P2pBackendKind;
+    use holochain_persistence_api::cas::content::AddressableContent;
     use tempfile;
     use test_utils::mock_signing::mock_conductor_api;
 
@@ -293,4 +352,34 @@ mod tests {
             .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
             .spawn();
     }
+
+    #[test]
+    fn with_storage_keys() {
+        let temp = tempdir().expect("test was supposed to create temp dir");
+        let storage_keys = open_storage_keys(temp.path(), &mut test_keystore("alice")).unwrap();
+        let context = ContextBuilder::new()
+            .with_file_storage(temp.path())
+            .expect("Filestorage should get instantiated with tempdir")
+            .with_storage_keys(storage_keys)
+            .with_conductor_api(mock_conductor_api(AgentId::generate_fake("alice")))
+            .spawn();
+
+        let entry = test_entry();
+        context.chain_storage.write().unwrap().add(&entry).unwrap();
+        assert_eq!(
+            context
+                .dht_storage
+                .read()
+                .unwrap()
+                .fetch(&entry.address())
+                .unwrap(),
+            Some(entry.content())
+        );
+        let on_disk = FilesystemStorage::new(temp.path().join("cas"))
+            .unwrap()
+            .fetch(&entry.address())
+            .unwrap()
+            .unwrap();
+        assert_ne!(on_disk, entry.content());
+    }
 }
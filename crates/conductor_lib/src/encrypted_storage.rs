@@ -0,0 +1,855 @@
+//! Wrappers around the content addressable and EAV storages of an instance that keep
+//! entries, headers, the persisted instance state and link tags encrypted at rest.
+//!
+//! Content gets encrypted with random content keys. These are saved next to the storage
+//! in [STORAGE_KEYS_FILE], encrypted with a key the agent's [Keystore] derives from its
+//! primary signing key (see [Keystore::get_storage_key]). Opening an encrypted storage
+//! hence needs the keystore to be unlocked through the passphrase service.
+//!
+//! Addresses are hashes of the plaintext and stay readable, so that the storages can still
+//! be queried by address. Only the link type and tag of EAV attributes are encrypted.
+//! The addresses of all stored content are listed in [STORAGE_ADDRESSES_FILE], as the
+//! storages themselves can't list what they hold.
+//!
+//! [rekey_storage] re-encrypts everything a storage holds with a new content key and drops
+//! the old content keys, so a leaked content key doesn't expose anything after a re-key.
+
+use crate::keystore::Keystore;
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    error::{HcResult, HolochainError},
+};
+use holochain_json_api::{error::JsonError, json::JsonString};
+use holochain_locksmith::{Mutex, RwLock};
+use holochain_persistence_api::{
+    cas::{
+        content::{Address, AddressableContent, Content},
+        storage::ContentAddressableStorage,
+    },
+    eav::{EavFilter, EntityAttributeValueStorage, IndexFilter},
+    error::{PersistenceError, PersistenceResult},
+};
+use lib3h_sodium::{aead, kx, secbuf::SecBuf};
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use uuid::Uuid;
+
+/// Name of the file in the directory of an encrypted storage that holds its content keys
+pub const STORAGE_KEYS_FILE: &str = "storage_keys.json";
+
+/// Name of the file in the directory of an encrypted storage that lists the addresses
+/// of its content, one per line
+pub const STORAGE_ADDRESSES_FILE: &str = "storage_addresses";
+
+/// The directories of the content addressable and EAV storages of an instance storage
+const STORAGE_DIRS: [&str; 2] = ["cas", "eav"];
+
+/// The CAS and EAV storages that encrypted storages wrap
+pub type InnerStorages = (
+    Arc<RwLock<dyn ContentAddressableStorage>>,
+    Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+);
+
+/// The content keys of an encrypted storage as they are saved in [STORAGE_KEYS_FILE]
+#[derive(Serialize, Deserialize)]
+struct StorageKeysFile {
+    /// Generation of the keystore's storage key that the content keys are encrypted with
+    generation: u64,
+    /// Index of the first content key in `keys`, the keys before it got dropped by a re-key
+    #[serde(default)]
+    first: usize,
+    /// Base64 encoded nonce and cipher of every content key, oldest first
+    keys: Vec<String>,
+}
+
+/// The addresses of the content of an encrypted storage, saved in [STORAGE_ADDRESSES_FILE]
+/// unless the storage only lives in memory
+struct AddressIndex {
+    path: Option<PathBuf>,
+    addresses: BTreeSet<Address>,
+}
+
+impl AddressIndex {
+    fn in_memory() -> Self {
+        AddressIndex {
+            path: None,
+            addresses: BTreeSet::new(),
+        }
+    }
+
+    fn open(path: PathBuf) -> HcResult<Self> {
+        let addresses = match fs::read_to_string(&path) {
+            Ok(lines) => lines
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(Address::from)
+                .collect(),
+            Err(ref error) if error.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(AddressIndex {
+            path: Some(path),
+            addresses,
+        })
+    }
+
+    fn add(&mut self, address: &Address) -> HcResult<()> {
+        if self.addresses.contains(address) {
+            return Ok(());
+        }
+        if let Some(path) = &self.path {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            writeln!(file, "{}", address)?;
+        }
+        self.addresses.insert(address.clone());
+        Ok(())
+    }
+}
+
+/// The decrypted content keys of an encrypted storage.
+/// The newest key encrypts everything that gets added, older keys are kept
+/// to read what was added before the storage got re-keyed, until the re-key
+/// has re-encrypted it.
+#[derive(Clone)]
+pub struct StorageKeys {
+    first: usize,
+    keys: Arc<Vec<Mutex<SecBuf>>>,
+    addresses: Arc<Mutex<AddressIndex>>,
+}
+
+impl fmt::Debug for StorageKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StorageKeys({} keys)", self.keys.len())
+    }
+}
+
+impl StorageKeys {
+    fn new(first: usize, keys: Vec<SecBuf>, addresses: AddressIndex) -> Self {
+        StorageKeys {
+            first,
+            keys: Arc::new(keys.into_iter().map(Mutex::new).collect()),
+            addresses: Arc::new(Mutex::new(addresses)),
+        }
+    }
+
+    /// Encrypts `data` with the newest content key.
+    /// Returns the index of that key (4 bytes, big endian) followed by nonce and cipher.
+    fn encrypt(&self, data: &[u8]) -> HcResult<Vec<u8>> {
+        let newest = self.keys.len() - 1;
+        let mut message = SecBuf::with_insecure(data.len());
+        message.write(0, data)?;
+        let mut encrypted = ((self.first + newest) as u32).to_be_bytes().to_vec();
+        encrypted.extend(seal(&mut *self.keys[newest].lock()?, &mut message)?);
+        Ok(encrypted)
+    }
+
+    /// Decrypts what [encrypt] returned with the content key it was encrypted with
+    fn decrypt(&self, data: &[u8]) -> HcResult<Vec<u8>> {
+        if data.len() < 4 {
+            return Err(HolochainError::ErrorGeneric(
+                "Encrypted data is truncated".to_string(),
+            ));
+        }
+        let (index, sealed) = data.split_at(4);
+        let index = u32::from_be_bytes([index[0], index[1], index[2], index[3]]) as usize;
+        let key = index
+            .checked_sub(self.first)
+            .and_then(|index| self.keys.get(index))
+            .ok_or_else(|| {
+                HolochainError::ErrorGeneric(format!("Content key {} of storage not found", index))
+            })?;
+        let mut message = SecBuf::with_insecure(sealed_message_len(sealed)?);
+        open(&mut *key.lock()?, sealed, &mut message)?;
+        let message = message.read_lock().to_vec();
+        Ok(message)
+    }
+
+    /// Lists `address` among the addresses of the storage's content
+    fn add_address(&self, address: &Address) -> HcResult<()> {
+        self.addresses.lock()?.add(address)
+    }
+}
+
+/// Encrypts `message` with `key` and a random nonce and returns nonce and cipher
+fn seal(key: &mut SecBuf, message: &mut SecBuf) -> HcResult<Vec<u8>> {
+    let mut nonce = SecBuf::with_insecure(aead::NONCEBYTES);
+    nonce.randomize();
+    let mut cipher = SecBuf::with_insecure(message.len() + aead::ABYTES);
+    aead::enc(message, key, None, &mut nonce, &mut cipher)?;
+    let mut sealed = nonce.read_lock().to_vec();
+    sealed.extend_from_slice(&cipher.read_lock());
+    Ok(sealed)
+}
+
+/// Length of the message that [seal] turned into `sealed`
+fn sealed_message_len(sealed: &[u8]) -> HcResult<usize> {
+    sealed
+        .len()
+        .checked_sub(aead::NONCEBYTES + aead::ABYTES)
+        .ok_or_else(|| HolochainError::ErrorGeneric("Encrypted data is truncated".to_string()))
+}
+
+/// Decrypts what [seal] returned into `message`, which must be [sealed_message_len] long
+fn open(key: &mut SecBuf, sealed: &[u8], message: &mut SecBuf) -> HcResult<()> {
+    let (nonce, cipher) = sealed.split_at(aead::NONCEBYTES);
+    let mut nonce_buf = SecBuf::with_insecure(nonce.len());
+    nonce_buf.write(0, nonce)?;
+    let mut cipher_buf = SecBuf::with_insecure(cipher.len());
+    cipher_buf.write(0, cipher)?;
+    aead::dec(message, key, None, &mut nonce_buf, &mut cipher_buf)?;
+    Ok(())
+}
+
+fn random_content_key() -> SecBuf {
+    let mut key = SecBuf::with_secure(kx::SESSIONKEYBYTES);
+    key.randomize();
+    key
+}
+
+fn copy_key(key: &mut SecBuf) -> HcResult<SecBuf> {
+    let mut copy = SecBuf::with_secure(key.len());
+    copy.write(0, &key.read_lock())?;
+    Ok(copy)
+}
+
+fn read_storage_keys(
+    path: &Path,
+    keystore: &mut Keystore,
+) -> HcResult<(StorageKeysFile, Vec<SecBuf>)> {
+    let keys_file: StorageKeysFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let mut storage_key = keystore.get_storage_key(keys_file.generation)?;
+    let keys = keys_file
+        .keys
+        .iter()
+        .map(|sealed| -> HcResult<SecBuf> {
+            let sealed = base64::decode(sealed)?;
+            let mut key = SecBuf::with_secure(sealed_message_len(&sealed)?);
+            open(&mut storage_key, &sealed, &mut key).map_err(|_| {
+                HolochainError::ErrorGeneric(format!(
+                    "Could not decrypt the keys in {}, was the storage created for another agent?",
+                    path.display()
+                ))
+            })?;
+            Ok(key)
+        })
+        .collect::<HcResult<Vec<_>>>()?;
+    Ok((keys_file, keys))
+}
+
+fn write_storage_keys(
+    path: &Path,
+    generation: u64,
+    first: usize,
+    keys: &mut [SecBuf],
+    keystore: &mut Keystore,
+) -> HcResult<()> {
+    let mut storage_key = keystore.get_storage_key(generation)?;
+    let mut sealed_keys = Vec::new();
+    for key in keys.iter_mut() {
+        sealed_keys.push(base64::encode(&seal(&mut storage_key, key)?));
+    }
+    let keys_file = StorageKeysFile {
+        generation,
+        first,
+        keys: sealed_keys,
+    };
+    // Replace the file in one go so an interrupted write can't lose the keys
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string_pretty(&keys_file)?)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn holds_data(path: &Path) -> bool {
+    STORAGE_DIRS.iter().any(|dir| {
+        fs::read_dir(path.join(dir))
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(false)
+    })
+}
+
+/// Opens the content keys of the encrypted storage in directory `path`,
+/// unlocking them with `keystore`.
+/// Creates a first content key if the storage is new and refuses to encrypt
+/// a storage that already holds unencrypted data.
+pub fn open_storage_keys<P: AsRef<Path>>(
+    path: P,
+    keystore: &mut Keystore,
+) -> HcResult<StorageKeys> {
+    let path = path.as_ref();
+    let keys_path = path.join(STORAGE_KEYS_FILE);
+    let addresses_path = path.join(STORAGE_ADDRESSES_FILE);
+    if !keys_path.exists() {
+        if holds_data(path) {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Storage at {} holds unencrypted data and can't be opened as encrypted storage",
+                path.display()
+            )));
+        }
+        fs::create_dir_all(path)?;
+        fs::write(&addresses_path, "")?;
+        let mut keys = vec![random_content_key()];
+        write_storage_keys(&keys_path, 1, 0, &mut keys, keystore)?;
+        return Ok(StorageKeys::new(
+            0,
+            keys,
+            AddressIndex::open(addresses_path)?,
+        ));
+    }
+    finish_rekey(path)?;
+    let (keys_file, keys) = read_storage_keys(&keys_path, keystore)?;
+    // A storage created before its addresses got listed can't be re-keyed,
+    // so its list must not be started as if it was complete
+    let addresses = if addresses_path.exists() {
+        AddressIndex::open(addresses_path)?
+    } else {
+        AddressIndex::in_memory()
+    };
+    Ok(StorageKeys::new(keys_file.first, keys, addresses))
+}
+
+fn rekeyed_dir(path: &Path, dir: &str) -> PathBuf {
+    path.join(format!("{}.rekey", dir))
+}
+
+fn retired_dir(path: &Path, dir: &str) -> PathBuf {
+    path.join(format!("{}.retired", dir))
+}
+
+/// Cleans up after a re-key of the storage in directory `path` that got interrupted:
+/// swaps in a re-encrypted storage that was being swapped in, and removes the
+/// re-encrypted storages that were not complete and the retired ones.
+/// Until a re-key is done, the keys file holds the old content keys as well as the new one,
+/// so both the retired and the re-encrypted storages can be read.
+fn finish_rekey(path: &Path) -> HcResult<()> {
+    for dir in STORAGE_DIRS.iter() {
+        let current = path.join(dir);
+        let rekeyed = rekeyed_dir(path, dir);
+        let retired = retired_dir(path, dir);
+        if !current.exists() {
+            if rekeyed.exists() {
+                fs::rename(&rekeyed, &current)?;
+            } else if retired.exists() {
+                fs::rename(&retired, &current)?;
+            }
+        }
+        if rekeyed.exists() {
+            fs::remove_dir_all(&rekeyed)?;
+        }
+        if retired.exists() {
+            fs::remove_dir_all(&retired)?;
+        }
+    }
+    Ok(())
+}
+
+/// Re-keys the encrypted storage in directory `path`: re-encrypts all content and link tags
+/// with a new content key, then drops the old content keys and encrypts the new one with the
+/// next generation of the agent's storage key. Returns the new generation.
+///
+/// `open_storages` opens the unencrypted CAS and EAV storages in the given directories,
+/// the same way the instance does. The storage must not be in use while it gets re-keyed.
+/// The re-encrypted storages are built next to the current ones and then replace them,
+/// so an interrupted re-key leaves the storage readable with the keys file.
+pub fn rekey_storage<P: AsRef<Path>>(
+    path: P,
+    keystore: &mut Keystore,
+    open_storages: impl Fn(&Path, &Path) -> HcResult<InnerStorages>,
+) -> HcResult<u64> {
+    let path = path.as_ref();
+    let keys_path = path.join(STORAGE_KEYS_FILE);
+    if !keys_path.exists() {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "No encrypted storage found at {}",
+            path.display()
+        )));
+    }
+    let addresses_path = path.join(STORAGE_ADDRESSES_FILE);
+    if !addresses_path.exists() {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Storage at {} does not list its addresses and can't be re-encrypted",
+            path.display()
+        )));
+    }
+    finish_rekey(path)?;
+
+    // Save the new content key next to the old ones before anything gets encrypted with it
+    let (keys_file, mut keys) = read_storage_keys(&keys_path, keystore)?;
+    let generation = keys_file.generation + 1;
+    let new_index = keys_file.first + keys.len();
+    let mut new_key = random_content_key();
+    keys.push(copy_key(&mut new_key)?);
+    write_storage_keys(&keys_path, generation, keys_file.first, &mut keys, keystore)?;
+
+    let addresses = AddressIndex::open(addresses_path)?;
+    let all_keys = StorageKeys::new(keys_file.first, keys, AddressIndex::in_memory());
+    let new_keys = StorageKeys::new(
+        new_index,
+        vec![copy_key(&mut new_key)?],
+        AddressIndex::in_memory(),
+    );
+    {
+        let (cas, eav) = open_storages(&path.join("cas"), &path.join("eav"))?;
+        let (new_cas, new_eav) =
+            open_storages(&rekeyed_dir(path, "cas"), &rekeyed_dir(path, "eav"))?;
+
+        let cas = EncryptedContentAddressableStorage::new(cas, all_keys.clone());
+        let mut new_cas = EncryptedContentAddressableStorage::new(new_cas, new_keys.clone());
+        for address in addresses.addresses.iter() {
+            if let Some(content) = cas.fetch(address)? {
+                new_cas.add_content(address, &content)?;
+            }
+        }
+
+        let eav = EncryptedEntityAttributeValueStorage::new(eav, all_keys);
+        let mut new_eav = EncryptedEntityAttributeValueStorage::new(new_eav, new_keys);
+        let everything = EaviQuery::new(
+            None.into(),
+            None.into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        for eavi in eav.fetch_eavi(&everything)? {
+            new_eav.add_eavi(&eavi)?;
+        }
+        // the storages get dropped here, which makes them write out what they buffer
+    }
+
+    for dir in STORAGE_DIRS.iter() {
+        fs::rename(path.join(dir), retired_dir(path, dir))?;
+        fs::rename(rekeyed_dir(path, dir), path.join(dir))?;
+    }
+    write_storage_keys(&keys_path, generation, new_index, &mut [new_key], keystore)?;
+    for dir in STORAGE_DIRS.iter() {
+        fs::remove_dir_all(retired_dir(path, dir))?;
+    }
+    Ok(generation)
+}
+
+fn to_persistence_error(error: HolochainError) -> PersistenceError {
+    PersistenceError::ErrorGeneric(error.to_string())
+}
+
+/// What an [EncryptedContentAddressableStorage] hands to the storage it wraps:
+/// the encrypted content, stored under the address of the plaintext
+#[derive(Serialize, Deserialize, Debug, Clone, DefaultJson)]
+struct EncryptedContent {
+    address: Address,
+    data: String,
+}
+
+impl AddressableContent for EncryptedContent {
+    fn address(&self) -> Address {
+        self.address.clone()
+    }
+
+    fn content(&self) -> Content {
+        self.to_owned().into()
+    }
+
+    fn try_from_content(content: &Content) -> Result<Self, JsonError> {
+        EncryptedContent::try_from(content.to_owned())
+    }
+}
+
+/// Content addressable storage that encrypts all content before handing it to the wrapped storage
+#[derive(Clone, Debug)]
+pub struct EncryptedContentAddressableStorage {
+    inner: Arc<RwLock<dyn ContentAddressableStorage>>,
+    keys: StorageKeys,
+}
+
+impl EncryptedContentAddressableStorage {
+    pub fn new(inner: Arc<RwLock<dyn ContentAddressableStorage>>, keys: StorageKeys) -> Self {
+        EncryptedContentAddressableStorage { inner, keys }
+    }
+
+    fn decrypt(&self, content: Content) -> HcResult<Content> {
+        let encrypted = EncryptedContent::try_from_content(&content)?;
+        let data = self.keys.decrypt(&base64::decode(&encrypted.data)?)?;
+        Ok(JsonString::from_json(std::str::from_utf8(&data)?))
+    }
+
+    fn add_content(&mut self, address: &Address, content: &Content) -> PersistenceResult<()> {
+        let data = self
+            .keys
+            .encrypt(String::from(content.to_owned()).as_bytes())
+            .map_err(to_persistence_error)?;
+        let encrypted = EncryptedContent {
+            address: address.clone(),
+            data: base64::encode(&data),
+        };
+        // listed first, so that no content is missing from the list
+        self.keys
+            .add_address(address)
+            .map_err(to_persistence_error)?;
+        self.inner.write().unwrap().add(&encrypted)
+    }
+}
+
+impl ContentAddressableStorage for EncryptedContentAddressableStorage {
+    fn add(&mut self, content: &dyn AddressableContent) -> PersistenceResult<()> {
+        self.add_content(&content.address(), &content.content())
+    }
+
+    fn contains(&self, address: &Address) -> PersistenceResult<bool> {
+        self.inner.read().unwrap().contains(address)
+    }
+
+    fn fetch(&self, address: &Address) -> PersistenceResult<Option<Content>> {
+        let maybe_content = self.inner.read().unwrap().fetch(address)?;
+        maybe_content
+            .map(|content| self.decrypt(content).map_err(to_persistence_error))
+            .transpose()
+    }
+
+    fn get_id(&self) -> Uuid {
+        self.inner.read().unwrap().get_id()
+    }
+}
+
+/// EAV storage that encrypts the link types and tags of attributes
+/// before handing them to the wrapped storage
+#[derive(Clone, Debug)]
+pub struct EncryptedEntityAttributeValueStorage {
+    inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+    keys: StorageKeys,
+}
+
+impl EncryptedEntityAttributeValueStorage {
+    pub fn new(
+        inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>>,
+        keys: StorageKeys,
+    ) -> Self {
+        EncryptedEntityAttributeValueStorage { inner, keys }
+    }
+
+    /// Link type and tag get encrypted together into the link type,
+    /// hex encoded so that the result is a valid link type of any storage
+    fn encrypt_link(&self, link_type: &str, tag: &str) -> HcResult<String> {
+        let link = serde_json::to_string(&(link_type, tag))?;
+        Ok(self
+            .keys
+            .encrypt(link.as_bytes())?
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    fn decrypt_link(&self, encrypted: &str) -> HcResult<(String, String)> {
+        let bytes = (0..encrypted.len())
+            .step_by(2)
+            .map(|i| {
+                encrypted
+                    .get(i..i + 2)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| {
+                        HolochainError::ErrorGeneric(format!(
+                            "Encrypted link is not hex encoded: {}",
+                            encrypted
+                        ))
+                    })
+            })
+            .collect::<HcResult<Vec<_>>>()?;
+        Ok(serde_json::from_slice(&self.keys.decrypt(&bytes)?)?)
+    }
+
+    fn encrypt_eavi(
+        &self,
+        eavi: &EntityAttributeValueIndex,
+    ) -> HcResult<EntityAttributeValueIndex> {
+        let attribute = match eavi.attribute() {
+            Attribute::LinkTag(link_type, tag) => {
+                Attribute::LinkTag(self.encrypt_link(&link_type, &tag)?, String::new())
+            }
+            Attribute::RemovedLink(address, link_type, tag) => {
+                Attribute::RemovedLink(address, self.encrypt_link(&link_type, &tag)?, String::new())
+            }
+            attribute => attribute,
+        };
+        Ok(EntityAttributeValueIndex::new_with_index(
+            &eavi.entity(),
+            &attribute,
+            &eavi.value(),
+            eavi.index(),
+        )?)
+    }
+
+    fn decrypt_eavi(
+        &self,
+        eavi: &EntityAttributeValueIndex,
+    ) -> HcResult<EntityAttributeValueIndex> {
+        let attribute = match eavi.attribute() {
+            Attribute::LinkTag(encrypted, _) => {
+                let (link_type, tag) = self.decrypt_link(&encrypted)?;
+                Attribute::LinkTag(link_type, tag)
+            }
+            Attribute::RemovedLink(address, encrypted, _) => {
+                let (link_type, tag) = self.decrypt_link(&encrypted)?;
+                Attribute::RemovedLink(address, link_type, tag)
+            }
+            attribute => attribute,
+        };
+        Ok(EntityAttributeValueIndex::new_with_index(
+            &eavi.entity(),
+            &attribute,
+            &eavi.value(),
+            eavi.index(),
+        )?)
+    }
+}
+
+impl EntityAttributeValueStorage<Attribute> for EncryptedEntityAttributeValueStorage {
+    fn add_eavi(
+        &mut self,
+        eavi: &EntityAttributeValueIndex,
+    ) -> PersistenceResult<Option<EntityAttributeValueIndex>> {
+        let encrypted = self.encrypt_eavi(eavi).map_err(to_persistence_error)?;
+        let maybe_added = self.inner.write().unwrap().add_eavi(&encrypted)?;
+        maybe_added
+            .map(|added| self.decrypt_eavi(&added).map_err(to_persistence_error))
+            .transpose()
+    }
+
+    fn fetch_eavi(
+        &self,
+        query: &EaviQuery,
+    ) -> PersistenceResult<BTreeSet<EntityAttributeValueIndex>> {
+        // Attributes are encrypted with random nonces, so only the entity and value
+        // constraints can be handed down. The whole query runs on the decrypted results.
+        let inner_query = EaviQuery::new(
+            EavFilter::predicate(|entity| query.entity().check(entity)),
+            None.into(),
+            EavFilter::predicate(|value| query.value().check(value)),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        let decrypted = self
+            .inner
+            .read()
+            .unwrap()
+            .fetch_eavi(&inner_query)?
+            .iter()
+            .map(|eavi| self.decrypt_eavi(eavi))
+            .collect::<HcResult<BTreeSet<_>>>()
+            .map_err(to_persistence_error)?;
+        Ok(query.run(decrypted.iter().cloned()))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{context_builder::open_file_storages, key_loaders::test_keystore};
+    use holochain_core_types::{
+        eav::test_eav,
+        entry::{test_entry, test_entry_a, test_entry_b},
+    };
+    use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+    use tempfile::tempdir;
+
+    fn test_keys() -> StorageKeys {
+        StorageKeys::new(0, vec![random_content_key()], AddressIndex::in_memory())
+    }
+
+    #[test]
+    fn encrypts_content_under_its_plaintext_address() {
+        let inner = Arc::new(RwLock::new(MemoryStorage::new()));
+        let mut storage = EncryptedContentAddressableStorage::new(inner.clone(), test_keys());
+        let entry = test_entry();
+        storage.add(&entry).unwrap();
+
+        assert!(storage.contains(&entry.address()).unwrap());
+        assert_eq!(
+            storage.fetch(&entry.address()).unwrap(),
+            Some(entry.content())
+        );
+        let stored = inner
+            .read()
+            .unwrap()
+            .fetch(&entry.address())
+            .unwrap()
+            .unwrap();
+        assert_ne!(stored, entry.content());
+        assert!(!String::from(stored).contains("test entry value"));
+
+        let other_storage = EncryptedContentAddressableStorage::new(inner, test_keys());
+        assert!(other_storage.fetch(&entry.address()).is_err());
+    }
+
+    #[test]
+    fn encrypts_link_tags() {
+        let inner: Arc<RwLock<dyn EntityAttributeValueStorage<Attribute>>> =
+            Arc::new(RwLock::new(EavMemoryStorage::new()));
+        let mut storage = EncryptedEntityAttributeValueStorage::new(inner.clone(), test_keys());
+        let eavi = test_eav();
+        assert_eq!(storage.add_eavi(&eavi).unwrap(), Some(eavi.clone()));
+
+        let query = EaviQuery::new(
+            Some(eavi.entity()).into(),
+            EavFilter::predicate(|attribute| match attribute {
+                Attribute::LinkTag(link_type, _) => link_type == "foo-attribute",
+                _ => false,
+            }),
+            None.into(),
+            IndexFilter::LatestByAttribute,
+            None,
+        );
+        let found = storage.fetch_eavi(&query).unwrap();
+        assert_eq!(found.into_iter().collect::<Vec<_>>(), vec![eavi]);
+
+        let stored = inner
+            .read()
+            .unwrap()
+            .fetch_eavi(&EaviQuery::new(
+                None.into(),
+                None.into(),
+                None.into(),
+                IndexFilter::Range(None, None),
+                None,
+            ))
+            .unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(!format!("{:?}", stored).contains("foo-tag"));
+    }
+
+    #[test]
+    fn keeps_other_attributes_queryable() {
+        let inner = Arc::new(RwLock::new(EavMemoryStorage::new()));
+        let mut storage = EncryptedEntityAttributeValueStorage::new(inner, test_keys());
+        let entity = test_entry_a().address();
+        let header = EntityAttributeValueIndex::new_with_index(
+            &entity,
+            &Attribute::EntryHeader,
+            &test_entry_b().address(),
+            1,
+        )
+        .unwrap();
+        storage.add_eavi(&header).unwrap();
+        storage.add_eavi(&test_eav()).unwrap();
+
+        let query = EaviQuery::new(
+            Some(entity).into(),
+            Some(Attribute::EntryHeader).into(),
+            None.into(),
+            IndexFilter::LatestByAttribute,
+            None,
+        );
+        let found = storage.fetch_eavi(&query).unwrap();
+        assert_eq!(found.into_iter().collect::<Vec<_>>(), vec![header]);
+    }
+
+    fn open_encrypted_file_storages(
+        path: &Path,
+        keys: StorageKeys,
+    ) -> (
+        EncryptedContentAddressableStorage,
+        EncryptedEntityAttributeValueStorage,
+    ) {
+        let (cas, eav) = open_file_storages(&path.join("cas"), &path.join("eav")).unwrap();
+        (
+            EncryptedContentAddressableStorage::new(cas, keys.clone()),
+            EncryptedEntityAttributeValueStorage::new(eav, keys),
+        )
+    }
+
+    #[test]
+    fn opens_and_rekeys_storage_keys() {
+        let dir = tempdir().unwrap();
+        let mut keystore = test_keystore("alice");
+        let keys = open_storage_keys(dir.path(), &mut keystore).unwrap();
+        assert!(dir.path().join(STORAGE_KEYS_FILE).exists());
+        assert!(dir.path().join(STORAGE_ADDRESSES_FILE).exists());
+        let encrypted = keys.encrypt(b"some content").unwrap();
+
+        let reopened = open_storage_keys(dir.path(), &mut keystore).unwrap();
+        assert_eq!(
+            reopened.decrypt(&encrypted).unwrap(),
+            b"some content".to_vec()
+        );
+
+        assert_eq!(
+            rekey_storage(dir.path(), &mut keystore, open_file_storages).unwrap(),
+            2
+        );
+        let rekeyed = open_storage_keys(dir.path(), &mut keystore).unwrap();
+        assert_eq!(rekeyed.keys.len(), 1);
+        assert!(rekeyed.decrypt(&encrypted).is_err());
+        let newer = rekeyed.encrypt(b"newer content").unwrap();
+        assert_eq!(&newer[0..4], &[0, 0, 0, 1]);
+        assert_eq!(rekeyed.decrypt(&newer).unwrap(), b"newer content".to_vec());
+
+        let result = open_storage_keys(dir.path(), &mut test_keystore("bob"));
+        assert!(format!("{:?}", result).contains("another agent"));
+    }
+
+    #[test]
+    fn rekey_re_encrypts_content_and_link_tags() {
+        let dir = tempdir().unwrap();
+        let mut keystore = test_keystore("alice");
+        let entry = test_entry();
+        let eavi = test_eav();
+        {
+            let keys = open_storage_keys(dir.path(), &mut keystore).unwrap();
+            let (mut cas, mut eav) = open_encrypted_file_storages(dir.path(), keys);
+            cas.add(&entry).unwrap();
+            eav.add_eavi(&eavi).unwrap();
+        }
+
+        rekey_storage(dir.path(), &mut keystore, open_file_storages).unwrap();
+        let keys_file: StorageKeysFile =
+            serde_json::from_str(&fs::read_to_string(dir.path().join(STORAGE_KEYS_FILE)).unwrap())
+                .unwrap();
+        assert_eq!(keys_file.first, 1);
+        assert_eq!(keys_file.keys.len(), 1);
+        for leftover in ["cas.rekey", "eav.rekey", "cas.retired", "eav.retired"].iter() {
+            assert!(!dir.path().join(leftover).exists());
+        }
+
+        let keys = open_storage_keys(dir.path(), &mut keystore).unwrap();
+        let (cas, eav) = open_encrypted_file_storages(dir.path(), keys);
+        assert_eq!(cas.fetch(&entry.address()).unwrap(), Some(entry.content()));
+        let stored = EncryptedContent::try_from_content(
+            &cas.inner
+                .read()
+                .unwrap()
+                .fetch(&entry.address())
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(&base64::decode(&stored.data).unwrap()[0..4], &[0, 0, 0, 1]);
+
+        let everything = EaviQuery::new(
+            None.into(),
+            None.into(),
+            None.into(),
+            IndexFilter::Range(None, None),
+            None,
+        );
+        let found = eav.fetch_eavi(&everything).unwrap();
+        assert_eq!(found.into_iter().collect::<Vec<_>>(), vec![eavi]);
+    }
+
+    #[test]
+    fn refuses_to_encrypt_unencrypted_storage() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("cas")).unwrap();
+        fs::write(dir.path().join("cas").join("QmSomething"), "{}").unwrap();
+        let result = open_storage_keys(dir.path(), &mut test_keystore("alice"));
+        assert!(format!("{:?}", result).contains("unencrypted data"));
+        assert!(
+            rekey_storage(dir.path(), &mut test_keystore("alice"), open_file_storages).is_err()
+        );
+    }
+}
@@ -93,7 +93,7 @@
 
 use crate::error::{HolochainInstanceError, HolochainResult};
 use holochain_core::{
-    context::Context,
+    context::{Context, ResultChunkSink},
     instance::Instance,
     nucleus::{call_zome_function, ZomeFnCall},
     persister::{Persister, SimplePersister},
@@ -112,6 +112,7 @@ use holochain_core::{
 };
 use holochain_persistence_api::cas::content::Address;
 use jsonrpc_core::IoHandler;
+use snowflake::ProcessUniqueId;
 use std::sync::Arc;
 
 use holochain_metrics::with_latency_publishing;
@@ -121,6 +122,7 @@ pub struct Holochain {
     instance: Option<Instance>,
     context: Option<Arc<Context>>,
     active: bool,
+    running: bool,
 }
 
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
@@ -168,6 +170,7 @@ impl Holochain {
                     instance: Some(instance),
                     context: Some(new_context),
                     active: false,
+                    running: true,
                 };
                 Ok(hc)
             }
@@ -186,6 +189,7 @@ impl Holochain {
             instance: Some(instance),
             context: Some(new_context),
             active: false,
+            running: true,
         })
     }
 
@@ -233,6 +237,7 @@ impl Holochain {
         }
         self.instance.as_ref().unwrap().stop_action_loop();
         self.active = false;
+        self.running = false;
         Ok(())
     }
 
@@ -242,8 +247,10 @@ impl Holochain {
         cap: CapabilityRequest,
         fn_name: &str,
         params: &str,
+        stream_id: Option<String>,
     ) -> HolochainResult<JsonString> {
-        let zome_call = ZomeFnCall::new(&zome, cap, &fn_name, JsonString::from_json(&params));
+        let zome_call = ZomeFnCall::new(&zome, cap, &fn_name, JsonString::from_json(&params))
+            .with_stream_id(stream_id);
         Ok(context.block_on(call_zome_function(zome_call, context.clone()))?)
     }
 
@@ -264,15 +271,50 @@ impl Holochain {
             zome,
             cap,
             fn_name,
-            params
+            params,
+            None
         )
     }
 
+    /// call a function in a zome, passing the result chunks it emits to the given sink
+    /// while it runs. All chunks have been passed to the sink once this returns.
+    pub fn call_zome_function_streaming(
+        context: Arc<Context>,
+        zome: &str,
+        cap: CapabilityRequest,
+        fn_name: &str,
+        params: &str,
+        sink: ResultChunkSink,
+    ) -> HolochainResult<JsonString> {
+        let stream_id = ProcessUniqueId::new().to_string();
+        context.add_result_chunk_sink(stream_id.clone(), sink);
+        let metric_name = format!("call_zome_function.{}.{}", zome, fn_name);
+        let result = with_latency_publishing!(
+            metric_name,
+            context.metric_publisher,
+            Self::call_inner,
+            context.clone(),
+            zome,
+            cap,
+            fn_name,
+            params,
+            Some(stream_id.clone())
+        );
+        context.remove_result_chunk_sink(&stream_id);
+        result
+    }
+
     /// checks to see if an instance is active
     pub fn active(&self) -> bool {
         self.active
     }
 
+    /// checks to see if the action loop and network of an instance are running,
+    /// which they are from its creation until it gets stopped, active or not
+    pub fn running(&self) -> bool {
+        self.running && self.instance.is_some()
+    }
+
     /// return
     pub fn state(&self) -> Result<StateWrapper, HolochainInstanceError> {
         self.check_instance()?;
@@ -542,6 +584,7 @@ mod tests {
         let (context, _, _) = test_context("bob");
         let mut hc = Holochain::new(dna.clone(), context).unwrap();
         assert!(!hc.active());
+        assert!(hc.running());
 
         // stop when not active returns error
         let result = hc.stop();
@@ -565,6 +608,8 @@ mod tests {
         let result = hc.stop();
         assert!(result.is_ok());
         assert!(!hc.active());
+        // stopping shuts the action loop and network down
+        assert!(!hc.running());
     }
 
     #[test]
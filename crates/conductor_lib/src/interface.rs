@@ -2,12 +2,16 @@ use crate::{conductor::broadcaster::Broadcaster, holo_signing_service::request_s
 use base64;
 use crossbeam_channel::Receiver;
 use holochain_core::{
+    agent::chain_export::ChainExport, context::ResultChunkSink,
     nucleus::actions::call_zome_function::make_cap_request_for_call, state_dump::DumpOptions,
 };
 
 use crate::Holochain;
 use holochain_core_types::{
-    agent::AgentId, dna::capabilities::CapabilityRequest, signature::Provenance,
+    agent::AgentId,
+    dna::{capabilities::CapabilityRequest, zome::Zome},
+    error::HolochainError,
+    signature::Provenance,
 };
 use holochain_dpki::key_bundle::KeyBundle;
 use holochain_json_api::json::JsonString;
@@ -15,16 +19,35 @@ use holochain_locksmith::{Mutex, RwLock};
 use holochain_persistence_api::cas::content::Address;
 use lib3h_sodium::secbuf::SecBuf;
 
-use jsonrpc_core::{self, types::params::Params, IoHandler, Value};
-use std::{collections::HashMap, convert::TryFrom, path::PathBuf, sync::Arc, thread};
+use jsonrpc_core::{
+    self,
+    futures::{future::Either, Future},
+    middleware::{NoopCallFuture, NoopFuture},
+    types::params::Params,
+    Call, IoHandler, Metadata, Middleware, Output, Value,
+};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
 
 use crate::{
-    conductor::{ConductorAdmin, ConductorDebug, ConductorTestAdmin, ConductorUiAdmin, CONDUCTOR},
+    conductor::{
+        passphrase_manager::PassphraseProvider, ConductorAdmin, ConductorDebug, ConductorTestAdmin,
+        ConductorUiAdmin, CONDUCTOR,
+    },
     config::{
         AgentConfiguration, Bridge, DnaConfiguration, InstanceConfiguration,
         InterfaceConfiguration, InterfaceDriver, UiBundleConfiguration, UiInterfaceConfiguration,
     },
-    keystore::{KeyType, Keystore, Secret},
+    keystore::{KeyType, Keystore, Secret, KEYBUNDLE_SIGNKEY_SUFFIX, PRIMARY_KEYBUNDLE_ID},
+    keystore_service::{KeystoreRequest, RemoteKeystore},
 };
 use holochain_dpki::utils::SeedContext;
 use serde_json::{self, map::Map};
@@ -32,6 +55,18 @@ use serde_json::{self, map::Map};
 pub type InterfaceError = String;
 pub type InstanceMap = HashMap<String, Arc<RwLock<Holochain>>>;
 
+/// Maximum number of zome calls of a "call_batch" request that are run at the same time
+pub const MAX_PARALLEL_BATCH_CALLS: usize = 8;
+
+/// Zome functions return their `ZomeApiResult` as JSON, so a call that went through can still
+/// have failed in the zome: its result is then an object with a single `Err` key.
+fn is_zome_error(result: &JsonString) -> bool {
+    match serde_json::from_str::<Value>(&result.to_string()) {
+        Ok(Value::Object(map)) => map.len() == 1 && map.contains_key("Err"),
+        _ => false,
+    }
+}
+
 /// An identifier for an instance that is usable by UI in making calls to the conductor
 /// this type allows us to implement this identifier differently, i.e. as a DNA/agent ID pair, etc
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Hash, Eq)]
@@ -50,6 +85,103 @@ pub trait DispatchRpc {
     fn handler(self) -> IoHandler;
 }
 
+/// Metadata of the requests that a handler built by [ConductorApiBuilder] receives
+pub trait CallerMetadata: Metadata + Default {
+    /// Where the result chunks of a zome call made with this request go, if the caller
+    /// can receive them
+    fn result_chunk_sink(&self) -> Option<ResultChunkSink> {
+        None
+    }
+
+    /// Tells the caller that all result chunks of this request have been sent
+    fn end_result_stream(&self) {}
+}
+
+impl CallerMetadata for () {}
+
+/// Request metadata of interfaces that can send notifications to the calling client.
+/// Lets the "call" method stream result chunks to the connection the call came from only,
+/// as `result_chunk` notifications carrying the id of the request and followed by a
+/// `result_end` notification.
+#[derive(Clone, Default)]
+pub struct InterfaceMetadata {
+    notify: Option<Arc<dyn Fn(String) + Send + Sync>>,
+    request_id: Option<jsonrpc_core::Id>,
+}
+
+impl InterfaceMetadata {
+    pub fn for_connection<F>(notify: F) -> Self
+    where
+        F: Fn(String) + Send + Sync + 'static,
+    {
+        InterfaceMetadata {
+            notify: Some(Arc::new(notify)),
+            request_id: None,
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: jsonrpc_core::Id) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+
+    fn notification(method: &str, request_id: &jsonrpc_core::Id, chunk: Option<String>) -> String {
+        let mut params = json!({ "request_id": request_id });
+        if let Some(chunk) = chunk {
+            params["chunk"] = Value::String(chunk);
+        }
+        json!({"jsonrpc": "2.0", "method": method, "params": params}).to_string()
+    }
+}
+
+impl Metadata for InterfaceMetadata {}
+
+impl CallerMetadata for InterfaceMetadata {
+    fn result_chunk_sink(&self) -> Option<ResultChunkSink> {
+        let notify = self.notify.clone()?;
+        let request_id = self.request_id.clone()?;
+        Some(Arc::new(move |chunk: JsonString| {
+            notify(Self::notification(
+                "result_chunk",
+                &request_id,
+                Some(chunk.to_string()),
+            ))
+        }))
+    }
+
+    fn end_result_stream(&self) {
+        if let (Some(notify), Some(request_id)) = (&self.notify, &self.request_id) {
+            notify(Self::notification("result_end", request_id, None));
+        }
+    }
+}
+
+/// Hands the id of each request to the method handling it through [InterfaceMetadata]
+#[derive(Default)]
+pub struct RequestIdMiddleware;
+
+impl Middleware<InterfaceMetadata> for RequestIdMiddleware {
+    type Future = NoopFuture;
+    type CallFuture = NoopCallFuture;
+
+    fn on_call<F, X>(
+        &self,
+        call: Call,
+        meta: InterfaceMetadata,
+        next: F,
+    ) -> Either<Self::CallFuture, X>
+    where
+        F: Fn(Call, InterfaceMetadata) -> X + Send + Sync,
+        X: Future<Item = Option<Output>, Error = ()> + Send + 'static,
+    {
+        let meta = match call {
+            Call::MethodCall(ref method_call) => meta.with_request_id(method_call.id.clone()),
+            _ => meta,
+        };
+        Either::B(next(call, meta))
+    }
+}
+
 macro_rules! conductor_call {
     ( |$conductor:ident| $call_expr:expr ) => {
         match * CONDUCTOR.lock().unwrap() {
@@ -85,7 +217,9 @@ macro_rules! conductor_call {
 ///
 /// Examples for method names are:
 /// {instance_id}/{zome}/{cap}/{func} -> a zome call
+/// call_batch                        -> several zome calls in one request
 /// info/list_instances               -> Map of InstanceConfigs, keyed by ID
+/// info/zomes                        -> Traits, functions and entry types of an instance's zomes
 /// admin/...                         -> see [with_admin_dna_functions]
 ///
 /// Each interface has their own handler, and each may be configured differently.
@@ -93,29 +227,39 @@ macro_rules! conductor_call {
 ///
 /// Call any sequence of with_* functions on a ConductorApiBuilder object and finalize
 /// with spawn() to retrieve the IoHandler.
-pub struct ConductorApiBuilder {
+/// Handlers for interfaces are built with [with_metadata] so that their requests carry
+/// [InterfaceMetadata].
+pub struct ConductorApiBuilder<M: CallerMetadata = ()> {
     instances: InstanceMap,
     instance_ids_map: PublicInstanceMap,
     instance_configs: HashMap<String, InstanceConfiguration>,
-    io: Box<IoHandler>,
+    io: Box<IoHandler<M>>,
 }
 
-#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
 impl ConductorApiBuilder {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
+        Self::with_metadata()
+    }
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CONDUCTOR_LIB)]
+impl<M: CallerMetadata> ConductorApiBuilder<M> {
+    pub fn with_metadata() -> Self {
         ConductorApiBuilder {
             instances: HashMap::new(),
             instance_ids_map: HashMap::new(),
             instance_configs: HashMap::new(),
-            io: Box::new(IoHandler::new()),
+            io: Box::new(IoHandler::default()),
         }
     }
 
     /// Finish the building and retrieve the populated handler
-    pub fn spawn(mut self) -> IoHandler {
+    pub fn spawn(mut self) -> IoHandler<M> {
         self.setup_info_api();
+        self.setup_info_zomes_api();
         self.setup_call_api();
+        self.setup_call_batch_api();
         *self.io
     }
 
@@ -125,6 +269,7 @@ impl ConductorApiBuilder {
         params: jsonrpc_core::Params,
         instances: InstanceMap,
         instance_ids_map: PublicInstanceMap,
+        result_chunk_sink: Option<ResultChunkSink>,
     ) -> Result<JsonString, jsonrpc_core::Error> {
         // We need to place this one here in order to avoid compiler lifetime issue
         let default_call_args = json!({});
@@ -200,8 +345,24 @@ impl ConductorApiBuilder {
             }
         };
 
-        Holochain::call_zome_function(context, &zome_name, cap_request, &func_name, &args_string)
-            .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))
+        match result_chunk_sink {
+            Some(sink) => Holochain::call_zome_function_streaming(
+                context,
+                &zome_name,
+                cap_request,
+                &func_name,
+                &args_string,
+                sink,
+            ),
+            None => Holochain::call_zome_function(
+                context,
+                &zome_name,
+                cap_request,
+                &func_name,
+                &args_string,
+            ),
+        }
+        .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))
     }
 
     /// Adds a "call" method for making zome function calls
@@ -209,11 +370,141 @@ impl ConductorApiBuilder {
         let instances = self.instances.clone();
         let instance_ids_map = self.instance_ids_map.clone();
 
-        self.io.add_method("call", move |params| {
-            let instances = instances.clone();
-            let instance_ids_map = instance_ids_map.clone();
-            let response = Self::method_call(params, instances, instance_ids_map)?;
-            Ok(Value::String(response.to_string()))
+        self.io
+            .add_method_with_meta("call", move |params, meta: M| {
+                let instances = instances.clone();
+                let instance_ids_map = instance_ids_map.clone();
+                let stream = match params {
+                    Params::Map(ref params_map) => {
+                        Self::get_as_bool("stream", params_map).unwrap_or(false)
+                    }
+                    _ => false,
+                };
+                let result_chunk_sink = if stream {
+                    meta.result_chunk_sink()
+                } else {
+                    None
+                };
+                let streamed = result_chunk_sink.is_some();
+                let response =
+                    Self::method_call(params, instances, instance_ids_map, result_chunk_sink);
+                // All chunks have been sent by now, so the end of the stream reaches the caller
+                // before the response:
+                if streamed {
+                    meta.end_result_stream();
+                }
+                Ok(Value::String(response?.to_string()))
+            });
+    }
+
+    /// Adds a "call_batch" method that takes an array of `calls`, each with the same params as
+    /// "call", and returns an array with one `{"result": ..}` or `{"error": ..}` object per call,
+    /// in the order of the request.
+    /// Calls are run by a pool of at most MAX_PARALLEL_BATCH_CALLS threads, each taking the next
+    /// call as soon as its previous one is done.  If `sequential` is set, they are run one after
+    /// the other instead and the batch stops at the first error, be it an error of the call itself
+    /// or a zome function returning an `Err`, so the response holds the results up to and
+    /// including the failing call.
+    fn setup_call_batch_api(&mut self) {
+        let instances = self.instances.clone();
+        let instance_ids_map = self.instance_ids_map.clone();
+
+        self.io.add_method("call_batch", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let calls: Vec<Params> = params_map
+                .get("calls")
+                .and_then(Value::as_array)
+                .ok_or_else(|| {
+                    jsonrpc_core::Error::invalid_params("`calls` param not provided as an array")
+                })?
+                .iter()
+                .map(|call| match call {
+                    Value::Object(map) => Params::Map(map.clone()),
+                    _ => Params::None,
+                })
+                .collect();
+            let sequential = params_map
+                .get("sequential")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+
+            let responses = if sequential {
+                let mut responses = Vec::new();
+                for call in calls {
+                    let response =
+                        Self::method_call(call, instances.clone(), instance_ids_map.clone(), None);
+                    let failed = response.as_ref().map(is_zome_error).unwrap_or(true);
+                    responses.push(response);
+                    if failed {
+                        break;
+                    }
+                }
+                responses
+            } else {
+                let call_count = calls.len();
+                let calls = Arc::new(calls);
+                let next_call = Arc::new(AtomicUsize::new(0));
+                let (response_sender, response_receiver) = crossbeam_channel::unbounded();
+                for _ in 0..call_count.min(MAX_PARALLEL_BATCH_CALLS) {
+                    let calls = calls.clone();
+                    let next_call = next_call.clone();
+                    let response_sender = response_sender.clone();
+                    let instances = instances.clone();
+                    let instance_ids_map = instance_ids_map.clone();
+                    let spawned =
+                        thread::Builder::new()
+                            .name("call_batch".to_string())
+                            .spawn(move || loop {
+                                let index = next_call.fetch_add(1, Ordering::SeqCst);
+                                let call = match calls.get(index) {
+                                    Some(call) => call.clone(),
+                                    None => break,
+                                };
+                                let response = Self::method_call(
+                                    call,
+                                    instances.clone(),
+                                    instance_ids_map.clone(),
+                                    None,
+                                );
+                                if response_sender.send((index, response)).is_err() {
+                                    break;
+                                }
+                            });
+                    if let Err(e) = spawned {
+                        error!("Could not spawn call_batch thread: {}", e);
+                    }
+                }
+                // Once all workers are done, the receiver is disconnected:
+                drop(response_sender);
+
+                let mut responses: Vec<Option<Result<JsonString, jsonrpc_core::Error>>> =
+                    (0..call_count).map(|_| None).collect();
+                for (index, response) in response_receiver.iter() {
+                    responses[index] = Some(response);
+                }
+                // Calls without a response were taken by a worker that panicked, or not taken
+                // at all because no worker could be spawned:
+                responses
+                    .into_iter()
+                    .map(|response| {
+                        response.unwrap_or_else(|| {
+                            let mut error = jsonrpc_core::Error::internal_error();
+                            error.message = "zome call thread panicked".to_string();
+                            Err(error)
+                        })
+                    })
+                    .collect()
+            };
+
+            Ok(Value::Array(
+                responses
+                    .into_iter()
+                    .map(|response| match response {
+                        Ok(result) => json!({ "result": result.to_string() }),
+                        Err(error) => json!({ "error": error }),
+                    })
+                    .collect(),
+            ))
         });
     }
 
@@ -241,6 +532,86 @@ impl ConductorApiBuilder {
         });
     }
 
+    /// Adds a "info/zomes" method that, given an `instance_id`, returns a JSON object describing
+    /// every zome of that instance's DNA, keyed by zome name: its traits, its functions with
+    /// their parameter and return types and the traits they are exposed through, and its entry
+    /// types with their link definitions.
+    fn setup_info_zomes_api(&mut self) {
+        let instances = self.instances.clone();
+        let instance_ids_map = self.instance_ids_map.clone();
+
+        self.io.add_method("info/zomes", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let instance_id = Self::get_as_string("instance_id", &params_map)?;
+            let public_id_str = PublicInstanceIdentifier::from(instance_id);
+            let instance = instance_ids_map
+                .get(&public_id_str)
+                .and_then(|id| instances.get(id))
+                .ok_or_else(|| {
+                    jsonrpc_core::Error::invalid_params(format!(
+                        "instance identifier invalid: {:?}",
+                        public_id_str
+                    ))
+                })?;
+            let dna = instance
+                .read()
+                .unwrap()
+                .context()
+                .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?
+                .get_dna()
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("instance has no DNA"))?;
+            let zomes: Map<String, Value> = dna
+                .zomes
+                .iter()
+                .map(|(zome_name, zome)| (zome_name.clone(), Self::describe_zome(zome)))
+                .collect();
+            Ok(Value::Object(zomes))
+        });
+    }
+
+    fn describe_zome(zome: &Zome) -> Value {
+        let functions: Vec<Value> = zome
+            .fn_declarations
+            .iter()
+            .map(|function| {
+                let traits: Vec<&String> = zome
+                    .traits
+                    .iter()
+                    .filter(|(_, trait_fns)| trait_fns.functions.contains(&function.name))
+                    .map(|(trait_name, _)| trait_name)
+                    .collect();
+                json!({
+                    "name": function.name,
+                    "inputs": function.inputs,
+                    "outputs": function.outputs,
+                    "traits": traits,
+                })
+            })
+            .collect();
+        let entry_types: Map<String, Value> = zome
+            .entry_types
+            .iter()
+            .map(|(entry_type, entry_type_def)| {
+                (
+                    entry_type.to_string(),
+                    json!({
+                        "description": String::from(entry_type_def.properties.clone()),
+                        "sharing": entry_type_def.sharing,
+                        "schema": entry_type_def.schema.clone().map(String::from),
+                        "links_to": entry_type_def.links_to,
+                        "linked_from": entry_type_def.linked_from,
+                    }),
+                )
+            })
+            .collect();
+        json!({
+            "description": zome.description,
+            "traits": zome.traits,
+            "functions": functions,
+            "entry_types": entry_types,
+        })
+    }
+
     /// Add a [InstanceConfig](struct.InstanceConfig.html) for a custom named instance
     pub fn with_named_instance_config(
         mut self,
@@ -372,6 +743,8 @@ impl ConductorApiBuilder {
     ///
     ///  * `admin/dna/install_from_file`:
     ///     Installs a DNA from a given local file.
+    ///     If the conductor config lists `trusted_dna_authors`, the DNA package has to be
+    ///     signed by one of them.
     ///     Params:
     ///     * `id`: [string] internal handle/name of the newly created DNA config
     ///     * `path`: [string] local file path to DNA file
@@ -413,6 +786,14 @@ impl ConductorApiBuilder {
     ///     Params:
     ///     * `id`: [string] Which instance to stop?
     ///
+    ///  * `admin/instance/import_chain`
+    ///     Replaces the genesis chain of a newly added, stopped instance with a chain exported by
+    ///     `hc chain export` (as JSON) for the same DNA and agent. Every entry gets validated and
+    ///     published again. Fails without changing the chain if anything is invalid.
+    ///     Params:
+    ///     * `id`: [string] Which instance to import the chain into?
+    ///     * `path`: [string] local file path to the exported chain
+    ///
     ///  * `admin/instance/list`
     ///     Returns an array of all instances that are configured.
     ///
@@ -579,6 +960,24 @@ impl ConductorApiBuilder {
             Ok(json!({"success": true}))
         });
 
+        self.io
+            .add_method("admin/instance/import_chain", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let path = Self::get_as_string("path", &params_map)?;
+                let chain: ChainExport = std::fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+                    .map_err(|e| {
+                        jsonrpc_core::Error::invalid_params(format!(
+                            "Could not read exported chain from {}: {}",
+                            path, e
+                        ))
+                    })?;
+                conductor_call!(|c| c.import_chain(&id, chain))?;
+                Ok(json!({"success": true}))
+            });
+
         self.io.add_method("admin/instance/list", move |_params| {
             let instances = conductor_call!(
                 |c| Ok(c.config().instances) as Result<Vec<InstanceConfiguration>, String>
@@ -1068,6 +1467,30 @@ impl ConductorApiBuilder {
         self
     }
 
+    /// Adds the reply method of the "admin" passphrase service to admin interfaces
+    ///
+    /// - `admin/passphrase/provide`
+    ///     Answers a `PassphraseRequest` signal. Does not need the conductor lock, so it
+    ///     also works while the conductor is still booting.
+    ///     Params:
+    ///         - passphrase [String] Passphrase to unlock the keystores with
+    ///     Fails if no passphrase has been requested.
+    pub fn with_passphrase_provider(mut self, passphrase_provider: PassphraseProvider) -> Self {
+        self.io
+            .add_method("admin/passphrase/provide", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let passphrase = Self::get_as_string("passphrase", &params_map)?;
+                passphrase_provider.provide(passphrase).map_err(|e| {
+                    let mut error = jsonrpc_core::Error::internal_error();
+                    error.message = e.to_string();
+                    error
+                })?;
+                Ok(json!({"success": true}))
+            });
+
+        self
+    }
+
     pub fn with_outsource_signing_callback(
         mut self,
         agent_id: AgentId,
@@ -1134,7 +1557,63 @@ impl ConductorApiBuilder {
         self
     }
 
+    /// Adds `agent/sign`, `agent/encrypt`, `agent/decrypt` and the `agent/keystore/*`
+    /// functions, served by a keystore in memory.
+    /// The agent's keybundle is taken from the keystore on every call instead of being
+    /// held here, so wiping the keystore's cache really removes the decrypted keys.
     pub fn with_agent_keystore_functions(mut self, keystore: Arc<Mutex<Keystore>>) -> Self {
+        let k = keystore.clone();
+        self.io.add_method("agent/sign", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let payload = Self::get_as_crypto_string("payload", &params_map)?;
+            let signature = k
+                .lock()
+                .unwrap()
+                .sign(
+                    &[PRIMARY_KEYBUNDLE_ID, KEYBUNDLE_SIGNKEY_SUFFIX].join(""),
+                    payload,
+                )
+                .map_err(|_| jsonrpc_core::Error::internal_error())?;
+            Ok(json!({ "signature": String::from(signature) }))
+        });
+
+        let k = keystore.clone();
+        self.io.add_method("agent/encrypt", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let payload = Self::get_as_crypto_string("payload", &params_map)?;
+            let mut message = SecBuf::with_insecure_from_string(payload);
+            let mut encrypted_message = k
+                .lock()
+                .unwrap()
+                .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+                .and_then(|mut keybundle| keybundle.encrypt(&mut message))
+                .map_err(|_| jsonrpc_core::Error::internal_error())?;
+            let encrypted_message = encrypted_message.read_lock();
+            Ok(json!({ "message": base64::encode(&**encrypted_message) }))
+        });
+
+        let k = keystore.clone();
+        self.io.add_method("agent/decrypt", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let payload = Self::get_as_crypto_string("payload", &params_map)?;
+            let decoded_message = base64::decode(&payload)
+                .map_err(|_| jsonrpc_core::Error::new(jsonrpc_core::ErrorCode::InternalError))?;
+            let mut decoded_message_buf = SecBuf::with_insecure(decoded_message.len());
+            decoded_message_buf
+                .from_array(&decoded_message)
+                .map_err(|_| jsonrpc_core::Error::new(jsonrpc_core::ErrorCode::InternalError))?;
+            let mut decrypted_buf = k
+                .lock()
+                .unwrap()
+                .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+                .and_then(|mut keybundle| keybundle.decrypt(&mut decoded_message_buf))
+                .map_err(|_| jsonrpc_core::Error::new(jsonrpc_core::ErrorCode::InternalError))?;
+            let decrypted_bytes = decrypted_buf.read_lock();
+            let decrypted_string = std::str::from_utf8(&**decrypted_bytes)
+                .map_err(|_| jsonrpc_core::Error::new(jsonrpc_core::ErrorCode::InternalError))?;
+            Ok(json!({ "message": decrypted_string }))
+        });
+
         let k = keystore.clone();
         self.io.add_method("agent/keystore/list", move |_params| {
             Ok(serde_json::Value::Array(
@@ -1258,6 +1737,112 @@ impl ConductorApiBuilder {
 
         self
     }
+
+    /// Adds `agent/sign`, `agent/encrypt`, `agent/decrypt` and the `agent/keystore/*`
+    /// functions, all served by an `hc-keystore` daemon instead of a keystore in memory.
+    pub fn with_remote_keystore_functions(mut self, remote: Arc<RemoteKeystore>) -> Self {
+        fn keystore_error(error: HolochainError) -> jsonrpc_core::Error {
+            jsonrpc_core::Error::invalid_params(format!("keystore error: {}", error))
+        }
+
+        let r = remote.clone();
+        self.io.add_method("agent/sign", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let payload = Self::get_as_crypto_string("payload", &params_map)?;
+            let signature = r
+                .sign(
+                    &[PRIMARY_KEYBUNDLE_ID, KEYBUNDLE_SIGNKEY_SUFFIX].join(""),
+                    payload,
+                )
+                .map_err(keystore_error)?;
+            Ok(json!({ "signature": String::from(signature) }))
+        });
+
+        let r = remote.clone();
+        self.io.add_method("agent/encrypt", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let payload = Self::get_as_crypto_string("payload", &params_map)?;
+            let encrypted_message = r
+                .encrypt(PRIMARY_KEYBUNDLE_ID, payload)
+                .map_err(keystore_error)?;
+            Ok(json!({ "message": encrypted_message }))
+        });
+
+        let r = remote.clone();
+        self.io.add_method("agent/decrypt", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let payload = Self::get_as_crypto_string("payload", &params_map)?;
+            let decrypted_message = r
+                .decrypt(PRIMARY_KEYBUNDLE_ID, payload)
+                .map_err(keystore_error)?;
+            Ok(json!({ "message": decrypted_message }))
+        });
+
+        let r = remote.clone();
+        self.io.add_method("agent/keystore/list", move |_params| {
+            r.request(KeystoreRequest::List).map_err(keystore_error)
+        });
+
+        let r = remote.clone();
+        self.io
+            .add_method("agent/keystore/add_random_seed", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                r.request(KeystoreRequest::AddRandomSeed {
+                    dst_id: Self::get_as_string("dst_id", &params_map)?,
+                    size: Self::get_as_int("size", &params_map)? as usize,
+                })
+                .map_err(keystore_error)
+            });
+
+        let r = remote.clone();
+        self.io
+            .add_method("agent/keystore/add_seed_from_seed", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                r.request(KeystoreRequest::AddSeedFromSeed {
+                    src_id: Self::get_as_string("src_id", &params_map)?,
+                    dst_id: Self::get_as_string("dst_id", &params_map)?,
+                    context: Self::get_as_string("context", &params_map)?,
+                    index: Self::get_as_int("index", &params_map)? as u64,
+                })
+                .map_err(keystore_error)
+            });
+
+        let r = remote.clone();
+        self.io
+            .add_method("agent/keystore/add_key_from_seed", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let pub_key = r
+                    .request(KeystoreRequest::AddKeyFromSeed {
+                        src_id: Self::get_as_string("src_id", &params_map)?,
+                        dst_id: Self::get_as_string("dst_id", &params_map)?,
+                        key_type: Self::get_as_string("key_type", &params_map)?,
+                    })
+                    .map_err(keystore_error)?;
+                Ok(json!({ "pub_key": pub_key }))
+            });
+
+        let r = remote.clone();
+        self.io.add_method("agent/keystore/sign", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let src_id = Self::get_as_string("src_id", &params_map)?;
+            let payload = Self::get_as_string("payload", &params_map)?;
+            let signature = r.sign(&src_id, payload).map_err(keystore_error)?;
+            Ok(json!({ "signature": String::from(signature) }))
+        });
+
+        self.io
+            .add_method("agent/keystore/get_public_key", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let pub_key = remote
+                    .request(KeystoreRequest::GetPublicKey {
+                        src_id: Self::get_as_string("src_id", &params_map)?,
+                    })
+                    .map_err(keystore_error)?;
+                Ok(json!({ "pub_key": pub_key }))
+            });
+
+        self
+    }
 }
 
 /// A Broadcaster is something that knows how to send a Signal back to a client.
@@ -1266,7 +1851,7 @@ impl ConductorApiBuilder {
 pub trait Interface {
     fn run(
         &mut self,
-        handler: IoHandler,
+        handler: IoHandler<InterfaceMetadata>,
         kill_switch: Receiver<()>,
     ) -> Result<(Broadcaster, thread::JoinHandle<()>), String>;
 }
@@ -1274,7 +1859,9 @@ pub trait Interface {
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::{conductor::tests::test_conductor, config::Configuration};
+    use crate::{
+        conductor::tests::test_conductor, config::Configuration, key_loaders::test_keystore,
+    };
     use holochain_dpki::SEED_SIZE;
 
     fn example_config_and_instances() -> (Configuration, InstanceMap) {
@@ -1327,6 +1914,43 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_agent_rpc_uses_the_keystore_after_a_wipe() {
+        let keystore = Arc::new(Mutex::new(test_keystore("bob")));
+        let handler = ConductorApiBuilder::new()
+            .with_agent_keystore_functions(keystore.clone())
+            .spawn();
+        let sign = || {
+            unwrap_response_if_valid(&handler
+                .handle_request_sync(r#"{"jsonrpc":"2.0","method":"agent/sign","params":{"payload":"dGVzdCAiIHBheWxvYWQ="},"id":"puid-0-1"}"#)
+                .expect("Invalid call to handler"))
+        };
+        let signature = sign();
+        assert!(signature.contains("signature"));
+
+        // the keys are decrypted again instead of being kept by the interface
+        keystore.lock().unwrap().wipe_cache();
+        assert_eq!(signature, sign());
+
+        let response_str = handler
+            .handle_request_sync(r#"{"jsonrpc":"2.0","method":"agent/encrypt","params":{"payload":"dGVzdCAiIHBheWxvYWQ="},"id":"puid-0-2"}"#)
+            .expect("Invalid call to handler");
+        let encrypted: serde_json::Value =
+            serde_json::from_str(&unwrap_response_if_valid(&response_str)).unwrap();
+        keystore.lock().unwrap().wipe_cache();
+        let response_str = handler
+            .handle_request_sync(&format!(
+                r#"{{"jsonrpc":"2.0","method":"agent/decrypt","params":{{"payload":"{}"}},"id":"puid-0-3"}}"#,
+                // the payload of every agent function is base64 encoded once more
+                base64::encode(encrypted["message"].as_str().unwrap())
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            unwrap_response_if_valid(&response_str),
+            r#"{"message":"test \" payload"}"#
+        );
+    }
+
     #[test]
     fn test_new_dispatcher() {
         let (config, instances) = example_config_and_instances();
@@ -1382,6 +2006,134 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_rpc_call_batch_method() {
+        let (config, instances) = example_config_and_instances();
+        let handler = ConductorApiBuilder::new()
+            .with_instances(instances.clone())
+            .with_instance_configs(config.instances)
+            .spawn();
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str("call_batch", Some(json!({}))))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"`calls` param not provided as an array"},"id":"0"}"#
+        );
+
+        let calls = json!([
+            {"instance_id": "bad instance id", "zome": "greeter", "function": "hello"},
+            {"instance_id": "test-instance-1"},
+        ]);
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "call_batch",
+                Some(json!({ "calls": calls })),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            unwrap_response_if_valid(&response_str),
+            r#"[{"error":{"code":-32602,"message":"instance identifier invalid: PublicInstanceIdentifier(\"bad instance id\")"}},{"error":{"code":-32602,"message":"`zome` param not provided"}}]"#
+        );
+
+        // Sequential batches stop at the first error
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "call_batch",
+                Some(json!({ "calls": calls, "sequential": true })),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            unwrap_response_if_valid(&response_str),
+            r#"[{"error":{"code":-32602,"message":"instance identifier invalid: PublicInstanceIdentifier(\"bad instance id\")"}}]"#
+        );
+
+        // The worker pool answers in the order of the request even with more calls than workers
+        let calls: Vec<_> = (0..MAX_PARALLEL_BATCH_CALLS * 3)
+            .map(|i| json!({"instance_id": format!("instance {}", i), "zome": "greeter", "function": "hello"}))
+            .collect();
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "call_batch",
+                Some(json!({ "calls": calls })),
+            ))
+            .expect("Invalid call to handler");
+        let responses: Vec<Value> =
+            serde_json::from_str(&unwrap_response_if_valid(&response_str)).unwrap();
+        assert_eq!(responses.len(), MAX_PARALLEL_BATCH_CALLS * 3);
+        for (i, response) in responses.iter().enumerate() {
+            assert_eq!(
+                response["error"]["message"],
+                format!(
+                    "instance identifier invalid: PublicInstanceIdentifier(\"instance {}\")",
+                    i
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_zome_error() {
+        assert!(is_zome_error(&JsonString::from_json(
+            r#"{"Err":{"Internal":"oops"}}"#
+        )));
+        assert!(!is_zome_error(&JsonString::from_json(r#"{"Ok":"hello"}"#)));
+        assert!(!is_zome_error(&JsonString::from_json(
+            r#"{"Err":1,"Ok":2}"#
+        )));
+        assert!(!is_zome_error(&JsonString::from_json(r#""Err""#)));
+    }
+
+    #[test]
+    fn test_rpc_info_zomes() {
+        let (config, instances) = example_config_and_instances();
+        let handler = ConductorApiBuilder::new()
+            .with_instances(instances.clone())
+            .with_instance_configs(config.instances)
+            .spawn();
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "info/zomes",
+                Some(json!({"instance_id" : "bad instance id"})),
+            ))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"instance identifier invalid: PublicInstanceIdentifier(\"bad instance id\")"},"id":"0"}"#
+        );
+
+        let response_str = handler
+            .handle_request_sync(&create_call_str(
+                "info/zomes",
+                Some(json!({"instance_id" : "test-instance-1"})),
+            ))
+            .expect("Invalid call to handler");
+        let result: serde_json::Value =
+            serde_json::from_str(&unwrap_response_if_valid(&response_str)).unwrap();
+        let hello = result["greeter"]["functions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|function| function["name"] == "hello")
+            .expect("hello function should be described")
+            .clone();
+        assert_eq!(
+            hello,
+            json!({
+                "name": "hello",
+                "inputs": [],
+                "outputs": [{"name": "greeting", "type": "String"}],
+                "traits": ["greetable", "hc_public"],
+            })
+        );
+        assert_eq!(
+            result["greeter"]["traits"]["greetable"],
+            json!({"functions": ["hello"]})
+        );
+    }
+
     #[test]
     fn test_rpc_call_method() {
         let (config, instances) = example_config_and_instances();
@@ -1512,4 +2264,98 @@ pub mod tests {
             r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Holochain Instance Error: Holochain instance is not active yet."},"id":"0"}"#
         );
     }
+
+    #[test]
+    fn test_rpc_call_stream_notifies_caller() {
+        let (config, instances) = example_config_and_instances();
+        let mut handler = jsonrpc_core::MetaIoHandler::with_middleware(RequestIdMiddleware);
+        handler.extend_with(
+            ConductorApiBuilder::with_metadata()
+                .with_instances(instances.clone())
+                .with_instance_configs(config.instances)
+                .spawn(),
+        );
+
+        let caller_notifications = Arc::new(Mutex::new(Vec::new()));
+        let connection = |notifications: &Arc<Mutex<Vec<String>>>| {
+            let notifications = notifications.clone();
+            InterfaceMetadata::for_connection(move |notification| {
+                notifications.lock().unwrap().push(notification)
+            })
+        };
+        let call = |stream: bool| {
+            create_call_str(
+                "call",
+                Some(json!({
+                    "instance_id" : "test-instance-1",
+                    "zome" : "greeter",
+                    "function" : "hello",
+                    "stream" : stream,
+                })),
+            )
+        };
+
+        handler
+            .handle_request_sync(&call(false), connection(&caller_notifications))
+            .expect("Invalid call to handler");
+        assert!(caller_notifications.lock().unwrap().is_empty());
+
+        let response_str = handler
+            .handle_request_sync(&call(true), connection(&caller_notifications))
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Holochain Instance Error: Holochain instance is not active yet."},"id":"0"}"#
+        );
+        let notifications = caller_notifications.lock().unwrap().clone();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&notifications[0]).unwrap(),
+            json!({"jsonrpc": "2.0", "method": "result_end", "params": {"request_id": "0"}})
+        );
+    }
+
+    #[test]
+    fn test_rpc_passphrase_provide() {
+        use crate::conductor::passphrase_manager::{PassphraseService, PassphraseServiceAdmin};
+        use std::time::Duration;
+
+        let service = PassphraseServiceAdmin::new(
+            Arc::new(RwLock::new(HashMap::new())),
+            vec!["admin interface".to_string()],
+        );
+        let handler = ConductorApiBuilder::new()
+            .with_passphrase_provider(service.provider())
+            .spawn();
+        let provide_call = create_call_str(
+            "admin/passphrase/provide",
+            Some(json!({"passphrase": "passphrase"})),
+        );
+
+        let response_str = handler
+            .handle_request_sync(&provide_call)
+            .expect("Invalid call to handler");
+        assert_eq!(
+            response_str,
+            r#"{"jsonrpc":"2.0","error":{"code":-32603,"message":"No passphrase has been requested"},"id":"0"}"#
+        );
+
+        let request = thread::spawn(move || service.request_passphrase());
+        loop {
+            let response_str = handler
+                .handle_request_sync(&provide_call)
+                .expect("Invalid call to handler");
+            if unwrap_response_if_valid(&response_str) == r#"{"success":true}"# {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let mut passphrase = request.join().unwrap().unwrap();
+        assert_eq!(
+            passphrase.compare(&mut SecBuf::with_insecure_from_string(
+                "passphrase".to_string()
+            )),
+            0
+        );
+    }
 }
@@ -1,4 +1,7 @@
-use crate::{conductor::broadcaster::Broadcaster, interface::Interface};
+use crate::{
+    conductor::broadcaster::Broadcaster,
+    interface::{Interface, InterfaceMetadata},
+};
 use crossbeam_channel::Receiver;
 use jsonrpc_core::IoHandler;
 use jsonrpc_http_server::ServerBuilder;
@@ -27,7 +30,7 @@ impl HttpInterface {
 impl Interface for HttpInterface {
     fn run(
         &mut self,
-        handler: IoHandler,
+        handler: IoHandler<InterfaceMetadata>,
         kill_switch: Receiver<()>,
     ) -> Result<(Broadcaster, thread::JoinHandle<()>), String> {
         let url = format!("0.0.0.0:{}", self.port);
@@ -1,7 +1,10 @@
-use crate::{conductor::broadcaster::Broadcaster, interface::Interface};
+use crate::{
+    conductor::broadcaster::Broadcaster,
+    interface::{Interface, InterfaceMetadata, RequestIdMiddleware},
+};
 use crossbeam_channel::Receiver;
-use jsonrpc_core::IoHandler;
-use jsonrpc_ws_server::ServerBuilder;
+use jsonrpc_core::{IoHandler, MetaIoHandler};
+use jsonrpc_ws_server::{RequestContext, ServerBuilder};
 use std::{net::SocketAddr, thread};
 use tokio::runtime::Runtime;
 
@@ -27,15 +30,26 @@ impl WebsocketInterface {
 impl Interface for WebsocketInterface {
     fn run(
         &mut self,
-        handler: IoHandler,
+        handler: IoHandler<InterfaceMetadata>,
         kill_switch: Receiver<()>,
     ) -> Result<(Broadcaster, thread::JoinHandle<()>), String> {
         let url = format!("0.0.0.0:{}", self.port);
         let runtime = Runtime::new().map_err(|e| e.to_string())?;
-        let server = ServerBuilder::new(handler)
-            .event_loop_executor(runtime.executor())
-            .start(&url.parse().expect("Invalid URL!"))
-            .map_err(|e| e.to_string())?;
+        let mut io = MetaIoHandler::with_middleware(RequestIdMiddleware);
+        io.extend_with(MetaIoHandler::from(handler));
+        // Notifications for a request are queued on its connection before the response,
+        // so the client receives them first.
+        let server = ServerBuilder::with_meta_extractor(io, |context: &RequestContext| {
+            let out = context.out.clone();
+            InterfaceMetadata::for_connection(move |notification| {
+                if let Err(error) = out.send(notification) {
+                    warn!("Could not send notification over websocket: {:?}", error);
+                }
+            })
+        })
+        .event_loop_executor(runtime.executor())
+        .start(&url.parse().expect("Invalid URL!"))
+        .map_err(|e| e.to_string())?;
         self.bound_address = Some(*server.addr());
         let broadcaster = Broadcaster::Ws(server.broadcaster());
         let handle = thread::Builder::new()
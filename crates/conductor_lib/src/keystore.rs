@@ -7,7 +7,7 @@ use holochain_dpki::{
     key_blob::{BlobType, Blobbable, KeyBlob},
     key_bundle::KeyBundle,
     keypair::{EncryptingKeyPair, KeyPair, SigningKeyPair},
-    seed::Seed,
+    seed::{MnemonicableSeed, RootSeed, Seed, SeedTrait},
     utils::{
         decrypt_with_passphrase_buf, encrypt_with_passphrase_buf, generate_derived_seed_buf,
         generate_random_buf, SeedContext,
@@ -35,10 +35,11 @@ const PCHECK_HEADER_SIZE: usize = 8;
 const PCHECK_HEADER: [u8; 8] = *b"PHCCHECK";
 const PCHECK_RANDOM_SIZE: usize = 32;
 const PCHECK_SIZE: usize = PCHECK_RANDOM_SIZE + PCHECK_HEADER_SIZE;
-const KEYBUNDLE_SIGNKEY_SUFFIX: &str = ":sign_key";
-const KEYBUNDLE_ENCKEY_SUFFIX: &str = ":enc_key";
+pub const KEYBUNDLE_SIGNKEY_SUFFIX: &str = ":sign_key";
+pub const KEYBUNDLE_ENCKEY_SUFFIX: &str = ":enc_key";
 pub const PRIMARY_KEYBUNDLE_ID: &str = "primary_keybundle";
 pub const STANDALONE_ROOT_SEED: &str = "root_seed";
+const STORAGE_KEY_CONTEXT: [u8; 8] = *b"HCSTORAG";
 
 pub enum Secret {
     SigningKey(SigningKeyPair),
@@ -90,7 +91,7 @@ pub struct Keystore {
     // The following fields are transient, i.e. not serialized to the keystore file:
     /// Using a secret from [secrets] will result in decrypting the secret and
     /// storing it in this cache.
-    /// TODO: maybe clear the cache for certain (not agent keys) items after some time?
+    /// It gets wiped by [wipe_cache] when the passphrase manager forgets the passphrase.
     #[serde(skip_serializing, skip_deserializing)]
     cache: HashMap<String, Arc<Mutex<Secret>>>,
 
@@ -149,6 +150,39 @@ impl Keystore {
         Ok((keystore, pub_key))
     }
 
+    /// Rebuild a "standalone" keystore from the mnemonic of its root seed, see
+    /// [get_root_seed_mnemonic]. The primary keybundle derived from the seed, and so the
+    /// agent address, is the same as in the original keystore.
+    pub fn new_standalone_from_mnemonic(
+        passphrase_manager: Arc<PassphraseManager>,
+        phrase: &str,
+        hash_config: Option<PwHashConfig>,
+    ) -> HcResult<(Self, Base32)> {
+        // tolerate the line breaks and extra spaces of a phrase copied from paper.
+        // new_with_mnemonic zeroes this copy once it has read the seed from it
+        let phrase = phrase.split_whitespace().collect::<Vec<&str>>().join(" ");
+        let seed = Seed::new_with_mnemonic(phrase, SeedType::Root)?;
+        Keystore::new_standalone_from_root_seed(
+            passphrase_manager,
+            &mut RootSeed::new(seed.buf),
+            hash_config,
+        )
+    }
+
+    /// Rebuild a "standalone" keystore from its root seed, e.g. as recombined from
+    /// the shares of [RootSeed::split]
+    pub fn new_standalone_from_root_seed(
+        passphrase_manager: Arc<PassphraseManager>,
+        root_seed: &mut RootSeed,
+        hash_config: Option<PwHashConfig>,
+    ) -> HcResult<(Self, Base32)> {
+        let mut keystore = Keystore::new(passphrase_manager, hash_config)?;
+        keystore.add_seed(STANDALONE_ROOT_SEED, &*root_seed.seed_mut().buf.read_lock())?;
+        let (pub_key, _) =
+            keystore.add_keybundle_from_seed(STANDALONE_ROOT_SEED, PRIMARY_KEYBUNDLE_ID)?;
+        Ok((keystore, pub_key))
+    }
+
     /// Load a keystore from file.
     /// This won't ask for a passphrase until a secret is used via the other functions.
     /// Secrets will get loaded to memory instantly but stay encrypted until requested.
@@ -285,6 +319,11 @@ impl Keystore {
         Ok(())
     }
 
+    /// Drops all decrypted secrets so that using them again requires the passphrase.
+    pub fn wipe_cache(&mut self) {
+        self.cache.clear();
+    }
+
     /// return a list of the identifiers stored in the keystore
     pub fn list(&self) -> Vec<String> {
         self.secrets.keys().map(|k| k.to_string()).collect()
@@ -334,6 +373,8 @@ impl Keystore {
 
         if !self.cache.contains_key(&src_id) {
             self.decrypt(&src_id)?;
+        } else if let Some(ref passphrase_manager) = self.passphrase_manager {
+            passphrase_manager.note_activity();
         }
 
         Ok(self.cache.get(&src_id).unwrap().clone()) // unwrap ok because we made sure src exists
@@ -512,6 +553,57 @@ impl Keystore {
         Ok(KeyBundle::new(sign_key, enc_key)?)
     }
 
+    /// returns a copy of the root seed of a "standalone" keystore,
+    /// e.g. to split it into shares with [RootSeed::split]
+    pub fn get_root_seed(&mut self) -> HcResult<RootSeed> {
+        let secret = self.get(STANDALONE_ROOT_SEED)?;
+        let mut secret = secret.lock().unwrap();
+        match *secret {
+            Secret::Seed(ref mut seed_buf) => {
+                let mut root_seed_buf = SecBuf::with_secure(seed_buf.len());
+                root_seed_buf.from_array(&*seed_buf.read_lock())?;
+                Ok(RootSeed::new(root_seed_buf))
+            }
+            _ => Err(HolochainError::ErrorGeneric(
+                "root seed secret is not a seed".to_string(),
+            )),
+        }
+    }
+
+    /// returns the root seed of a "standalone" keystore as a 24 word BIP39 mnemonic,
+    /// from which [new_standalone_from_mnemonic] can rebuild the keystore
+    pub fn get_root_seed_mnemonic(&mut self) -> HcResult<String> {
+        self.get_root_seed()?.seed_mut().get_mnemonic()
+    }
+
+    /// derives the key that protects the keys of an encrypted instance storage from the
+    /// seed of the primary signing key, so it can only be recomputed by unlocking the keystore.
+    /// Every `generation` (starting at 1) gives another key.
+    pub fn get_storage_key(&mut self, generation: u64) -> HcResult<SecBuf> {
+        let src_sign_id_str = [PRIMARY_KEYBUNDLE_ID, KEYBUNDLE_SIGNKEY_SUFFIX].join("");
+        let secret = self.get(&src_sign_id_str)?;
+        let mut secret = secret.lock().unwrap();
+        match *secret {
+            Secret::SigningKey(ref mut key_pair) => {
+                // an ed25519 private key starts with the seed it was generated from
+                let mut seed = SecBuf::with_secure(SEED_SIZE);
+                {
+                    let private_key = key_pair.private().read_lock();
+                    seed.write(0, &private_key[0..SEED_SIZE])?;
+                }
+                generate_derived_seed_buf(
+                    &mut seed,
+                    &SeedContext::new(STORAGE_KEY_CONTEXT),
+                    generation,
+                    SEED_SIZE,
+                )
+            }
+            _ => Err(HolochainError::ErrorGeneric(
+                "source secret is not a signing key".to_string(),
+            )),
+        }
+    }
+
     /// signs some data using a keypair in the keystore
     /// returns the signature
     pub fn sign(&mut self, src_id_str: &str, data: String) -> HcResult<Signature> {
@@ -820,6 +912,110 @@ pub mod tests {
         assert!(key_bundle.enc_keys.is_same(&mut key_bundle_copy.enc_keys));
     }
 
+    #[test]
+    fn test_keystore_storage_key() {
+        let mut keystore = new_test_keystore(random_test_passphrase());
+        assert!(keystore.get_storage_key(1).is_err());
+
+        keystore.add_random_seed("root_seed", SEED_SIZE).unwrap();
+        keystore
+            .add_keybundle_from_seed("root_seed", PRIMARY_KEYBUNDLE_ID)
+            .unwrap();
+        let mut key_1 = keystore.get_storage_key(1).unwrap();
+        let mut key_2 = keystore.get_storage_key(2).unwrap();
+        assert_eq!(key_1.len(), SEED_SIZE);
+        assert_eq!(key_1.compare(&mut keystore.get_storage_key(1).unwrap()), 0);
+        assert_ne!(key_1.compare(&mut key_2), 0);
+        assert!(keystore.get_storage_key(0).is_err());
+
+        let mut other_keystore = new_test_keystore(random_test_passphrase());
+        other_keystore
+            .add_random_seed("root_seed", SEED_SIZE)
+            .unwrap();
+        other_keystore
+            .add_keybundle_from_seed("root_seed", PRIMARY_KEYBUNDLE_ID)
+            .unwrap();
+        assert_ne!(
+            key_1.compare(&mut other_keystore.get_storage_key(1).unwrap()),
+            0
+        );
+    }
+
+    #[test]
+    fn test_keystore_wipe_cache() {
+        let mut keystore = new_test_keystore(random_test_passphrase());
+        keystore.add_random_seed("root_seed", SEED_SIZE).unwrap();
+        let seed = match *keystore.get("root_seed").unwrap().lock().unwrap() {
+            Secret::Seed(ref mut buf) => buf.read_lock().to_vec(),
+            _ => unreachable!(),
+        };
+        assert!(keystore.cache.contains_key("root_seed"));
+
+        keystore.wipe_cache();
+        assert!(keystore.cache.is_empty());
+        assert_eq!(keystore.list(), vec!["root_seed".to_string()]);
+
+        // the secret gets decrypted again when used
+        match *keystore.get("root_seed").unwrap().lock().unwrap() {
+            Secret::Seed(ref mut buf) => assert_eq!(buf.read_lock().to_vec(), seed),
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn test_keystore_standalone_mnemonic_roundtrip() {
+        let (mut keystore, pub_key) = Keystore::new_standalone(
+            mock_passphrase_manager(random_test_passphrase()),
+            test_hash_config(),
+        )
+        .unwrap();
+        let mnemonic = keystore.get_root_seed_mnemonic().unwrap();
+        assert_eq!(24, mnemonic.split(' ').count());
+
+        // restoring works with any passphrase, and with the phrase spread over lines
+        let (mut restored, restored_pub_key) = Keystore::new_standalone_from_mnemonic(
+            mock_passphrase_manager(random_test_passphrase()),
+            &format!(" {}\n", mnemonic.replacen(' ', "\n", 12)),
+            test_hash_config(),
+        )
+        .unwrap();
+        assert_eq!(pub_key, restored_pub_key);
+        assert_eq!(mnemonic, restored.get_root_seed_mnemonic().unwrap());
+        let mut key_bundle = keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID).unwrap();
+        let mut restored_key_bundle = restored.get_keybundle(PRIMARY_KEYBUNDLE_ID).unwrap();
+        assert!(key_bundle
+            .sign_keys
+            .is_same(&mut restored_key_bundle.sign_keys));
+        assert!(key_bundle
+            .enc_keys
+            .is_same(&mut restored_key_bundle.enc_keys));
+
+        assert!(Keystore::new_standalone_from_mnemonic(
+            mock_passphrase_manager(random_test_passphrase()),
+            "not a mnemonic",
+            test_hash_config(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_keystore_standalone_from_split_root_seed() {
+        let (mut keystore, pub_key) = Keystore::new_standalone(
+            mock_passphrase_manager(random_test_passphrase()),
+            test_hash_config(),
+        )
+        .unwrap();
+        let mut shares = keystore.get_root_seed().unwrap().split(2, 3).unwrap();
+        let mut root_seed = RootSeed::new_from_shares(&mut shares[1..]).unwrap();
+        let (_, restored_pub_key) = Keystore::new_standalone_from_root_seed(
+            mock_passphrase_manager(random_test_passphrase()),
+            &mut root_seed,
+            test_hash_config(),
+        )
+        .unwrap();
+        assert_eq!(pub_key, restored_pub_key);
+    }
+
     #[test]
     /// Tests if the keystore encrypted with holochain_common::DEFAULT_PASSPHRASE can be decrypted,
     /// no matter what passphrase we get from the passphrase manager
@@ -0,0 +1,571 @@
+//! Serving a [Keystore] from a process of its own, so that decrypted secrets never
+//! live in the conductor's memory.
+//!
+//! The `hc-keystore` daemon unlocks a keystore file and serves it with a
+//! [KeystoreServer] on a Unix domain socket. Agents configured with a
+//! `keystore_socket` instead of a `keystore_file` reach it through a
+//! [RemoteKeystore], which the conductor wires into the same `agent/sign`,
+//! `agent/encrypt`, `agent/decrypt` and `agent/keystore/*` functions a local
+//! keystore provides.
+//!
+//! The protocol is one JSON encoded [KeystoreRequest] per line, each answered by
+//! one JSON encoded [KeystoreResponse] line. Payloads are passed as they are given
+//! to the conductor API, ciphers as base64.
+
+use crate::keystore::{KeyType, Keystore, Secret};
+use holochain_core_types::{
+    agent::Base32,
+    error::{HcResult, HolochainError},
+    signature::Signature,
+};
+use holochain_dpki::utils::SeedContext;
+use holochain_locksmith::Mutex;
+use lib3h_sodium::secbuf::SecBuf;
+use serde_json::Value;
+use std::{path::PathBuf, sync::Arc};
+
+#[cfg(unix)]
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    process, thread,
+};
+
+/// What a [RemoteKeystore] can ask of the keystore daemon
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum KeystoreRequest {
+    List,
+    GetPublicKey {
+        src_id: String,
+    },
+    /// the signing public key, i.e. the agent address, of a keybundle
+    GetKeybundleId {
+        keybundle_id: String,
+    },
+    AddRandomSeed {
+        dst_id: String,
+        size: usize,
+    },
+    AddSeedFromSeed {
+        src_id: String,
+        dst_id: String,
+        /// 8 bytes
+        context: String,
+        index: u64,
+    },
+    AddKeyFromSeed {
+        src_id: String,
+        dst_id: String,
+        /// "signing" or "encrypting"
+        key_type: String,
+    },
+    Sign {
+        src_id: String,
+        payload: String,
+    },
+    Encrypt {
+        keybundle_id: String,
+        payload: String,
+    },
+    Decrypt {
+        keybundle_id: String,
+        /// base64 encoded cipher
+        payload: String,
+    },
+}
+
+impl KeystoreRequest {
+    /// whether carrying out the request twice has the same effect as carrying it out once,
+    /// so that it can be sent again when the connection fails before it is answered
+    pub fn is_idempotent(&self) -> bool {
+        match self {
+            KeystoreRequest::AddRandomSeed { .. }
+            | KeystoreRequest::AddSeedFromSeed { .. }
+            | KeystoreRequest::AddKeyFromSeed { .. } => false,
+            _ => true,
+        }
+    }
+}
+
+/// The daemon's answer to a [KeystoreRequest]
+pub type KeystoreResponse = Result<Value, String>;
+
+/// Runs a request against a keystore, as the daemon does for every request it receives
+pub fn handle_request(keystore: &mut Keystore, request: KeystoreRequest) -> KeystoreResponse {
+    run_request(keystore, request).map_err(|error| error.to_string())
+}
+
+fn run_request(keystore: &mut Keystore, request: KeystoreRequest) -> HcResult<Value> {
+    match request {
+        KeystoreRequest::List => Ok(json!(keystore.list())),
+        KeystoreRequest::GetPublicKey { src_id } => {
+            let secret = keystore.get(&src_id)?;
+            let secret = secret.lock().unwrap();
+            match *secret {
+                Secret::SigningKey(ref keypair) => Ok(json!(keypair.public)),
+                Secret::EncryptingKey(ref keypair) => Ok(json!(keypair.public)),
+                _ => Err(HolochainError::ErrorGeneric(format!(
+                    r#""{}" must be a signing or encrypting key"#,
+                    src_id
+                ))),
+            }
+        }
+        KeystoreRequest::GetKeybundleId { keybundle_id } => {
+            Ok(json!(keystore.get_keybundle(&keybundle_id)?.get_id()))
+        }
+        KeystoreRequest::AddRandomSeed { dst_id, size } => keystore
+            .add_random_seed(&dst_id, size)
+            .map(|_| json!({"success": true})),
+        KeystoreRequest::AddSeedFromSeed {
+            src_id,
+            dst_id,
+            context,
+            index,
+        } => {
+            let context_bytes = context.as_bytes();
+            if context_bytes.len() != 8 {
+                return Err(HolochainError::ErrorGeneric(
+                    "`context` has to be 8 bytes".to_string(),
+                ));
+            }
+            let mut context_bytes_array: [u8; 8] = Default::default();
+            context_bytes_array.copy_from_slice(context_bytes);
+            keystore
+                .add_seed_from_seed(
+                    &src_id,
+                    &dst_id,
+                    &SeedContext::new(context_bytes_array),
+                    index,
+                )
+                .map(|_| json!({"success": true}))
+        }
+        KeystoreRequest::AddKeyFromSeed {
+            src_id,
+            dst_id,
+            key_type,
+        } => {
+            let key_type = match key_type.to_lowercase().as_str() {
+                "signing" => KeyType::Signing,
+                "encrypting" => KeyType::Encrypting,
+                _ => {
+                    return Err(HolochainError::ErrorGeneric(
+                        "`key_type` has to be one of 'signing' or 'encrypting'".to_string(),
+                    ))
+                }
+            };
+            keystore
+                .add_key_from_seed(&src_id, &dst_id, key_type)
+                .map(|pub_key| json!(pub_key))
+        }
+        KeystoreRequest::Sign { src_id, payload } => keystore
+            .sign(&src_id, payload)
+            .map(|signature| json!(String::from(signature))),
+        KeystoreRequest::Encrypt {
+            keybundle_id,
+            payload,
+        } => {
+            let mut message = SecBuf::with_insecure_from_string(payload);
+            keystore
+                .get_keybundle(&keybundle_id)?
+                .encrypt(&mut message)
+                .map(|mut encrypted| json!(base64::encode(&**encrypted.read_lock())))
+        }
+        KeystoreRequest::Decrypt {
+            keybundle_id,
+            payload,
+        } => {
+            let cipher = base64::decode(&payload).map_err(|_| {
+                HolochainError::ErrorGeneric("`payload` has to be base64 encoded".to_string())
+            })?;
+            let mut cipher_buf = SecBuf::with_insecure(cipher.len());
+            cipher_buf.from_array(&cipher)?;
+            let mut decrypted = keystore
+                .get_keybundle(&keybundle_id)?
+                .decrypt(&mut cipher_buf)?;
+            let decrypted = decrypted.read_lock();
+            std::str::from_utf8(&**decrypted)
+                .map(|message| json!(message))
+                .map_err(|_| HolochainError::ErrorGeneric("decrypted invalid utf8".to_string()))
+        }
+    }
+}
+
+/// Serves a keystore on a Unix domain socket, one thread per connection
+#[cfg(unix)]
+pub struct KeystoreServer {
+    path: PathBuf,
+    listener: UnixListener,
+    keystore: Arc<Mutex<Keystore>>,
+}
+
+#[cfg(unix)]
+impl KeystoreServer {
+    /// Binds the socket at `path`, replacing a socket left behind by a daemon that is gone.
+    /// Only the user running the daemon may connect to it.
+    pub fn bind(path: PathBuf, keystore: Arc<Mutex<Keystore>>) -> HcResult<Self> {
+        Self::remove_stale_socket(&path)?;
+        let listener = Self::bind_private(&path).map_err(|e| {
+            HolochainError::ErrorGeneric(format!(
+                "Could not create keystore socket {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(KeystoreServer {
+            path,
+            listener,
+            keystore,
+        })
+    }
+
+    /// Removes the socket at `path` if no daemon is listening on it anymore
+    fn remove_stale_socket(path: &Path) -> HcResult<()> {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if !metadata.file_type().is_socket() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Can't create keystore socket {}: the file exists and is not a socket",
+                path.display()
+            )));
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Keystore socket {} is in use by another keystore daemon",
+                path.display()
+            )));
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// Binds the socket in a new directory only the current user can enter, restricts it
+    /// to that user and then moves it to `path`, so there is no moment at which
+    /// someone else could connect to it.
+    fn bind_private(path: &Path) -> io::Result<UnixListener> {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let private_dir = parent.join(format!(".hc-keystore-{}", process::id()));
+        fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+        let bound = (|| -> io::Result<UnixListener> {
+            let private_path = private_dir.join("socket");
+            let listener = UnixListener::bind(&private_path)?;
+            fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))?;
+            fs::rename(&private_path, path)?;
+            Ok(listener)
+        })();
+        let _ = fs::remove_dir_all(&private_dir);
+        bound
+    }
+
+    /// accepts connections until the listener fails
+    pub fn run(&self) -> HcResult<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let keystore = self.keystore.clone();
+            thread::spawn(move || {
+                if let Err(error) = Self::serve(stream, keystore) {
+                    debug!("keystore connection closed: {}", error);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn serve(stream: UnixStream, keystore: Arc<Mutex<Keystore>>) -> HcResult<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let response: KeystoreResponse = serde_json::from_str(&line?)
+                .map_err(|e| format!("invalid keystore request: {}", e))
+                .and_then(|request| handle_request(&mut keystore.lock().unwrap(), request));
+            writer.write_all(serde_json::to_string(&response)?.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl Drop for KeystoreServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+type Connection = (BufReader<UnixStream>, UnixStream);
+
+/// A keystore held by the keystore daemon, reached through its socket.
+/// Connects on first use and reconnects after the daemon went away. A request
+/// is only sent again when the connection fails before it is answered if it is
+/// [idempotent](KeystoreRequest::is_idempotent), since the daemon may already
+/// have carried it out.
+pub struct RemoteKeystore {
+    path: PathBuf,
+    #[cfg(unix)]
+    connection: Mutex<Option<Connection>>,
+}
+
+impl RemoteKeystore {
+    pub fn new(path: PathBuf) -> Self {
+        RemoteKeystore {
+            path,
+            #[cfg(unix)]
+            connection: Mutex::new(None),
+        }
+    }
+
+    #[cfg(unix)]
+    fn connect(&self) -> HcResult<Connection> {
+        let stream = UnixStream::connect(&self.path).map_err(|e| {
+            HolochainError::ErrorGeneric(format!(
+                "Could not connect to keystore at {}: {}",
+                self.path.display(),
+                e
+            ))
+        })?;
+        Ok((BufReader::new(stream.try_clone()?), stream))
+    }
+
+    /// whether the daemon closed the connection since its last answer,
+    /// e.g. because it was restarted
+    #[cfg(unix)]
+    fn is_closed(connection: &mut Connection) -> bool {
+        let (reader, writer) = connection;
+        if writer.set_nonblocking(true).is_err() {
+            return true;
+        }
+        let closed = match reader.fill_buf() {
+            Err(ref error) => error.kind() != io::ErrorKind::WouldBlock,
+            // end of file, or data nobody asked for
+            Ok(_) => true,
+        };
+        writer.set_nonblocking(false).is_err() || closed
+    }
+
+    #[cfg(unix)]
+    fn send(connection: &mut Connection, request: &str) -> HcResult<String> {
+        let (reader, writer) = connection;
+        writer.write_all(request.as_bytes())?;
+        writer.write_all(b"\n")?;
+        let mut response = String::new();
+        if reader.read_line(&mut response)? == 0 {
+            return Err(HolochainError::ErrorGeneric(
+                "Keystore closed the connection".to_string(),
+            ));
+        }
+        Ok(response)
+    }
+
+    /// sends a request to the daemon and waits for its answer
+    #[cfg(unix)]
+    pub fn request(&self, request: KeystoreRequest) -> HcResult<Value> {
+        let resend = request.is_idempotent();
+        let request = serde_json::to_string(&request)?;
+        let mut connection = self.connection.lock().unwrap();
+        let mut current = match connection.take() {
+            Some(mut current) if !Self::is_closed(&mut current) => current,
+            // not connected yet, or the daemon was restarted
+            _ => self.connect()?,
+        };
+        let response = match Self::send(&mut current, &request) {
+            Ok(response) => response,
+            Err(error) if !resend => {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "{} (the keystore may have carried out the request anyway)",
+                    error
+                )))
+            }
+            Err(_) => {
+                current = self.connect()?;
+                Self::send(&mut current, &request)?
+            }
+        };
+        *connection = Some(current);
+        let response: KeystoreResponse = serde_json::from_str(&response)?;
+        response.map_err(HolochainError::ErrorGeneric)
+    }
+
+    #[cfg(not(unix))]
+    pub fn request(&self, _request: KeystoreRequest) -> HcResult<Value> {
+        Err(HolochainError::ErrorGeneric(format!(
+            "Unix domain sockets are not available on non-Unix systems. Can't reach the keystore at {}.",
+            self.path.display()
+        )))
+    }
+
+    fn request_string(&self, request: KeystoreRequest) -> HcResult<String> {
+        self.request(request)?
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| HolochainError::ErrorGeneric("Unexpected keystore response".into()))
+    }
+
+    pub fn get_keybundle_id(&self, keybundle_id: &str) -> HcResult<Base32> {
+        self.request_string(KeystoreRequest::GetKeybundleId {
+            keybundle_id: keybundle_id.to_string(),
+        })
+    }
+
+    pub fn sign(&self, src_id: &str, payload: String) -> HcResult<Signature> {
+        self.request_string(KeystoreRequest::Sign {
+            src_id: src_id.to_string(),
+            payload,
+        })
+        .map(Signature::from)
+    }
+
+    /// returns the base64 encoded cipher
+    pub fn encrypt(&self, keybundle_id: &str, payload: String) -> HcResult<String> {
+        self.request_string(KeystoreRequest::Encrypt {
+            keybundle_id: keybundle_id.to_string(),
+            payload,
+        })
+    }
+
+    /// takes a base64 encoded cipher
+    pub fn decrypt(&self, keybundle_id: &str, payload: String) -> HcResult<String> {
+        self.request_string(KeystoreRequest::Decrypt {
+            keybundle_id: keybundle_id.to_string(),
+            payload,
+        })
+    }
+}
+
+#[cfg(all(test, unix))]
+pub mod tests {
+    use super::*;
+    use crate::{
+        key_loaders::mock_passphrase_manager,
+        keystore::{test_hash_config, KEYBUNDLE_SIGNKEY_SUFFIX, PRIMARY_KEYBUNDLE_ID},
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn remote_keystore_signs_encrypts_and_decrypts() {
+        let (mut keystore, pub_key) = Keystore::new_standalone(
+            mock_passphrase_manager("secret".to_string()),
+            test_hash_config(),
+        )
+        .unwrap();
+        let mut keybundle = keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID).unwrap();
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("keystore.sock");
+        let server = KeystoreServer::bind(path.clone(), Arc::new(Mutex::new(keystore))).unwrap();
+        thread::spawn(move || server.run());
+
+        let remote = RemoteKeystore::new(path);
+        assert_eq!(
+            pub_key,
+            remote.get_keybundle_id(PRIMARY_KEYBUNDLE_ID).unwrap()
+        );
+
+        let signature = remote
+            .sign(
+                &[PRIMARY_KEYBUNDLE_ID, KEYBUNDLE_SIGNKEY_SUFFIX].join(""),
+                "some data".to_string(),
+            )
+            .unwrap();
+        let mut data = SecBuf::with_insecure_from_string("some data".to_string());
+        let signature_bytes = base64::decode(&String::from(signature)).unwrap();
+        let mut signature_buf = SecBuf::with_insecure(signature_bytes.len());
+        signature_buf.from_array(&signature_bytes).unwrap();
+        assert!(keybundle.verify(&mut data, &mut signature_buf));
+
+        let cipher = remote
+            .encrypt(PRIMARY_KEYBUNDLE_ID, "a secret message".to_string())
+            .unwrap();
+        assert_eq!(
+            "a secret message",
+            remote.decrypt(PRIMARY_KEYBUNDLE_ID, cipher).unwrap()
+        );
+
+        // errors come back as errors, and the connection stays usable
+        assert!(remote.sign("no such key", "data".to_string()).is_err());
+        assert!(remote
+            .request(KeystoreRequest::List)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .contains(&json!(
+                [PRIMARY_KEYBUNDLE_ID, KEYBUNDLE_SIGNKEY_SUFFIX].join("")
+            )));
+    }
+
+    #[test]
+    fn resends_only_idempotent_requests() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("keystore.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        // a daemon that goes away after reading the request on its first two connections
+        let daemon = thread::spawn(move || {
+            let mut received = Vec::new();
+            for (index, stream) in listener.incoming().take(3).enumerate() {
+                let stream = stream.unwrap();
+                let mut writer = stream.try_clone().unwrap();
+                let mut request = String::new();
+                BufReader::new(stream).read_line(&mut request).unwrap();
+                let request: KeystoreRequest = serde_json::from_str(&request).unwrap();
+                received.push(request);
+                if index == 2 {
+                    writer.write_all(b"{\"Ok\":[]}\n").unwrap();
+                }
+            }
+            received
+        });
+
+        let remote = RemoteKeystore::new(path);
+        let add_seed = KeystoreRequest::AddRandomSeed {
+            dst_id: "seed".to_string(),
+            size: 32,
+        };
+        let result = remote.request(add_seed.clone());
+        assert!(format!("{:?}", result.err()).contains("may have carried out"));
+        assert_eq!(json!([]), remote.request(KeystoreRequest::List).unwrap());
+        assert_eq!(
+            vec![add_seed, KeystoreRequest::List, KeystoreRequest::List],
+            daemon.join().unwrap()
+        );
+    }
+
+    #[test]
+    fn binds_a_private_socket_and_replaces_stale_ones() {
+        let (keystore, _) = Keystore::new_standalone(
+            mock_passphrase_manager("secret".to_string()),
+            test_hash_config(),
+        )
+        .unwrap();
+        let keystore = Arc::new(Mutex::new(keystore));
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("keystore.sock");
+
+        // a socket nobody listens on anymore
+        drop(UnixListener::bind(&path).unwrap());
+        let server = KeystoreServer::bind(path.clone(), keystore.clone()).unwrap();
+        let metadata = fs::symlink_metadata(&path).unwrap();
+        assert!(metadata.file_type().is_socket());
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        // only the socket is left in its directory
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // a socket still in use
+        let result = KeystoreServer::bind(path.clone(), keystore.clone());
+        assert!(format!("{:?}", result.err()).contains("in use"));
+        drop(server);
+        assert!(!path.exists());
+
+        fs::write(&path, "not a socket").unwrap();
+        let result = KeystoreServer::bind(path, keystore);
+        assert!(format!("{:?}", result.err()).contains("not a socket"));
+    }
+}
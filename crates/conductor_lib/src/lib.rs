@@ -149,6 +149,13 @@ pub mod dpki_instance;
     clippy::let_and_return,
     clippy::collapsible_if
 )]
+pub mod encrypted_storage;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
 pub mod error;
 #[allow(
     clippy::suspicious_else_formatting,
@@ -205,6 +212,13 @@ pub mod keystore;
     clippy::let_and_return,
     clippy::collapsible_if
 )]
+pub mod keystore_service;
+#[allow(
+    clippy::suspicious_else_formatting,
+    clippy::redundant_closure,
+    clippy::let_and_return,
+    clippy::collapsible_if
+)]
 pub mod logger;
 #[allow(
     clippy::suspicious_else_formatting,
@@ -15,4 +15,7 @@ pub enum SignalWrapper {
     InstanceStats {
         instance_stats: HashMap<String, InstanceStats>,
     },
+    /// Sent over admin interfaces by the "admin" passphrase service.
+    /// Clients reply by calling `admin/passphrase/provide`.
+    PassphraseRequest,
 }
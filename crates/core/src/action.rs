@@ -110,6 +110,11 @@ pub enum Action {
     /// Does not validate, assumes entry is valid.
     Commit((Entry, Option<Address>, Vec<Provenance>)),
 
+    /// Appends an already signed header from an exported chain, and its entry, to the source chain.
+    /// A header without a link starts the chain over.
+    /// Does not validate, assumes entry is valid.
+    ImportChainHeader((ChainHeader, Entry)),
+
     // -------------
     // DHT actions:
     // -------------
@@ -177,6 +182,11 @@ pub enum Action {
     /// Err(HolochainError::Timeout) to NetworkState::custom_direct_message_replys.
     SendDirectMessageTimeout(String),
 
+    /// Resolves the custom direct message with the given ID, which the network queued
+    /// until the given recipient is online, by adding an
+    /// Err(HolochainError::DirectMessageQueued) to NetworkState::custom_direct_message_replys.
+    SendDirectMessageQueued((String, Address)),
+
     /// Makes the network module forget about the direct message
     /// connection with the given ID.
     /// Triggered when we got an answer to our initial DM.
@@ -0,0 +1,74 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    agent::state::AgentActionResponse,
+    context::Context,
+    instance::dispatch_action,
+};
+use futures::{future::Future, task::Poll};
+use holochain_core_types::{chain_header::ChainHeader, entry::Entry, error::HolochainError};
+use holochain_persistence_api::cas::content::Address;
+use snowflake::ProcessUniqueId;
+use std::{pin::Pin, sync::Arc};
+
+/// Import Action Creator
+/// Appends a header taken from an exported chain, with its entry, to the source chain
+/// without creating or signing a new header.
+///
+/// Returns a future that resolves to the address of the entry.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn import_chain_header(
+    chain_header: ChainHeader,
+    entry: Entry,
+    context: &Arc<Context>,
+) -> Result<Address, HolochainError> {
+    let action_wrapper = ActionWrapper::new(Action::ImportChainHeader((chain_header, entry)));
+    dispatch_action(context.action_channel(), action_wrapper.clone());
+    let id = ProcessUniqueId::new();
+    ImportChainHeaderFuture {
+        context: context.clone(),
+        action: action_wrapper,
+        id,
+    }
+    .await
+}
+
+/// ImportChainHeaderFuture resolves to ActionResponse
+/// Tracks the state for a response to its ActionWrapper
+pub struct ImportChainHeaderFuture {
+    context: Arc<Context>,
+    action: ActionWrapper,
+    id: ProcessUniqueId,
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+impl Future for ImportChainHeaderFuture {
+    type Output = Result<Address, HolochainError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context) -> Poll<Self::Output> {
+        if let Some(err) = self.context.action_channel_error("ImportChainHeaderFuture") {
+            return Poll::Ready(Err(err));
+        }
+        self.context
+            .register_waker(self.id.clone(), cx.waker().clone());
+        if let Some(state) = self.context.try_state() {
+            match state.agent().actions().get(&self.action) {
+                Some(r) => match r.response() {
+                    AgentActionResponse::ImportChainHeader(result) => {
+                        dispatch_action(
+                            self.context.action_channel(),
+                            ActionWrapper::new(Action::ClearActionResponse(
+                                self.action.id().to_string(),
+                            )),
+                        );
+                        self.context.unregister_waker(self.id.clone());
+                        Poll::Ready(result.clone())
+                    }
+                    _ => unreachable!(),
+                },
+                None => Poll::Pending,
+            }
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Exports of a whole source chain, headers and entries, and the checks
+//! that tell whether such a chain is intact.
+
+use crate::{agent::chain_store::ChainStore, content_store::GetContent};
+use holochain_core_types::{
+    chain_header::ChainHeader,
+    entry::{entry_type::EntryType, Entry},
+    error::HcResult,
+};
+use holochain_dpki::utils::Verify;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
+use std::collections::HashMap;
+
+/// A header of a source chain together with the entry it points to
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ChainElement {
+    pub header_address: Address,
+    pub header: ChainHeader,
+    /// None if the entry is missing from the CAS
+    pub entry: Option<Entry>,
+}
+
+/// All headers and entries of a source chain, oldest first
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ChainExport {
+    pub elements: Vec<ChainElement>,
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+impl ChainExport {
+    /// Reads the chain ending in `top_chain_header` out of `chain_store`
+    pub fn from_chain_store(
+        chain_store: &ChainStore,
+        top_chain_header: &Option<ChainHeader>,
+    ) -> HcResult<Self> {
+        let mut elements = chain_store
+            .iter(top_chain_header)
+            .map(|header| {
+                Ok(ChainElement {
+                    header_address: header.address(),
+                    entry: chain_store.get(header.entry_address())?,
+                    header,
+                })
+            })
+            .collect::<HcResult<Vec<_>>>()?;
+        elements.reverse();
+        Ok(ChainExport { elements })
+    }
+
+    /// Checks the links between headers, that every entry is present and matches its
+    /// header and that all provenances signed it.
+    /// Returns a description of every problem found, so an empty list means the chain is intact.
+    pub fn verify(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut previous: Option<Address> = None;
+        let mut previous_of_type: HashMap<EntryType, Address> = HashMap::new();
+
+        for element in &self.elements {
+            let header = &element.header;
+            let address = header.address();
+            if address != element.header_address {
+                problems.push(format!(
+                    "header {} has been modified, its address is {}",
+                    element.header_address, address
+                ));
+            }
+            if header.link() != previous {
+                problems.push(format!(
+                    "header {} links to {} instead of {}",
+                    address,
+                    describe_link(&header.link()),
+                    describe_link(&previous)
+                ));
+            }
+            let previous_same_type =
+                previous_of_type.insert(header.entry_type().clone(), address.clone());
+            if header.link_same_type() != previous_same_type {
+                problems.push(format!(
+                    "header {} links to {} as previous {:?} header instead of {}",
+                    address,
+                    describe_link(&header.link_same_type()),
+                    header.entry_type(),
+                    describe_link(&previous_same_type)
+                ));
+            }
+
+            match element.entry {
+                None => problems.push(format!(
+                    "entry {} of header {} is missing",
+                    header.entry_address(),
+                    address
+                )),
+                Some(ref entry) if entry.address() != *header.entry_address() => {
+                    problems.push(format!(
+                        "entry of header {} has address {} instead of {}",
+                        address,
+                        entry.address(),
+                        header.entry_address()
+                    ))
+                }
+                Some(_) => {}
+            }
+
+            if header.provenances().is_empty() {
+                problems.push(format!("header {} has no provenance", address));
+            }
+            for provenance in header.provenances() {
+                match provenance.verify(header.entry_address().to_string()) {
+                    Ok(true) => {}
+                    Ok(false) => problems.push(format!(
+                        "signature of {} on header {} is invalid",
+                        provenance.source(),
+                        address
+                    )),
+                    Err(error) => problems.push(format!(
+                        "signature of {} on header {} could not be checked: {}",
+                        provenance.source(),
+                        address,
+                        error
+                    )),
+                }
+            }
+
+            previous = Some(address);
+        }
+        problems
+    }
+
+    /// Number of headers, counted from the oldest, that both chains have in common
+    pub fn common_length(&self, other: &ChainExport) -> usize {
+        self.elements
+            .iter()
+            .zip(other.elements.iter())
+            .take_while(|(a, b)| a.header_address == b.header_address)
+            .count()
+    }
+}
+
+fn describe_link(link: &Option<Address>) -> String {
+    link.as_ref()
+        .map(|address| address.to_string())
+        .unwrap_or_else(|| "nothing".to_string())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{agent::chain_store::tests::test_chain_store, content_store::AddContent};
+    use holochain_core_types::{
+        entry::{test_entry, test_entry_b, test_entry_c},
+        signature::{Provenance, Signature},
+        time::test_iso_8601,
+    };
+    use test_utils::mock_signing::{mock_signer, registered_test_agent};
+
+    fn signed_header(
+        entry: &Entry,
+        link: Option<Address>,
+        link_same_type: Option<Address>,
+    ) -> ChainHeader {
+        let agent = registered_test_agent("chain_export");
+        let signature = mock_signer(entry.address().to_string(), &agent);
+        ChainHeader::new(
+            &entry.entry_type(),
+            &entry.address(),
+            &[Provenance::new(agent.address(), Signature::from(signature))],
+            &link,
+            &link_same_type,
+            &None,
+            &test_iso_8601(),
+        )
+    }
+
+    /// commits test_entry, test_entry_b and test_entry again and exports the chain
+    fn test_export() -> ChainExport {
+        let mut chain_store = test_chain_store();
+        let header_a = signed_header(&test_entry(), None, None);
+        let header_b = signed_header(&test_entry_b(), Some(header_a.address()), None);
+        let header_c = signed_header(
+            &test_entry(),
+            Some(header_b.address()),
+            Some(header_a.address()),
+        );
+        for header in &[&header_a, &header_b, &header_c] {
+            chain_store.add(*header).unwrap();
+        }
+        chain_store.add(&test_entry()).unwrap();
+        chain_store.add(&test_entry_b()).unwrap();
+
+        ChainExport::from_chain_store(&chain_store, &Some(header_c)).unwrap()
+    }
+
+    #[test]
+    fn exports_a_chain_oldest_first() {
+        let export = test_export();
+        assert_eq!(export.elements.len(), 3);
+        assert_eq!(export.elements[0].header.link(), None);
+        assert_eq!(export.elements[0].entry, Some(test_entry()));
+        assert_eq!(export.elements[1].entry, Some(test_entry_b()));
+        assert_eq!(
+            export.elements[2].header.link(),
+            Some(export.elements[1].header_address.clone())
+        );
+        assert_eq!(export.verify(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn verify_finds_broken_chains() {
+        let mut missing_header = test_export();
+        missing_header.elements.remove(0);
+        let problems = missing_header.verify();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("instead of nothing"));
+
+        let mut wrong_entry = test_export();
+        wrong_entry.elements[1].entry = Some(test_entry_c());
+        let problems = wrong_entry.verify();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("instead of"));
+
+        let mut missing_entry = test_export();
+        missing_entry.elements[1].entry = None;
+        assert_eq!(missing_entry.verify().len(), 1);
+
+        let mut forged = test_export();
+        let header = forged.elements[1].header.clone();
+        let forged_header = ChainHeader::new(
+            header.entry_type(),
+            header.entry_address(),
+            &[Provenance::new(
+                header.provenances()[0].source(),
+                Signature::from(mock_signer(
+                    "something else".to_string(),
+                    &registered_test_agent("chain_export"),
+                )),
+            )],
+            &header.link(),
+            &header.link_same_type(),
+            &None,
+            header.timestamp(),
+        );
+        forged.elements[1].header = forged_header;
+        let problems = forged.verify();
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("is invalid")));
+        assert!(problems.iter().any(|problem| problem.contains("modified")));
+    }
+
+    #[test]
+    fn finds_the_common_history_of_two_chains() {
+        let export = test_export();
+        let mut shorter = export.clone();
+        shorter.elements.pop();
+        assert_eq!(export.common_length(&export), 3);
+        assert_eq!(export.common_length(&shorter), 2);
+        assert_eq!(export.common_length(&ChainExport::default()), 0);
+    }
+}
@@ -7,12 +7,14 @@ use holochain_core_types::{
         HcResult,
         RibosomeErrorCode::{self, *},
     },
+    network::query::SortOrder,
 };
 use holochain_locksmith::RwLock;
 use holochain_persistence_api::cas::{
     content::{Address, AddressableContent, Content},
     storage::ContentAddressableStorage,
 };
+use holochain_wasm_utils::api_serialization::QueryFilter;
 use std::{str::FromStr, sync::Arc};
 
 #[derive(Debug, Clone)]
@@ -36,6 +38,9 @@ pub struct ChainStoreQueryOptions {
     pub start: usize,
     pub limit: usize,
     pub headers: bool,
+    pub ordering: SortOrder,
+    pub filter: QueryFilter,
+    pub after: Option<Address>,
 }
 
 #[derive(Debug)]
@@ -96,26 +101,10 @@ impl ChainStore {
         };
         let headers = options.headers;
 
-        let vector = match entry_type_names {
-            // Vec<Address> or Vec<ChainHeader>
+        let matching: Box<dyn Iterator<Item = ChainHeader>> = match entry_type_names {
             [] | [""] | ["**"] => {
                 // No filtering desired; uses bare .iter()
-                if headers {
-                    ChainStoreQueryResult::Headers(
-                        self.iter(start_chain_header)
-                            .skip(start)
-                            .take(limit)
-                            .collect(),
-                    )
-                } else {
-                    ChainStoreQueryResult::Addresses(
-                        self.iter(start_chain_header)
-                            .skip(start)
-                            .take(limit)
-                            .map(|header| header.entry_address().to_owned())
-                            .collect(),
-                    )
-                }
+                Box::new(self.iter(start_chain_header))
             }
             [one] if !is_glob_str(one) => {
                 // Single EntryType without "glob" pattern; uses .iter_type()
@@ -123,22 +112,7 @@ impl ChainStore {
                     Ok(inner) => inner,
                     Err(..) => return Err(UnknownEntryType),
                 };
-                if headers {
-                    ChainStoreQueryResult::Headers(
-                        self.iter_type(start_chain_header, &entry_type)
-                            .skip(start)
-                            .take(limit)
-                            .collect(),
-                    )
-                } else {
-                    ChainStoreQueryResult::Addresses(
-                        self.iter_type(start_chain_header, &entry_type)
-                            .skip(start)
-                            .take(limit)
-                            .map(|header| header.entry_address().to_owned())
-                            .collect(),
-                    )
-                }
+                Box::new(self.iter_type(start_chain_header, &entry_type))
             }
             rest => {
                 // 1 or more EntryTypes, may or may not include glob wildcards.  Create a
@@ -156,31 +130,48 @@ impl ChainStore {
                     );
                 }
                 let globset = builder.build().map_err(|_| UnknownEntryType)?;
-                if headers {
-                    ChainStoreQueryResult::Headers(
-                        self.iter(start_chain_header)
-                            .filter(|header| {
-                                !globset.matches(header.entry_type().to_string()).is_empty()
-                            })
-                            .skip(start)
-                            .take(limit)
-                            .collect(),
-                    )
-                } else {
-                    ChainStoreQueryResult::Addresses(
-                        self.iter(start_chain_header)
-                            .filter(|header| {
-                                !globset.matches(header.entry_type().to_string()).is_empty()
-                            })
-                            .skip(start)
-                            .take(limit)
-                            .map(|header| header.entry_address().to_owned())
-                            .collect(),
-                    )
-                }
+                Box::new(
+                    self.iter(start_chain_header).filter(move |header| {
+                        !globset.matches(header.entry_type().to_string()).is_empty()
+                    }),
+                )
             }
         };
 
+        let filter = options.filter;
+        let filtered = matching.filter(move |header| filter.matches(header));
+
+        // The chain can only be walked from the top, so oldest-first requires collecting it
+        let ordered: Box<dyn Iterator<Item = ChainHeader>> = match options.ordering {
+            SortOrder::Descending => Box::new(filtered),
+            SortOrder::Ascending => {
+                let mut all: Vec<ChainHeader> = filtered.collect();
+                all.reverse();
+                Box::new(all.into_iter())
+            }
+        };
+
+        // A cursor that is not found in the (filtered) chain yields no results
+        let paged: Box<dyn Iterator<Item = ChainHeader>> = match options.after {
+            Some(cursor) => Box::new(
+                ordered
+                    .skip_while(move |header| header.address() != cursor)
+                    .skip(1),
+            ),
+            None => ordered,
+        };
+
+        let selected = paged.skip(start).take(limit);
+        let vector = if headers {
+            ChainStoreQueryResult::Headers(selected.collect())
+        } else {
+            ChainStoreQueryResult::Addresses(
+                selected
+                    .map(|header| header.entry_address().to_owned())
+                    .collect(),
+            )
+        };
+
         Ok(vector)
     }
 }
@@ -312,17 +303,21 @@ pub mod tests {
     use self::tempfile::tempdir;
     use crate::agent::chain_store::{ChainStore, ChainStoreQueryOptions, ChainStoreQueryResult};
     use holochain_core_types::{
+        agent::{test_agent_id, test_agent_id_with_name},
         chain_header::{test_chain_header, test_provenances, ChainHeader},
         entry::{
             entry_type::{test_entry_type_b, AppEntryType},
             test_entry, test_entry_b, test_entry_c, Entry,
         },
-        time::test_iso_8601,
+        network::query::SortOrder,
+        signature::{Provenance, Signature},
+        time::{test_iso_8601, Iso8601},
     };
     use holochain_json_api::json::{JsonString, RawString};
     use holochain_locksmith::RwLock;
     use holochain_persistence_api::cas::content::AddressableContent;
     use holochain_persistence_file::cas::file::FilesystemStorage;
+    use holochain_wasm_utils::api_serialization::QueryFilter;
     use tempfile;
 
     pub fn test_chain_store() -> ChainStore {
@@ -544,6 +539,7 @@ pub mod tests {
                     start: 0,
                     limit: 1,
                     headers: false,
+                    ..Default::default()
                 },
             )
             .unwrap()
@@ -802,6 +798,132 @@ pub mod tests {
         }
     }
 
+    #[test]
+    /// show query() ordering, header filters and cursor pagination
+    fn query_options_test() {
+        let chain_store = test_chain_store();
+
+        let chain_header_a = ChainHeader::new(
+            &test_entry().entry_type(),
+            &test_entry().address(),
+            &test_provenances("sig-a"),
+            &None,
+            &None,
+            &None,
+            &Iso8601::from(1000),
+        );
+        let entry = test_entry_b();
+        let chain_header_b = ChainHeader::new(
+            &entry.entry_type(),
+            &entry.address(),
+            &[Provenance::new(
+                test_agent_id_with_name("alice").address(),
+                Signature::from("sig-b"),
+            )],
+            &Some(chain_header_a.address()),
+            &None,
+            &None,
+            &Iso8601::from(2000),
+        );
+        let entry = test_entry_c();
+        let chain_header_c = ChainHeader::new(
+            &entry.entry_type(),
+            &entry.address(),
+            &test_provenances("sig-c"),
+            &Some(chain_header_b.address()),
+            &Some(chain_header_b.address()),
+            &Some(chain_header_b.address()),
+            &Iso8601::from(3000),
+        );
+
+        let storage = chain_store.content_storage.clone();
+        for chain_header in vec![&chain_header_a, &chain_header_b, &chain_header_c] {
+            (*storage.write().unwrap())
+                .add(chain_header)
+                .expect("could not add header to cas");
+        }
+
+        let query = |options: ChainStoreQueryOptions| match chain_store
+            .query(&Some(chain_header_c.clone()), &[], options)
+            .unwrap()
+        {
+            ChainStoreQueryResult::Addresses(addresses) => addresses,
+            other => panic!("Unexpected query value {:?}", other),
+        };
+
+        // Oldest first
+        let found = query(ChainStoreQueryOptions {
+            ordering: SortOrder::Ascending,
+            ..Default::default()
+        });
+        let expected = vec![
+            chain_header_a.entry_address().clone(),
+            chain_header_b.entry_address().clone(),
+            chain_header_c.entry_address().clone(),
+        ];
+        assert_eq!(expected, found);
+
+        // Timestamp window is inclusive of since, exclusive of until
+        let found = query(ChainStoreQueryOptions {
+            filter: QueryFilter {
+                since: Some(Iso8601::from(2000)),
+                until: Some(Iso8601::from(3000)),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert_eq!(vec![chain_header_b.entry_address().clone()], found);
+
+        // By author
+        let found = query(ChainStoreQueryOptions {
+            filter: QueryFilter {
+                provenance: Some(test_agent_id().address()),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let expected = vec![
+            chain_header_c.entry_address().clone(),
+            chain_header_a.entry_address().clone(),
+        ];
+        assert_eq!(expected, found);
+
+        // By updated/deleted header
+        let found = query(ChainStoreQueryOptions {
+            filter: QueryFilter {
+                link_update_delete: Some(chain_header_b.address()),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        assert_eq!(vec![chain_header_c.entry_address().clone()], found);
+
+        // Cursor pagination continues after the given header, in either order
+        let found = query(ChainStoreQueryOptions {
+            limit: 1,
+            after: Some(chain_header_c.address()),
+            ..Default::default()
+        });
+        assert_eq!(vec![chain_header_b.entry_address().clone()], found);
+        let found = query(ChainStoreQueryOptions {
+            ordering: SortOrder::Ascending,
+            after: Some(chain_header_a.address()),
+            ..Default::default()
+        });
+        let expected = vec![
+            chain_header_b.entry_address().clone(),
+            chain_header_c.entry_address().clone(),
+        ];
+        assert_eq!(expected, found);
+
+        // Unknown cursor
+        let found = query(ChainStoreQueryOptions {
+            after: Some(test_entry().address()),
+            ..Default::default()
+        });
+        assert!(found.is_empty());
+    }
+
     use globset::{Glob, GlobBuilder, GlobSetBuilder};
 
     #[test]
@@ -1,6 +1,7 @@
 /// Agent is the module that handles the user’s identity and source chain for every Phenotype.
 ///
 pub mod actions;
+pub mod chain_export;
 pub mod chain_store;
 pub mod state;
 
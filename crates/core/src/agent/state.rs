@@ -7,6 +7,7 @@ use crate::{
 use holochain_persistence_api::cas::content::{Address, AddressableContent, Content};
 
 use crate::{
+    clock,
     content_store::{AddContent, GetContent},
     state::{ActionResponse, StateWrapper, ACTION_PRUNE_MS},
 };
@@ -161,6 +162,7 @@ impl AddressableContent for AgentStateSnapshot {
 #[allow(clippy::large_enum_variant)]
 pub enum AgentActionResponse {
     Commit(Result<Address, HolochainError>),
+    ImportChainHeader(Result<Address, HolochainError>),
     FetchEntry(Option<Entry>),
     GetLinks(Result<Vec<Address>, HolochainError>),
     LinkEntries(Result<Entry, HolochainError>),
@@ -298,6 +300,41 @@ fn reduce_commit_entry(
     );
 }
 
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+fn reduce_import_chain_header(
+    agent_state: &mut AgentState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (chain_header, entry) = unwrap_to!(action => Action::ImportChainHeader);
+
+    let top_address = agent_state
+        .top_chain_header
+        .as_ref()
+        .map(|chain_header| chain_header.address());
+    let result = if chain_header.link().is_some() && chain_header.link() != top_address {
+        Err(HolochainError::ErrorGeneric(format!(
+            "Imported header {} does not link to the top of the chain",
+            chain_header.address()
+        )))
+    } else {
+        agent_state
+            .chain_store
+            .add(entry)
+            .and_then(|_| agent_state.chain_store.add(chain_header))
+            .map(|_| {
+                agent_state.top_chain_header = Some(chain_header.clone());
+                entry.address()
+            })
+    };
+
+    agent_state.actions.insert(
+        action_wrapper.clone(),
+        Response::from(AgentActionResponse::ImportChainHeader(result)),
+    );
+}
+
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
 fn reduce_prune(agent_state: &mut AgentState, _root_state: &State, action_wrapper: &ActionWrapper) {
     assert_eq!(action_wrapper.action(), &Action::Prune);
@@ -306,7 +343,7 @@ fn reduce_prune(agent_state: &mut AgentState, _root_state: &State, action_wrappe
         .actions
         .iter()
         .filter_map(|(action, response)| {
-            if let Ok(elapsed) = response.created_at.elapsed() {
+            if let Some(elapsed) = clock::elapsed(response.created_at) {
                 if elapsed > Duration::from_millis(ACTION_PRUNE_MS) {
                     return Some(action);
                 }
@@ -344,6 +381,7 @@ fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<AgentReduceFn> {
     match action_wrapper.action() {
         Action::ClearActionResponse(_) => Some(reduce_clear_action_response),
         Action::Commit(_) => Some(reduce_commit_entry),
+        Action::ImportChainHeader(_) => Some(reduce_import_chain_header),
         Action::Prune => Some(reduce_prune),
         _ => None,
     }
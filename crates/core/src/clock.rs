@@ -0,0 +1,87 @@
+//! the clock core's network timeouts and the pruning of action responses are measured with
+//!
+//! this is the system clock unless something else gets set with [set_clock], e.g. by a
+//! simulation that runs core on a virtual clock which only moves when the simulation steps.
+//! The clock is shared by every instance in the process.
+
+use holochain_locksmith::RwLock;
+use lazy_static::lazy_static;
+#[cfg(test)]
+use std::cell::RefCell;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+pub type Clock = Arc<dyn Fn() -> SystemTime + Send + Sync>;
+
+lazy_static! {
+    static ref CLOCK: RwLock<Option<Clock>> = RwLock::new(None);
+}
+
+#[cfg(test)]
+thread_local! {
+    /// a clock for the thread of one test only, since tests run in parallel
+    /// and the clock set with [set_clock] is shared by all of them
+    static TEST_CLOCK: RefCell<Option<Clock>> = RefCell::new(None);
+}
+
+/// Runs `f` with `clock` as the clock of the current thread
+#[cfg(test)]
+pub fn with_test_clock<T>(clock: Clock, f: impl FnOnce() -> T) -> T {
+    TEST_CLOCK.with(|test_clock| *test_clock.borrow_mut() = Some(clock));
+    let result = f();
+    TEST_CLOCK.with(|test_clock| *test_clock.borrow_mut() = None);
+    result
+}
+
+/// Makes core read the time from `clock` instead of the system clock
+pub fn set_clock(clock: Clock) {
+    *CLOCK.write().unwrap() = Some(clock);
+}
+
+/// Goes back to the system clock
+pub fn reset_clock() {
+    *CLOCK.write().unwrap() = None;
+}
+
+pub fn now() -> SystemTime {
+    #[cfg(test)]
+    {
+        let test_time = TEST_CLOCK.with(|clock| clock.borrow().as_ref().map(|clock| clock()));
+        if let Some(time) = test_time {
+            return time;
+        }
+    }
+    match *CLOCK.read().unwrap() {
+        Some(ref clock) => clock(),
+        None => SystemTime::now(),
+    }
+}
+
+/// How much time has passed since `earlier`, or None if `earlier` is in the future
+pub fn elapsed(earlier: SystemTime) -> Option<Duration> {
+    now().duration_since(earlier).ok()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_clock_that_is_set() {
+        let start = SystemTime::now();
+        assert!(elapsed(start).is_some());
+
+        let earlier = start - Duration::from_secs(1);
+        with_test_clock(Arc::new(move || earlier), || {
+            assert_eq!(now(), earlier);
+            assert_eq!(elapsed(start), None);
+            assert_eq!(
+                elapsed(earlier - Duration::from_secs(3600)),
+                Some(Duration::from_secs(3600))
+            );
+        });
+        assert!(now() >= start);
+    }
+}
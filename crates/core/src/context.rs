@@ -39,6 +39,7 @@ use holochain_persistence_api::{
 use holochain_tracing as ht;
 use jsonrpc_core::{self, IoHandler};
 use std::{
+    collections::HashMap,
     sync::{
         atomic::{AtomicBool, Ordering::Relaxed},
         Arc,
@@ -56,6 +57,9 @@ use test_utils::mock_signing::mock_conductor_api;
 pub type ActionSender = ht::channel::SpanSender<ActionWrapper>;
 pub type ActionReceiver = ht::channel::SpanReceiver<ActionWrapper>;
 
+/// Receives the chunks that a zome function emits with `hdk::emit_result_chunk`
+pub type ResultChunkSink = Arc<dyn Fn(JsonString) + Send + Sync>;
+
 pub struct P2pNetworkWrapper(Arc<Mutex<Option<P2pNetwork>>>);
 
 impl P2pNetworkWrapper {
@@ -104,6 +108,7 @@ pub struct Context {
     pub p2p_config: P2pConfig,
     pub conductor_api: ConductorApi,
     pub(crate) signal_tx: Option<Sender<Signal>>,
+    result_chunk_sinks: Arc<RwLock<HashMap<String, ResultChunkSink>>>,
     pub(crate) instance_is_alive: Arc<AtomicBool>,
     pub state_dump_logging: bool,
     thread_pool: ThreadPool,
@@ -172,6 +177,7 @@ impl Context {
                 conductor_api,
                 agent_id,
             )),
+            result_chunk_sinks: Arc::new(RwLock::new(HashMap::new())),
             instance_is_alive: Arc::new(AtomicBool::new(true)),
             state_dump_logging,
             thread_pool: ThreadPool::new().expect("Could not create thread pool for futures"),
@@ -210,6 +216,7 @@ impl Context {
             eav_storage: eav,
             p2p_config,
             conductor_api: ConductorApi::new(Self::test_check_conductor_api(None, agent_id)),
+            result_chunk_sinks: Arc::new(RwLock::new(HashMap::new())),
             instance_is_alive: Arc::new(AtomicBool::new(true)),
             state_dump_logging,
             thread_pool: ThreadPool::new().expect("Could not create thread pool for futures"),
@@ -330,6 +337,27 @@ impl Context {
         self.signal_tx.as_ref()
     }
 
+    /// Sets where the chunks emitted by the zome call with the given stream id go
+    /// (see [ZomeFnCall::with_stream_id]) until [remove_result_chunk_sink] is called.
+    pub fn add_result_chunk_sink(&self, stream_id: String, sink: ResultChunkSink) {
+        self.result_chunk_sinks
+            .write()
+            .unwrap()
+            .insert(stream_id, sink);
+    }
+
+    pub fn remove_result_chunk_sink(&self, stream_id: &str) {
+        self.result_chunk_sinks.write().unwrap().remove(stream_id);
+    }
+
+    pub fn result_chunk_sink(&self, stream_id: &str) -> Option<ResultChunkSink> {
+        self.result_chunk_sinks
+            .read()
+            .unwrap()
+            .get(stream_id)
+            .cloned()
+    }
+
     pub fn observer_channel(&self) -> &Sender<Observer> {
         self.observer_channel
             .as_ref()
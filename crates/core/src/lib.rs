@@ -41,6 +41,7 @@ pub mod action;
 // #[autotrace]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod agent;
+pub mod clock;
 // #[autotrace]
 #[allow(clippy::suspicious_else_formatting, clippy::redundant_closure)]
 pub mod consistency;
@@ -1,5 +1,6 @@
 use crate::{
     action::{Action, ActionWrapper, DirectMessageData},
+    clock,
     context::Context,
     instance::dispatch_action,
     network::direct_message::{CustomDirectMessage, DirectMessage},
@@ -8,7 +9,7 @@ use futures::{future::Future, task::Poll};
 use holochain_core_types::{error::HolochainError, time::Timeout};
 use holochain_persistence_api::cas::content::Address;
 use snowflake::ProcessUniqueId;
-use std::{pin::Pin, sync::Arc, time::SystemTime};
+use std::{pin::Pin, sync::Arc};
 
 /// SendDirectMessage Action Creator for custom (=app) messages
 /// This triggers the network module to open a synchronous node-to-node connection
@@ -31,7 +32,7 @@ pub async fn custom_send(
     };
     let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
         direct_message_data,
-        Some((SystemTime::now(), timeout.into())),
+        Some((clock::now(), timeout.into())),
     )));
     dispatch_action(context.action_channel(), action_wrapper);
 
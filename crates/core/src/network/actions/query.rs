@@ -1,5 +1,6 @@
 use crate::{
     action::{Action, ActionWrapper, GetEntryKey, GetLinksKey, QueryKey, QueryPayload},
+    clock,
     context::Context,
     instance::dispatch_action,
     network::query::{GetLinksNetworkQuery, NetworkQueryResult},
@@ -14,7 +15,6 @@ use std::{pin::Pin, sync::Arc};
 
 use holochain_wasm_utils::api_serialization::get_links::{GetLinksArgs, LinksStatusRequestKind};
 use snowflake::ProcessUniqueId;
-use std::time::SystemTime;
 
 /// FetchEntry Action Creator
 /// This is the network version of get_entry that makes the network module start
@@ -68,7 +68,7 @@ pub async fn query(
     let entry = Action::Query((
         key.clone(),
         payload.clone(),
-        Some((SystemTime::now(), timeout.into())),
+        Some((clock::now(), timeout.into())),
     ));
     let action_wrapper = ActionWrapper::new(entry);
     dispatch_action(context.action_channel(), action_wrapper.clone());
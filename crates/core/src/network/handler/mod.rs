@@ -31,7 +31,9 @@ use holochain_core_types::{
     chain_header::ChainHeader, eav::Attribute, entry::Entry, error::HolochainError,
 };
 use holochain_json_api::json::JsonString;
-use holochain_net::connection::net_connection::NetHandler;
+use holochain_net::{
+    connection::net_connection::NetHandler, sim2h_worker::as_direct_message_queued,
+};
 use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use lib3h_protocol::{
     data_types::{DirectMessageData, GenericResultData, StoreEntryAspectData},
@@ -260,6 +262,28 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
                 );
                 handle_send_message_result(message_data, context.clone())
             }
+            Lib3hServerProtocol::SuccessResult(success_data) => {
+                if !is_my_dna(&my_dna_address, &success_data.space_address.to_string()) {
+                    return Ok(());
+                }
+                // ignore if it's not addressed to me
+                if !is_my_id(&context, &success_data.to_agent_id.to_string()) {
+                    return Ok(());
+                }
+                if let Some(queued) = as_direct_message_queued(&success_data) {
+                    log_debug!(
+                        context,
+                        "net/handle: DirectMessageQueued: {} for {}",
+                        queued.request_id,
+                        queued.to_agent_id
+                    );
+                    handle_send_message_queued(
+                        queued.request_id,
+                        queued.to_agent_id.into(),
+                        context.clone(),
+                    )
+                }
+            }
             Lib3hServerProtocol::Connected(peer_data) => {
                 log_debug!(context, "net/handle: Connected: {:?}", peer_data);
                 return Ok(());
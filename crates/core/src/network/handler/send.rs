@@ -11,6 +11,7 @@ use crate::{
 use std::sync::Arc;
 
 use holochain_json_api::{error::JsonError, json::JsonString};
+use holochain_persistence_api::cas::content::Address;
 use lib3h_protocol::data_types::DirectMessageData;
 use std::convert::TryFrom;
 
@@ -149,3 +150,14 @@ pub fn handle_send_message_result(message_data: DirectMessageData, context: Arc<
         }
     };
 }
+
+/// The network could not deliver our direct message because the recipient is offline,
+/// and queued it until they are back.
+/// This resolves the pending send with an error that says so, instead of letting it time out.
+#[autotrace]
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn handle_send_message_queued(request_id: String, recipient: Address, context: Arc<Context>) {
+    let action_wrapper =
+        ActionWrapper::new(Action::SendDirectMessageQueued((request_id, recipient)));
+    dispatch_action(context.action_channel(), action_wrapper);
+}
@@ -1,10 +1,11 @@
 use crate::{
     action::{ActionWrapper, ValidationKey},
+    clock,
     network::{direct_message::DirectMessage, reducers::send_message, state::NetworkState},
     state::State,
 };
 use holochain_core_types::{chain_header::ChainHeader, error::HolochainError};
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 
 // Some thought needs to go in to how long this should really be
 // Should probably also be configurable via config or env vars
@@ -45,7 +46,7 @@ pub fn reduce_get_validation_package(
         .insert(key.clone(), result);
 
     let timeout = (
-        SystemTime::now(),
+        clock::now(),
         Duration::from_millis(GET_VALIDATION_PACKAGE_MESSAGE_TIMEOUT_MS),
     );
     tracing::debug!(new_val_pack = ?key);
@@ -39,7 +39,10 @@ use crate::{
             respond_fetch::reduce_respond_fetch_data,
             respond_gossip_list::reduce_respond_gossip_list,
             respond_query::reduce_respond_query,
-            send_direct_message::{reduce_send_direct_message, reduce_send_direct_message_timeout},
+            send_direct_message::{
+                reduce_send_direct_message, reduce_send_direct_message_queued,
+                reduce_send_direct_message_timeout,
+            },
             shutdown::reduce_shutdown,
         },
         state::NetworkState,
@@ -86,6 +89,7 @@ fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<NetworkReduceFn> {
         Action::RespondQuery(_) => Some(reduce_respond_query),
         Action::SendDirectMessage(_) => Some(reduce_send_direct_message),
         Action::SendDirectMessageTimeout(_) => Some(reduce_send_direct_message_timeout),
+        Action::SendDirectMessageQueued(_) => Some(reduce_send_direct_message_queued),
         Action::ShutdownNetwork => Some(reduce_shutdown),
         _ => None,
     }
@@ -1,5 +1,6 @@
 use crate::{
     action::{Action, ActionWrapper},
+    clock,
     network::state::NetworkState,
     state::{State, ACTION_PRUNE_MS},
 };
@@ -17,7 +18,7 @@ pub fn reduce_prune(
         .actions
         .iter()
         .filter(|(_, response)| {
-            if let Ok(elapsed) = response.created_at.elapsed() {
+            if let Some(elapsed) = clock::elapsed(response.created_at) {
                 if elapsed > Duration::from_millis(ACTION_PRUNE_MS) {
                     return false;
                 }
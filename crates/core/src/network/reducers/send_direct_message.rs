@@ -1,6 +1,6 @@
 use crate::{
     action::{ActionWrapper, DirectMessageData},
-    network::{reducers::send, state::NetworkState},
+    network::{direct_message::DirectMessage, reducers::send, state::NetworkState},
     state::State,
 };
 use holochain_core_types::error::HolochainError;
@@ -84,6 +84,35 @@ pub fn reduce_send_direct_message_timeout(
     );
 }
 
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn reduce_send_direct_message_queued(
+    network_state: &mut NetworkState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let (id, recipient) = unwrap_to!(action => crate::action::Action::SendDirectMessageQueued);
+
+    // Only custom messages have a sender waiting for the reply, other
+    // requests (like validation packages) keep waiting for their timeout.
+    match network_state.direct_message_connections.get(id) {
+        Some(DirectMessage::Custom(_)) => (),
+        _ => return,
+    }
+
+    network_state.direct_message_timeouts.remove(id);
+    network_state.direct_message_connections.remove(id);
+
+    if network_state.custom_direct_message_replys.get(id).is_some() {
+        return;
+    }
+
+    network_state.custom_direct_message_replys.insert(
+        id.clone(),
+        Err(HolochainError::DirectMessageQueued(recipient.to_string())),
+    );
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -159,4 +188,59 @@ mod tests {
             )))
         );
     }
+
+    #[test]
+    pub fn reduce_send_direct_message_queued_test() {
+        let netname = Some("reduce_send_direct_message_queued_test");
+        let context = test_context("alice", netname);
+        let mut store = test_store(context.clone());
+        store = store.reduce(ActionWrapper::new(Action::InitializeChain(Dna::new())));
+
+        let dna_address: Address = "reduce_send_direct_message_queued_test".into();
+        let handler = create_handler(&context, dna_address.to_string());
+
+        let action_wrapper = ActionWrapper::new(Action::InitNetwork(NetworkSettings {
+            p2p_config: test_memory_network_config(netname),
+            dna_address,
+            agent_id: String::from("alice"),
+            handler,
+        }));
+        store = store.reduce(action_wrapper);
+
+        let msg_id = String::from("any");
+        let direct_message_data = DirectMessageData {
+            address: Address::from("bob"),
+            message: DirectMessage::Custom(CustomDirectMessage {
+                zome: String::from("test"),
+                payload: Ok(String::from("test")),
+            }),
+            msg_id: msg_id.clone(),
+            is_response: false,
+        };
+        store = store.reduce(ActionWrapper::new(Action::SendDirectMessage((
+            direct_message_data,
+            None,
+        ))));
+
+        store = store.reduce(ActionWrapper::new(Action::SendDirectMessageQueued((
+            msg_id.clone(),
+            Address::from("bob"),
+        ))));
+
+        assert_eq!(
+            store
+                .network()
+                .custom_direct_message_replys
+                .get(&msg_id)
+                .cloned(),
+            Some(Err(HolochainError::DirectMessageQueued(String::from(
+                "bob"
+            ))))
+        );
+        assert!(store
+            .network()
+            .direct_message_connections
+            .get(&msg_id)
+            .is_none());
+    }
 }
@@ -38,6 +38,8 @@ pub struct ZomeFnCall {
     pub cap: CapabilityRequest,
     pub fn_name: String,
     pub parameters: JsonString,
+    /// Set if the caller listens for result chunks emitted by the zome function
+    pub stream_id: Option<String>,
 }
 
 impl ZomeFnCall {
@@ -55,9 +57,15 @@ impl ZomeFnCall {
             cap,
             fn_name: function.to_string(),
             parameters: parameters.into(),
+            stream_id: None,
         }
     }
 
+    pub fn with_stream_id(mut self, stream_id: Option<String>) -> Self {
+        self.stream_id = stream_id;
+        self
+    }
+
     pub fn create<J: Into<JsonString>>(
         context: Arc<Context>,
         zome: &str,
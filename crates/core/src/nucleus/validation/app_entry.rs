@@ -30,6 +30,14 @@ pub async fn validate_app_entry(
     let zome_name = dna
         .get_zome_name_for_app_entry_type(&app_entry_type)
         .ok_or(ValidationError::NotImplemented)?;
+    if let Entry::App(_, entry_value) = &entry {
+        let entry_type_def = dna
+            .get_entry_type_def(&String::from(app_entry_type.clone()))
+            .ok_or(ValidationError::NotImplemented)?;
+        entry_type_def
+            .validate_against_schema(entry_value)
+            .map_err(|e| ValidationError::Fail(format!("Schema validation failed: {}", e)))?;
+    }
     if let Some(expected_link_update) = link.clone() {
         get_entry_from_dht(&context.clone(), &expected_link_update).map_err(|_| {
             ValidationError::UnresolvedDependencies(vec![expected_link_update.clone()])
@@ -1,5 +1,6 @@
 use crate::{
     action::{Action, ActionWrapper},
+    clock,
     context::Context,
     instance::dispatch_action,
 };
@@ -9,7 +10,7 @@ use std::sync::Arc;
 pub fn check_network_processes_for_timeouts(context: Arc<Context>) {
     let state = context.state().expect("Couldn't get state in timeout job");
     for (key, (time, duration)) in state.network().query_timeouts.iter() {
-        if let Ok(elapsed) = time.elapsed() {
+        if let Some(elapsed) = clock::elapsed(*time) {
             if elapsed > *duration {
                 dispatch_action(
                     context.action_channel(),
@@ -20,7 +21,7 @@ pub fn check_network_processes_for_timeouts(context: Arc<Context>) {
     }
 
     for (key, (time, duration)) in state.network().direct_message_timeouts.iter() {
-        if let Ok(elapsed) = time.elapsed() {
+        if let Some(elapsed) = clock::elapsed(*time) {
             if elapsed > *duration {
                 dispatch_action(
                     context.action_channel(),
@@ -31,7 +32,7 @@ pub fn check_network_processes_for_timeouts(context: Arc<Context>) {
     }
 
     for (key, (time, duration)) in state.network().get_validation_package_timeouts.iter() {
-        if let Ok(elapsed) = time.elapsed() {
+        if let Some(elapsed) = clock::elapsed(*time) {
             if elapsed > *duration {
                 dispatch_action(
                     context.action_channel(),
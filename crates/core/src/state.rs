@@ -4,6 +4,7 @@ use crate::{
         chain_store::ChainStore,
         state::{AgentState, AgentStateSnapshot},
     },
+    clock,
     content_store::GetContent,
     context::Context,
     dht::dht_store::DhtStore,
@@ -41,7 +42,7 @@ pub struct ActionResponse<T> {
 impl<T> ActionResponse<T> {
     pub fn new(response: T) -> Self {
         ActionResponse::<T> {
-            created_at: SystemTime::now(),
+            created_at: clock::now(),
             response,
         }
     }
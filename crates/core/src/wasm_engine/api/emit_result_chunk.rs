@@ -0,0 +1,138 @@
+use crate::wasm_engine::{api::ZomeApiResult, Runtime};
+use holochain_wasm_utils::api_serialization::emit_result_chunk::EmitResultChunkArgs;
+use std::convert::TryFrom;
+use wasmi::{RuntimeArgs, RuntimeValue};
+
+/// ZomeApiFunction::EmitResultChunk function code
+/// args: [0] encoded MemoryAllocation as u64
+/// Expected complex argument: EmitResultChunkArgs
+/// Returns an HcApiReturnCode as I64
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub fn invoke_emit_result_chunk(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
+    let call_data = runtime.zome_call_data()?;
+    let context = call_data.context;
+
+    let args_str = runtime.load_json_string_from_args(&args);
+    let emit_result_chunk_args = match EmitResultChunkArgs::try_from(args_str.clone()) {
+        Ok(args) => args,
+        // Exit on error
+        Err(error) => {
+            log_error!(
+                context,
+                "zome: invoke_emit_result_chunk failed to \
+                 deserialize arguments: {:?} with error {:?}",
+                args_str,
+                error
+            );
+            return ribosome_error_code!(ArgumentDeserializationFailed);
+        }
+    };
+
+    // Chunks only make sense to a caller that asked for a stream, all others just get
+    // the return value of the zome function.
+    let stream_id = match call_data.call.stream_id {
+        Some(stream_id) => stream_id,
+        None => {
+            log_debug!(
+                context,
+                "zome: invoke_emit_result_chunk() dropped chunk of call without stream id"
+            );
+            return ribosome_success!();
+        }
+    };
+
+    // The sink writes the chunk out right away, so chunks reach the caller in order and
+    // before the zome function returns.
+    match context.result_chunk_sink(&stream_id) {
+        Some(sink) => sink(emit_result_chunk_args.chunk),
+        None => log_error!(
+            context,
+            "zome: invoke_emit_result_chunk() could not send chunk because stream {} is not open",
+            stream_id
+        ),
+    }
+
+    ribosome_success!()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::{
+        instance::tests::test_instance_and_context,
+        nucleus::{tests::test_capability_request, ZomeFnCall},
+        wasm_engine::{
+            self,
+            api::{
+                tests::{
+                    test_function_name, test_parameters, test_zome_api_function_wasm,
+                    test_zome_name,
+                },
+                ZomeApiFunction,
+            },
+            runtime::WasmCallData,
+            Defn,
+        },
+    };
+    use crossbeam_channel::unbounded;
+    use holochain_json_api::json::JsonString;
+    use holochain_wasm_utils::api_serialization::emit_result_chunk::EmitResultChunkArgs;
+    use std::sync::Arc;
+
+    pub fn test_args_bytes() -> Vec<u8> {
+        let args_string: JsonString = EmitResultChunkArgs {
+            chunk: JsonString::from_json("[1,2,3]"),
+        }
+        .into();
+        args_string.to_string().into_bytes()
+    }
+
+    /// Calls the test zome function with the given stream id while a sink for the stream
+    /// "stream-1" is open and returns the chunks that reached it
+    fn call_with_stream_id(stream_id: Option<String>) -> Vec<JsonString> {
+        let wasm = test_zome_api_function_wasm(ZomeApiFunction::EmitResultChunk.as_str());
+        let dna = test_utils::create_test_dna_with_wasm(&test_zome_name(), wasm.clone());
+
+        let (_instance, context) =
+            test_instance_and_context(dna, None).expect("Could not create test instance");
+
+        let (tx, rx) = unbounded::<JsonString>();
+        context.add_result_chunk_sink(
+            String::from("stream-1"),
+            Arc::new(move |chunk| tx.send(chunk).unwrap()),
+        );
+
+        let zome_call = ZomeFnCall::new(
+            &test_zome_name(),
+            test_capability_request(context.clone(), &test_function_name(), test_parameters()),
+            &test_function_name(),
+            test_parameters(),
+        )
+        .with_stream_id(stream_id);
+        wasm_engine::run_dna(
+            Some(test_args_bytes()),
+            WasmCallData::new_zome_call(context.clone(), zome_call),
+        )
+        .expect("test should be callable");
+        context.remove_result_chunk_sink("stream-1");
+
+        rx.try_iter().collect()
+    }
+
+    #[test]
+    fn test_zome_api_function_emit_result_chunk() {
+        assert_eq!(
+            call_with_stream_id(Some(String::from("stream-1"))),
+            vec![JsonString::from_json("[1,2,3]")]
+        );
+    }
+
+    #[test]
+    fn test_zome_api_function_emit_result_chunk_without_stream() {
+        assert!(call_with_stream_id(None).is_empty());
+    }
+
+    #[test]
+    fn test_zome_api_function_emit_result_chunk_to_other_stream() {
+        assert!(call_with_stream_id(Some(String::from("stream-2"))).is_empty());
+    }
+}
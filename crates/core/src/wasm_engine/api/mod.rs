@@ -4,6 +4,7 @@
 pub mod call;
 pub mod commit;
 pub mod debug;
+pub mod emit_result_chunk;
 pub mod emit_signal;
 pub mod entry_address;
 pub mod get_entry;
@@ -33,6 +34,7 @@ use crate::wasm_engine::{
         commit::invoke_commit_app_entry,
         crypto::invoke_crypto,
         debug::invoke_debug,
+        emit_result_chunk::invoke_emit_result_chunk,
         emit_signal::invoke_emit_signal,
         entry_address::invoke_entry_address,
         get_entry::invoke_get_entry,
@@ -154,6 +156,9 @@ link_zome_api! {
 
     ///send a meta
     "hc_meta",Meta,invoke_meta;
+
+    /// Send a piece of the result of the current zome call to the caller that asked for a stream
+    "hc_emit_result_chunk", EmitResultChunk, invoke_emit_result_chunk;
 }
 
 #[cfg(test)]
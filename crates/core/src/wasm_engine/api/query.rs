@@ -12,7 +12,9 @@ use holochain_core_types::{
     entry::{entry_type::EntryType, Entry},
     error::HolochainError,
 };
-use holochain_wasm_utils::api_serialization::{QueryArgs, QueryArgsNames, QueryResult};
+use holochain_wasm_utils::api_serialization::{
+    QueryArgs, QueryArgsNames, QueryArgsOptions, QueryResult,
+};
 use std::{convert::TryFrom, sync::Arc};
 use wasmi::{RuntimeArgs, RuntimeValue};
 
@@ -59,6 +61,10 @@ use wasmi::{RuntimeArgs, RuntimeValue};
 /// `*`         Zero or more of any character
 /// `**/`       Zero or more of any namespace component
 ///
+/// The matching headers can be further restricted by `options.filter_by` (timestamp window,
+/// provenance and link_update_delete), returned oldest-first with `options.ordering`, and paged
+/// through by passing the address of the last header seen as `options.after`.
+///
 pub fn invoke_query(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
     let context = runtime.context()?;
     // deserialize args.
@@ -81,11 +87,7 @@ pub fn invoke_query(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult
             agent.chain_store().query(
                 &Some(top),
                 refs.as_slice(), // Vec<&str> -> Vec[&str]
-                ChainStoreQueryOptions {
-                    start: query.options.start,
-                    limit: query.options.limit,
-                    headers: query.options.headers,
-                },
+                chain_store_query_options(&query.options),
             )
         }
         QueryArgsNames::QueryName(name) => {
@@ -93,11 +95,7 @@ pub fn invoke_query(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult
             agent.chain_store().query(
                 &Some(top),
                 refs.as_slice(), // Vec<&str> -> &[&str]
-                ChainStoreQueryOptions {
-                    start: query.options.start,
-                    limit: query.options.limit,
-                    headers: query.options.headers,
-                },
+                chain_store_query_options(&query.options),
             )
         }
     };
@@ -151,6 +149,17 @@ pub fn invoke_query(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult
     runtime.store_result(result)
 }
 
+fn chain_store_query_options(options: &QueryArgsOptions) -> ChainStoreQueryOptions {
+    ChainStoreQueryOptions {
+        start: options.start,
+        limit: options.limit,
+        headers: options.headers,
+        ordering: options.ordering.clone(),
+        filter: options.filter_by.clone(),
+        after: options.after.clone(),
+    }
+}
+
 /// Get an local-chain Entry via the provided context, returning Entry or HolochainError on failure
 fn get_entry_from_chain(
     context: &Arc<Context>,
@@ -0,0 +1,261 @@
+use crate::{
+    agent::{
+        actions::import_chain_header::import_chain_header,
+        chain_export::{ChainElement, ChainExport},
+    },
+    context::Context,
+    dht::actions::hold_aspect::hold_aspect_no_ack,
+    entry::CanPublish,
+    network::actions::{publish::publish, publish_header_entry::publish_header_entry},
+    nucleus::{
+        actions::build_validation_package::build_validation_package, validation::validate_entry,
+    },
+};
+
+use holochain_core_types::{
+    entry::{entry_type::EntryType, Entry},
+    error::HolochainError,
+    network::entry_aspect::EntryAspect,
+    validation::{EntryLifecycle, ValidationData},
+};
+
+use holochain_persistence_api::cas::content::AddressableContent;
+
+use snowflake::ProcessUniqueId;
+use std::sync::Arc;
+
+/// Replaces the chain of a freshly initialized instance with an exported chain of the same
+/// DNA and agent, e.g. to move an agent to a new machine or to recover from corrupted storage.
+///
+/// 1. Checks the links, entry hashes and signatures of the exported chain and that it was
+///    authored by this instance's agent for this instance's DNA.
+/// 2. Refuses to overwrite a chain holding more than its genesis entries.
+/// 3. Imports the headers one by one, validating every entry that is validated when authored
+///    with the DNA's callbacks before importing it. If one is invalid, the genesis chain is
+///    restored.
+/// 4. Stores the imported entries in the DHT store, now that every header got accepted,
+///    and publishes them and their headers again.
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_CORE)]
+pub async fn import_chain(
+    export: &ChainExport,
+    context: &Arc<Context>,
+) -> Result<(), HolochainError> {
+    let problems = export.verify();
+    if !problems.is_empty() {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Can't import a broken chain: {}",
+            problems.join("; ")
+        )));
+    }
+    check_chain_owner(export, context)?;
+
+    let state = context.state()?;
+    let genesis_chain = ChainExport::from_chain_store(
+        &state.agent().chain_store(),
+        &state.agent().top_chain_header(),
+    )?;
+    if let Some(element) = genesis_chain
+        .elements
+        .iter()
+        .find(|element| !is_genesis_entry_type(element.header.entry_type()))
+    {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Can only import into a fresh chain but this chain already holds the {:?} entry {}",
+            element.header.entry_type(),
+            element.header.entry_address()
+        )));
+    }
+
+    log_debug!(
+        context,
+        "workflow/import_chain: importing {} headers...",
+        export.elements.len()
+    );
+    if let Err(error) = import_elements(&export.elements, true, context).await {
+        log_error!(
+            context,
+            "workflow/import_chain: {}, restoring the genesis chain",
+            error
+        );
+        import_elements(&genesis_chain.elements, false, context).await?;
+        return Err(error);
+    }
+
+    log_debug!(context, "workflow/import_chain: holding and publishing...");
+    for element in &export.elements {
+        let entry = element
+            .entry
+            .clone()
+            .expect("import_elements() imported every entry");
+        hold_aspect_no_ack(
+            &ProcessUniqueId::new(),
+            EntryAspect::Content(entry, element.header.clone()),
+            context.clone(),
+        )
+        .await?;
+        let address = element.header.entry_address().clone();
+        if element.header.entry_type().can_publish(context) {
+            publish(address.clone(), context).await?;
+        }
+        publish_header_entry(address, context).await?;
+    }
+    log_debug!(context, "workflow/import_chain: imported and published!");
+    Ok(())
+}
+
+/// Checks that the exported chain starts with this instance's DNA and agent
+/// and that the agent signed every header
+fn check_chain_owner(export: &ChainExport, context: &Arc<Context>) -> Result<(), HolochainError> {
+    let dna = context.get_dna().ok_or(HolochainError::DnaMissing)?;
+    let agent_address = context.agent_id.address();
+
+    let entries = || {
+        export
+            .elements
+            .iter()
+            .filter_map(|element| element.entry.as_ref())
+    };
+    match entries().find(|entry| entry.entry_type() == EntryType::Dna) {
+        Some(Entry::Dna(exported_dna)) if exported_dna.address() == dna.address() => {}
+        Some(_) => {
+            return Err(HolochainError::ErrorGeneric(
+                "The chain to import belongs to a different DNA".to_string(),
+            ))
+        }
+        None => {
+            return Err(HolochainError::ErrorGeneric(
+                "The chain to import has no DNA entry".to_string(),
+            ))
+        }
+    }
+    if !entries().any(|entry| entry.address() == agent_address) {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "The chain to import does not belong to agent {}",
+            agent_address
+        )));
+    }
+    match export.elements.iter().find(|element| {
+        !element
+            .header
+            .provenances()
+            .iter()
+            .any(|provenance| provenance.source() == agent_address)
+    }) {
+        Some(element) => Err(HolochainError::ErrorGeneric(format!(
+            "Header {} of the chain to import is not signed by agent {}",
+            element.header_address, agent_address
+        ))),
+        None => Ok(()),
+    }
+}
+
+async fn import_elements(
+    elements: &[ChainElement],
+    validate: bool,
+    context: &Arc<Context>,
+) -> Result<(), HolochainError> {
+    for element in elements {
+        let entry = element.entry.clone().ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!(
+                "Entry of header {} is missing",
+                element.header_address
+            ))
+        })?;
+
+        // Validate against the chain as it was when the entry got authored and before
+        // importing stores the entry anywhere.
+        if validate && is_validated_when_authored(&entry.entry_type()) {
+            let validation_package = build_validation_package(&entry, context.clone(), &vec![])?;
+            let validation_data = ValidationData {
+                package: validation_package,
+                lifecycle: EntryLifecycle::Chain,
+            };
+            validate_entry(
+                entry.clone(),
+                element.header.link_update_delete(),
+                validation_data,
+                context,
+            )
+            .await
+            .map_err(|error| {
+                HolochainError::ValidationFailed(format!(
+                    "Entry {} of the imported chain is invalid: {}",
+                    entry.address(),
+                    HolochainError::from(error)
+                ))
+            })?;
+        }
+
+        import_chain_header(element.header.clone(), entry, context).await?;
+    }
+    Ok(())
+}
+
+/// Entries that initializing an instance commits
+fn is_genesis_entry_type(entry_type: &EntryType) -> bool {
+    match entry_type {
+        EntryType::Dna | EntryType::AgentId | EntryType::CapTokenGrant => true,
+        _ => false,
+    }
+}
+
+/// Entries that `author_entry` validates with the DNA's callbacks before committing them
+fn is_validated_when_authored(entry_type: &EntryType) -> bool {
+    match entry_type {
+        EntryType::App(_) | EntryType::LinkAdd | EntryType::LinkRemove | EntryType::Deletion => {
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        nucleus::actions::tests::{instance_by_name, test_dna},
+        workflows::author_entry::author_entry,
+    };
+    use holochain_core_types::entry::test_entry_with_value;
+
+    fn chain_of(context: &Arc<Context>) -> ChainExport {
+        let state = context.state().unwrap();
+        ChainExport::from_chain_store(
+            &state.agent().chain_store(),
+            &state.agent().top_chain_header(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn imports_a_chain_into_a_fresh_instance_of_the_same_agent() {
+        let mut dna = test_dna();
+        dna.uuid = "imports_a_chain_into_a_fresh_instance".to_string();
+        let netname = Some("imports_a_chain_into_a_fresh_instance, the network");
+        let (_instance1, context1) = instance_by_name("jill", dna.clone(), netname);
+        context1
+            .block_on(author_entry(
+                &test_entry_with_value("{\"stuff\":\"test entry value\"}"),
+                None,
+                &context1,
+                &vec![],
+            ))
+            .unwrap();
+        let export = chain_of(&context1);
+
+        let (_instance2, context2) = instance_by_name("jill", dna.clone(), netname);
+        assert_ne!(chain_of(&context2), export);
+        context2.block_on(import_chain(&export, &context2)).unwrap();
+        assert_eq!(chain_of(&context2), export);
+
+        // the chain now holds an app entry and is no longer fresh
+        let result = context2.block_on(import_chain(&export, &context2));
+        assert!(format!("{:?}", result).contains("fresh chain"));
+
+        let (_instance3, context3) = instance_by_name("jack", dna, netname);
+        let genesis_chain = chain_of(&context3);
+        let result = context3.block_on(import_chain(&export, &context3));
+        assert!(format!("{:?}", result).contains("does not belong to agent"));
+        assert_eq!(chain_of(&context3), genesis_chain);
+    }
+}
@@ -19,6 +19,8 @@ pub mod hold_entry_update;
 #[autotrace]
 pub mod hold_link;
 #[autotrace]
+pub mod import_chain;
+#[autotrace]
 pub mod remove_link;
 #[autotrace]
 pub mod respond_validation_package_request;
@@ -1,9 +1,10 @@
 //! File holding all the structs for handling entry types defined by DNA.
 
-use dna::zome::ZomeEntryTypes;
+use dna::{schema, zome::ZomeEntryTypes};
 use entry::entry_type::EntryType;
 use holochain_json_api::{error::JsonError, json::JsonString};
 use serde::{ser::SerializeMap, Deserialize, Deserializer, Serializer};
+use serde_json::Value;
 use std::collections::BTreeMap;
 
 /// Enum for Zome EntryType "sharing" property.
@@ -137,6 +138,12 @@ pub struct EntryTypeDef {
     /// An array of link definitions for links pointing to entries of this type
     #[serde(default)]
     pub linked_from: Vec<LinkedFrom>,
+
+    /// Optional JSON Schema that every entry of this type must conform to.
+    /// It is enforced before the zome's validation callback is run.
+    /// @see dna::schema for the supported keywords
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<JsonString>,
 }
 
 fn empty_properties() -> JsonString {
@@ -150,6 +157,7 @@ impl Default for EntryTypeDef {
             sharing: Sharing::default(),
             links_to: Vec::default(),
             linked_from: Vec::default(),
+            schema: None,
         }
     }
 }
@@ -159,6 +167,36 @@ impl EntryTypeDef {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// Checks that the schema, if any, is valid JSON using only supported keywords.
+    pub fn check_schema(&self) -> Result<(), String> {
+        match self.schema_value()? {
+            Some(schema) => schema::check_schema(&schema),
+            None => Ok(()),
+        }
+    }
+
+    /// Validates an entry value against the schema, if any.  The schema is compiled once
+    /// and cached, see [schema::compiled].
+    pub fn validate_against_schema(&self, entry_value: &JsonString) -> Result<(), String> {
+        let schema = match &self.schema {
+            Some(schema) => schema::compiled(&String::from(schema.to_owned()))?,
+            None => return Ok(()),
+        };
+        let instance: Value = serde_json::from_str(&String::from(entry_value.to_owned()))
+            .map_err(|e| format!("entry is not valid JSON: {}", e))?;
+        schema.validate(&instance)
+    }
+
+    fn schema_value(&self) -> Result<Option<Value>, String> {
+        self.schema
+            .as_ref()
+            .map(|schema| {
+                serde_json::from_str(&String::from(schema.to_owned()))
+                    .map_err(|e| format!("schema is not valid JSON: {}", e))
+            })
+            .transpose()
+    }
 }
 
 #[cfg(test)]
@@ -210,4 +248,35 @@ mod tests {
 
         assert_eq!(fixture, entry);
     }
+    #[test]
+    fn schema_validation() {
+        let fixture: EntryTypeDef = serde_json::from_str(
+            r#"{
+                "properties": "{}",
+                "schema": "{\"type\": \"object\", \"required\": [\"title\"]}"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(Ok(()), fixture.check_schema());
+        assert_eq!(
+            Ok(()),
+            fixture.validate_against_schema(&JsonString::from_json("{\"title\": \"hi\"}"))
+        );
+        assert_eq!(
+            Err("/: missing required property \"title\"".to_string()),
+            fixture.validate_against_schema(&JsonString::from_json("{}"))
+        );
+
+        // Entry types without a schema accept anything and serialize as before
+        let entry = EntryTypeDef::new();
+        assert_eq!(
+            Ok(()),
+            entry.validate_against_schema(&JsonString::from_json("[]"))
+        );
+        assert!(!serde_json::to_string(&entry).unwrap().contains("schema"));
+
+        let mut entry = EntryTypeDef::new();
+        entry.schema = Some(JsonString::from_json("{\"type\": \"text\"}"));
+        assert!(entry.check_schema().is_err());
+    }
 }
@@ -28,6 +28,7 @@ pub mod bridges;
 pub mod capabilities;
 pub mod entry_types;
 pub mod fn_declarations;
+pub mod schema;
 pub mod traits;
 pub mod wasm;
 pub mod zome;
@@ -260,6 +261,26 @@ impl Dna {
             .collect()
     }
 
+    /// Checks the optional JSON Schema of every entry type, returning one result per schema.
+    pub fn verify_entry_schemas(&self) -> Vec<HcResult<()>> {
+        self.zomes
+            .iter()
+            .flat_map(|(zome_name, zome)| {
+                zome.entry_types
+                    .iter()
+                    .filter(|(_, entry_type_def)| entry_type_def.schema.is_some())
+                    .map(move |(entry_type, entry_type_def)| {
+                        entry_type_def.check_schema().map_err(|e| {
+                            HolochainError::ErrorGeneric(format!(
+                                "Invalid schema for entry type {} in zome {}: {}",
+                                entry_type, zome_name, e
+                            ))
+                        })
+                    })
+            })
+            .collect()
+    }
+
     // Check that all the zomes in the DNA have code with the required callbacks
     // TODO: Add more advanced checks that actually try and call required functions
     pub fn verify(&self) -> HcResult<()> {
@@ -277,6 +298,7 @@ impl Dna {
                     )))
                 }
             })
+            .chain(self.verify_entry_schemas())
             .filter_map(|r| r.err())
             .collect();
         if errors.is_empty() {
@@ -0,0 +1,433 @@
+//! A small subset of JSON Schema used to check the structure of app entries against the
+//! optional `schema` of their [EntryTypeDef](crate::dna::entry_types::EntryTypeDef).
+//!
+//! Supported keywords are `type`, `enum`, `const`, `properties`, `required`,
+//! `additionalProperties` (boolean or schema), `items`, `minItems`, `maxItems`, `minLength`,
+//! `maxLength`, `pattern`, `minimum`, `maximum`, `exclusiveMinimum` and `exclusiveMaximum`.
+//! The annotation keywords `$schema`, `$id`, `title`, `description`, `default` and `examples`
+//! are accepted and ignored.  Any other keyword is rejected by [check_schema] so that a DNA can
+//! never silently rely on a constraint that isn't enforced.
+
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+const VALIDATION_KEYWORDS: &[&str] = &[
+    "type",
+    "enum",
+    "const",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+];
+
+const ANNOTATION_KEYWORDS: &[&str] = &[
+    "$schema",
+    "$id",
+    "title",
+    "description",
+    "default",
+    "examples",
+];
+
+const TYPE_NAMES: &[&str] = &[
+    "null", "boolean", "object", "array", "number", "integer", "string",
+];
+
+/// Checks that the given value is a schema made only of supported keywords with well-formed
+/// arguments.
+pub fn check_schema(schema: &Value) -> Result<(), String> {
+    check_schema_at(schema, "#")
+}
+
+/// A schema that passed [check_schema], with the regular expressions of its `pattern`
+/// keywords compiled ahead of validation.
+#[derive(Debug)]
+pub struct CompiledSchema {
+    schema: Value,
+    patterns: HashMap<String, Regex>,
+}
+
+impl CompiledSchema {
+    /// Checks the schema and compiles its patterns.
+    pub fn compile(schema: Value) -> Result<Self, String> {
+        check_schema(&schema)?;
+        let mut patterns = HashMap::new();
+        collect_patterns(&schema, &mut patterns)?;
+        Ok(CompiledSchema { schema, patterns })
+    }
+
+    /// Validates an instance against this schema, returning a description of the first
+    /// violation found.
+    pub fn validate(&self, instance: &Value) -> Result<(), String> {
+        validate_at(&self.schema, instance, "", &self.patterns)
+    }
+}
+
+lazy_static! {
+    /// Schemas compiled so far, keyed by their JSON source.  Entry type definitions are
+    /// cloned out of the DNA for every validation, so this is what keeps each entry type's
+    /// schema from being parsed again for every entry.
+    static ref COMPILED_SCHEMAS: RwLock<HashMap<String, Result<Arc<CompiledSchema>, String>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Returns the compiled form of the schema with the given JSON source, parsing and compiling
+/// it the first time it is asked for.
+pub fn compiled(source: &str) -> Result<Arc<CompiledSchema>, String> {
+    if let Some(compiled) = COMPILED_SCHEMAS
+        .read()
+        .expect("schema cache lock poisoned")
+        .get(source)
+    {
+        return compiled.clone();
+    }
+    let compiled = serde_json::from_str(source)
+        .map_err(|e| format!("schema is not valid JSON: {}", e))
+        .and_then(CompiledSchema::compile)
+        .map(Arc::new);
+    COMPILED_SCHEMAS
+        .write()
+        .expect("schema cache lock poisoned")
+        .insert(source.to_string(), compiled.clone());
+    compiled
+}
+
+fn collect_patterns(schema: &Value, patterns: &mut HashMap<String, Regex>) -> Result<(), String> {
+    let schema = match schema {
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        if !patterns.contains_key(pattern) {
+            let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+            patterns.insert(pattern.to_string(), regex);
+        }
+    }
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        for property in properties.values() {
+            collect_patterns(property, patterns)?;
+        }
+    }
+    for keyword in &["additionalProperties", "items"] {
+        if let Some(subschema) = schema.get(*keyword) {
+            collect_patterns(subschema, patterns)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_schema_at(schema: &Value, path: &str) -> Result<(), String> {
+    let object = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(object) => object,
+        _ => return Err(format!("{}: schema must be an object or a boolean", path)),
+    };
+    for (keyword, argument) in object {
+        let keyword_path = format!("{}/{}", path, keyword);
+        let well_formed = match keyword.as_str() {
+            "type" => match argument {
+                Value::String(name) => TYPE_NAMES.contains(&name.as_str()),
+                Value::Array(names) => names
+                    .iter()
+                    .all(|name| name.as_str().map_or(false, |n| TYPE_NAMES.contains(&n))),
+                _ => false,
+            },
+            "enum" => argument.is_array(),
+            "properties" => match argument {
+                Value::Object(properties) => {
+                    for (name, property) in properties {
+                        check_schema_at(property, &format!("{}/{}", keyword_path, name))?;
+                    }
+                    true
+                }
+                _ => false,
+            },
+            "required" => argument
+                .as_array()
+                .map_or(false, |names| names.iter().all(Value::is_string)),
+            "additionalProperties" | "items" => {
+                check_schema_at(argument, &keyword_path)?;
+                true
+            }
+            "minItems" | "maxItems" | "minLength" | "maxLength" => argument.is_u64(),
+            "minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum" => argument.is_number(),
+            "pattern" => argument
+                .as_str()
+                .map_or(false, |pattern| Regex::new(pattern).is_ok()),
+            keyword
+                if VALIDATION_KEYWORDS.contains(&keyword)
+                    || ANNOTATION_KEYWORDS.contains(&keyword) =>
+            {
+                true
+            }
+            _ => return Err(format!("{}: unsupported schema keyword", keyword_path)),
+        };
+        if !well_formed {
+            return Err(format!("{}: malformed argument {}", keyword_path, argument));
+        }
+    }
+    Ok(())
+}
+
+fn type_matches(type_name: &str, instance: &Value) -> bool {
+    match type_name {
+        "null" => instance.is_null(),
+        "boolean" => instance.is_boolean(),
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "number" => instance.is_number(),
+        "integer" => {
+            instance.is_i64()
+                || instance.is_u64()
+                || instance.as_f64().map_or(false, |n| n.fract() == 0.0)
+        }
+        "string" => instance.is_string(),
+        _ => false,
+    }
+}
+
+fn validate_at(
+    schema: &Value,
+    instance: &Value,
+    path: &str,
+    patterns: &HashMap<String, Regex>,
+) -> Result<(), String> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{}: no value is allowed here", at(path))),
+        Value::Object(schema) => schema,
+        _ => return Err(format!("{}: invalid schema", at(path))),
+    };
+    let fail = |reason: String| Err(format!("{}: {}", at(path), reason));
+
+    if let Some(types) = schema.get("type") {
+        let matches = match types {
+            Value::String(name) => type_matches(name, instance),
+            Value::Array(names) => names
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|name| type_matches(name, instance)),
+            _ => false,
+        };
+        if !matches {
+            return fail(format!("expected type {}, got {}", types, instance));
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(instance) {
+            return fail(format!("{} is not one of {:?}", instance, allowed));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != instance {
+            return fail(format!("expected {}, got {}", expected, instance));
+        }
+    }
+
+    match instance {
+        Value::Object(object) => validate_object(schema, object, path, patterns)?,
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    return fail(format!("expected at least {} items", min));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if (items.len() as u64) > max {
+                    return fail(format!("expected at most {} items", max));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{}/{}", path, index), patterns)?;
+                }
+            }
+        }
+        Value::String(string) => {
+            let length = string.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if length < min {
+                    return fail(format!("expected at least {} characters", min));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if length > max {
+                    return fail(format!("expected at most {} characters", max));
+                }
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                let regex = patterns.get(pattern).ok_or_else(|| {
+                    format!("{}: pattern {:?} was not compiled", at(path), pattern)
+                })?;
+                if !regex.is_match(string) {
+                    return fail(format!("{:?} does not match pattern {:?}", string, pattern));
+                }
+            }
+        }
+        Value::Number(number) => {
+            let n = number.as_f64().unwrap_or_default();
+            let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+            if let Some(min) = bound("minimum") {
+                if n < min {
+                    return fail(format!("{} is less than the minimum {}", n, min));
+                }
+            }
+            if let Some(max) = bound("maximum") {
+                if n > max {
+                    return fail(format!("{} is greater than the maximum {}", n, max));
+                }
+            }
+            if let Some(min) = bound("exclusiveMinimum") {
+                if n <= min {
+                    return fail(format!("{} is not greater than {}", n, min));
+                }
+            }
+            if let Some(max) = bound("exclusiveMaximum") {
+                if n >= max {
+                    return fail(format!("{} is not less than {}", n, max));
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) => (),
+    }
+    Ok(())
+}
+
+fn validate_object(
+    schema: &Map<String, Value>,
+    object: &Map<String, Value>,
+    path: &str,
+    patterns: &HashMap<String, Regex>,
+) -> Result<(), String> {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                return Err(format!("{}: missing required property {:?}", at(path), name));
+            }
+        }
+    }
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, value) in object {
+        let property_path = format!("{}/{}", path, name);
+        match properties.and_then(|properties| properties.get(name)) {
+            Some(property_schema) => {
+                validate_at(property_schema, value, &property_path, patterns)?
+            }
+            None => {
+                if let Some(additional) = schema.get("additionalProperties") {
+                    validate_at(additional, value, &property_path, patterns)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn at(path: &str) -> &str {
+    if path.is_empty() {
+        "/"
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn post_schema() -> Value {
+        json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": "post",
+            "type": "object",
+            "required": ["content", "date_created"],
+            "additionalProperties": false,
+            "properties": {
+                "content": { "type": "string", "minLength": 1, "maxLength": 280 },
+                "date_created": { "type": "string", "pattern": "^[0-9]{4}-" },
+                "tags": { "type": "array", "items": { "enum": ["news", "misc"] }, "maxItems": 2 },
+                "likes": { "type": "integer", "minimum": 0 }
+            }
+        })
+    }
+
+    #[test]
+    fn check_schema_test() {
+        assert_eq!(Ok(()), check_schema(&post_schema()));
+        assert_eq!(Ok(()), check_schema(&json!(true)));
+        assert_eq!(
+            Err("#/properties/content/format: unsupported schema keyword".to_string()),
+            check_schema(&json!({"properties": {"content": {"format": "email"}}})),
+        );
+        assert_eq!(
+            Err("#/type: malformed argument \"text\"".to_string()),
+            check_schema(&json!({"type": "text"})),
+        );
+        assert!(check_schema(&json!({"pattern": "("})).is_err());
+        assert!(check_schema(&json!("object")).is_err());
+    }
+
+    #[test]
+    fn validate_test() {
+        let schema = CompiledSchema::compile(post_schema()).unwrap();
+        assert_eq!(
+            Ok(()),
+            schema.validate(
+                &json!({"content": "hi", "date_created": "2020-01-01", "tags": ["news"], "likes": 3})
+            )
+        );
+        assert_eq!(
+            Err("/: missing required property \"date_created\"".to_string()),
+            schema.validate(&json!({"content": "hi"})),
+        );
+        assert_eq!(
+            Err("/content: expected at least 1 characters".to_string()),
+            schema.validate(&json!({"content": "", "date_created": "2020"})),
+        );
+        assert_eq!(
+            Err("/extra: no value is allowed here".to_string()),
+            schema.validate(&json!({"content": "hi", "date_created": "2020", "extra": 1})),
+        );
+        assert!(schema
+            .validate(&json!({"content": "hi", "date_created": "2020", "tags": ["other"]}))
+            .is_err());
+        assert!(schema
+            .validate(&json!({"content": "hi", "date_created": "2020", "likes": 1.5}))
+            .is_err());
+        assert!(schema
+            .validate(&json!({"content": "hi", "date_created": "20-01"}))
+            .is_err());
+        assert!(schema.validate(&json!("hi")).is_err());
+    }
+
+    #[test]
+    fn compiled_test() {
+        let source = post_schema().to_string();
+        let first = compiled(&source).unwrap();
+        assert!(Arc::ptr_eq(&first, &compiled(&source).unwrap()));
+        assert!(first
+            .validate(&json!({"content": "hi", "date_created": "20-01"}))
+            .is_err());
+        assert!(compiled("{\"type\": ")
+            .unwrap_err()
+            .starts_with("schema is not valid JSON"));
+        assert_eq!(
+            Err("#/format: unsupported schema keyword".to_string()),
+            compiled("{\"format\": \"email\"}").map(|_| ()),
+        );
+    }
+}
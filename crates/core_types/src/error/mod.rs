@@ -118,6 +118,9 @@ pub enum HolochainError {
     EntryNotFoundLocally,
     EntryIsPrivate,
     List(Vec<HolochainError>),
+    /// a direct message was queued by the network until its recipient
+    /// is online, instead of being delivered
+    DirectMessageQueued(String),
 }
 
 pub type HcResult<T> = Result<T, HolochainError>;
@@ -176,6 +179,11 @@ impl fmt::Display for HolochainError {
                     .join("\n");
                 write!(f, "A list of errors has been generated {}", error_list)
             }
+            DirectMessageQueued(recipient) => write!(
+                f,
+                "Direct message queued until {} is online, no response yet",
+                recipient
+            ),
         }
     }
 }
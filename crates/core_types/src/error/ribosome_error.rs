@@ -209,6 +209,7 @@ impl From<HolochainError> for RibosomeErrorCode {
             HolochainError::EntryNotFoundLocally => RibosomeErrorCode::Unspecified,
             HolochainError::EntryIsPrivate => RibosomeErrorCode::Unspecified,
             HolochainError::List(_) => RibosomeErrorCode::Unspecified,
+            HolochainError::DirectMessageQueued(_) => RibosomeErrorCode::Unspecified,
         }
     }
 }
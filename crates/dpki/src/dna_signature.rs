@@ -0,0 +1,147 @@
+//! Signatures of DNA authors: an author signs the multihash of a DNA with their
+//! signing key so that conductors can check a DNA package was released by
+//! someone they trust.
+//!
+//! The signatures travel in the `authors` array of the DNA package file. `Dna`
+//! itself does not keep them, so they don't change the DNA's hash.
+
+use crate::{key_bundle::KeyBundle, utils::verify_bufs};
+use holochain_core_types::{
+    agent::Base32,
+    dna::Dna,
+    error::{HcResult, HolochainError},
+};
+use lib3h_sodium::secbuf::SecBuf;
+use serde_derive::{Deserialize, Serialize};
+
+/// An entry of the `authors` array of a DNA package
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct DnaAuthor {
+    pub identifier: String,
+    /// public signing key of the author
+    #[serde(default)]
+    pub public_key_source: Base32,
+    /// base64 encoded signature of the DNA's multihash, empty if not signed
+    #[serde(default)]
+    pub signature: String,
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_DPKI)]
+impl DnaAuthor {
+    /// sign `dna` as `identifier` with the signing key of `keybundle`
+    pub fn sign(identifier: String, dna: &Dna, keybundle: &mut KeyBundle) -> HcResult<Self> {
+        let mut data = multihash_buf(dna)?;
+        let signature = keybundle.sign(&mut data)?;
+        let signature = base64::encode(&**signature.read_lock());
+        Ok(DnaAuthor {
+            identifier,
+            public_key_source: keybundle.get_id(),
+            signature,
+        })
+    }
+
+    /// true if this author's signature is a valid signature of `dna`
+    pub fn verify(&self, dna: &Dna) -> HcResult<bool> {
+        if self.signature.is_empty() {
+            return Ok(false);
+        }
+        let signature_bytes = base64::decode(&self.signature)?;
+        let mut signature = SecBuf::with_insecure(signature_bytes.len());
+        signature.from_array(&signature_bytes)?;
+        verify_bufs(
+            self.public_key_source.clone(),
+            &mut multihash_buf(dna)?,
+            &mut signature,
+        )
+    }
+}
+
+fn multihash_buf(dna: &Dna) -> HcResult<SecBuf> {
+    let multihash = dna.multihash()?;
+    let mut buf = SecBuf::with_insecure(multihash.len());
+    buf.from_array(&multihash)?;
+    Ok(buf)
+}
+
+/// the `authors` of a DNA package file, given as JSON
+pub fn read_dna_authors(dna_package: &str) -> HcResult<Vec<DnaAuthor>> {
+    let package: serde_json::Value = serde_json::from_str(dna_package)?;
+    match package.get("authors") {
+        Some(authors) => serde_json::from_value(authors.clone()).map_err(|e| {
+            HolochainError::ErrorGeneric(format!("Invalid authors in DNA package: {}", e))
+        }),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Finds the author among `authors` whose key is in `trusted_keys` and who validly signed `dna`
+pub fn find_trusted_author<'a>(
+    dna: &Dna,
+    authors: &'a [DnaAuthor],
+    trusted_keys: &[Base32],
+) -> HcResult<Option<&'a DnaAuthor>> {
+    for author in authors {
+        if trusted_keys.contains(&author.public_key_source) && author.verify(dna)? {
+            return Ok(Some(author));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::generate_random_seed_buf;
+    use serde_json::json;
+
+    fn test_dna(name: &str) -> Dna {
+        let mut dna = Dna::new();
+        dna.name = name.to_string();
+        dna
+    }
+
+    #[test]
+    fn it_should_sign_and_verify_a_dna() {
+        let mut keybundle = KeyBundle::new_from_seed_buf(&mut generate_random_seed_buf()).unwrap();
+        let author = DnaAuthor::sign("Author".to_string(), &test_dna("a"), &mut keybundle).unwrap();
+        assert_eq!(author.public_key_source, keybundle.get_id());
+        assert!(author.verify(&test_dna("a")).unwrap());
+        assert!(!author.verify(&test_dna("b")).unwrap());
+
+        let unsigned = DnaAuthor {
+            signature: String::new(),
+            ..author.clone()
+        };
+        assert!(!unsigned.verify(&test_dna("a")).unwrap());
+    }
+
+    #[test]
+    fn it_should_find_a_trusted_author_in_a_package() {
+        let dna = test_dna("a");
+        let mut trusted = KeyBundle::new_from_seed_buf(&mut generate_random_seed_buf()).unwrap();
+        let mut other = KeyBundle::new_from_seed_buf(&mut generate_random_seed_buf()).unwrap();
+        let other_author = DnaAuthor::sign("Other".to_string(), &dna, &mut other).unwrap();
+        let trusted_author = DnaAuthor::sign("Trusted".to_string(), &dna, &mut trusted).unwrap();
+
+        let package = json!({
+            "name": "a",
+            "authors": [other_author, trusted_author],
+        })
+        .to_string();
+        let authors = read_dna_authors(&package).unwrap();
+        assert_eq!(authors.len(), 2);
+
+        assert_eq!(
+            Some(&authors[1]),
+            find_trusted_author(&dna, &authors, &[trusted.get_id()]).unwrap()
+        );
+        assert_eq!(
+            None,
+            find_trusted_author(&test_dna("b"), &authors, &[trusted.get_id()]).unwrap()
+        );
+        assert_eq!(
+            Vec::<DnaAuthor>::new(),
+            read_dna_authors(r#"{"name": "a"}"#).unwrap()
+        );
+    }
+}
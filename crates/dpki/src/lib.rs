@@ -12,6 +12,7 @@ extern crate holochain_common;
 pub const CONTEXT_SIZE: usize = 8;
 pub const SEED_SIZE: usize = 32;
 pub const AGENT_ID_CTX: [u8; 8] = *b"HCAGNTID";
+pub const REVOCATION_CTX: [u8; 8] = *b"HCREVOKE";
 pub(crate) const SIGNATURE_SIZE: usize = 64;
 
 lazy_static! {
@@ -21,11 +22,14 @@ lazy_static! {
         hcid::HcidEncoding::with_kind("hck0").expect("HCID failed miserably with_hck0.");
 }
 
+pub mod dna_signature;
 pub mod key_blob;
 pub mod key_bundle;
 pub mod keypair;
 pub mod password_encryption;
+pub mod revocation;
 pub mod seed;
+pub mod shamir;
 pub mod utils;
 
 new_relic_setup!("NEW_RELIC_LICENSE_KEY");
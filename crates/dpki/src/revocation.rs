@@ -0,0 +1,78 @@
+//! Revocation certificates: statements, signed with the key of a `RevocationSeed`,
+//! that a device or a DNA agent key must no longer be trusted.
+
+use crate::keypair::SigningKeyPair;
+use holochain_core_types::{agent::Base32, error::HcResult, signature::Signature};
+use lib3h_sodium::secbuf::SecBuf;
+use serde_derive::{Deserialize, Serialize};
+
+/// What a revocation certificate invalidates, identified by its public signing key
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RevokedKey {
+    /// the device key of a `DeviceSeed`, and with it everything derived from that seed
+    Device(Base32),
+    /// the signing key of a DNA agent `KeyBundle`
+    KeyBundle(Base32),
+}
+
+impl RevokedKey {
+    /// the public signing key that is revoked
+    pub fn public_key(&self) -> &Base32 {
+        match self {
+            RevokedKey::Device(key) | RevokedKey::KeyBundle(key) => key,
+        }
+    }
+}
+
+/// The part of a certificate that gets signed
+#[derive(Serialize)]
+struct RevocationPayload<'a> {
+    revoked: &'a RevokedKey,
+    revocation_key: &'a Base32,
+    reason: &'a str,
+}
+
+/// A revocation of a key, signed by a revocation key
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RevocationCertificate {
+    pub revoked: RevokedKey,
+    /// public key of the revocation keypair that signed this certificate
+    pub revocation_key: Base32,
+    pub reason: String,
+    pub signature: Signature,
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_DPKI)]
+impl RevocationCertificate {
+    /// revoke `revoked`, signing with the keypair of a revocation seed
+    pub fn new(
+        revoked: RevokedKey,
+        reason: String,
+        revocation_keys: &mut SigningKeyPair,
+    ) -> HcResult<Self> {
+        let revocation_key = revocation_keys.public.clone();
+        let payload = Self::payload(&revoked, &revocation_key, &reason)?;
+        let mut payload_buf = SecBuf::with_insecure_from_string(payload);
+        let signature_buf = revocation_keys.sign(&mut payload_buf)?;
+        let signature = base64::encode(&**signature_buf.read_lock());
+        Ok(RevocationCertificate {
+            revoked,
+            revocation_key,
+            reason,
+            signature: Signature::from(signature),
+        })
+    }
+
+    /// the data the signature is over
+    pub fn signed_data(&self) -> HcResult<String> {
+        Self::payload(&self.revoked, &self.revocation_key, &self.reason)
+    }
+
+    fn payload(revoked: &RevokedKey, revocation_key: &Base32, reason: &str) -> HcResult<String> {
+        Ok(serde_json::to_string(&RevocationPayload {
+            revoked,
+            revocation_key,
+            reason,
+        })?)
+    }
+}
@@ -1,8 +1,11 @@
 use crate::{
     key_bundle::KeyBundle,
+    keypair::{KeyPair, SigningKeyPair},
     password_encryption::*,
-    utils::{generate_derived_seed_buf, SeedContext},
-    AGENT_ID_CTX, SEED_SIZE,
+    revocation::{RevocationCertificate, RevokedKey},
+    shamir::{combine_seed_shares, split_seed_buf, SeedShare},
+    utils::{generate_derived_seed_buf, zero_bytes, zero_string, SeedContext},
+    AGENT_ID_CTX, REVOCATION_CTX, SEED_SIZE,
 };
 use bip39::{Language, Mnemonic, MnemonicType};
 use holochain_core_types::error::{HcResult, HolochainError};
@@ -46,6 +49,7 @@ pub enum SeedType {
 /// Enum of all the different behaviors a Seed can have
 pub enum TypedSeed {
     Root(RootSeed),
+    Revocation(RevocationSeed),
     Device(DeviceSeed),
     DevicePin(DevicePinSeed),
 }
@@ -73,6 +77,7 @@ pub trait MnemonicableSeed
 where
     Self: Sized,
 {
+    /// the phrase is zeroed once the seed has been read from it
     fn new_with_mnemonic(phrase: String, seed_type: SeedType) -> HcResult<Self>;
     fn get_mnemonic(&mut self) -> HcResult<String>;
 }
@@ -112,6 +117,7 @@ impl Seed {
     pub fn into_typed(self) -> HcResult<TypedSeed> {
         match self.kind {
             SeedType::Root => Ok(TypedSeed::Root(RootSeed::new(self.buf))),
+            SeedType::Revocation => Ok(TypedSeed::Revocation(RevocationSeed::new(self.buf))),
             SeedType::Device => Ok(TypedSeed::Device(DeviceSeed::new(self.buf))),
             SeedType::DevicePin => Ok(TypedSeed::DevicePin(DevicePinSeed::new(self.buf))),
             _ => Err(HolochainError::ErrorGeneric(
@@ -128,10 +134,19 @@ impl MnemonicableSeed for Seed {
             HolochainError::ErrorGeneric(format!("Error loading Mnemonic phrase: {}", e))
         })?;
 
-        let entropy = mnemonic.entropy().to_owned();
-        assert_eq!(entropy.len(), SEED_SIZE);
+        let mut entropy = mnemonic.entropy().to_owned();
+        zero_string(&mut mnemonic.into_phrase());
+        if entropy.len() != SEED_SIZE {
+            zero_bytes(&mut entropy);
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Mnemonic phrase should have {} words",
+                MnemonicType::Words24.word_count()
+            )));
+        }
         let mut seed_buf = SecBuf::with_secure(entropy.len());
-        seed_buf.from_array(entropy.as_slice())?;
+        let copied = seed_buf.from_array(entropy.as_slice());
+        zero_bytes(&mut entropy);
+        copied?;
         // Done
         Ok(Self {
             kind: seed_type,
@@ -190,6 +205,90 @@ impl RootSeed {
             generate_derived_seed_buf(&mut self.inner.buf, seed_context, index, SEED_SIZE)?;
         Ok(DeviceSeed::new(device_seed_buf))
     }
+
+    /// Generate Revocation Seed, derived with the `REVOCATION_CTX` context
+    /// @param {number} index - the index number in this seed group, must not be zero
+    pub fn generate_revocation_seed(&mut self, index: u64) -> HcResult<RevocationSeed> {
+        let context = SeedContext::new(REVOCATION_CTX);
+        let revocation_seed_buf =
+            generate_derived_seed_buf(&mut self.inner.buf, &context, index, SEED_SIZE)?;
+        Ok(RevocationSeed::new(revocation_seed_buf))
+    }
+
+    /// Split into `shares` Shamir shares, any `threshold` of which recreate this seed
+    pub fn split(&mut self, threshold: u8, shares: u8) -> HcResult<Vec<SeedShare>> {
+        split_seed_buf(&mut self.inner.buf, threshold, shares)
+    }
+
+    /// Recreate a root seed from at least as many of its shares as its threshold
+    pub fn new_from_shares(shares: &mut [SeedShare]) -> HcResult<Self> {
+        Ok(RootSeed::new(combine_seed_shares(shares)?))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// RevocationSeed
+//--------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+pub struct RevocationSeed {
+    inner: Seed,
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_DPKI)]
+impl SeedTrait for RevocationSeed {
+    fn seed(&self) -> &Seed {
+        &self.inner
+    }
+    fn seed_mut(&mut self) -> &mut Seed {
+        &mut self.inner
+    }
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_DPKI)]
+impl RevocationSeed {
+    /// Construct from a 32 bytes seed buffer
+    pub fn new(seed_buf: SecBuf) -> Self {
+        RevocationSeed {
+            inner: Seed::new_with_initializer(
+                SeedInitializer::Seed(seed_buf),
+                SeedType::Revocation,
+            ),
+        }
+    }
+
+    /// generate the keypair that signs revocation certificates;
+    /// its public key is the one verifiers should trust to revoke keys
+    pub fn generate_revocation_key(&mut self) -> HcResult<SigningKeyPair> {
+        SigningKeyPair::new_from_seed(&mut self.inner.buf)
+    }
+
+    /// sign a certificate revoking a device seed
+    pub fn revoke_device(
+        &mut self,
+        device_seed: &mut DeviceSeed,
+        reason: String,
+    ) -> HcResult<RevocationCertificate> {
+        let device_key = device_seed.generate_device_key()?;
+        RevocationCertificate::new(
+            RevokedKey::Device(device_key.public()),
+            reason,
+            &mut self.generate_revocation_key()?,
+        )
+    }
+
+    /// sign a certificate revoking a DNA agent keybundle
+    pub fn revoke_key_bundle(
+        &mut self,
+        key_bundle: &KeyBundle,
+        reason: String,
+    ) -> HcResult<RevocationCertificate> {
+        RevocationCertificate::new(
+            RevokedKey::KeyBundle(key_bundle.get_id()),
+            reason,
+            &mut self.generate_revocation_key()?,
+        )
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -232,6 +331,12 @@ impl DeviceSeed {
         pw_hash(pin, &mut self.inner.buf, &mut hash, config)?;
         Ok(DevicePinSeed::new(hash))
     }
+
+    /// generate the signing keypair that identifies this device,
+    /// which is the key revocation certificates name to revoke it
+    pub fn generate_device_key(&mut self) -> HcResult<SigningKeyPair> {
+        SigningKeyPair::new_from_seed(&mut self.inner.buf)
+    }
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -422,6 +527,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_should_create_a_revocation_seed() {
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        let mut revocation_seed_1 = root_seed.generate_revocation_seed(1).unwrap();
+        assert_eq!(SeedType::Revocation, revocation_seed_1.seed().kind);
+        let _ = root_seed.generate_revocation_seed(0).unwrap_err();
+        let mut revocation_seed_1_b = root_seed.generate_revocation_seed(1).unwrap();
+        let mut revocation_seed_2 = root_seed.generate_revocation_seed(2).unwrap();
+        assert!(revocation_seed_1
+            .generate_revocation_key()
+            .unwrap()
+            .is_same(&mut revocation_seed_1_b.generate_revocation_key().unwrap()));
+        assert!(!revocation_seed_1
+            .generate_revocation_key()
+            .unwrap()
+            .is_same(&mut revocation_seed_2.generate_revocation_key().unwrap()));
+
+        // a revocation seed is not the device seed of the same index
+        let context = SeedContext::new(*b"HCDEVICE");
+        let mut device_seed_1 = root_seed.generate_device_seed(&context, 1).unwrap();
+        assert!(
+            revocation_seed_1
+                .seed_mut()
+                .buf
+                .compare(&mut device_seed_1.seed_mut().buf)
+                != 0
+        );
+    }
+
+    #[test]
+    fn it_should_revoke_a_device_and_a_dna_key() {
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        let mut revocation_seed = root_seed.generate_revocation_seed(1).unwrap();
+        let revocation_key = revocation_seed.generate_revocation_key().unwrap().public();
+        let context = SeedContext::new(*b"HCDEVICE");
+        let mut device_seed = root_seed.generate_device_seed(&context, 1).unwrap();
+        let device_key = device_seed.generate_device_key().unwrap().public();
+
+        let device_revocation = revocation_seed
+            .revoke_device(&mut device_seed, "lost phone".to_string())
+            .unwrap();
+        assert_eq!(RevokedKey::Device(device_key), device_revocation.revoked);
+        assert_eq!(revocation_key, device_revocation.revocation_key);
+        assert!(utils::verify_revocation_certificate(&device_revocation).unwrap());
+
+        let mut pin = generate_random_seed_buf();
+        let key_bundle = device_seed
+            .generate_device_pin_seed(&mut pin, TEST_CONFIG)
+            .unwrap()
+            .generate_dna_key(1)
+            .unwrap();
+        let key_revocation = revocation_seed
+            .revoke_key_bundle(&key_bundle, "compromised".to_string())
+            .unwrap();
+        assert_eq!(
+            RevokedKey::KeyBundle(key_bundle.get_id()),
+            key_revocation.revoked
+        );
+        assert!(utils::verify_revocation_certificate(&key_revocation).unwrap());
+    }
+
+    #[test]
+    fn it_should_split_and_combine_a_root_seed() {
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        let shares = root_seed.split(2, 3).unwrap();
+        let mut mnemonics: Vec<String> = shares
+            .into_iter()
+            .map(|mut share| share.get_mnemonic().unwrap())
+            .collect();
+        mnemonics.remove(0);
+        let mut shares: Vec<SeedShare> = mnemonics
+            .into_iter()
+            .map(|mnemonic| SeedShare::new_with_mnemonic(&mnemonic).unwrap())
+            .collect();
+        let mut combined = RootSeed::new_from_shares(&mut shares).unwrap();
+        assert_eq!(SeedType::Root, combined.seed().kind);
+        assert_eq!(
+            0,
+            root_seed
+                .seed_mut()
+                .buf
+                .compare(&mut combined.seed_mut().buf)
+        );
+    }
+
     #[test]
     fn it_should_create_a_device_pin_seed() {
         let seed_buf = generate_random_seed_buf();
@@ -478,6 +668,13 @@ mod tests {
         assert_eq!(0, seed.buf.compare(&mut seed_2.buf));
     }
 
+    #[test]
+    fn it_should_refuse_a_short_mnemonic() {
+        // a valid 12 word phrase only holds 16 bytes
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        assert!(Seed::new_with_mnemonic(mnemonic.to_string(), SeedType::Root).is_err());
+    }
+
     #[test]
     fn it_should_change_into_typed() {
         // Root
@@ -504,6 +701,14 @@ mod tests {
             TypedSeed::DevicePin(typed_seed) => typed_seed,
             _ => unreachable!(),
         };
+        // Revocation
+        let seed_buf = generate_random_seed_buf();
+        let seed = Seed::new(seed_buf, SeedType::Revocation);
+        let unknown_seed = seed.into_typed().unwrap();
+        let _ = match unknown_seed {
+            TypedSeed::Revocation(typed_seed) => typed_seed,
+            _ => unreachable!(),
+        };
         // App
         let seed_buf = generate_random_seed_buf();
         let seed = Seed::new(seed_buf, SeedType::DNA);
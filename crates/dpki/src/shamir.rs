@@ -0,0 +1,322 @@
+//! M-of-N Shamir secret sharing of seeds, so that a root seed can be backed up
+//! as N shares of which any M recreate it, while fewer reveal nothing about it.
+//!
+//! Every byte of the seed is the constant term of its own random polynomial of
+//! degree M - 1 over GF(256), and share `x` holds the value of each polynomial at `x`.
+//!
+//! A share is written down as a 36 word mnemonic: 12 words for a header holding the
+//! threshold, the share's index, an identifier of the split it belongs to and a
+//! checksum over the whole share, followed by the 24 words of the share's bytes.
+//! Both parts are BIP39 mnemonics with their own checksums.
+
+use crate::SEED_SIZE;
+use bip39::{Language, Mnemonic, MnemonicType};
+use holochain_core_types::error::{HcResult, HolochainError};
+use lib3h_sodium::{hash::sha256, secbuf::SecBuf};
+
+const HEADER_SIZE: usize = 16;
+const SPLIT_ID_SIZE: usize = 10;
+const CHECKSUM_SIZE: usize = 4;
+
+/// multiplication in GF(256) with the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// multiplicative inverse in GF(256), a^254; `a` must not be zero
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    for _ in 0..254 {
+        result = gf_mul(result, a);
+    }
+    result
+}
+
+/// One share of a split seed
+pub struct SeedShare {
+    /// how many shares recreate the seed
+    pub threshold: u8,
+    /// the x coordinate of this share, from 1
+    pub index: u8,
+    /// random identifier of the split, shared by all its shares
+    split_id: [u8; SPLIT_ID_SIZE],
+    /// the polynomials' values at `index`
+    buf: SecBuf,
+}
+
+#[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_DPKI)]
+impl SeedShare {
+    fn checksum(&mut self, header: &[u8]) -> HcResult<[u8; CHECKSUM_SIZE]> {
+        let mut data = SecBuf::with_secure(HEADER_SIZE - CHECKSUM_SIZE + SEED_SIZE);
+        {
+            let mut data = data.write_lock();
+            data[..HEADER_SIZE - CHECKSUM_SIZE].copy_from_slice(header);
+            data[HEADER_SIZE - CHECKSUM_SIZE..].copy_from_slice(&self.buf.read_lock());
+        }
+        let mut hash = SecBuf::with_insecure(32);
+        sha256(&mut data, &mut hash)?;
+        let mut checksum = [0; CHECKSUM_SIZE];
+        checksum.copy_from_slice(&hash.read_lock()[..CHECKSUM_SIZE]);
+        Ok(checksum)
+    }
+
+    fn header(&mut self) -> HcResult<[u8; HEADER_SIZE]> {
+        let mut header = [0; HEADER_SIZE];
+        header[0] = self.threshold;
+        header[1] = self.index;
+        header[2..2 + SPLIT_ID_SIZE].copy_from_slice(&self.split_id);
+        let checksum = self.checksum(&header[..HEADER_SIZE - CHECKSUM_SIZE])?;
+        header[HEADER_SIZE - CHECKSUM_SIZE..].copy_from_slice(&checksum);
+        Ok(header)
+    }
+
+    /// the 36 word mnemonic of this share
+    // TODO: We need some way of zeroing the internal memory used by mnemonic
+    pub fn get_mnemonic(&mut self) -> HcResult<String> {
+        let header = self.header()?;
+        let header_phrase = Mnemonic::from_entropy(&header, Language::English)
+            .map_err(|e| {
+                HolochainError::ErrorGeneric(format!("Error generating Mnemonic phrase: {}", e))
+            })?
+            .into_phrase();
+        let share_phrase = Mnemonic::from_entropy(&self.buf.read_lock(), Language::English)
+            .map_err(|e| {
+                HolochainError::ErrorGeneric(format!("Error generating Mnemonic phrase: {}", e))
+            })?
+            .into_phrase();
+        Ok(format!("{} {}", header_phrase, share_phrase))
+    }
+
+    /// read a share from its mnemonic, checking both BIP39 checksums and the share's own
+    pub fn new_with_mnemonic(phrase: &str) -> HcResult<Self> {
+        let words: Vec<&str> = phrase.split_whitespace().collect();
+        let header_words = MnemonicType::Words12.word_count();
+        if words.len() != header_words + MnemonicType::Words24.word_count() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Seed share mnemonic should have {} words",
+                header_words + MnemonicType::Words24.word_count()
+            )));
+        }
+        let entropy = |words: &[&str]| {
+            Mnemonic::from_phrase(words.join(" "), Language::English)
+                .map(|mnemonic| mnemonic.entropy().to_owned())
+                .map_err(|e| {
+                    HolochainError::ErrorGeneric(format!("Error loading Mnemonic phrase: {}", e))
+                })
+        };
+        let header = entropy(&words[..header_words])?;
+        let share = entropy(&words[header_words..])?;
+
+        let mut buf = SecBuf::with_secure(SEED_SIZE);
+        buf.from_array(&share)?;
+        let mut split_id = [0; SPLIT_ID_SIZE];
+        split_id.copy_from_slice(&header[2..2 + SPLIT_ID_SIZE]);
+        let mut seed_share = SeedShare {
+            threshold: header[0],
+            index: header[1],
+            split_id,
+            buf,
+        };
+        if seed_share.header()?[..] != header[..] {
+            return Err(HolochainError::ErrorGeneric(
+                "Seed share checksum mismatch".to_string(),
+            ));
+        }
+        if seed_share.threshold < 2 || seed_share.index == 0 {
+            return Err(HolochainError::ErrorGeneric(
+                "Invalid seed share".to_string(),
+            ));
+        }
+        Ok(seed_share)
+    }
+}
+
+/// split a 32 byte seed into `shares` shares, any `threshold` of which recreate it
+pub fn split_seed_buf(seed: &mut SecBuf, threshold: u8, shares: u8) -> HcResult<Vec<SeedShare>> {
+    if seed.len() != SEED_SIZE {
+        return Err(HolochainError::ErrorGeneric(
+            "Invalid seed size".to_string(),
+        ));
+    }
+    if threshold < 2 || shares < threshold {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Cannot split a seed into {} shares with a threshold of {}",
+            shares, threshold
+        )));
+    }
+    // the coefficients of x^1 to x^(threshold - 1) of every byte's polynomial
+    let mut coefficients = SecBuf::with_secure(SEED_SIZE * (threshold as usize - 1));
+    coefficients.randomize();
+    let mut split_id = [0; SPLIT_ID_SIZE];
+    {
+        let mut random = SecBuf::with_insecure(SPLIT_ID_SIZE);
+        random.randomize();
+        split_id.copy_from_slice(&random.read_lock());
+    }
+
+    let seed = seed.read_lock();
+    let coefficients = coefficients.read_lock();
+    Ok((1..=shares)
+        .map(|x| {
+            let mut buf = SecBuf::with_secure(SEED_SIZE);
+            {
+                let mut y = buf.write_lock();
+                for (i, y_i) in y.iter_mut().enumerate() {
+                    // Horner's rule, from the highest coefficient down to the seed byte
+                    let mut value = 0;
+                    for degree in (1..threshold as usize).rev() {
+                        let coefficient = coefficients[(degree - 1) * SEED_SIZE + i];
+                        value = gf_mul(value, x) ^ coefficient;
+                    }
+                    *y_i = gf_mul(value, x) ^ seed[i];
+                }
+            }
+            SeedShare {
+                threshold,
+                index: x,
+                split_id,
+                buf,
+            }
+        })
+        .collect())
+}
+
+/// recreate a seed from at least `threshold` distinct shares of the same split
+pub fn combine_seed_shares(shares: &mut [SeedShare]) -> HcResult<SecBuf> {
+    let (threshold, split_id) = match shares.first() {
+        Some(share) => (share.threshold, share.split_id),
+        None => return Err(HolochainError::ErrorGeneric("No seed shares".to_string())),
+    };
+    if shares
+        .iter()
+        .any(|share| share.threshold != threshold || share.split_id != split_id)
+    {
+        return Err(HolochainError::ErrorGeneric(
+            "Seed shares are from different splits".to_string(),
+        ));
+    }
+    let mut indexes: Vec<u8> = Vec::new();
+    let mut used: Vec<&mut SeedShare> = Vec::new();
+    for share in shares.iter_mut() {
+        if !indexes.contains(&share.index) && used.len() < threshold as usize {
+            indexes.push(share.index);
+            used.push(share);
+        }
+    }
+    if used.len() < threshold as usize {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "{} distinct seed shares are needed, got {}",
+            threshold,
+            used.len()
+        )));
+    }
+
+    // Lagrange interpolation at x = 0
+    let mut seed = SecBuf::with_secure(SEED_SIZE);
+    {
+        let mut seed = seed.write_lock();
+        for byte in seed.iter_mut() {
+            *byte = 0;
+        }
+        for (j, share) in used.iter_mut().enumerate() {
+            let x_j = indexes[j];
+            let mut basis = 1;
+            for (m, x_m) in indexes.iter().enumerate() {
+                if m != j {
+                    // x_m / (x_m - x_j), subtraction being xor in GF(256)
+                    basis = gf_mul(basis, gf_mul(*x_m, gf_inv(x_m ^ x_j)));
+                }
+            }
+            let y = share.buf.read_lock();
+            for (byte, y_i) in seed.iter_mut().zip(y.iter()) {
+                *byte ^= gf_mul(basis, *y_i);
+            }
+        }
+    }
+    Ok(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::generate_random_seed_buf;
+
+    #[test]
+    fn it_should_multiply_and_invert_in_gf256() {
+        assert_eq!(0xc1, gf_mul(0x57, 0x83));
+        for a in 1..=255 {
+            assert_eq!(1, gf_mul(a, gf_inv(a)));
+        }
+    }
+
+    #[test]
+    fn it_should_combine_any_threshold_of_shares() {
+        let mut seed = generate_random_seed_buf();
+        let mut shares = split_seed_buf(&mut seed, 3, 5).unwrap();
+        assert_eq!(5, shares.len());
+
+        for picked in &[[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let mut subset: Vec<SeedShare> = picked
+                .iter()
+                .map(|i| SeedShare::new_with_mnemonic(&shares[*i].get_mnemonic().unwrap()).unwrap())
+                .collect();
+            let mut combined = combine_seed_shares(&mut subset).unwrap();
+            assert_eq!(0, combined.compare(&mut seed));
+        }
+    }
+
+    #[test]
+    fn it_should_refuse_too_few_or_mixed_shares() {
+        let mut seed = generate_random_seed_buf();
+        let mut shares = split_seed_buf(&mut seed, 3, 5).unwrap();
+        assert!(combine_seed_shares(&mut shares[..2]).is_err());
+
+        // the same share twice is not two shares
+        let mut repeated = vec![
+            SeedShare::new_with_mnemonic(&shares[0].get_mnemonic().unwrap()).unwrap(),
+            SeedShare::new_with_mnemonic(&shares[0].get_mnemonic().unwrap()).unwrap(),
+            SeedShare::new_with_mnemonic(&shares[1].get_mnemonic().unwrap()).unwrap(),
+        ];
+        assert!(combine_seed_shares(&mut repeated).is_err());
+
+        let mut other_shares = split_seed_buf(&mut seed, 3, 5).unwrap();
+        let mut mixed = vec![
+            SeedShare::new_with_mnemonic(&shares[0].get_mnemonic().unwrap()).unwrap(),
+            SeedShare::new_with_mnemonic(&shares[1].get_mnemonic().unwrap()).unwrap(),
+            SeedShare::new_with_mnemonic(&other_shares[2].get_mnemonic().unwrap()).unwrap(),
+        ];
+        assert!(combine_seed_shares(&mut mixed).is_err());
+
+        assert!(split_seed_buf(&mut seed, 4, 3).is_err());
+        assert!(split_seed_buf(&mut seed, 1, 3).is_err());
+    }
+
+    #[test]
+    fn it_should_detect_a_wrong_word() {
+        let mut seed = generate_random_seed_buf();
+        let mut shares = split_seed_buf(&mut seed, 2, 3).unwrap();
+        let mnemonic = shares[1].get_mnemonic().unwrap();
+        assert_eq!(36, mnemonic.split(' ').count());
+
+        // swap two words of the share part, which its BIP39 checksum may not catch
+        // but the share's own checksum does
+        let mut words: Vec<&str> = mnemonic.split(' ').collect();
+        words.swap(12, 13);
+        if words[12] != words[13] {
+            assert!(SeedShare::new_with_mnemonic(&words.join(" ")).is_err());
+        }
+        assert!(SeedShare::new_with_mnemonic(&mnemonic.replacen(' ', "", 1)).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     password_encryption::{pw_dec, pw_enc, EncryptedData, PwHashConfig},
+    revocation::RevocationCertificate,
     CODEC_HCS0, CONTEXT_SIZE, SEED_SIZE,
 };
 use hcid::*;
@@ -10,7 +11,7 @@ use holochain_core_types::{
 };
 use holochain_persistence_api::cas::content::Address;
 use lib3h_sodium::{kdf, secbuf::SecBuf, sign};
-use std::str;
+use std::{ptr, str};
 
 /// a trait for things that have a provenance that can be verified
 pub trait Verify {
@@ -79,6 +80,34 @@ pub fn verify_bufs(
     Ok(lib3h_sodium::sign::verify(signature, data, &mut pub_key))
 }
 
+/// Verify that a revocation certificate was signed by the revocation key it names
+pub fn verify_revocation_certificate(certificate: &RevocationCertificate) -> HcResult<bool> {
+    verify(
+        Address::from(certificate.revocation_key.clone()),
+        certificate.signed_data()?,
+        certificate.signature.clone(),
+    )
+}
+
+/// Find a certificate among `certificates` that revokes `key` and that was validly
+/// signed by `revocation_key`, the revocation key trusted for it.
+/// Certificates signed by any other key are ignored.
+pub fn find_revocation<'a>(
+    key: &Base32,
+    revocation_key: &Base32,
+    certificates: &'a [RevocationCertificate],
+) -> HcResult<Option<&'a RevocationCertificate>> {
+    for certificate in certificates {
+        if certificate.revoked.public_key() == key
+            && &certificate.revocation_key == revocation_key
+            && verify_revocation_certificate(certificate)?
+        {
+            return Ok(Some(certificate));
+        }
+    }
+    Ok(None)
+}
+
 pub struct SeedContext {
     inner: [u8; 8],
 }
@@ -129,6 +158,20 @@ pub fn generate_random_seed_buf() -> SecBuf {
     generate_random_buf(SEED_SIZE)
 }
 
+/// overwrites secret bytes that don't live in a SecBuf with zeros once they have been used
+pub fn zero_bytes(secret: &mut [u8]) {
+    for byte in secret.iter_mut() {
+        // volatile, so that writes to memory that is about to be freed aren't optimized away
+        unsafe { ptr::write_volatile(byte, 0u8) };
+    }
+}
+
+/// overwrites a secret string, e.g. a mnemonic, with zeros (which are still valid UTF-8)
+/// once it has been used
+pub fn zero_string(secret: &mut String) {
+    zero_bytes(unsafe { secret.as_mut_vec() });
+}
+
 /// encrypt and base64 encode a secbuf
 #[holochain_tracing_macros::newrelic_autotrace(HOLOCHAIN_DPKI)]
 pub fn encrypt_with_passphrase_buf(
@@ -175,6 +218,13 @@ mod tests {
     use crate::SIGNATURE_SIZE;
     use lib3h_sodium::{secbuf::SecBuf, sign};
 
+    #[test]
+    fn zero_string_overwrites_the_secret() {
+        let mut secret = String::from("abandon ability");
+        zero_string(&mut secret);
+        assert_eq!("\0".repeat(15), secret);
+    }
+
     #[test]
     fn it_should_hcid_roundtrip() {
         let mut pub_sec_buf = SecBuf::with_insecure(sign::PUBLICKEYBYTES);
@@ -209,6 +259,64 @@ mod tests {
         assert!(res.unwrap());
     }
 
+    #[test]
+    fn it_should_find_valid_revocations_only() {
+        use crate::{keypair::KeyPair, revocation::RevokedKey, seed::RootSeed};
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        let mut revocation_keys = root_seed
+            .generate_revocation_seed(1)
+            .unwrap()
+            .generate_revocation_key()
+            .unwrap();
+        let revocation_key = revocation_keys.public();
+        let mut other_keys = RootSeed::new(generate_random_seed_buf())
+            .generate_revocation_seed(1)
+            .unwrap()
+            .generate_revocation_key()
+            .unwrap();
+        let revoked = "HcScic3VAmEP9ucmrw4MMFKVARIvvdn43k6TYdrMwNBxq1dbNpqhHFi4ZRs7cqi".to_string();
+
+        let certificate = RevocationCertificate::new(
+            RevokedKey::KeyBundle(revoked.clone()),
+            "compromised".to_string(),
+            &mut revocation_keys,
+        )
+        .unwrap();
+        let certificates = vec![certificate.clone()];
+        assert_eq!(
+            Some(&certificate),
+            find_revocation(&revoked, &revocation_key, &certificates).unwrap()
+        );
+        // not the key we trust to revoke it
+        assert_eq!(
+            None,
+            find_revocation(&revoked, &other_keys.public(), &certificates).unwrap()
+        );
+        // not this key
+        assert_eq!(
+            None,
+            find_revocation(&revocation_key, &revocation_key, &certificates).unwrap()
+        );
+
+        // a certificate naming the trusted key without being signed by it
+        let mut forged = RevocationCertificate::new(
+            RevokedKey::KeyBundle(revoked.clone()),
+            "compromised".to_string(),
+            &mut other_keys,
+        )
+        .unwrap();
+        forged.revocation_key = revocation_key.clone();
+        assert!(!verify_revocation_certificate(&forged).unwrap());
+        // or altered after signing
+        let mut altered = certificate;
+        altered.revoked = RevokedKey::Device(revoked.clone());
+        assert!(!verify_revocation_certificate(&altered).unwrap());
+        assert_eq!(
+            None,
+            find_revocation(&revoked, &revocation_key, &[forged, altered]).unwrap()
+        );
+    }
+
     #[test]
     fn it_should_round_trip_passphrase_encryption() {
         let data_size = 32;
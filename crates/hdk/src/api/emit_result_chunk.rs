@@ -0,0 +1,42 @@
+use crate::{error::ZomeApiResult, Dispatch};
+use holochain_json_api::json::JsonString;
+use holochain_wasm_utils::api_serialization::emit_result_chunk::EmitResultChunkArgs;
+
+/// Sends a piece of the result of the current zome function call to its caller before the
+/// function returns.
+///
+/// Zome functions that produce large results (e.g. long histories) can use this to hand them
+/// over incrementally instead of building the whole result in WASM memory and returning it in
+/// a single message.
+///
+/// Chunks are only delivered if the zome function was called with `"stream": true` over a
+/// websocket interface (see the conductor's `call` JSON-RPC method). They are then sent, in
+/// order, as notifications to the connection that made the call, followed by an end-of-stream
+/// notification and the response itself. If the caller didn't ask for a stream, chunks are
+/// dropped, so a function that streams its result should also say so in its return value.
+/// # Examples
+/// ```rust
+/// # #[macro_use]
+/// # extern crate hdk;
+/// # use hdk::error::ZomeApiResult;
+/// # use hdk::holochain_json_api::json::JsonString;
+/// # use hdk::holochain_core_types::error::RibosomeEncodingBits;
+/// # use hdk::holochain_core_types::error::RibosomeEncodedValue;
+/// # #[no_mangle]
+/// # pub fn hc_emit_result_chunk(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+///
+/// pub fn handle_get_history(pages: Vec<Vec<String>>) -> ZomeApiResult<usize> {
+///     for page in pages.iter() {
+///         hdk::emit_result_chunk(JsonString::from(page.clone()))?;
+///     }
+///     Ok(pages.len())
+/// }
+/// ```
+pub fn emit_result_chunk<J: Into<JsonString>>(chunk: J) -> ZomeApiResult<()> {
+    let _: ZomeApiResult<()> = Dispatch::EmitResultChunk.with_input(EmitResultChunkArgs {
+        chunk: chunk.into(),
+    });
+    // internally returns RibosomeEncodedValue::Success which is a zero length allocation
+    // return Ok(()) unconditionally instead of the "error" from success
+    Ok(())
+}
@@ -25,6 +25,7 @@ mod capability;
 mod commit_entry;
 mod debug;
 mod decrypt;
+mod emit_result_chunk;
 mod emit_signal;
 mod encrypt;
 mod entry_address;
@@ -49,6 +50,7 @@ pub use self::{
     commit_entry::{commit_entry, commit_entry_result},
     debug::debug,
     decrypt::decrypt,
+    emit_result_chunk::emit_result_chunk,
     emit_signal::emit_signal,
     encrypt::encrypt,
     entry_address::entry_address,
@@ -196,6 +198,7 @@ def_api_fns! {
     hc_commit_capability_grant, CommitCapabilityGrant;
     hc_commit_capability_claim, CommitCapabilityClaim;
     hc_emit_signal, EmitSignal;
+    hc_emit_result_chunk, EmitResultChunk;
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -67,6 +67,24 @@ use holochain_wasm_utils::api_serialization::{
 /// //     HeadersWithEntries(Vec<(ChainHeader, Entry)>),  // true     true
 /// // }
 /// ```
+///
+/// Results are newest-first by default; set `ordering: SortOrder::Ascending` to get them
+/// oldest-first.  `filter_by` restricts them to a timestamp window (`since` inclusive, `until`
+/// exclusive), to headers signed by a given `provenance` agent address, or to headers whose
+/// `link_update_delete` points at a given header.  To page through a long chain, pass the address
+/// of the last header of the previous page as `after`:
+///
+/// ```
+/// // pub fn get_recent_post_headers(last_seen: Option<Address>) -> ZomeApiResult<QueryResult> {
+/// //    hdk::query_result("post".into(), QueryArgsOptions{
+/// //        headers: true,
+/// //        limit: 10,
+/// //        filter_by: QueryFilter{ since: Some(Iso8601::from(1_577_836_800)), ..Default::default() },
+/// //        after: last_seen,
+/// //        ..Default::default()
+/// //    })
+/// // }
+/// ```
 pub fn query(
     entry_type_names: QueryArgsNames,
     start: usize,
@@ -80,6 +98,7 @@ pub fn query(
             limit,
             headers: false,
             entries: false,
+            ..Default::default()
         },
     )
     .and_then(|result| match result {
@@ -1,7 +1,15 @@
-use crate::{error::ZomeApiResult, Dispatch};
-use holochain_core_types::time::Timeout;
+use crate::{
+    error::{ZomeApiError, ZomeApiResult},
+    Dispatch,
+};
+use holochain_core_types::{
+    error::{CoreError, HolochainError},
+    time::Timeout,
+};
+use holochain_json_api::json::JsonString;
 use holochain_persistence_api::cas::content::Address;
 use holochain_wasm_utils::api_serialization::send::{SendArgs, SendOptions};
+use std::convert::TryFrom;
 
 /// Sends a node-to-node message to the given agent, specified by their address.
 /// Addresses of agents can be accessed using [hdk::AGENT_ADDRESS](struct.AGENT_ADDRESS.html).
@@ -10,6 +18,12 @@ use holochain_wasm_utils::api_serialization::send::{SendArgs, SendOptions};
 ///
 /// This function dispatches a message to the receiver, and will wait up to 60 seconds before returning a timeout error. The `send` function will return the string returned
 /// by the `receive` callback of the other node.
+///
+/// If the receiver is offline and the network (a sim2h server with direct message queuing
+/// turned on) stores the message until the receiver comes back, `send` returns
+/// `ZomeApiError::DirectMessageQueued` right away instead of waiting for the timeout.
+/// The message will be handled by the receiver's `receive` callback later, but its response is
+/// not returned to the sender.
 /// # Examples
 /// ```rust
 /// # #[macro_use]
@@ -89,6 +103,8 @@ use holochain_wasm_utils::api_serialization::send::{SendArgs, SendOptions};
 /// # pub fn hc_commit_capability_claim(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
 /// # #[no_mangle]
 /// # pub fn hc_emit_signal(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
+/// # #[no_mangle]
+/// # pub fn hc_emit_result_chunk(_: RibosomeEncodingBits) -> RibosomeEncodingBits { RibosomeEncodedValue::Success.into() }
 ///
 /// fn handle_send_message(to_agent: Address, message: String) -> ZomeApiResult<String> {
 ///     // because the function signature of hdk::send is the same as the
@@ -126,9 +142,26 @@ use holochain_wasm_utils::api_serialization::send::{SendArgs, SendOptions};
 ///}
 /// ```
 pub fn send(to_agent: Address, payload: String, timeout: Timeout) -> ZomeApiResult<String> {
-    Dispatch::Send.with_input(SendArgs {
-        to_agent,
-        payload,
-        options: SendOptions(timeout),
-    })
+    Dispatch::Send
+        .with_input(SendArgs {
+            to_agent,
+            payload,
+            options: SendOptions(timeout),
+        })
+        .map_err(queued_error)
+}
+
+/// Core errors reach the zome as `ZomeApiError::Internal`, this picks out the
+/// queued acknowledgement so zomes don't have to parse it themselves.
+fn queued_error(error: ZomeApiError) -> ZomeApiError {
+    if let ZomeApiError::Internal(ref core_error) = error {
+        if let Ok(CoreError {
+            kind: HolochainError::DirectMessageQueued(recipient),
+            ..
+        }) = CoreError::try_from(JsonString::from_json(core_error))
+        {
+            return ZomeApiError::DirectMessageQueued(recipient);
+        }
+    }
+    error
 }
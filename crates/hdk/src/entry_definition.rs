@@ -66,22 +66,25 @@ pub struct ValidatingLinkDefinition {
 
 /// The `entry` macro is a helper for creating `ValidatingEntryType` definitions
 /// for use within the [define_zome](define_zome!) macro.
-/// It has 7 component parts:
+/// It has 8 component parts, one of which is optional:
 /// 1. name: `name` is simply the descriptive name of the entry type, such as "post", or "user".
 ///      It is what must be given as the `entry_type_name` argument when calling [commit_entry](api::commit_entry()) and the other data read/write functions.
 /// 2. description: `description` is something that is primarily for human readers of your code, just describe this entry type
 /// 3. sharing: `sharing` defines what distribution over the DHT, or not, occurs with entries of this type, possible values
 ///      are defined in the [Sharing](holochain_core_types::dna::entry_types::Sharing) enum
-/// 4. native_type: `native_type` references a given Rust struct, which provides a clear schema for entries of this type.
-/// 5. validation_package: `validation_package` is a special identifier, which declares which data is required from peers
+/// 4. schema (optional): `schema` is a JSON Schema, given as a `serde_json::Value` or a JSON string, that every
+///      entry of this type must conform to. Holochain rejects non-conforming entries before calling `validation`,
+///      and `hc package` refuses DNAs with malformed schemas. See [schema](holochain_core_types::dna::schema) for the supported keywords.
+/// 5. native_type: `native_type` references a given Rust struct, which provides a clear schema for entries of this type.
+/// 6. validation_package: `validation_package` is a special identifier, which declares which data is required from peers
 ///      when attempting to validate entries of this type.
 ///      Possible values are found within [ValidationPackageDefinition](ValidationPackageDefinition)
-/// 6. validation: `validation` is a callback function which will be called any time that a
+/// 7. validation: `validation` is a callback function which will be called any time that a
 ///      (DHT) node processes or stores this entry, triggered through actions such as [commit_entry](api::commit_entry()), [update_entry](api::update_entry()), [remove_entry](api::remove_entry()).
 ///      It always expects two arguments, the first of which is the entry attempting to be validated,
 ///      the second is the validation `context`, which offers a variety of metadata useful for validation.
 ///      See [ValidationData](ValidationData) for more details.
-/// 7. links: `links` is a vector of link definitions represented by `ValidatingLinkDefinition`.
+/// 8. links: `links` is a vector of link definitions represented by `ValidatingLinkDefinition`.
 ///     Links can be defined with the `link!` macro or, more concise, with either the `to!` or `from!` macro,
 ///     to define an association pointing from this entry type to another, or one that points back from
 ///     the other entry type to this one.
@@ -171,6 +174,7 @@ macro_rules! entry {
         name: $name:expr,
         description: $properties:expr,
         sharing: $sharing:expr,
+        $(schema: $schema:expr,)?
        // $(native_type: $native_type:ty,)*
 
         validation_package: || $package_creator:expr,
@@ -189,6 +193,9 @@ macro_rules! entry {
             let mut entry_type = $crate::holochain_core_types::dna::entry_types::EntryTypeDef::new();
             entry_type.properties = $crate::holochain_json_api::json::JsonString::from($properties);
             entry_type.sharing = $sharing;
+            $(
+                entry_type.schema = Some($crate::holochain_json_api::json::JsonString::from_json(&$schema.to_string()));
+            )?
 
             $($(
                 match $link_expr.direction {
@@ -19,6 +19,9 @@ pub enum ZomeApiError {
     HashNotFound,
     ValidationFailed(String),
     Timeout(String),
+    /// `hdk::send` could not reach the recipient, but the network queued the
+    /// message until the recipient (given here) is online
+    DirectMessageQueued(String),
 }
 
 impl From<ZomeApiError> for HolochainError {
@@ -26,6 +29,7 @@ impl From<ZomeApiError> for HolochainError {
         match zome_api_error {
             ZomeApiError::ValidationFailed(s) => HolochainError::ValidationFailed(s),
             ZomeApiError::Timeout(s) => HolochainError::Timeout(s),
+            ZomeApiError::DirectMessageQueued(s) => HolochainError::DirectMessageQueued(s),
             _ => HolochainError::RibosomeFailed(zome_api_error.to_string()),
         }
     }
@@ -42,6 +46,7 @@ impl From<HolochainError> for ZomeApiError {
         match holochain_error {
             HolochainError::ValidationFailed(s) => ZomeApiError::ValidationFailed(s),
             HolochainError::Timeout(s) => ZomeApiError::Timeout(s),
+            HolochainError::DirectMessageQueued(s) => ZomeApiError::DirectMessageQueued(s),
             _ => ZomeApiError::Internal(holochain_error.to_string()),
         }
     }
@@ -106,6 +111,7 @@ impl fmt::Display for ZomeApiError {
             ZomeApiError::HashNotFound => write!(f, "Hash not found"),
             ZomeApiError::ValidationFailed(msg) => write!(f, "{}", msg),
             ZomeApiError::Timeout(s) => write!(f, "Timeout({})", s),
+            ZomeApiError::DirectMessageQueued(s) => write!(f, "DirectMessageQueued({})", s),
         }
     }
 }
@@ -30,7 +30,7 @@ pub use crate::{
             EntryHistory, GetEntryOptions, GetEntryResult, GetEntryResultType, StatusRequestKind,
         },
         get_links::{GetLinksOptions, GetLinksResult, GetLinksResultCount, LinksStatusRequestKind},
-        QueryArgsOptions, QueryResult,
+        QueryArgsOptions, QueryFilter, QueryResult,
     },
     EntryValidationData, LinkValidationData, ValidationPackageDefinition,
 };
@@ -34,7 +34,7 @@ use shrust::{Shell, ShellIO};
 
 use holochain_conductor_lib::{
     conductor::{mount_conductor_from_config, Conductor, ConductorDebug, CONDUCTOR},
-    config::{self, load_configuration, Configuration},
+    config::{self, load_configuration, Configuration, PassphraseServiceConfig},
 };
 use holochain_core::{
     network::handler::fetch::fetch_aspects_for_entry, state_dump::DumpOptions,
@@ -336,6 +336,13 @@ fn bootstrap_from_config(path: &str) -> Result<(), HolochainError> {
     mount_conductor_from_config(config);
     let mut conductor_guard = CONDUCTOR.lock().unwrap();
     let conductor = conductor_guard.as_mut().expect("Conductor must be mounted");
+    // The admin passphrase service receives passphrases through the admin interfaces,
+    // so these have to be up before unlocking the keys. They keep running when
+    // all other interfaces get started.
+    if let PassphraseServiceConfig::Admin = conductor.config().passphrase_service {
+        println!("Starting admin interfaces to receive passphrases...");
+        conductor.start_admin_interfaces();
+    }
     println!("Unlocking agent keys:");
     conductor
         .config()
@@ -0,0 +1,60 @@
+//! hc-keystore unlocks a keystore file and serves it to conductors on a Unix
+//! domain socket, see `holochain_conductor_lib::keystore_service`.
+
+use holochain_core_types::error::HcResult;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "hc-keystore",
+    about = "Holds the keys of a holochain agent outside the conductor"
+)]
+struct Cli {
+    #[structopt(long, short, help = "Keystore file, as created by `hc keygen`")]
+    keystore: PathBuf,
+
+    #[structopt(long, short, help = "Path of the Unix domain socket to serve on")]
+    socket: PathBuf,
+}
+
+fn main() {
+    env_logger::init();
+    let args = Cli::from_args();
+    if let Err(error) = run(args) {
+        eprintln!("hc-keystore: {}", error);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(unix)]
+fn run(args: Cli) -> HcResult<()> {
+    use holochain_conductor_lib::{
+        conductor::passphrase_manager::{PassphraseManager, PassphraseServiceCmd},
+        keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
+        keystore_service::KeystoreServer,
+    };
+    use holochain_locksmith::Mutex;
+    use std::sync::Arc;
+
+    let passphrase_manager = Arc::new(PassphraseManager::new(Arc::new(Mutex::new(
+        PassphraseServiceCmd {},
+    ))));
+    let mut keystore = Keystore::new_from_file(args.keystore, passphrase_manager, None)?;
+    // unlock now rather than on the first request, so the prompt shows up here
+    let public_key = keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID)?.get_id();
+    let server = KeystoreServer::bind(args.socket.clone(), Arc::new(Mutex::new(keystore)))?;
+    println!(
+        "Serving keys of {} on {}",
+        public_key,
+        args.socket.display()
+    );
+    server.run()
+}
+
+#[cfg(not(unix))]
+fn run(_args: Cli) -> HcResult<()> {
+    Err(holochain_core_types::error::HolochainError::ErrorGeneric(
+        "hc-keystore needs Unix domain sockets, which are not available on this system".into(),
+    ))
+}
@@ -1,4 +1,7 @@
-use crate::{cloudwatch::CloudWatchLogger, logger::LoggerMetricPublisher, MetricPublisher};
+use crate::{
+    cloudwatch::CloudWatchLogger, logger::LoggerMetricPublisher,
+    prometheus::PrometheusMetricPublisher, MetricPublisher,
+};
 use holochain_locksmith::RwLock;
 use holochain_tracing_macros::newrelic_autotrace;
 use std::sync::Arc;
@@ -10,6 +13,7 @@ use std::sync::Arc;
 pub enum MetricPublisherConfig {
     Logger,
     CloudWatchLogs(CloudWatchLogsConfig),
+    Prometheus(PrometheusConfig),
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
@@ -24,6 +28,17 @@ pub struct CloudWatchLogsConfig {
     pub assume_role_arn: Option<String>,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct PrometheusConfig {
+    /// The address the `/metrics` endpoint is served at.
+    #[serde(default = "default_prometheus_bind_address")]
+    pub bind_address: String,
+}
+
+fn default_prometheus_bind_address() -> String {
+    crate::prometheus::DEFAULT_BIND_ADDRESS.to_string()
+}
+
 impl Default for MetricPublisherConfig {
     fn default() -> Self {
         Self::default_logger()
@@ -59,6 +74,9 @@ impl MetricPublisherConfig {
                     ))),
                 }
             }
+            Self::Prometheus(PrometheusConfig { bind_address }) => {
+                Arc::new(RwLock::new(PrometheusMetricPublisher::new(bind_address)))
+            }
         };
         publisher
     }
@@ -77,4 +95,11 @@ impl MetricPublisherConfig {
             assume_role_arn: None,
         })
     }
+
+    /// The default prometheus exposition endpoint configuration.
+    pub fn default_prometheus() -> Self {
+        Self::Prometheus(PrometheusConfig {
+            bind_address: default_prometheus_bind_address(),
+        })
+    }
 }
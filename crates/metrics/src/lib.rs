@@ -21,6 +21,8 @@ pub mod cloudwatch;
 pub mod config;
 pub mod logger;
 pub mod metrics;
+pub mod prometheus;
+pub mod report;
 pub mod stats;
 
 pub use cloudwatch::*;
@@ -2,6 +2,7 @@ extern crate structopt;
 use crate::structopt::StructOpt;
 use holochain_metrics::{
     cloudwatch::*,
+    report::{RegressionReport, Thresholds},
     stats::{StatCheck, StatsByMetric, StatsRecord},
     *,
 };
@@ -94,6 +95,37 @@ enum Command {
         #[structopt(name = "result_csv_file", short = "rf")]
         result_csv_file: PathBuf,
     },
+    #[structopt(
+        name = "compare",
+        about = "Compares the stats csv files of two runs and reports regressions, exiting with status 1 if any"
+    )]
+    Compare {
+        #[structopt(name = "baseline", long = "baseline", short = "b")]
+        baseline: PathBuf,
+        #[structopt(name = "candidate", long = "candidate", short = "c")]
+        candidate: PathBuf,
+        #[structopt(
+            name = "thresholds",
+            long = "thresholds",
+            short = "t",
+            help = "Toml file with the allowed percent change per metric"
+        )]
+        thresholds: Option<PathBuf>,
+        #[structopt(
+            name = "format",
+            long = "format",
+            default_value = "markdown",
+            possible_values = &["markdown", "json"]
+        )]
+        format: String,
+        #[structopt(
+            name = "output",
+            long = "output",
+            short = "o",
+            help = "Writes the report to this file instead of stdout"
+        )]
+        output: Option<PathBuf>,
+    },
 }
 
 fn setup_aws_env() {
@@ -158,6 +190,18 @@ fn main() {
             actual_csv_file,
             result_csv_file,
         } => print_stat_check(expected_csv_file, actual_csv_file, result_csv_file),
+        Command::Compare {
+            baseline,
+            candidate,
+            thresholds,
+            format,
+            output,
+        } => {
+            let passed = print_comparison(baseline, candidate, thresholds, &format, output);
+            if !passed {
+                std::process::exit(1);
+            }
+        }
     }
 }
 
@@ -275,3 +319,34 @@ fn print_metric_stats(csv_file: PathBuf, aggregation_pattern: Option<String>) {
     let stats_by_metric = StatsByMetric::group_by_regex(&re, metrics);
     stats_by_metric.write_csv(std::io::stdout()).unwrap();
 }
+
+/// Writes the regression report of `candidate_csv_file` against `baseline_csv_file`
+/// to `output` or stdout. Returns whether no metric regressed.
+fn print_comparison(
+    baseline_csv_file: PathBuf,  // StatsByMetric
+    candidate_csv_file: PathBuf, // StatsByMetric
+    thresholds_file: Option<PathBuf>,
+    format: &str,
+    output: Option<PathBuf>,
+) -> bool {
+    let read_stats = |path: PathBuf| {
+        let mut reader = BufReader::new(File::open(path).unwrap());
+        StatsByMetric::<StatsRecord>::from_reader(&mut reader).unwrap()
+    };
+    let baseline = read_stats(baseline_csv_file);
+    let candidate = read_stats(candidate_csv_file);
+    let thresholds = thresholds_file
+        .map(|path| Thresholds::from_file(path).unwrap())
+        .unwrap_or_default();
+
+    let report = RegressionReport::compare(&baseline, &candidate, &thresholds);
+    let rendered = match format {
+        "json" => report.to_json().unwrap(),
+        _ => report.to_markdown(),
+    };
+    match output {
+        Some(path) => std::fs::write(path, rendered).unwrap(),
+        None => println!("{}", rendered),
+    }
+    report.passed
+}
@@ -0,0 +1,405 @@
+/// Prometheus support for metric publishing. Metrics are aggregated in memory
+/// and served in the text exposition format at `http://<bind_address>/metrics`.
+use crate::{Metric, MetricPublisher};
+use holochain_locksmith::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as FmtWrite,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    time::Duration,
+};
+
+/// The default address the exposition endpoint binds to.
+pub const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:9187";
+
+/// How long a scrape connection may take to send its request or receive the response,
+/// so that idle or slow clients don't hold on to their thread forever.
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Upper bounds of the histogram buckets. Metric samples are latencies in
+/// milliseconds more often than not, so the buckets are chosen for those.
+pub const HISTOGRAM_BUCKETS: &[f64] = &[
+    1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+];
+
+/// Metrics whose name ends with this suffix are summed into a counter instead
+/// of being observed by a histogram.
+pub const COUNTER_SUFFIX: &str = ".count";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Aggregate {
+    Counter(f64),
+    Histogram {
+        buckets: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+impl Aggregate {
+    fn for_metric_name(name: &str) -> Self {
+        if name.ends_with(COUNTER_SUFFIX) {
+            Aggregate::Counter(0.0)
+        } else {
+            Aggregate::Histogram {
+                buckets: vec![0; HISTOGRAM_BUCKETS.len()],
+                sum: 0.0,
+                count: 0,
+            }
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        match self {
+            Aggregate::Counter(total) => *total += value,
+            Aggregate::Histogram {
+                buckets,
+                sum,
+                count,
+            } => {
+                for (bucket, bound) in buckets.iter_mut().zip(HISTOGRAM_BUCKETS) {
+                    if value <= *bound {
+                        *bucket += 1;
+                    }
+                }
+                *sum += value;
+                *count += 1;
+            }
+        }
+    }
+}
+
+/// Aggregated metric values keyed by metric name and stream id.
+#[derive(Debug, Default)]
+pub struct PrometheusRegistry {
+    series: BTreeMap<(String, Option<String>), Aggregate>,
+}
+
+impl PrometheusRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sample to the counter or histogram of its metric.
+    pub fn observe(&mut self, metric: &Metric) {
+        self.series
+            .entry((metric.name.clone(), metric.stream_id.clone()))
+            .or_insert_with(|| Aggregate::for_metric_name(&metric.name))
+            .observe(metric.value);
+    }
+
+    /// Renders all aggregated metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut last_name: Option<&String> = None;
+        for ((name, stream_id), aggregate) in &self.series {
+            let exposed_name = exposed_name(name);
+            let labels = stream_id
+                .as_ref()
+                .map(|stream_id| format!("stream_id=\"{}\"", escape_label_value(stream_id)));
+            let first_of_family = last_name != Some(name);
+            last_name = Some(name);
+            match aggregate {
+                Aggregate::Counter(total) => {
+                    if first_of_family {
+                        let _ = writeln!(out, "# TYPE {} counter", exposed_name);
+                    }
+                    let _ = writeln!(out, "{}{} {}", exposed_name, braces(&labels, None), total);
+                }
+                Aggregate::Histogram {
+                    buckets,
+                    sum,
+                    count,
+                } => {
+                    if first_of_family {
+                        let _ = writeln!(out, "# TYPE {} histogram", exposed_name);
+                    }
+                    for (bucket, bound) in buckets.iter().zip(HISTOGRAM_BUCKETS) {
+                        let le = format!("le=\"{}\"", bound);
+                        let _ = writeln!(
+                            out,
+                            "{}_bucket{} {}",
+                            exposed_name,
+                            braces(&labels, Some(&le)),
+                            bucket
+                        );
+                    }
+                    let le = "le=\"+Inf\"".to_string();
+                    let _ = writeln!(
+                        out,
+                        "{}_bucket{} {}",
+                        exposed_name,
+                        braces(&labels, Some(&le)),
+                        count
+                    );
+                    let _ = writeln!(out, "{}_sum{} {}", exposed_name, braces(&labels, None), sum);
+                    let _ = writeln!(
+                        out,
+                        "{}_count{} {}",
+                        exposed_name,
+                        braces(&labels, None),
+                        count
+                    );
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Turns a holochain metric name such as `sim2h_worker.tick.latency` into a valid
+/// Prometheus metric name such as `sim2h_worker_tick_latency`. Counter names get
+/// the conventional `_total` suffix in place of `.count`.
+fn exposed_name(name: &str) -> String {
+    let (name, suffix) = if name.ends_with(COUNTER_SUFFIX) {
+        (&name[..name.len() - COUNTER_SUFFIX.len()], "_total")
+    } else {
+        (name, "")
+    };
+    let mut exposed: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if exposed.chars().next().map_or(true, |c| c.is_ascii_digit()) {
+        exposed.insert(0, '_');
+    }
+    exposed + suffix
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn braces(labels: &Option<String>, extra: Option<&String>) -> String {
+    let all: Vec<&String> = labels.iter().chain(extra).collect();
+    if all.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "{{{}}}",
+            all.iter()
+                .map(|label| label.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+}
+
+lazy_static! {
+    /// One registry per bind address, so that every publisher created from the same
+    /// configuration (e.g. one per conductor instance) feeds the same endpoint.
+    static ref REGISTRIES: Mutex<HashMap<String, Arc<Mutex<PrometheusRegistry>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Returns the registry serving the given bind address, binding the address
+/// and starting its HTTP server thread the first time it is asked for.
+pub fn registry_for(bind_address: &str) -> Arc<Mutex<PrometheusRegistry>> {
+    let mut registries = REGISTRIES.lock().unwrap();
+    if let Some(registry) = registries.get(bind_address) {
+        return registry.clone();
+    }
+    let registry = Arc::new(Mutex::new(PrometheusRegistry::new()));
+    match TcpListener::bind(bind_address) {
+        Ok(listener) => spawn_server(listener, registry.clone()),
+        Err(e) => error!(
+            "Could not bind prometheus exporter to {}: {:?}. Metrics will not be served.",
+            bind_address, e
+        ),
+    }
+    registries.insert(bind_address.to_string(), registry.clone());
+    registry
+}
+
+fn spawn_server(listener: TcpListener, registry: Arc<Mutex<PrometheusRegistry>>) {
+    let name = match listener.local_addr() {
+        Ok(address) => format!("prometheus_exporter/{}", address),
+        Err(_) => "prometheus_exporter".to_string(),
+    };
+    let spawned = std::thread::Builder::new()
+        .name(name)
+        .spawn(move || serve(listener, registry));
+    if let Err(e) = spawned {
+        error!("Could not start prometheus exporter thread: {:?}", e);
+    }
+}
+
+/// Answers every scrape on a thread of its own, so that a slow client doesn't hold up the others.
+fn serve(listener: TcpListener, registry: Arc<Mutex<PrometheusRegistry>>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let registry = registry.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("prometheus_scrape".to_string())
+                    .spawn(move || {
+                        if let Err(e) = respond(stream, &registry) {
+                            debug!("Error answering prometheus scrape: {:?}", e);
+                        }
+                    });
+                if let Err(e) = spawned {
+                    warn!("Could not start prometheus scrape thread: {:?}", e);
+                }
+            }
+            Err(e) => warn!("Error accepting prometheus scrape connection: {:?}", e),
+        }
+    }
+}
+
+fn respond(stream: TcpStream, registry: &Arc<Mutex<PrometheusRegistry>>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+    stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the request headers so the client doesn't see a reset.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let (status, content_type, body) = if method == "GET" && path == "/metrics" {
+        (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            registry.lock().unwrap().render(),
+        )
+    } else {
+        ("404 Not Found", "text/plain", "Not Found\n".to_string())
+    };
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// A metric publisher that aggregates metrics into counters and histograms
+/// scraped by Prometheus.
+#[derive(Clone)]
+pub struct PrometheusMetricPublisher {
+    registry: Arc<Mutex<PrometheusRegistry>>,
+}
+
+impl PrometheusMetricPublisher {
+    pub fn new(bind_address: &str) -> Self {
+        Self {
+            registry: registry_for(bind_address),
+        }
+    }
+}
+
+impl MetricPublisher for PrometheusMetricPublisher {
+    fn publish(&mut self, metric: &Metric) {
+        self.registry.lock().unwrap().observe(metric);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn can_render_histograms_and_counters() {
+        let mut registry = PrometheusRegistry::new();
+        registry.observe(&Metric::new("sim2h_worker.tick.latency", None, None, 3.0));
+        registry.observe(&Metric::new("sim2h_worker.tick.latency", None, None, 300.0));
+        registry.observe(&Metric::new(
+            "messages.count",
+            Some("a".to_string()),
+            None,
+            2.0,
+        ));
+        registry.observe(&Metric::new(
+            "messages.count",
+            Some("a".to_string()),
+            None,
+            5.0,
+        ));
+
+        let rendered = registry.render();
+        assert!(
+            rendered.contains("# TYPE messages_total counter\nmessages_total{stream_id=\"a\"} 7\n")
+        );
+        assert!(rendered.contains("# TYPE sim2h_worker_tick_latency histogram\n"));
+        assert!(rendered.contains("sim2h_worker_tick_latency_bucket{le=\"1\"} 0\n"));
+        assert!(rendered.contains("sim2h_worker_tick_latency_bucket{le=\"5\"} 1\n"));
+        assert!(rendered.contains("sim2h_worker_tick_latency_bucket{le=\"500\"} 2\n"));
+        assert!(rendered.contains("sim2h_worker_tick_latency_bucket{le=\"+Inf\"} 2\n"));
+        assert!(rendered.contains("sim2h_worker_tick_latency_sum 303\n"));
+        assert!(rendered.contains("sim2h_worker_tick_latency_count 2\n"));
+    }
+
+    #[test]
+    fn can_expose_metric_names() {
+        assert_eq!(
+            "call_zome_function_foo_bar_latency",
+            exposed_name("call_zome_function.foo.bar.latency")
+        );
+        assert_eq!("sim2h_handle_message", exposed_name("sim2h-handle-message"));
+        assert_eq!("_1st", exposed_name("1st"));
+    }
+
+    #[test]
+    fn can_serve_metrics_endpoint() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let bind_address = listener.local_addr().unwrap();
+        let registry = Arc::new(Mutex::new(PrometheusRegistry::new()));
+        spawn_server(listener, registry.clone());
+        let mut publisher = PrometheusMetricPublisher { registry };
+        publisher.publish(&Metric::new("test.latency", None, None, 42.0));
+
+        let mut stream = TcpStream::connect(bind_address).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("test_latency_count 1\n"));
+    }
+
+    #[test]
+    fn idle_clients_do_not_block_scrapes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let bind_address = listener.local_addr().unwrap();
+        let registry = Arc::new(Mutex::new(PrometheusRegistry::new()));
+        spawn_server(listener, registry);
+
+        // connects but never sends its request
+        let _idle = TcpStream::connect(bind_address).unwrap();
+
+        let mut stream = TcpStream::connect(bind_address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+}
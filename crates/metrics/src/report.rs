@@ -0,0 +1,301 @@
+/// Regression reports comparing the descriptive statistics of a baseline run
+/// against those of a candidate run, as produced by `metrics compare`.
+use crate::stats::{
+    DescriptiveStats, GroupingKey, LessThanStatCheck, StatCheck, StatFailure, StatsByMetric,
+    StatsRecord,
+};
+use std::{collections::HashMap, error::Error, fmt::Write, path::Path};
+
+/// The percent change allowed for metrics without a threshold of their own.
+pub const DEFAULT_PERCENT_CHANGE_ALLOWED: f64 = 0.05;
+
+fn default_percent_change_allowed() -> f64 {
+    DEFAULT_PERCENT_CHANGE_ALLOWED
+}
+
+/// Allowed percent change per metric name, e.g. loaded from
+///
+/// ```toml
+/// default = 0.1
+///
+/// [metrics]
+/// "sim2h_worker.tick.latency" = 0.25
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    #[serde(default = "default_percent_change_allowed")]
+    pub default: f64,
+    #[serde(default)]
+    pub metrics: HashMap<String, f64>,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            default: DEFAULT_PERCENT_CHANGE_ALLOWED,
+            metrics: HashMap::new(),
+        }
+    }
+}
+
+impl Thresholds {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// The percent change allowed for the given metric.
+    pub fn for_metric(&self, metric: &str) -> f64 {
+        self.metrics.get(metric).cloned().unwrap_or(self.default)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComparisonStatus {
+    /// Within the allowed change.
+    Passed,
+    /// Changed more than allowed.
+    Regressed,
+    /// In the baseline but not in the candidate.
+    Missing,
+    /// In the candidate but not in the baseline.
+    New,
+}
+
+impl ComparisonStatus {
+    pub fn is_failure(self) -> bool {
+        match self {
+            ComparisonStatus::Regressed | ComparisonStatus::Missing => true,
+            ComparisonStatus::Passed | ComparisonStatus::New => false,
+        }
+    }
+}
+
+/// The comparison of a single metric of a single stream.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricComparison {
+    pub stream_id: String,
+    pub metric: String,
+    pub status: ComparisonStatus,
+    pub percent_change_allowed: f64,
+    pub baseline: Option<StatsRecord>,
+    pub candidate: Option<StatsRecord>,
+    pub failures: Vec<StatFailure>,
+}
+
+/// The comparison of every metric of a baseline and a candidate run.
+#[derive(Debug, Clone, Serialize)]
+pub struct RegressionReport {
+    pub passed: bool,
+    pub comparisons: Vec<MetricComparison>,
+}
+
+impl RegressionReport {
+    /// Checks every baseline metric against the candidate with a `LessThanStatCheck`
+    /// using the metric's threshold. Comparisons are sorted by stream id and metric.
+    pub fn compare(
+        baseline: &StatsByMetric<StatsRecord>,
+        candidate: &StatsByMetric<StatsRecord>,
+        thresholds: &Thresholds,
+    ) -> Self {
+        let mut keys: Vec<&GroupingKey> = baseline.keys().chain(candidate.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        let comparisons: Vec<MetricComparison> = keys
+            .into_iter()
+            .map(|key| {
+                let percent_change_allowed = thresholds.for_metric(key.metric());
+                let baseline_stat = baseline.get(key);
+                let candidate_stat = candidate.get(key);
+                let (status, failures) = match (baseline_stat, candidate_stat) {
+                    (Some(expected), Some(actual)) => {
+                        match LessThanStatCheck::new(percent_change_allowed).check(expected, actual)
+                        {
+                            Ok(_) => (ComparisonStatus::Passed, Vec::new()),
+                            Err((_, failures)) => (ComparisonStatus::Regressed, failures),
+                        }
+                    }
+                    (Some(_), None) => (ComparisonStatus::Missing, Vec::new()),
+                    _ => (ComparisonStatus::New, Vec::new()),
+                };
+                MetricComparison {
+                    stream_id: key.stream_id().to_string(),
+                    metric: key.metric().to_string(),
+                    status,
+                    percent_change_allowed,
+                    baseline: baseline_stat.cloned(),
+                    candidate: candidate_stat.cloned(),
+                    failures,
+                }
+            })
+            .collect();
+
+        Self {
+            passed: !comparisons.iter().any(|c| c.status.is_failure()),
+            comparisons,
+        }
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &MetricComparison> {
+        self.comparisons.iter().filter(|c| c.status.is_failure())
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let failed = self.failures().count();
+        let _ = writeln!(out, "# Metrics comparison\n");
+        if self.passed {
+            let _ = writeln!(
+                out,
+                "**Passed**: {} metrics within thresholds.\n",
+                self.comparisons.len()
+            );
+        } else {
+            let _ = writeln!(
+                out,
+                "**Failed**: {} of {} metrics regressed or are missing.\n",
+                failed,
+                self.comparisons.len()
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "| Status | Stream | Metric | Allowed | Baseline mean | Candidate mean | Mean change |"
+        );
+        let _ = writeln!(out, "|---|---|---|---|---|---|---|");
+        for comparison in &self.comparisons {
+            let mean = |stat: &Option<StatsRecord>| {
+                stat.as_ref()
+                    .map(|stat| format!("{:.2}", stat.mean()))
+                    .unwrap_or_else(|| "-".to_string())
+            };
+            let change = match (&comparison.baseline, &comparison.candidate) {
+                (Some(baseline), Some(candidate)) => format!(
+                    "{:+.1}%",
+                    (candidate.mean() - baseline.mean()) / baseline.mean() * 100.0
+                ),
+                _ => "-".to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "| {:?} | {} | {} | {:.1}% | {} | {} | {} |",
+                comparison.status,
+                comparison.stream_id,
+                comparison.metric,
+                comparison.percent_change_allowed * 100.0,
+                mean(&comparison.baseline),
+                mean(&comparison.candidate),
+                change
+            );
+        }
+
+        if !self.passed {
+            let _ = writeln!(out, "\n## Failures");
+            for comparison in self.failures() {
+                let _ = writeln!(
+                    out,
+                    "\n### {}: {} ({:?})\n",
+                    comparison.stream_id, comparison.metric, comparison.status
+                );
+                if comparison.failures.is_empty() {
+                    let _ = writeln!(out, "- not present in the candidate run");
+                }
+                for failure in &comparison.failures {
+                    let _ = writeln!(out, "- {}", failure);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter::FromIterator;
+
+    fn stats(entries: Vec<(&str, f64)>) -> StatsByMetric<StatsRecord> {
+        StatsByMetric::from_iter(entries.into_iter().map(|(metric, mean)| {
+            (
+                GroupingKey::new("run", metric),
+                StatsRecord {
+                    metric: Some(metric.to_string()),
+                    stream_id: Some("run".to_string()),
+                    mean,
+                    max: mean * 2.0,
+                    min: mean / 2.0,
+                    cnt: 100.0,
+                    stddev: 10.0,
+                    variance: 100.0,
+                },
+            )
+        }))
+    }
+
+    #[test]
+    fn can_compare_with_per_metric_thresholds() {
+        let baseline = stats(vec![("a.latency", 100.0), ("b.latency", 100.0), ("c", 1.0)]);
+        let candidate = stats(vec![("a.latency", 102.0), ("b.latency", 120.0), ("d", 1.0)]);
+
+        let report = RegressionReport::compare(&baseline, &candidate, &Thresholds::default());
+        let statuses: Vec<_> = report
+            .comparisons
+            .iter()
+            .map(|c| (c.metric.as_str(), c.status))
+            .collect();
+        assert_eq!(
+            vec![
+                ("a.latency", ComparisonStatus::Passed),
+                ("b.latency", ComparisonStatus::Regressed),
+                ("c", ComparisonStatus::Missing),
+                ("d", ComparisonStatus::New),
+            ],
+            statuses
+        );
+        assert!(!report.passed);
+
+        let thresholds: Thresholds = toml::from_str(
+            r#"
+            [metrics]
+            "b.latency" = 0.25
+            "#,
+        )
+        .unwrap();
+        assert_eq!(DEFAULT_PERCENT_CHANGE_ALLOWED, thresholds.default);
+        let report = RegressionReport::compare(&baseline, &baseline, &thresholds);
+        assert!(report.passed);
+        let report = RegressionReport::compare(
+            &stats(vec![("b.latency", 100.0)]),
+            &stats(vec![("b.latency", 120.0)]),
+            &thresholds,
+        );
+        assert!(report.passed);
+    }
+
+    #[test]
+    fn can_render_markdown_report() {
+        let report = RegressionReport::compare(
+            &stats(vec![("b.latency", 100.0)]),
+            &stats(vec![("b.latency", 120.0)]),
+            &Thresholds::default(),
+        );
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("**Failed**: 1 of 1 metrics regressed or are missing."));
+        assert!(
+            markdown.contains("| Regressed | run | b.latency | 5.0% | 100.00 | 120.00 | +20.0% |")
+        );
+        assert!(markdown.contains("### run: b.latency (Regressed)"));
+        assert!(markdown.contains("- Mean: Expected 100, Actual was 120"));
+        assert!(report
+            .to_json()
+            .unwrap()
+            .contains("\"status\": \"regressed\""));
+    }
+}
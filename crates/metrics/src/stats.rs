@@ -347,7 +347,13 @@ pub struct LessThanStatCheck {
     percent_change_allowed: f64,
 }
 
-impl LessThanStatCheck {}
+impl LessThanStatCheck {
+    pub fn new(percent_change_allowed: f64) -> Self {
+        Self {
+            percent_change_allowed,
+        }
+    }
+}
 
 impl Default for LessThanStatCheck {
     fn default() -> Self {
@@ -456,7 +462,7 @@ impl Commute for OnlineStats {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 /// (metric name, run name)
 pub struct GroupingKey(String, String);
 
@@ -473,6 +479,14 @@ impl GroupingKey {
 
         Self(stream_id, metric)
     }
+
+    pub fn stream_id(&self) -> &str {
+        &self.0
+    }
+
+    pub fn metric(&self) -> &str {
+        &self.1
+    }
 }
 
 /// All combined descriptive statistics mapped by name of the metric
@@ -0,0 +1,277 @@
+//! configurable network faults for the in-memory server, so that lost, late,
+//! duplicated and reordered messages and network partitions can be reproduced
+//! in tests. Every random decision comes from a generator seeded by the config,
+//! so a test sending the same messages in the same order meets the same faults.
+//! Delays are measured on the wall clock though: a delayed message is delivered
+//! by the first poll of the server after it is due, so when it arrives relative
+//! to the messages of other threads depends on scheduling and is not reproducible.
+//! Messages between partitions are held back, not dropped, and go through the
+//! fault model once the partitions change so that they can reach their chain.
+//!
+//! faults are configured in the `LegacyInMemory` backend config:
+//! ```json
+//! {
+//!   "serverName": "faulty-network",
+//!   "faults": {
+//!     "seed": 42,
+//!     "latency": { "kind": "Uniform", "min_ms": 10, "max_ms": 200 },
+//!     "drop_probability": 0.05,
+//!     "duplicate_probability": 0.01,
+//!     "reorder_probability": 0.1,
+//!     "reorder_delay_ms": 500,
+//!     "partitions": { "island": ["HcScic3VAmEP9ucmrw4MMFKVARIvvdn43k6TYdrMwNBxq1dbNpqhHFi4ZRs7cqi"] }
+//!   }
+//! }
+//! ```
+//! and partitions can be made and healed at runtime with
+//! `partition_memory_network` and `heal_memory_network`.
+
+use lib3h_protocol::types::AgentPubKey;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::BTreeMap, time::Duration};
+
+/// how long a message takes to be delivered
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind")]
+pub enum LatencyConfig {
+    /// delivered as soon as it is sent
+    None,
+    Fixed {
+        ms: u64,
+    },
+    Uniform {
+        min_ms: u64,
+        max_ms: u64,
+    },
+    /// mostly fast with a long tail of slow messages
+    Exponential {
+        mean_ms: u64,
+    },
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        LatencyConfig::None
+    }
+}
+
+/// the faults of an in-memory network, found under `"faults"` in its backend config
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct FaultConfig {
+    /// seeds every random decision
+    pub seed: u64,
+    pub latency: LatencyConfig,
+    /// probability of a message never being delivered
+    pub drop_probability: f64,
+    /// probability of a message being delivered twice
+    pub duplicate_probability: f64,
+    /// probability of a message being held back by `reorder_delay_ms`,
+    /// letting the messages sent after it overtake it
+    pub reorder_probability: f64,
+    pub reorder_delay_ms: u64,
+    /// named groups of agents that can only reach each other until the partition
+    /// is healed, holding back the messages sent to everyone else
+    pub partitions: BTreeMap<String, Vec<AgentPubKey>>,
+}
+
+impl FaultConfig {
+    /// true if messages may not be delivered instantly and reliably
+    pub fn is_faulty(&self) -> bool {
+        self.latency != LatencyConfig::None
+            || self.drop_probability > 0.0
+            || self.duplicate_probability > 0.0
+            || self.reorder_probability > 0.0
+            || !self.partitions.is_empty()
+    }
+}
+
+/// decides what happens to each message sent through an in-memory server
+pub(crate) struct FaultModel {
+    config: FaultConfig,
+    rng: StdRng,
+}
+
+impl FaultModel {
+    pub fn new(config: FaultConfig) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &FaultConfig {
+        &self.config
+    }
+
+    /// the partition an agent is in, if any
+    fn partition_of(&self, agent: &AgentPubKey) -> Option<&String> {
+        self.config
+            .partitions
+            .iter()
+            .find(|(_, agents)| agents.contains(agent))
+            .map(|(name, _)| name)
+    }
+
+    /// agents can reach each other if they are in the same partition, or both in none.
+    /// Messages from the server itself reach everyone.
+    pub fn can_reach(&self, from: Option<&AgentPubKey>, to: &AgentPubKey) -> bool {
+        match from {
+            None => true,
+            Some(from) => self.partition_of(from) == self.partition_of(to),
+        }
+    }
+
+    /// cuts `agents` off from everyone outside the partition `name`,
+    /// moving them out of any other partition they were in
+    pub fn partition(&mut self, name: &str, agents: Vec<AgentPubKey>) {
+        for others in self.config.partitions.values_mut() {
+            others.retain(|agent| !agents.contains(agent));
+        }
+        self.config
+            .partitions
+            .retain(|_, others| !others.is_empty());
+        self.config.partitions.insert(name.to_string(), agents);
+    }
+
+    /// reconnects the agents of partition `name` to the rest of the network,
+    /// false if there is no such partition
+    pub fn heal(&mut self, name: &str) -> bool {
+        self.config.partitions.remove(name).is_some()
+    }
+
+    fn latency(&mut self) -> Duration {
+        let ms = match self.config.latency {
+            LatencyConfig::None => 0,
+            LatencyConfig::Fixed { ms } => ms,
+            LatencyConfig::Uniform { min_ms, max_ms } => {
+                if max_ms > min_ms {
+                    self.rng.gen_range(min_ms, max_ms + 1)
+                } else {
+                    min_ms
+                }
+            }
+            LatencyConfig::Exponential { mean_ms } => {
+                let uniform: f64 = self.rng.gen();
+                (-(1.0 - uniform).ln() * mean_ms as f64) as u64
+            }
+        };
+        Duration::from_millis(ms)
+    }
+
+    /// the delays after which each copy of a message is delivered,
+    /// none if the message is dropped
+    pub fn delays(&mut self) -> Vec<Duration> {
+        // draw every decision, even for a dropped message, so that the
+        // numbers drawn for a message only depend on the ones before it
+        let dropped = self.rng.gen::<f64>() < self.config.drop_probability;
+        let duplicated = self.rng.gen::<f64>() < self.config.duplicate_probability;
+        let copies = if duplicated { 2 } else { 1 };
+        let delays: Vec<Duration> = (0..copies)
+            .map(|_| {
+                let reordered = self.rng.gen::<f64>() < self.config.reorder_probability;
+                let latency = self.latency();
+                if reordered {
+                    latency + Duration::from_millis(self.config.reorder_delay_ms)
+                } else {
+                    latency
+                }
+            })
+            .collect();
+        if dropped {
+            Vec::new()
+        } else {
+            delays
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agents(names: &[&str]) -> Vec<AgentPubKey> {
+        names.iter().map(|name| AgentPubKey::from(*name)).collect()
+    }
+
+    #[test]
+    fn can_parse_fault_config() {
+        let config: FaultConfig = serde_json::from_str(
+            r#"{
+                "seed": 42,
+                "latency": { "kind": "Uniform", "min_ms": 10, "max_ms": 50 },
+                "drop_probability": 0.1,
+                "partitions": { "island": ["alice"] }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            FaultConfig {
+                seed: 42,
+                latency: LatencyConfig::Uniform {
+                    min_ms: 10,
+                    max_ms: 50
+                },
+                drop_probability: 0.1,
+                partitions: vec![("island".to_string(), agents(&["alice"]))]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            },
+            config
+        );
+        assert!(config.is_faulty());
+        assert!(!FaultConfig::default().is_faulty());
+    }
+
+    #[test]
+    fn same_seed_makes_same_decisions() {
+        let config = FaultConfig {
+            seed: 7,
+            latency: LatencyConfig::Exponential { mean_ms: 100 },
+            drop_probability: 0.2,
+            duplicate_probability: 0.2,
+            reorder_probability: 0.2,
+            reorder_delay_ms: 500,
+            ..Default::default()
+        };
+        let mut first = FaultModel::new(config.clone());
+        let mut second = FaultModel::new(config);
+        let decisions: Vec<Vec<Duration>> = (0..100).map(|_| first.delays()).collect();
+        assert_eq!(
+            decisions,
+            (0..100).map(|_| second.delays()).collect::<Vec<_>>()
+        );
+        assert!(decisions.iter().any(|delays| delays.is_empty()));
+        assert!(decisions.iter().any(|delays| delays.len() == 2));
+    }
+
+    #[test]
+    fn reliable_by_default() {
+        let mut model = FaultModel::new(FaultConfig::default());
+        for _ in 0..100 {
+            assert_eq!(vec![Duration::from_millis(0)], model.delays());
+        }
+    }
+
+    #[test]
+    fn partitions_can_be_healed() {
+        let mut model = FaultModel::new(FaultConfig::default());
+        let alice = AgentPubKey::from("alice");
+        let bob = AgentPubKey::from("bob");
+        let carol = AgentPubKey::from("carol");
+        model.partition("island", agents(&["alice", "bob"]));
+        assert!(model.can_reach(Some(&alice), &bob));
+        assert!(!model.can_reach(Some(&alice), &carol));
+        assert!(!model.can_reach(Some(&carol), &bob));
+        // the server itself is never cut off
+        assert!(model.can_reach(None, &alice));
+
+        // moving bob to another partition takes him off the island
+        model.partition("rock", agents(&["bob"]));
+        assert!(!model.can_reach(Some(&alice), &bob));
+        assert!(model.heal("island"));
+        assert!(model.can_reach(Some(&alice), &carol));
+        assert!(!model.heal("island"));
+    }
+}
@@ -1,10 +1,12 @@
 //! provides in-memory p2p "server" for use in scenario testing
 //! the server connects all the memory_workers together, so there is no real gossiping going around.
 //! Could have pluggable DHT strategy. Full-sync currently hard-coded: #fullsync
+//! Messages can be delayed, dropped, duplicated, reordered or partitioned by a `FaultModel`.
+//! Partitioned messages are held back until a partition change lets them through.
 
 #![allow(non_snake_case)]
 
-use super::memory_book::*;
+use super::{fault_model::*, memory_book::*};
 use crate::{connection::NetResult, error::NetworkError, tweetlog::*};
 
 use lib3h_protocol::{
@@ -20,7 +22,10 @@ use lib3h_protocol::{
 use holochain_locksmith::{Mutex, RwLock};
 use holochain_persistence_api::cas::content::Address;
 use lib3h_protocol::types::SpaceHash;
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    time::Instant,
+};
 
 type RequestId = String;
 
@@ -33,6 +38,37 @@ lazy_static! {
         RwLock::new(HashMap::new());
 }
 
+/// run `f` on the in-memory server named `server_name`
+fn with_memory_server<T>(
+    server_name: &str,
+    f: impl FnOnce(&mut InMemoryServer) -> T,
+) -> NetResult<T> {
+    let server_map = MEMORY_SERVER_MAP.read().unwrap();
+    let mut server = server_map
+        .get(server_name)
+        .ok_or_else(|| format_err!("No InMemoryServer named {}", server_name))?
+        .lock()
+        .unwrap();
+    Ok(f(&mut server))
+}
+
+/// cut `agents` of the in-memory network `server_name` off from every agent
+/// outside the partition `name`, until it is healed
+pub fn partition_memory_network(
+    server_name: &str,
+    name: &str,
+    agents: Vec<AgentPubKey>,
+) -> NetResult<()> {
+    with_memory_server(server_name, |server| server.partition(name, agents))?
+}
+
+/// reconnect the agents of partition `name` to the rest of the in-memory
+/// network `server_name` and deliver the messages held back by it,
+/// false if there is no such partition
+pub fn heal_memory_network(server_name: &str, name: &str) -> NetResult<bool> {
+    with_memory_server(server_name, |server| server.heal(name))?
+}
+
 /// a global server for routing messages between nodes in-memory
 pub(crate) struct InMemoryServer {
     // keep track of senders by ChainId (dna_address::agent_id)
@@ -58,6 +94,17 @@ pub(crate) struct InMemoryServer {
     // used for making unique request ids
     request_count: usize,
 
+    // what happens to the messages we deliver
+    faults: FaultModel,
+    // messages the fault model delayed: (deliver_at, sequence) -> (chain_id, message)
+    delayed: BTreeMap<(Instant, u64), (ChainId, Lib3hServerProtocol)>,
+    delayed_count: u64,
+    // messages a partition held back, in the order they were sent:
+    // (sending agent, chain_id, message)
+    held: Vec<(Option<AgentPubKey>, ChainId, Lib3hServerProtocol)>,
+    // the agent that sent the message being served, if it is known
+    current_source: Option<AgentPubKey>,
+
     // Logger
     log: TweetProxy,
 }
@@ -127,7 +174,7 @@ impl InMemoryServer {
 /// Public API
 impl InMemoryServer {
     /// create a new in-memory network server
-    pub fn new(name: String) -> Self {
+    pub fn new(name: String, faults: FaultConfig) -> Self {
         Self {
             name,
             senders: HashMap::new(),
@@ -138,6 +185,11 @@ impl InMemoryServer {
             stored_book: HashMap::new(),
             request_count: 0,
             trackdna_book: HashSet::new(),
+            faults: FaultModel::new(faults),
+            delayed: BTreeMap::new(),
+            delayed_count: 0,
+            held: Vec::new(),
+            current_source: None,
             log: TweetProxy::new("memory_server"),
         }
     }
@@ -161,9 +213,71 @@ impl InMemoryServer {
                 .t(&format!("--- '{}' CLEAR CHANNELS", self.name.clone()));
             self.senders.clear();
             self.senders_by_dna.clear();
+            self.delayed.clear();
+            self.held.clear();
         }
     }
 
+    /// the faults this server was configured with, and the partitions since
+    pub fn fault_config(&self) -> &FaultConfig {
+        self.faults.config()
+    }
+
+    /// cut `agents` off from everyone outside the partition `name`.
+    /// Agents moved out of another partition get the messages held back for them.
+    pub fn partition(&mut self, name: &str, agents: Vec<AgentPubKey>) -> NetResult<()> {
+        self.log.i(&format!(
+            "---- '{}' partition {}: {:?}",
+            self.name, name, agents
+        ));
+        self.faults.partition(name, agents);
+        self.priv_release_held()
+    }
+
+    /// reconnect the agents of partition `name` and deliver the messages held back
+    /// by it, false if there is no such partition
+    pub fn heal(&mut self, name: &str) -> NetResult<bool> {
+        self.log.i(&format!("---- '{}' heal {}", self.name, name));
+        if !self.faults.heal(name) {
+            return Ok(false);
+        }
+        self.priv_release_held()?;
+        Ok(true)
+    }
+
+    /// hand the held back messages that can now reach their chain to the fault model,
+    /// in the order they were sent
+    fn priv_release_held(&mut self) -> NetResult<()> {
+        let held = std::mem::replace(&mut self.held, Vec::new());
+        let current_source = self.current_source.take();
+        let mut result = Ok(());
+        for (source, chain_id, data) in held {
+            if result.is_err() {
+                self.held.push((source, chain_id, data));
+                continue;
+            }
+            self.current_source = source;
+            result = self.priv_deliver(&chain_id, data);
+        }
+        self.current_source = current_source;
+        result
+    }
+
+    /// deliver the delayed messages whose time has come, true if there were any
+    pub fn deliver_due(&mut self) -> NetResult<bool> {
+        let now = Instant::now();
+        let mut did_work = false;
+        while let Some(key) = self.delayed.keys().next().cloned() {
+            if key.0 > now {
+                break;
+            }
+            let (chain_id, data) = self.delayed.remove(&key).expect("key exists");
+            self.priv_deliver_now(&chain_id, data)?;
+            did_work = true;
+        }
+        Ok(did_work)
+    }
+
     /// register a chain's handler with the server (for message routing)
     pub fn register_chain(
         &mut self,
@@ -205,6 +319,38 @@ impl InMemoryServer {
 
     /// process a message sent by a node to the "network"
     pub fn serve(&mut self, data: Lib3hClientProtocol) -> NetResult<()> {
+        self.current_source = source_agent(&data);
+        let result = self.priv_serve(data);
+        self.current_source = None;
+        result
+    }
+}
+
+/// the agent that sent a message, which partitions apply to
+fn source_agent(data: &Lib3hClientProtocol) -> Option<AgentPubKey> {
+    match data {
+        Lib3hClientProtocol::JoinSpace(msg) | Lib3hClientProtocol::LeaveSpace(msg) => {
+            Some(msg.agent_id.clone())
+        }
+        Lib3hClientProtocol::SendDirectMessage(msg)
+        | Lib3hClientProtocol::HandleSendDirectMessageResult(msg) => {
+            Some(msg.from_agent_id.clone())
+        }
+        Lib3hClientProtocol::PublishEntry(msg) => Some(msg.provider_agent_id.clone()),
+        Lib3hClientProtocol::HandleFetchEntryResult(msg) => Some(msg.provider_agent_id.clone()),
+        Lib3hClientProtocol::QueryEntry(msg) => Some(msg.requester_agent_id.clone()),
+        Lib3hClientProtocol::HandleQueryEntryResult(msg) => Some(msg.responder_agent_id.clone()),
+        Lib3hClientProtocol::HandleGetAuthoringEntryListResult(msg)
+        | Lib3hClientProtocol::HandleGetGossipingEntryListResult(msg) => {
+            Some(msg.provider_agent_id.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Serving
+impl InMemoryServer {
+    fn priv_serve(&mut self, data: Lib3hClientProtocol) -> NetResult<()> {
         self.log
             .d(&format!(">>>> '{}' recv: {:?}", self.name.clone(), data));
         // serve only Lib3hClientProtocol
@@ -404,8 +550,7 @@ impl InMemoryServer {
         chain_id: &str,
         data: Lib3hServerProtocol,
     ) -> NetResult<()> {
-        let maybe_sender = self.senders.get_mut(chain_id);
-        if maybe_sender.is_none() {
+        if !self.senders.contains_key(chain_id) {
             self.log.e(&format!(
                 "#### ({}) error: No sender channel found for {}",
                 self.name.clone(),
@@ -417,10 +562,69 @@ impl InMemoryServer {
                 chain_id,
             ));
         }
-        let sender = maybe_sender.unwrap();
-        self.log
-            .d(&format!("<<<< '{}' send: {:?}", self.name.clone(), data));
-        sender.send(data)?;
+        self.priv_deliver(chain_id, data)
+    }
+
+    /// hand a message to the fault model, then deliver each copy it lets through,
+    /// right away or on the first `deliver_due` after its delay
+    fn priv_deliver(&mut self, chain_id: &str, data: Lib3hServerProtocol) -> NetResult<()> {
+        if !self.faults.config().is_faulty() {
+            return self.priv_deliver_now(chain_id, data);
+        }
+        let to_agent_id = undo_chain_id(&chain_id.to_string()).1;
+        if !self
+            .faults
+            .can_reach(self.current_source.as_ref(), &to_agent_id)
+        {
+            self.log.d(&format!(
+                "xxxx '{}' partitioned from {}, holding back: {:?}",
+                self.name.clone(),
+                chain_id,
+                data
+            ));
+            self.held
+                .push((self.current_source.clone(), chain_id.to_string(), data));
+            return Ok(());
+        }
+        let delays = self.faults.delays();
+        if delays.is_empty() {
+            self.log.d(&format!(
+                "xxxx '{}' dropped for {}: {:?}",
+                self.name.clone(),
+                chain_id,
+                data
+            ));
+        }
+        let now = Instant::now();
+        for delay in delays {
+            if delay.as_millis() == 0 {
+                self.priv_deliver_now(chain_id, data.clone())?;
+            } else {
+                self.delayed_count += 1;
+                self.delayed.insert(
+                    (now + delay, self.delayed_count),
+                    (chain_id.to_string(), data.clone()),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// send a message down the channel of a chain
+    fn priv_deliver_now(&mut self, chain_id: &str, data: Lib3hServerProtocol) -> NetResult<()> {
+        match self.senders.get_mut(chain_id) {
+            Some(sender) => {
+                self.log
+                    .d(&format!("<<<< '{}' send: {:?}", self.name.clone(), data));
+                sender.send(data)?;
+            }
+            // the chain left before a delayed message reached it
+            None => self.log.w(&format!(
+                "#### ({}) dropping message for departed {}",
+                self.name.clone(),
+                chain_id
+            )),
+        }
         Ok(())
     }
     /// send a message to the appropriate channel based on chain_id (dna_address::to_agent_id)
@@ -437,16 +641,21 @@ impl InMemoryServer {
 
     /// send a message to all nodes connected with this dna address
     fn priv_send_all(&mut self, dna_address: &Address, data: Lib3hServerProtocol) -> NetResult<()> {
-        if let Some(arr) = self.senders_by_dna.get_mut(dna_address) {
-            self.log.d(&format!(
-                "<<<< '{}' send all: {:?} ({})",
-                self.name.clone(),
-                data.clone(),
-                dna_address.clone()
-            ));
-            for (_k, val) in arr.iter_mut() {
-                (*val).send(data.clone())?;
-            }
+        let chain_ids: Vec<ChainId> = match self.senders_by_dna.get(dna_address) {
+            Some(arr) => arr
+                .keys()
+                .map(|agent_id| into_chain_id(dna_address, agent_id))
+                .collect(),
+            None => return Ok(()),
+        };
+        self.log.d(&format!(
+            "<<<< '{}' send all: {:?} ({})",
+            self.name.clone(),
+            data.clone(),
+            dna_address.clone()
+        ));
+        for chain_id in chain_ids {
+            self.priv_deliver(&chain_id, data.clone())?;
         }
         Ok(())
     }
@@ -623,20 +832,22 @@ impl InMemoryServer {
         }
         // #fullsync
         // Have the requester respond to itself
-        match self.senders_by_dna.entry(dna_address.to_owned().into()) {
-            Entry::Occupied(mut e) => {
-                if !e.get().is_empty() {
-                    for (k, r) in e.get_mut().iter() {
-                        if k == &msg.requester_agent_id {
-                            self.log.i(&format!("---- HandleQueryEntry {}", k));
-                            r.send(Lib3hServerProtocol::HandleQueryEntry(msg.clone()).into())?;
-                            return Ok(());
-                        }
-                    }
-                }
-            }
-            _ => unreachable!(),
-        };
+        let requester_is_registered = self
+            .senders_by_dna
+            .get(&dna_address.clone().into())
+            .map_or(false, |senders| {
+                senders.contains_key(&msg.requester_agent_id)
+            });
+        if requester_is_registered {
+            self.log
+                .i(&format!("---- HandleQueryEntry {}", msg.requester_agent_id));
+            self.priv_send_one(
+                &dna_address.into(),
+                &msg.requester_agent_id,
+                Lib3hServerProtocol::HandleQueryEntry(msg.clone()),
+            )?;
+            return Ok(());
+        }
 
         // No node found, send an empty FetchEntryResultData
         // TODO: should send a FailureResult instead?
@@ -789,3 +1000,132 @@ impl InMemoryServer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib3h_protocol::data_types::SpaceData;
+    use std::time::Duration;
+
+    static DNA_ADDRESS: &str = "QmYsFu7QGaVeUUac1E4BWST7BR38cYvzRaaTc3YS9WqsTu";
+
+    fn join(
+        server: &mut InMemoryServer,
+        agent: &str,
+    ) -> crossbeam_channel::Receiver<Lib3hServerProtocol> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let agent_id = AgentPubKey::from(agent);
+        server
+            .register_chain(&Address::from(DNA_ADDRESS), &agent_id, tx)
+            .unwrap();
+        server
+            .serve(Lib3hClientProtocol::JoinSpace(SpaceData {
+                request_id: "join".to_string(),
+                space_address: DNA_ADDRESS.to_string().into(),
+                agent_id,
+            }))
+            .unwrap();
+        rx
+    }
+
+    fn send_direct_message(server: &mut InMemoryServer, from: &str, to: &str) {
+        server
+            .serve(Lib3hClientProtocol::SendDirectMessage(DirectMessageData {
+                space_address: DNA_ADDRESS.to_string().into(),
+                request_id: "dm".to_string(),
+                to_agent_id: to.into(),
+                from_agent_id: from.into(),
+                content: b"hello".to_vec().into(),
+            }))
+            .unwrap();
+    }
+
+    fn direct_messages_received(rx: &crossbeam_channel::Receiver<Lib3hServerProtocol>) -> usize {
+        rx.try_iter()
+            .filter(|msg| match msg {
+                Lib3hServerProtocol::HandleSendDirectMessage(_) => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    fn faulty_server(faults: FaultConfig) -> InMemoryServer {
+        let mut server = InMemoryServer::new("faulty".to_string(), faults);
+        server.clock_in();
+        server
+    }
+
+    #[test]
+    fn partitions_hold_messages_back_until_healed() {
+        let mut server = faulty_server(FaultConfig::default());
+        let _alice = join(&mut server, "alice");
+        let bob = join(&mut server, "bob");
+
+        server.partition("island", vec!["alice".into()]).unwrap();
+        send_direct_message(&mut server, "alice", "bob");
+        assert_eq!(0, direct_messages_received(&bob));
+
+        // the held back message arrives once the partition heals
+        assert!(server.heal("island").unwrap());
+        assert_eq!(1, direct_messages_received(&bob));
+        send_direct_message(&mut server, "alice", "bob");
+        assert_eq!(1, direct_messages_received(&bob));
+        assert!(!server.heal("island").unwrap());
+    }
+
+    #[test]
+    fn moving_agents_between_partitions_releases_their_messages() {
+        let mut server = faulty_server(FaultConfig::default());
+        let _alice = join(&mut server, "alice");
+        let bob = join(&mut server, "bob");
+
+        server.partition("island", vec!["alice".into()]).unwrap();
+        send_direct_message(&mut server, "alice", "bob");
+        server.partition("rock", vec!["bob".into()]).unwrap();
+        assert_eq!(0, direct_messages_received(&bob));
+
+        server
+            .partition("island", vec!["alice".into(), "bob".into()])
+            .unwrap();
+        assert_eq!(1, direct_messages_received(&bob));
+    }
+
+    #[test]
+    fn delayed_messages_are_delivered_when_due() {
+        let mut server = faulty_server(FaultConfig {
+            latency: LatencyConfig::Fixed { ms: 50 },
+            ..Default::default()
+        });
+        let _alice = join(&mut server, "alice");
+        let bob = join(&mut server, "bob");
+
+        send_direct_message(&mut server, "alice", "bob");
+        server.deliver_due().unwrap();
+        assert_eq!(0, direct_messages_received(&bob));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(server.deliver_due().unwrap());
+        assert_eq!(1, direct_messages_received(&bob));
+    }
+
+    #[test]
+    fn messages_can_be_dropped_or_duplicated() {
+        let mut server = faulty_server(FaultConfig {
+            duplicate_probability: 1.0,
+            ..Default::default()
+        });
+        let _alice = join(&mut server, "alice");
+        let bob = join(&mut server, "bob");
+        send_direct_message(&mut server, "alice", "bob");
+        assert_eq!(2, direct_messages_received(&bob));
+
+        let mut server = faulty_server(FaultConfig {
+            drop_probability: 1.0,
+            ..Default::default()
+        });
+        let _alice = join(&mut server, "alice");
+        let bob = join(&mut server, "bob");
+        send_direct_message(&mut server, "alice", "bob");
+        assert_eq!(0, direct_messages_received(&bob));
+    }
+}
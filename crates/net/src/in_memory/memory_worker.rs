@@ -1,6 +1,6 @@
 //! provides fake in-memory p2p worker for use in scenario testing
 
-use super::memory_server::*;
+use super::{fault_model::FaultConfig, memory_server::*};
 use crate::{
     connection::{
         net_connection::{NetHandler, NetWorker},
@@ -12,6 +12,7 @@ use holochain_json_api::json::JsonString;
 use holochain_locksmith::Mutex;
 use holochain_persistence_api::{cas::content::Address, hash::HashString};
 use lib3h_protocol::{protocol_client::Lib3hClientProtocol, protocol_server::Lib3hServerProtocol};
+use log::*;
 use std::collections::{hash_map::Entry, HashMap};
 
 /// a p2p worker for mocking in-memory scenario tests
@@ -87,8 +88,17 @@ impl NetWorker for InMemoryWorker {
                 .wrap(Lib3hServerProtocol::P2pReady)
                 .into()))?;
         }
+        // deliver the messages our InMemoryServer delayed
+        let mut did_something = {
+            let server_map = MEMORY_SERVER_MAP.read().unwrap();
+            let mut server = server_map
+                .get(&self.server_name)
+                .expect("InMemoryServer should exist")
+                .lock()
+                .unwrap();
+            server.deliver_due()?
+        };
         // check for messages from our InMemoryServer
-        let mut did_something = false;
         for (_, receiver) in self.receiver_per_dna.iter_mut() {
             if let Ok(data) = receiver.try_recv() {
                 did_something = true;
@@ -125,12 +135,16 @@ impl InMemoryWorker {
             .as_str()
             .unwrap_or("(unnamed)")
             .to_string();
+        let faults: FaultConfig = match config.get("faults") {
+            Some(faults) => serde_json::from_value(faults.clone())?,
+            None => FaultConfig::default(),
+        };
         // Create server with that name if it doesn't already exist
         let mut server_map = MEMORY_SERVER_MAP.write().unwrap();
         if !server_map.contains_key(&server_name) {
             server_map.insert(
                 server_name.clone(),
-                Mutex::new(InMemoryServer::new(server_name.clone())),
+                Mutex::new(InMemoryServer::new(server_name.clone(), faults.clone())),
             );
         }
         let mut server = server_map
@@ -138,6 +152,12 @@ impl InMemoryWorker {
             .expect("InMemoryServer should exist")
             .lock()
             .unwrap();
+        if config.get("faults").is_some() && *server.fault_config() != faults {
+            warn!(
+                "InMemoryServer '{}' already exists, ignoring the faults configured for this worker",
+                server_name
+            );
+        }
         server.clock_in();
 
         Ok(InMemoryWorker {
@@ -1,5 +1,6 @@
 //! Module for the in-memory network module
 
+pub mod fault_model;
 pub mod memory_book;
 pub mod memory_server;
 pub mod memory_worker;
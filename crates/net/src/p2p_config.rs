@@ -1,4 +1,4 @@
-use crate::sim2h_worker::Sim2hConfig;
+use crate::{in_memory::fault_model::FaultConfig, sim2h_worker::Sim2hConfig};
 use holochain_json_api::{error::JsonError, json::JsonString};
 use lib3h::engine::{EngineConfig, GatewayId, TransportConfig};
 use lib3h_protocol::uri::Lib3hUri;
@@ -121,6 +121,19 @@ impl P2pConfig {
         )
     }
 
+    /// a memory backend whose messages suffer the given faults, the first
+    /// worker to create the named server decides its faults
+    pub fn new_with_faulty_memory_backend(server_name: &str, faults: FaultConfig) -> Self {
+        P2pConfig::new(
+            P2pBackendKind::LegacyInMemory,
+            BackendConfig::Json(json!({
+                "serverName": server_name,
+                "faults": faults,
+            })),
+            None,
+        )
+    }
+
     pub fn new_with_sim2h_backend(sim2h_url: &str) -> Self {
         P2pConfig::new(
             P2pBackendKind::SIM2H,
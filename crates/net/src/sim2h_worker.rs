@@ -26,10 +26,12 @@ use lib3h_protocol::{
 };
 use log::*;
 use sim2h::{
+    clock::Instant,
     crypto::{Provenance, SignedWireMessage},
-    generate_ack_receipt_hash, TcpWss, WireError, WireMessage, WIRE_VERSION,
+    generate_ack_receipt_hash, DirectMessageQueuedData, Sim2hWss, WireError, WireMessage,
+    WIRE_VERSION,
 };
-use std::{convert::TryFrom, time::Instant};
+use std::convert::TryFrom;
 
 use url::Url;
 use url2::prelude::*;
@@ -39,13 +41,29 @@ const MAX_CONNECTION_TIMEOUT_MS: u64 = 60000;
 const SIM2H_WORKER_INTERNAL_REQUEST_ID: &str = "SIM2H_WORKER";
 const RESEND_WIRE_MESSAGE_MS: u64 = 10000;
 const BATCHING_INTERVAL_MS: u64 = 1000;
+/// reconnects to a sim2h node we were redirected to before going back to the configured one
+const MAX_REDIRECT_CONNECTION_ATTEMPTS: u32 = 3;
 
-fn connect(url: Lib3hUri, timeout_ms: u64) -> NetResult<TcpWss> {
-    //    let config = WssConnectConfig::new(TlsConnectConfig::new(TcpConnectConfig::default()));
-    let config = WssConnectConfig::new(TcpConnectConfig {
-        connect_timeout_ms: Some(timeout_ms),
-    });
-    Ok(InStreamWss::connect(&url::Url::from(url).into(), config)?)
+fn connect(url: Lib3hUri, timeout_ms: u64) -> NetResult<Sim2hWss> {
+    Ok(Sim2hWss::connect(url, timeout_ms)?)
+}
+
+/// Lib3hServerProtocol has no message for a direct message that sim2h queued for an
+/// offline agent, so we hand it to core as a SuccessResult carrying the queued data.
+pub fn direct_message_queued_result(data: DirectMessageQueuedData) -> GenericResultData {
+    GenericResultData {
+        request_id: data.request_id.clone(),
+        space_address: data.space_address.clone(),
+        to_agent_id: data.from_agent_id.clone(),
+        result_info: serde_json::to_string(&data)
+            .expect("DirectMessageQueuedData should serialize")
+            .into(),
+    }
+}
+
+/// The queued direct message a SuccessResult is about, if any.
+pub fn as_direct_message_queued(result: &GenericResultData) -> Option<DirectMessageQueuedData> {
+    serde_json::from_slice(result.result_info.as_bytes()).ok()
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, DefaultJson, PartialEq)]
@@ -74,10 +92,12 @@ impl From<WireMessage> for BufferedMessage {
 #[allow(non_snake_case, dead_code)]
 pub struct Sim2hWorker {
     handler: NetHandler,
-    connection: Option<TcpWss>,
+    connection: Option<Sim2hWss>,
     inbox: Vec<ht::EncodedSpanWrap<Lib3hClientProtocol>>,
     to_core: Vec<ht::EncodedSpanWrap<Lib3hServerProtocol>>,
     server_url: Lib3hUri,
+    configured_server_url: Lib3hUri,
+    failed_redirect_attempts: u32,
     space_data: Option<SpaceData>,
     agent_id: Address,
     conductor_api: ConductorApi,
@@ -112,12 +132,15 @@ impl Sim2hWorker {
         tracer: Option<ht::Tracer>,
     ) -> NetResult<Self> {
         let reconnect_interval = Duration::from_millis(INITIAL_CONNECTION_TIMEOUT_MS);
+        let server_url: Lib3hUri = url::Url::from(url2!("{}", config.sim2h_url)).into();
         let mut instance = Self {
             handler,
             connection: None,
             inbox: Vec::new(),
             to_core: Vec::new(),
-            server_url: url::Url::from(url2!("{}", config.sim2h_url)).into(),
+            server_url: server_url.clone(),
+            configured_server_url: server_url,
+            failed_redirect_attempts: 0,
             space_data: None,
             agent_id,
             conductor_api,
@@ -176,6 +199,7 @@ impl Sim2hWorker {
     fn check_reconnect(&mut self) {
         if self.connection_ready() {
             self.reset_backoff();
+            self.failed_redirect_attempts = 0;
             return;
         }
 
@@ -192,6 +216,21 @@ impl Sim2hWorker {
 
         self.backoff();
 
+        if self.server_url != self.configured_server_url {
+            // the node we were redirected to may have left the federation,
+            // in which case the configured node redirects us to the new owner
+            self.failed_redirect_attempts += 1;
+            if self.failed_redirect_attempts > MAX_REDIRECT_CONNECTION_ATTEMPTS {
+                warn!(
+                    "Could not connect to redirected sim2h server {}, going back to {}",
+                    self.server_url, self.configured_server_url
+                );
+                self.server_url = self.configured_server_url.clone();
+                self.failed_redirect_attempts = 0;
+                self.reset_backoff();
+            }
+        }
+
         self.time_of_last_connection_attempt = Instant::now();
         self.connection = None;
         if let Ok(connection) = connect(self.server_url.clone(), self.connection_timeout_backoff) {
@@ -642,8 +681,14 @@ impl Sim2hWorker {
                         error!("Uh oh, we got a MessageWhileInLimbo errro and we don't have space data. Did core send a message before sending a join? This should not happen.");
                     }
                 }
+                WireError::RateLimited => warn!("Sim2h server is rate limiting us, unacknowledged messages will be resent"),
+                WireError::JoinRefused(reason) => error!("Sim2h server refused to let us join the space: {}", reason),
                 WireError::Other(e) => error!("Got error from Sim2h server: {:?}", e),
             },
+            WireMessage::DirectMessageQueued(data) => {
+                debug!("Sim2h server queued our direct message {} for {}", data.request_id, data.to_agent_id);
+                self.to_core.push(span.wrap(Lib3hServerProtocol::SuccessResult(direct_message_queued_result(data))).into());
+            }
             WireMessage::Status => error!("Got a Status from the Sim2h server, weird! Ignoring"),
             WireMessage::Debug => error!("Got a Debug from the Sim2h server, weird! Ignoring"),
             WireMessage::DebugResponse(_) => error!("Got a DebugResponse from the Sim2h server, weird! Ignoring"),
@@ -653,7 +698,10 @@ impl Sim2hWorker {
                     panic!("holochain SIM2H WIRE_VERSION ({}) does not match SIM2H server WIRE_VERSION ({}) - cannot continue", WIRE_VERSION, response.version);
                 }
                 debug!("HelloResponse {:?}", response);
-                self.set_full_sync(response.redundant_count == 0);
+                match response.redirect {
+                    Some(redirect) => self.redirect(redirect),
+                    None => self.set_full_sync(response.redundant_count == 0),
+                }
             }
             WireMessage::StatusResponse(_) => error!("Got a StatusResponse from the Sim2h server, weird! Ignoring (I use Hello not Status)"),
             WireMessage::Ack(hash) => {
@@ -699,12 +747,28 @@ impl Sim2hWorker {
         }
     }
 
+    /// the sim2h server we joined through does not serve our space,
+    /// reconnect to the one that does and join it there
+    fn redirect(&mut self, server_url: Lib3hUri) {
+        info!(
+            "Sim2h server {} redirected us to {}",
+            self.server_url, server_url
+        );
+        self.server_url = server_url;
+        self.failed_redirect_attempts = 0;
+        self.connection = None;
+        self.reset_backoff();
+        self.time_of_last_connection_attempt = Instant::now()
+            .checked_sub(self.reconnect_interval * 2)
+            .unwrap();
+    }
+
     /// test function for proving out reconnects
     /// note this cannot be cfg(test) because we want to invoke it
     /// from integration testing
     pub fn test_close_connection_cause_reconnect(&mut self) {
         self.connection = None;
-        self.time_of_last_connection_attempt = std::time::Instant::now()
+        self.time_of_last_connection_attempt = Instant::now()
             .checked_sub(self.reconnect_interval * 2)
             .unwrap();
     }
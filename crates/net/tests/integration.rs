@@ -15,7 +15,10 @@ use lib3h_protocol::{
     uri::Lib3hUri,
 };
 use lib3h_sodium::SodiumCryptoSystem;
-use sim2h::{run_sim2h, DhtAlgorithm};
+use sim2h::{
+    federation::{node_index_for_space, FederationConfig},
+    run_sim2h_with_options, DhtAlgorithm, Sim2hOptions,
+};
 use std::sync::Arc;
 
 struct Server {
@@ -33,6 +36,10 @@ impl Drop for Server {
 
 impl Server {
     pub fn new(url: &str) -> Self {
+        Self::with_options(url, Sim2hOptions::default())
+    }
+
+    pub fn with_options(url: &str, options: Sim2hOptions) -> Self {
         let url = url2!("{}", url);
 
         let (snd, rcv) = crossbeam_channel::unbounded();
@@ -41,14 +48,16 @@ impl Server {
 
         let srv_cont = cont.clone();
         let thread = Some(std::thread::spawn(move || {
-            let (mut rt, binding) = run_sim2h(
+            let (mut rt, binding) = run_sim2h_with_options(
                 Box::new(SodiumCryptoSystem::new()),
                 Lib3hUri(url.into()),
                 DhtAlgorithm::FullSync,
+                options,
             );
             rt.block_on(async move {
                 tokio::task::spawn(async move {
-                    snd.send(binding.await.unwrap()).unwrap();
+                    snd.send(binding.await.unwrap().expect("could not start sim2h"))
+                        .unwrap();
                 });
 
                 while *srv_cont.lock().unwrap() {
@@ -71,10 +80,8 @@ impl Server {
     }
 }
 
-#[test]
-fn sim2h_worker_talks_to_sim2h() {
-    let _ = env_logger::builder().is_test(true).try_init();
-
+/// a new agent id, and a conductor api that signs for it
+fn test_agent() -> (String, ConductorApi) {
     let crypto = Box::new(SodiumCryptoSystem::new());
 
     let mut pub_key = crypto.buf_new_insecure(crypto.sign_public_key_bytes());
@@ -84,12 +91,6 @@ fn sim2h_worker_talks_to_sim2h() {
     let enc = hcid::HcidEncoding::with_kind("hcs0").unwrap();
     let agent_id = enc.encode(&*pub_key).unwrap();
 
-    let srv = Server::new("ws://127.0.0.1:0");
-    let bound_uri = srv.bound_uri().clone();
-    println!("GOT BOUND: {:?}", bound_uri);
-
-    // -- beg sim2h worker test -- //
-
     let io = Arc::new(RwLock::new(IoHandler::new()));
 
     let sec_key = Arc::new(Mutex::new(sec_key.box_clone()));
@@ -116,6 +117,21 @@ fn sim2h_worker_talks_to_sim2h() {
         },
     );
 
+    (agent_id, ConductorApi::new(io))
+}
+
+#[test]
+fn sim2h_worker_talks_to_sim2h() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let (agent_id, conductor_api) = test_agent();
+
+    let srv = Server::new("ws://127.0.0.1:0");
+    let bound_uri = srv.bound_uri().clone();
+    println!("GOT BOUND: {:?}", bound_uri);
+
+    // -- beg sim2h worker test -- //
+
     struct ResultData {
         pub got_handle_store: bool,
         pub got_handle_dm: bool,
@@ -179,7 +195,7 @@ fn sim2h_worker_talks_to_sim2h() {
             sim2h_url: srv.bound_uri().as_str().to_string(),
         },
         agent_id.clone().into(),
-        ConductorApi::new(io.clone()),
+        conductor_api,
         None,
     )
     .unwrap();
@@ -249,3 +265,83 @@ fn sim2h_worker_talks_to_sim2h() {
 
     assert!(ResultData::is_ok(&result_data));
 }
+
+#[test]
+fn sim2h_worker_is_redirected_to_the_federated_node_serving_its_space() {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let (agent_id, conductor_api) = test_agent();
+
+    // two sim2h nodes in this process, talking over in-memory websockets
+    let names: Vec<String> = (0..2)
+        .map(|i| format!("sim2h-federation-{}-{}", i, std::process::id()))
+        .collect();
+    let nodes: Vec<Lib3hUri> = names
+        .iter()
+        .map(|name| Lib3hUri(url2!("mem://{}:4242", name).into()))
+        .collect();
+    let servers: Vec<Server> = names
+        .iter()
+        .enumerate()
+        .map(|(this_node, name)| {
+            Server::with_options(
+                &format!("mem://{}", name),
+                Sim2hOptions {
+                    federation: Some(FederationConfig {
+                        nodes: nodes.clone(),
+                        this_node,
+                    }),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+    assert_eq!(nodes[0], *servers[0].bound_uri());
+
+    // connect to the node that does not serve our space
+    let space_address: String = "BLA".to_string();
+    let owner = node_index_for_space(&space_address.clone().into(), nodes.len());
+    let other = (owner + 1) % nodes.len();
+
+    let got_handle_g_list = Arc::new(Mutex::new(false));
+    let got_handle_g_list_worker = got_handle_g_list.clone();
+    let mut worker = Sim2hWorker::new(
+        NetHandler::new(Box::new(move |msg| {
+            if let Lib3hServerProtocol::HandleGetGossipingEntryList(_) = msg.unwrap().data {
+                *got_handle_g_list_worker.lock().unwrap() = true;
+            }
+            Ok(())
+        })),
+        Sim2hConfig {
+            sim2h_url: servers[other].bound_uri().as_str().to_string(),
+        },
+        agent_id.clone().into(),
+        conductor_api,
+        None,
+    )
+    .unwrap();
+
+    worker
+        .receive(ht::test_wrap_enc(Lib3hClientProtocol::JoinSpace(
+            SpaceData {
+                agent_id: agent_id.clone().into(),
+                request_id: "".to_string(),
+                space_address: space_address.into(),
+            },
+        )))
+        .unwrap();
+
+    for _ in 0..80 {
+        std::thread::sleep(std::time::Duration::from_millis(25));
+        println!("tick: {:?}", worker.tick());
+        if *got_handle_g_list.lock().unwrap() {
+            break;
+        }
+    }
+
+    assert!(*got_handle_g_list.lock().unwrap());
+    assert_eq!(
+        Some(url::Url::from(nodes[owner].clone())),
+        worker.p2p_endpoint()
+    );
+}
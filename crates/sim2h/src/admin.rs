@@ -0,0 +1,449 @@
+//! an optional http api for inspecting a live sim2h and acting on it without
+//! a wire protocol client. Every endpoint answers json:
+//!
+//! - `GET /status` the `StatusData` of a `StatusResponse`
+//! - `GET /spaces` every space with its agent, entry and aspect counts
+//! - `GET /spaces/<space>/agents` the agents joined to a space, their connection
+//!   age and how many of the aspects they should hold they are known to hold
+//! - `GET /connections` every open connection, its age and who joined on it
+//! - `POST /agents/<agent>/disconnect` closes the connections of an agent
+//! - `POST /spaces/<space>/drop` closes every connection joined to a space,
+//!   which drops the space and everything sim2h knew about it
+//!
+//! both actions only kick: sim2h workers reconnect straight away and join
+//! again, rebuilding a dropped space from what they hold. To keep an agent or
+//! a space out, deny it in the admission policy (`--admission-policy-file`),
+//! which is re-read on SIGHUP, and then disconnect it here.
+//!
+//! there is no authentication, so the api must only be bound to an address
+//! that operators alone can reach.
+
+use crate::{error::*, sim2h_im_state::Store, Sim2hHandle};
+use lib3h_protocol::{
+    types::{AgentPubKey, SpaceHash},
+    uri::Lib3hUri,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::*;
+
+/// a space and how much sim2h knows about it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpaceInfo {
+    pub space: String,
+    pub agents: usize,
+    pub entries: usize,
+    pub aspects: usize,
+}
+
+/// an agent joined to a space
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentInfo {
+    pub agent: String,
+    pub uri: String,
+    /// how long the agent's connection has been open, `None` if it just closed
+    pub connected_secs: Option<u64>,
+    /// the aspects the agent is known to hold, of those it should hold
+    pub held_aspects: usize,
+    /// the aspects the agent should hold with the current dht algorithm
+    pub should_hold_aspects: usize,
+    /// `held_aspects / should_hold_aspects`, 1 if it should hold nothing
+    pub coverage: f64,
+}
+
+/// an open connection, and who joined which space on it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionInfo {
+    pub uri: String,
+    pub connected_secs: u64,
+    pub agent: Option<String>,
+    pub space: Option<String>,
+}
+
+/// the connections an admin action closed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisconnectedInfo {
+    pub disconnected: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum AdminRequest {
+    Status,
+    Spaces,
+    SpaceAgents(SpaceHash),
+    Connections,
+    DisconnectAgent(AgentPubKey),
+    DropSpace(SpaceHash),
+}
+
+impl AdminRequest {
+    fn parse(method: &str, path: &str) -> Option<Self> {
+        let path = path.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (method, segments.as_slice()) {
+            ("GET", ["status"]) => Some(AdminRequest::Status),
+            ("GET", ["spaces"]) => Some(AdminRequest::Spaces),
+            ("GET", ["spaces", space, "agents"]) => {
+                Some(AdminRequest::SpaceAgents((*space).into()))
+            }
+            ("GET", ["connections"]) => Some(AdminRequest::Connections),
+            ("POST", ["agents", agent, "disconnect"]) => {
+                Some(AdminRequest::DisconnectAgent((*agent).into()))
+            }
+            ("POST", ["spaces", space, "drop"]) => Some(AdminRequest::DropSpace((*space).into())),
+            _ => None,
+        }
+    }
+}
+
+fn spaces(state: &Store) -> Vec<SpaceInfo> {
+    let mut spaces: Vec<SpaceInfo> = state
+        .spaces
+        .iter()
+        .map(|(space_hash, space)| SpaceInfo {
+            space: space_hash.to_string(),
+            agents: space.connections.len(),
+            entries: space.entry_to_all_aspects.len(),
+            aspects: space.all_aspects.len(),
+        })
+        .collect();
+    spaces.sort_by(|a, b| a.space.cmp(&b.space));
+    spaces
+}
+
+fn space_agents(
+    state: &Store,
+    space_hash: &SpaceHash,
+    ages: &HashMap<Lib3hUri, Duration>,
+) -> Option<Vec<AgentInfo>> {
+    let space = state.spaces.get(space_hash)?;
+    let mut agents: Vec<AgentInfo> = space
+        .uri_to_connection
+        .iter()
+        .map(|(uri, agent_id)| {
+            let (held_aspects, should_hold_aspects) = state
+                .agent_holding_counts(space_hash, agent_id)
+                .unwrap_or((0, 0));
+            AgentInfo {
+                agent: agent_id.to_string(),
+                uri: uri.to_string(),
+                connected_secs: ages.get(&**uri).map(Duration::as_secs),
+                held_aspects,
+                should_hold_aspects,
+                coverage: if should_hold_aspects == 0 {
+                    1.0
+                } else {
+                    held_aspects as f64 / should_hold_aspects as f64
+                },
+            }
+        })
+        .collect();
+    agents.sort_by(|a, b| a.agent.cmp(&b.agent));
+    Some(agents)
+}
+
+fn connections(state: &Store, ages: Vec<(Lib3hUri, Duration)>) -> Vec<ConnectionInfo> {
+    let mut connections: Vec<ConnectionInfo> = ages
+        .into_iter()
+        .map(|(uri, age)| {
+            let joined = state.get_space_info_from_uri(&uri);
+            ConnectionInfo {
+                uri: uri.to_string(),
+                connected_secs: age.as_secs(),
+                agent: joined.as_ref().map(|(agent_id, _)| agent_id.to_string()),
+                space: joined
+                    .as_ref()
+                    .map(|(_, space_hash)| space_hash.to_string()),
+            }
+        })
+        .collect();
+    connections.sort_by(|a, b| b.connected_secs.cmp(&a.connected_secs));
+    connections
+}
+
+/// the connections `agent` joined any space on
+fn agent_uris(state: &Store, agent: &AgentPubKey) -> Vec<Lib3hUri> {
+    let mut uris: Vec<Lib3hUri> = Vec::new();
+    for space_hash in state.spaces.keys() {
+        if let Some(uri) = state.lookup_joined(space_hash, agent) {
+            if !uris.contains(uri) {
+                uris.push(uri.clone());
+            }
+        }
+    }
+    uris
+}
+
+/// the connections joined to a space, `None` if there is no such space
+fn space_uris(state: &Store, space_hash: &SpaceHash) -> Option<Vec<Lib3hUri>> {
+    let space = state.spaces.get(space_hash)?;
+    Some(
+        space
+            .uri_to_connection
+            .keys()
+            .map(|uri| (**uri).clone())
+            .collect(),
+    )
+}
+
+fn json<T: Serialize>(status: &'static str, body: &T) -> (&'static str, String) {
+    (
+        status,
+        serde_json::to_string(body).expect("admin responses must be serializable"),
+    )
+}
+
+fn not_found(error: String) -> (&'static str, String) {
+    let mut body = HashMap::new();
+    body.insert("error", error);
+    json("404 Not Found", &body)
+}
+
+fn disconnect(sim2h_handle: &Sim2hHandle, uris: Vec<Lib3hUri>) -> (&'static str, String) {
+    let disconnected = DisconnectedInfo {
+        disconnected: uris.iter().map(|uri| uri.to_string()).collect(),
+    };
+    sim2h_handle.disconnect(uris);
+    json("200 OK", &disconnected)
+}
+
+async fn answer(sim2h_handle: &Sim2hHandle, request: AdminRequest) -> (&'static str, String) {
+    let state = sim2h_handle.state().get_clone().await;
+    match request {
+        AdminRequest::Status => json("200 OK", &sim2h_handle.status().await),
+        AdminRequest::Spaces => json("200 OK", &spaces(&state)),
+        AdminRequest::SpaceAgents(space_hash) => {
+            let ages = sim2h_handle
+                .connection_mgr()
+                .list_connection_ages()
+                .await
+                .into_iter()
+                .collect();
+            match space_agents(&state, &space_hash, &ages) {
+                Some(agents) => json("200 OK", &agents),
+                None => not_found(format!("no space {}", space_hash)),
+            }
+        }
+        AdminRequest::Connections => {
+            let ages = sim2h_handle.connection_mgr().list_connection_ages().await;
+            json("200 OK", &connections(&state, ages))
+        }
+        AdminRequest::DisconnectAgent(agent) => {
+            let uris = agent_uris(&state, &agent);
+            if uris.is_empty() {
+                return not_found(format!("agent {} is not joined to any space", agent));
+            }
+            warn!("admin api disconnecting agent {}", agent);
+            disconnect(sim2h_handle, uris)
+        }
+        AdminRequest::DropSpace(space_hash) => match space_uris(&state, &space_hash) {
+            Some(uris) => {
+                warn!("admin api dropping space {}", space_hash);
+                disconnect(sim2h_handle, uris)
+            }
+            None => not_found(format!("no space {}", space_hash)),
+        },
+    }
+}
+
+async fn respond(stream: tokio::net::TcpStream, sim2h_handle: Sim2hHandle) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // drain the request headers, none of the requests have a body
+    let mut header = String::new();
+    while reader.read_line(&mut header).await? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let (status, body) = match AdminRequest::parse(method, path) {
+        Some(request) => answer(&sim2h_handle, request).await,
+        None => not_found(format!("no endpoint {} {}", method, path)),
+    };
+
+    let mut stream = reader.into_inner();
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.flush().await
+}
+
+/// binds `bind_address` and serves the admin api there on the current tokio runtime
+pub(crate) fn spawn_admin_api(bind_address: &str, sim2h_handle: Sim2hHandle) -> Sim2hResult<()> {
+    let listener = std::net::TcpListener::bind(bind_address)?;
+    listener.set_nonblocking(true)?;
+    let mut listener = tokio::net::TcpListener::from_std(listener)?;
+    info!("serving sim2h admin api at http://{}", bind_address);
+    tokio::task::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let sim2h_handle = sim2h_handle.clone();
+                    tokio::task::spawn(async move {
+                        if let Err(e) = respond(stream, sim2h_handle).await {
+                            debug!("error answering admin api request: {:?}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("error accepting admin api connection: {:?}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib3h_crypto_api::CryptoSystem;
+    use lib3h_protocol::types::{AspectHash, EntryHash};
+
+    fn gen_agent() -> AgentPubKey {
+        let crypto = lib3h_sodium::SodiumCryptoSystem::new();
+        let enc = hcid::HcidEncoding::with_kind("hcs0").unwrap();
+        let mut pub_key = crypto.buf_new_insecure(crypto.sign_public_key_bytes());
+        let mut sec_key = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+        crypto.sign_keypair(&mut pub_key, &mut sec_key).unwrap();
+        enc.encode(&*pub_key).unwrap().into()
+    }
+
+    #[test]
+    fn can_parse_requests() {
+        assert_eq!(
+            Some(AdminRequest::Status),
+            AdminRequest::parse("GET", "/status")
+        );
+        assert_eq!(
+            Some(AdminRequest::SpaceAgents("QmSpace".into())),
+            AdminRequest::parse("GET", "/spaces/QmSpace/agents/?pretty")
+        );
+        assert_eq!(
+            Some(AdminRequest::DisconnectAgent("HcSAgent".into())),
+            AdminRequest::parse("POST", "/agents/HcSAgent/disconnect")
+        );
+        assert_eq!(
+            Some(AdminRequest::DropSpace("QmSpace".into())),
+            AdminRequest::parse("POST", "/spaces/QmSpace/drop")
+        );
+        // actions need a POST
+        assert_eq!(None, AdminRequest::parse("GET", "/spaces/QmSpace/drop"));
+        assert_eq!(None, AdminRequest::parse("GET", "/nothing"));
+    }
+
+    #[test]
+    fn can_report_spaces_agents_and_connections() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let alice = gen_agent();
+            let bob = gen_agent();
+            let space_hash: SpaceHash = "QmSpace".into();
+            let alice_uri: Lib3hUri = url::Url::parse("ws://alice").unwrap().into();
+            let bob_uri: Lib3hUri = url::Url::parse("ws://bob").unwrap().into();
+            let stranger_uri: Lib3hUri = url::Url::parse("ws://stranger").unwrap().into();
+
+            let store = Store::new(
+                Box::new(lib3h_sodium::SodiumCryptoSystem::new()),
+                0, /* full sync */
+                None,
+            );
+            store
+                .new_connection(space_hash.clone(), alice.clone(), alice_uri.clone())
+                .await;
+            store
+                .new_connection(space_hash.clone(), bob.clone(), bob_uri.clone())
+                .await;
+            let entry_hash: EntryHash = "entry".into();
+            let aspects: im::HashSet<AspectHash> =
+                vec!["a1".into(), "a2".into()].into_iter().collect();
+            store
+                .agent_holds_aspects(
+                    space_hash.clone(),
+                    alice.clone(),
+                    entry_hash.clone(),
+                    aspects,
+                )
+                .await;
+            store
+                .agent_holds_aspects(
+                    space_hash.clone(),
+                    bob.clone(),
+                    entry_hash,
+                    vec!["a1".into()].into_iter().collect(),
+                )
+                .await;
+            let state = store.get_clone().await;
+
+            assert_eq!(
+                vec![SpaceInfo {
+                    space: "QmSpace".to_string(),
+                    agents: 2,
+                    entries: 1,
+                    aspects: 2,
+                }],
+                spaces(&state)
+            );
+
+            let ages: HashMap<Lib3hUri, Duration> =
+                vec![(alice_uri.clone(), Duration::from_secs(30))]
+                    .into_iter()
+                    .collect();
+            let agents = space_agents(&state, &space_hash, &ages).unwrap();
+            let alice_info = agents
+                .iter()
+                .find(|info| info.agent == alice.to_string())
+                .unwrap();
+            assert_eq!(Some(30), alice_info.connected_secs);
+            assert_eq!(
+                (2, 2),
+                (alice_info.held_aspects, alice_info.should_hold_aspects)
+            );
+            let bob_info = agents
+                .iter()
+                .find(|info| info.agent == bob.to_string())
+                .unwrap();
+            assert_eq!(None, bob_info.connected_secs);
+            assert_eq!(0.5, bob_info.coverage);
+            assert!(space_agents(&state, &"QmOther".into(), &ages).is_none());
+
+            let connections = connections(
+                &state,
+                vec![
+                    (stranger_uri.clone(), Duration::from_secs(5)),
+                    (alice_uri.clone(), Duration::from_secs(30)),
+                ],
+            );
+            assert_eq!(
+                vec![
+                    ConnectionInfo {
+                        uri: alice_uri.to_string(),
+                        connected_secs: 30,
+                        agent: Some(alice.to_string()),
+                        space: Some("QmSpace".to_string()),
+                    },
+                    ConnectionInfo {
+                        uri: stranger_uri.to_string(),
+                        connected_secs: 5,
+                        agent: None,
+                        space: None,
+                    },
+                ],
+                connections
+            );
+
+            assert_eq!(vec![bob_uri], agent_uris(&state, &bob));
+            assert_eq!(2, space_uris(&state, &space_hash).unwrap().len());
+        });
+    }
+}
@@ -0,0 +1,261 @@
+//! admission control for joining spaces
+//! a policy file can restrict which spaces (DNA hashes) can be joined,
+//! which agents can join them, and how many agents a space can hold
+
+use crate::{connection_state::AgentId, error::*, MutexExt};
+use holochain_locksmith::Mutex;
+use lib3h_protocol::types::SpaceHash;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// the contents of an admission policy file, e.g.
+///
+/// ```toml
+/// allowed_spaces = ["QmSpaceHash"]
+/// denied_agents = ["HcSAgentId"]
+/// max_agents_per_space = 100
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AdmissionPolicy {
+    /// spaces that can be joined, any space if `None`
+    #[serde(default)]
+    pub allowed_spaces: Option<BTreeSet<String>>,
+    /// agents that can join, any agent if `None`
+    #[serde(default)]
+    pub allowed_agents: Option<BTreeSet<String>>,
+    /// agents that can never join
+    #[serde(default)]
+    pub denied_agents: BTreeSet<String>,
+    #[serde(default)]
+    pub max_agents_per_space: Option<usize>,
+}
+
+impl AdmissionPolicy {
+    pub fn from_toml(toml: &str) -> Sim2hResult<Self> {
+        toml::from_str(toml).map_err(|e| format!("invalid admission policy: {}", e).into())
+    }
+
+    /// checks that `agent` can join `space`, given how many agents are in
+    /// that space already and whether `agent` is one of them
+    pub fn check(
+        &self,
+        space: &SpaceHash,
+        agent: &AgentId,
+        agents_in_space: usize,
+        already_joined: bool,
+    ) -> Result<(), String> {
+        let space = space.to_string();
+        let agent = agent.to_string();
+        if let Some(allowed_spaces) = &self.allowed_spaces {
+            if !allowed_spaces.contains(&space) {
+                return Err(format!("space {} is not allowed", space));
+            }
+        }
+        if self.denied_agents.contains(&agent) {
+            return Err(format!("agent {} is denied", agent));
+        }
+        if let Some(allowed_agents) = &self.allowed_agents {
+            if !allowed_agents.contains(&agent) {
+                return Err(format!("agent {} is not allowed", agent));
+            }
+        }
+        match self.max_agents_per_space {
+            Some(max) if !already_joined && agents_in_space >= max => {
+                Err(format!("space {} is full ({} agents)", space, max))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// admission control state reported in `StatusData`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AdmissionStatus {
+    pub policy_file: String,
+    /// number of allowed spaces, if restricted
+    pub allowed_spaces: Option<usize>,
+    /// number of allowed agents, if restricted
+    pub allowed_agents: Option<usize>,
+    pub denied_agents: usize,
+    pub max_agents_per_space: Option<usize>,
+    pub rejected_joins: u64,
+    pub reloads: u64,
+}
+
+/// the admission policy of a running sim2h, reloadable from its file
+pub struct Admission {
+    policy_file: Option<PathBuf>,
+    policy: Mutex<AdmissionPolicy>,
+    rejected_joins: AtomicU64,
+    reloads: AtomicU64,
+}
+
+impl Admission {
+    /// admits anyone
+    pub fn open() -> Self {
+        Self {
+            policy_file: None,
+            policy: Mutex::new(AdmissionPolicy::default()),
+            rejected_joins: AtomicU64::new(0),
+            reloads: AtomicU64::new(0),
+        }
+    }
+
+    pub fn from_file(policy_file: PathBuf) -> Sim2hResult<Self> {
+        let policy = AdmissionPolicy::from_toml(&std::fs::read_to_string(&policy_file)?)?;
+        Ok(Self {
+            policy_file: Some(policy_file),
+            policy: Mutex::new(policy),
+            rejected_joins: AtomicU64::new(0),
+            reloads: AtomicU64::new(0),
+        })
+    }
+
+    /// true if joins are checked against a policy file
+    pub fn is_enabled(&self) -> bool {
+        self.policy_file.is_some()
+    }
+
+    /// re-reads the policy file, keeping the current policy if it is invalid
+    pub fn reload(&self) -> Sim2hResult<()> {
+        if let Some(policy_file) = &self.policy_file {
+            let policy = AdmissionPolicy::from_toml(&std::fs::read_to_string(policy_file)?)?;
+            *self.policy.f_lock() = policy;
+            self.reloads.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// checks a `JoinSpace` signed by `signer`, counting refusals
+    pub fn check_join(
+        &self,
+        space: &SpaceHash,
+        agent: &AgentId,
+        signer: &AgentId,
+        agents_in_space: usize,
+        already_joined: bool,
+    ) -> Result<(), String> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let result = if agent != signer {
+            Err(format!(
+                "{}: {} joining as {}",
+                SIGNER_MISMATCH_ERR_STR, signer, agent
+            ))
+        } else {
+            self.policy
+                .f_lock()
+                .check(space, agent, agents_in_space, already_joined)
+        };
+        if result.is_err() {
+            self.rejected_joins.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    pub fn status(&self) -> Option<AdmissionStatus> {
+        let policy_file = self.policy_file.as_ref()?;
+        let policy = self.policy.f_lock();
+        Some(AdmissionStatus {
+            policy_file: policy_file.to_string_lossy().to_string(),
+            allowed_spaces: policy.allowed_spaces.as_ref().map(BTreeSet::len),
+            allowed_agents: policy.allowed_agents.as_ref().map(BTreeSet::len),
+            denied_agents: policy.denied_agents.len(),
+            max_agents_per_space: policy.max_agents_per_space,
+            rejected_joins: self.rejected_joins.load(Ordering::Relaxed),
+            reloads: self.reloads.load(Ordering::Relaxed),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_policy_admits_anyone() {
+        let policy = AdmissionPolicy::from_toml("").unwrap();
+        assert_eq!(AdmissionPolicy::default(), policy);
+        assert_eq!(
+            Ok(()),
+            policy.check(&"QmSpace".into(), &"HcSAlice".into(), 1000, false)
+        );
+    }
+
+    #[test]
+    fn policy_checks_spaces_agents_and_capacity() {
+        let policy = AdmissionPolicy::from_toml(
+            r#"
+            allowed_spaces = ["QmSpace"]
+            allowed_agents = ["HcSAlice", "HcSBob"]
+            denied_agents = ["HcSBob"]
+            max_agents_per_space = 2
+            "#,
+        )
+        .unwrap();
+        let space: SpaceHash = "QmSpace".into();
+        let alice: AgentId = "HcSAlice".into();
+
+        assert_eq!(Ok(()), policy.check(&space, &alice, 1, false));
+        assert_eq!(
+            Err("space QmOther is not allowed".to_string()),
+            policy.check(&"QmOther".into(), &alice, 0, false)
+        );
+        assert_eq!(
+            Err("agent HcSBob is denied".to_string()),
+            policy.check(&space, &"HcSBob".into(), 0, false)
+        );
+        assert_eq!(
+            Err("agent HcSCarol is not allowed".to_string()),
+            policy.check(&space, &"HcSCarol".into(), 0, false)
+        );
+        assert_eq!(
+            Err("space QmSpace is full (2 agents)".to_string()),
+            policy.check(&space, &alice, 2, false)
+        );
+        // reconnecting agents are already counted
+        assert_eq!(Ok(()), policy.check(&space, &alice, 2, true));
+    }
+
+    #[test]
+    fn admission_reloads_and_counts_rejections() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy_file = dir.path().join("admission.toml");
+        std::fs::write(&policy_file, "allowed_spaces = []").unwrap();
+        let admission = Admission::from_file(policy_file.clone()).unwrap();
+        let space: SpaceHash = "QmSpace".into();
+        let alice: AgentId = "HcSAlice".into();
+
+        assert!(admission
+            .check_join(&space, &alice, &alice, 0, false)
+            .is_err());
+        assert!(admission
+            .check_join(&space, &alice, &"HcSBob".into(), 0, false)
+            .is_err());
+
+        std::fs::write(&policy_file, "allowed_spaces = [\"QmSpace\"]").unwrap();
+        admission.reload().unwrap();
+        assert_eq!(
+            Ok(()),
+            admission.check_join(&space, &alice, &alice, 0, false)
+        );
+
+        std::fs::write(&policy_file, "allowed_spaces = 12").unwrap();
+        assert!(admission.reload().is_err());
+        assert_eq!(
+            Ok(()),
+            admission.check_join(&space, &alice, &alice, 0, false)
+        );
+
+        let status = admission.status().unwrap();
+        assert_eq!(Some(1), status.allowed_spaces);
+        assert_eq!(2, status.rejected_joins);
+        assert_eq!(1, status.reloads);
+        assert!(Admission::open().status().is_none());
+    }
+}
@@ -0,0 +1,9 @@
+//! the clock sim2h's timeouts, intervals and expiries are measured with
+//!
+//! this is tokio's clock rather than the system's: on a runtime whose time has
+//! been paused with `tokio::time::pause` (tokio's `test-util` feature) it only
+//! moves when advanced, letting a simulation skip through hours of gossip
+//! intervals in moments. Everywhere else it is the system's monotonic clock.
+//! The `Sim2hWorker` in holochain_net reads the same clock.
+
+pub use tokio::time::Instant;
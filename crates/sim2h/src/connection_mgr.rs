@@ -25,10 +25,11 @@ pub enum ConMgrEvent {
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 enum ConMgrCommand {
-    Connect(Lib3hUri, TcpWss),
+    Connect(Lib3hUri, Sim2hWss),
     SendData(Lib3hUri, WsFrame),
     Disconnect(Lib3hUri),
     ListConnections(tokio::sync::oneshot::Sender<Vec<Lib3hUri>>),
+    ListConnectionAges(tokio::sync::oneshot::Sender<Vec<(Lib3hUri, std::time::Duration)>>),
 }
 
 type EvtSend = tokio::sync::mpsc::UnboundedSender<ConMgrEvent>;
@@ -45,7 +46,7 @@ struct CmdInfo {
     evt_send: EvtSend,
     cmd_recv: CmdRecv,
     uri: Lib3hUri,
-    wss: TcpWss,
+    wss: Sim2hWss,
     frame: Option<WsFrame>,
 }
 
@@ -90,6 +91,7 @@ fn process_control_cmds(cmd_info: &mut CmdInfo) -> Loop {
                     }
                     ConMgrCommand::Connect(_, _) => unreachable!(),
                     ConMgrCommand::ListConnections(_) => unreachable!(),
+                    ConMgrCommand::ListConnectionAges(_) => unreachable!(),
                 }
             }
             Err(tokio::sync::mpsc::error::TryRecvError::Empty) => break,
@@ -147,7 +149,7 @@ fn process_websocket_frames(cmd_info: &mut CmdInfo) -> Loop {
 #[allow(clippy::complexity)]
 #[instrument(skip(uri, wss, evt_send, cmd_recv))]
 /// internal websocket polling loop
-async fn wss_task(uri: Lib3hUri, wss: TcpWss, evt_send: EvtSend, cmd_recv: CmdRecv) {
+async fn wss_task(uri: Lib3hUri, wss: Sim2hWss, evt_send: EvtSend, cmd_recv: CmdRecv) {
     // TODO - this should be done with tokio tcp streams && selecting
     //        for now, we're just pausing when no work happens
 
@@ -203,7 +205,7 @@ async fn wss_task(uri: Lib3hUri, wss: TcpWss, evt_send: EvtSend, cmd_recv: CmdRe
 
 #[tracing::instrument(skip(uri, wss, evt_send))]
 /// internal actually spawn the above wss_task into the tokio runtime
-fn spawn_wss_task(uri: Lib3hUri, wss: TcpWss, evt_send: EvtSend) -> CmdSend {
+fn spawn_wss_task(uri: Lib3hUri, wss: Sim2hWss, evt_send: EvtSend) -> CmdSend {
     debug!(?uri);
     let (cmd_send, cmd_recv) = tokio::sync::mpsc::unbounded_channel();
     tokio::task::spawn(wss_task(uri, wss, evt_send, cmd_recv).instrument(debug_span!("wss_task")));
@@ -238,6 +240,12 @@ async fn con_mgr_task(mut con_mgr: ConnectionMgr, weak_ref_dummy: Weak<()>) {
     warn!("sim2h connection manager task ENDING");
 }
 
+/// a websocket connection task, and when it was handed to us
+struct ManagedWss {
+    cmd_send: CmdSend,
+    connected_at: crate::clock::Instant,
+}
+
 /// ConnectionMgr tracks a set of open websocket connections
 /// allowing you to send data to them and checking them for incoming data
 pub struct ConnectionMgr {
@@ -246,7 +254,7 @@ pub struct ConnectionMgr {
     evt_send_from_children: EvtSend,
     evt_recv_from_children: EvtRecv,
     connection_count: ConnectionCount,
-    wss_map: std::collections::HashMap<Lib3hUri, CmdSend>,
+    wss_map: std::collections::HashMap<Lib3hUri, ManagedWss>,
 }
 
 impl ConnectionMgr {
@@ -284,12 +292,16 @@ impl ConnectionMgr {
         )
     }
 
-    fn handle_connect_data(&mut self, uri: Lib3hUri, wss: InStreamWss<InStreamTcp>) {
+    fn handle_connect_data(&mut self, uri: Lib3hUri, wss: Sim2hWss) {
         debug!(?uri);
         let cmd_send = spawn_wss_task(uri.clone(), wss, self.evt_send_from_children.clone());
-        if let Some(old) = self.wss_map.insert(uri.clone(), cmd_send) {
+        let managed = ManagedWss {
+            cmd_send,
+            connected_at: crate::clock::Instant::now(),
+        };
+        if let Some(old) = self.wss_map.insert(uri.clone(), managed) {
             error!("REPLACING ACTIVE CONNECTION: {}", uri);
-            let _ = old.send(ConMgrCommand::Disconnect(uri));
+            let _ = old.cmd_send.send(ConMgrCommand::Disconnect(uri));
         }
     }
 
@@ -297,8 +309,11 @@ impl ConnectionMgr {
     fn handle_send_data(&mut self, uri: Lib3hUri, frame: WsFrame) {
         debug!(?uri);
         let mut remove = false;
-        if let Some(cmd_send) = self.wss_map.get(&uri) {
-            if let Err(_) = cmd_send.send(ConMgrCommand::SendData(uri.clone(), frame)) {
+        if let Some(managed) = self.wss_map.get(&uri) {
+            if let Err(_) = managed
+                .cmd_send
+                .send(ConMgrCommand::SendData(uri.clone(), frame))
+            {
                 tracing::error!(?uri);
                 remove = true;
             }
@@ -321,9 +336,9 @@ impl ConnectionMgr {
                     match cmd {
                         ConMgrCommand::SendData(uri, frame) => self.handle_send_data(uri, frame),
                         ConMgrCommand::Disconnect(uri) => {
-                            if let Some(cmd_send) = self.wss_map.remove(&uri) {
+                            if let Some(managed) = self.wss_map.remove(&uri) {
                                 tracing::error!(?uri);
-                                let _ = cmd_send.send(ConMgrCommand::Disconnect(uri));
+                                let _ = managed.cmd_send.send(ConMgrCommand::Disconnect(uri));
                             }
                         }
                         ConMgrCommand::Connect(uri, wss) => {
@@ -332,6 +347,16 @@ impl ConnectionMgr {
                         ConMgrCommand::ListConnections(respond) => {
                             let _ = respond.send(self.wss_map.keys().cloned().collect());
                         }
+                        ConMgrCommand::ListConnectionAges(respond) => {
+                            let _ = respond.send(
+                                self.wss_map
+                                    .iter()
+                                    .map(|(uri, managed)| {
+                                        (uri.clone(), managed.connected_at.elapsed())
+                                    })
+                                    .collect(),
+                            );
+                        }
                     }
                 }
                 Err(tokio::sync::mpsc::error::TryRecvError::Empty) => break,
@@ -358,8 +383,10 @@ impl ConnectionMgr {
                     *did_work = true;
                     match evt {
                         ConMgrEvent::Disconnect(uri, maybe_err) => {
-                            if let Some(cmd_send) = self.wss_map.remove(&uri) {
-                                let _ = cmd_send.send(ConMgrCommand::Disconnect(uri.clone()));
+                            if let Some(managed) = self.wss_map.remove(&uri) {
+                                let _ = managed
+                                    .cmd_send
+                                    .send(ConMgrCommand::Disconnect(uri.clone()));
                             }
                             if let Err(_) = self
                                 .evt_send_to_parent
@@ -463,7 +490,7 @@ impl ConnectionMgrHandle {
 
     #[tracing::instrument(skip(self))]
     /// send in a websocket connection to be managed
-    pub fn connect(&self, uri: Lib3hUri, wss: TcpWss) {
+    pub fn connect(&self, uri: Lib3hUri, wss: Sim2hWss) {
         debug!(?uri);
         if let Err(e) = self.send_cmd.send(ConMgrCommand::Connect(uri, wss)) {
             tracing::error!("failed to send on channel - shutting down? {:?}", e);
@@ -504,4 +531,21 @@ impl ConnectionMgrHandle {
             }
         }
     }
+
+    #[tracing::instrument(skip(self))]
+    /// list the managed websocket connections with how long they have been open
+    pub async fn list_connection_ages(&self) -> Vec<(Lib3hUri, std::time::Duration)> {
+        let (s, r) = tokio::sync::oneshot::channel();
+        if let Err(e) = self.send_cmd.send(ConMgrCommand::ListConnectionAges(s)) {
+            error!("failed to send on channel - shutting down? {:?}", e);
+            return vec![];
+        }
+        match r.await {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::error!("{:?}", e);
+                vec![]
+            }
+        }
+    }
 }
@@ -0,0 +1,369 @@
+//! store-and-forward of direct messages to agents that are not connected
+//! messages are kept for a limited time, up to a size cap per recipient
+//! and a size cap for all recipients together, and delivered when the
+//! recipient joins the space again
+
+use crate::{clock::Instant, wire_message::WireMessage, MutexExt};
+use holochain_locksmith::Mutex;
+use lib3h_protocol::types::{AgentPubKey, SpaceHash};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+/// how often we drop the expired messages of agents that never came back
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// how direct messages to absent agents are queued
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DmQueueConfig {
+    /// how long a queued message is kept before it is dropped
+    pub ttl_secs: u64,
+    /// the most message content, in bytes, queued for a single agent
+    pub max_bytes_per_agent: usize,
+    /// the most message content, in bytes, queued for all agents together
+    pub max_total_bytes: usize,
+}
+
+/// direct message queue counters reported in `StatusData`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DmQueueStats {
+    /// messages waiting for their recipient
+    pub queued_messages: usize,
+    /// bytes of content waiting for their recipient
+    pub queued_bytes: usize,
+    /// agents with messages waiting for them
+    pub queued_agents: usize,
+    /// total queued messages delivered to a rejoining agent
+    pub delivered_messages: u64,
+    /// total queued messages dropped for being older than the ttl
+    pub expired_messages: u64,
+    /// total messages not queued because their recipient's queue, or the
+    /// queues of all agents together, were full
+    pub rejected_messages: u64,
+}
+
+#[derive(Debug)]
+struct QueuedMessage {
+    expires_at: Instant,
+    size: usize,
+    message: WireMessage,
+}
+
+#[derive(Debug)]
+struct DmQueueState {
+    queues: HashMap<(SpaceHash, AgentPubKey), VecDeque<QueuedMessage>>,
+    stats: DmQueueStats,
+    last_prune: Instant,
+}
+
+impl DmQueueState {
+    /// drops the expired messages at the front of a queue, they all
+    /// have the same ttl so the oldest ones are first
+    fn expire(stats: &mut DmQueueStats, queue: &mut VecDeque<QueuedMessage>, now: Instant) {
+        while queue
+            .front()
+            .map_or(false, |queued| queued.expires_at <= now)
+        {
+            let expired = queue.pop_front().expect("front exists");
+            stats.expired_messages += 1;
+            stats.queued_messages -= 1;
+            stats.queued_bytes -= expired.size;
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        let stats = &mut self.stats;
+        self.queues.retain(|_, queue| {
+            Self::expire(stats, queue, now);
+            !queue.is_empty()
+        });
+        self.last_prune = now;
+    }
+}
+
+/// the direct messages waiting for agents to (re)join their space
+pub struct DmQueue {
+    config: Option<DmQueueConfig>,
+    state: Mutex<DmQueueState>,
+}
+
+impl DmQueue {
+    /// queues nothing if `config` is `None`
+    pub fn new(config: Option<DmQueueConfig>) -> Self {
+        Self {
+            config,
+            state: Mutex::new(DmQueueState {
+                queues: HashMap::new(),
+                stats: DmQueueStats::default(),
+                last_prune: Instant::now(),
+            }),
+        }
+    }
+
+    /// false if direct messages to absent agents are dropped
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// how long queued messages are kept
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.config.as_ref().map_or(0, |config| config.ttl_secs))
+    }
+
+    /// queue a message with `size` bytes of content until `agent` joins `space`
+    pub fn enqueue(
+        &self,
+        space: &SpaceHash,
+        agent: &AgentPubKey,
+        message: WireMessage,
+        size: usize,
+    ) -> Result<(), String> {
+        self.enqueue_at(space, agent, message, size, Instant::now())
+    }
+
+    fn enqueue_at(
+        &self,
+        space: &SpaceHash,
+        agent: &AgentPubKey,
+        message: WireMessage,
+        size: usize,
+        now: Instant,
+    ) -> Result<(), String> {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return Err("direct messages are not queued".to_string()),
+        };
+        let mut state = self.state.f_lock();
+        let state = &mut *state;
+        if now.saturating_duration_since(state.last_prune) >= PRUNE_INTERVAL
+            || state.stats.queued_bytes + size > config.max_total_bytes
+        {
+            state.prune(now);
+        }
+        if state.stats.queued_bytes + size > config.max_total_bytes {
+            state.stats.rejected_messages += 1;
+            return Err(format!(
+                "direct message queues are full ({} bytes)",
+                config.max_total_bytes
+            ));
+        }
+
+        let queue = state
+            .queues
+            .entry((space.clone(), agent.clone()))
+            .or_insert_with(VecDeque::new);
+        DmQueueState::expire(&mut state.stats, queue, now);
+        let queued_bytes: usize = queue.iter().map(|queued| queued.size).sum();
+        if queued_bytes + size > config.max_bytes_per_agent {
+            state.stats.rejected_messages += 1;
+            return Err(format!(
+                "queue for {} is full ({} bytes)",
+                agent, config.max_bytes_per_agent
+            ));
+        }
+
+        queue.push_back(QueuedMessage {
+            expires_at: now + Duration::from_secs(config.ttl_secs),
+            size,
+            message,
+        });
+        state.stats.queued_messages += 1;
+        state.stats.queued_bytes += size;
+        Ok(())
+    }
+
+    /// removes and returns the unexpired messages queued for `agent` in `space`,
+    /// oldest first
+    pub fn take(&self, space: &SpaceHash, agent: &AgentPubKey) -> Vec<WireMessage> {
+        self.take_at(space, agent, Instant::now())
+    }
+
+    fn take_at(&self, space: &SpaceHash, agent: &AgentPubKey, now: Instant) -> Vec<WireMessage> {
+        let mut state = self.state.f_lock();
+        let state = &mut *state;
+        let mut queue = match state.queues.remove(&(space.clone(), agent.clone())) {
+            Some(queue) => queue,
+            None => return Vec::new(),
+        };
+        DmQueueState::expire(&mut state.stats, &mut queue, now);
+        state.stats.delivered_messages += queue.len() as u64;
+        state.stats.queued_messages -= queue.len();
+        queue
+            .into_iter()
+            .map(|queued| {
+                state.stats.queued_bytes -= queued.size;
+                queued.message
+            })
+            .collect()
+    }
+
+    /// current queue counters, `None` if nothing is queued
+    pub fn stats(&self) -> Option<DmQueueStats> {
+        self.config.as_ref()?;
+        let state = self.state.f_lock();
+        Some(DmQueueStats {
+            queued_agents: state.queues.len(),
+            ..state.stats.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(ttl_secs: u64, max_bytes_per_agent: usize) -> (DmQueue, SpaceHash, AgentPubKey) {
+        capped_queue(ttl_secs, max_bytes_per_agent, usize::max_value())
+    }
+
+    fn capped_queue(
+        ttl_secs: u64,
+        max_bytes_per_agent: usize,
+        max_total_bytes: usize,
+    ) -> (DmQueue, SpaceHash, AgentPubKey) {
+        (
+            DmQueue::new(Some(DmQueueConfig {
+                ttl_secs,
+                max_bytes_per_agent,
+                max_total_bytes,
+            })),
+            SpaceHash::from("space"),
+            AgentPubKey::from("alice"),
+        )
+    }
+
+    #[test]
+    fn nothing_queued_by_default() {
+        let queue = DmQueue::new(None);
+        assert!(!queue.is_enabled());
+        assert!(queue
+            .enqueue(&"space".into(), &"alice".into(), WireMessage::Ping, 1)
+            .is_err());
+        assert!(queue.stats().is_none());
+    }
+
+    #[test]
+    fn delivers_queued_messages_in_order() {
+        let (queue, space, alice) = queue(60, 100);
+        queue
+            .enqueue(&space, &alice, WireMessage::Ping, 10)
+            .unwrap();
+        queue
+            .enqueue(&space, &alice, WireMessage::Pong, 10)
+            .unwrap();
+        assert_eq!(
+            Some(DmQueueStats {
+                queued_messages: 2,
+                queued_bytes: 20,
+                queued_agents: 1,
+                ..Default::default()
+            }),
+            queue.stats()
+        );
+
+        // other agents and spaces have their own queues
+        assert!(queue.take(&space, &"bob".into()).is_empty());
+        assert!(queue.take(&"other".into(), &alice).is_empty());
+
+        assert_eq!(
+            vec![WireMessage::Ping, WireMessage::Pong],
+            queue.take(&space, &alice)
+        );
+        assert!(queue.take(&space, &alice).is_empty());
+        assert_eq!(
+            Some(DmQueueStats {
+                delivered_messages: 2,
+                ..Default::default()
+            }),
+            queue.stats()
+        );
+    }
+
+    #[test]
+    fn drops_expired_messages() {
+        let (queue, space, alice) = queue(60, 100);
+        let now = Instant::now();
+        queue
+            .enqueue_at(&space, &alice, WireMessage::Ping, 10, now)
+            .unwrap();
+        queue
+            .enqueue_at(
+                &space,
+                &alice,
+                WireMessage::Pong,
+                10,
+                now + Duration::from_secs(30),
+            )
+            .unwrap();
+        assert_eq!(
+            vec![WireMessage::Pong],
+            queue.take_at(&space, &alice, now + Duration::from_secs(61))
+        );
+        let stats = queue.stats().unwrap();
+        assert_eq!(1, stats.expired_messages);
+        assert_eq!(1, stats.delivered_messages);
+        assert_eq!(0, stats.queued_bytes);
+    }
+
+    #[test]
+    fn caps_queued_bytes_per_agent() {
+        let (queue, space, alice) = queue(60, 100);
+        let now = Instant::now();
+        queue
+            .enqueue_at(&space, &alice, WireMessage::Ping, 60, now)
+            .unwrap();
+        assert_eq!(
+            Err("queue for alice is full (100 bytes)".to_string()),
+            queue.enqueue_at(&space, &alice, WireMessage::Ping, 60, now)
+        );
+        // bob's queue is not full
+        queue
+            .enqueue_at(&space, &"bob".into(), WireMessage::Ping, 60, now)
+            .unwrap();
+        // expired messages free up room
+        queue
+            .enqueue_at(
+                &space,
+                &alice,
+                WireMessage::Ping,
+                60,
+                now + Duration::from_secs(60),
+            )
+            .unwrap();
+        let stats = queue.stats().unwrap();
+        assert_eq!(1, stats.rejected_messages);
+        assert_eq!(1, stats.expired_messages);
+        assert_eq!(2, stats.queued_messages);
+        assert_eq!(2, stats.queued_agents);
+    }
+
+    #[test]
+    fn caps_queued_bytes_of_all_agents() {
+        let (queue, space, alice) = capped_queue(60, 100, 150);
+        let now = Instant::now();
+        queue
+            .enqueue_at(&space, &alice, WireMessage::Ping, 100, now)
+            .unwrap();
+        assert_eq!(
+            Err("direct message queues are full (150 bytes)".to_string()),
+            queue.enqueue_at(&space, &"bob".into(), WireMessage::Ping, 60, now)
+        );
+        // expired messages of agents that never came back free up room
+        queue
+            .enqueue_at(
+                &space,
+                &"bob".into(),
+                WireMessage::Ping,
+                60,
+                now + Duration::from_secs(60),
+            )
+            .unwrap();
+        let stats = queue.stats().unwrap();
+        assert_eq!(1, stats.rejected_messages);
+        assert_eq!(1, stats.expired_messages);
+        assert_eq!(60, stats.queued_bytes);
+        assert_eq!(1, stats.queued_agents);
+    }
+}
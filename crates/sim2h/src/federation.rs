@@ -0,0 +1,233 @@
+//! partitioning of spaces between several sim2h nodes
+//! every node of a federation is configured with the same list of node urls,
+//! and each space is owned by the node whose hash range contains the hash of
+//! the space address. Agents joining a space on a node that doesn't own it
+//! are redirected to the owner, so all the agents of a space meet on one node.
+//! Ownership only depends on the shared node list, never on which nodes a node
+//! can reach, so every node agrees on the owner of a space and no two nodes serve
+//! it to different agents: as no agent of a space is ever connected elsewhere,
+//! nodes don't need to forward messages to each other. The spaces of a node that
+//! is down are unavailable until it is back.
+
+use crate::{error::*, RECEIPT_HASH_SEED};
+use lib3h_protocol::{types::SpaceHash, uri::Lib3hUri};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    hash::Hasher,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use twox_hash::XxHash64;
+
+/// the nodes of a federation and which one of them we are
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FederationConfig {
+    /// the urls clients use to connect to each node, in the same order on every node
+    pub nodes: Vec<Lib3hUri>,
+    /// the index of this node in `nodes`
+    pub this_node: usize,
+}
+
+/// federation state reported in `StatusData`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FederationStatus {
+    pub nodes: usize,
+    pub this_node: usize,
+    /// total joins redirected to another node
+    pub redirected_joins: u64,
+    /// the indexes of the nodes that could not be reached at the last check
+    pub unreachable_nodes: Vec<usize>,
+}
+
+/// the index of the node owning `space` in a federation of `node_count` nodes.
+/// the u64 range of space hashes is split into `node_count` contiguous ranges.
+pub fn node_index_for_space(space: &SpaceHash, node_count: usize) -> usize {
+    let mut hasher = XxHash64::with_seed(RECEIPT_HASH_SEED);
+    hasher.write(space.to_string().as_bytes());
+    ((u128::from(hasher.finish()) * node_count as u128) >> 64) as usize
+}
+
+/// decides which spaces this sim2h node serves
+pub struct Federation {
+    config: Option<FederationConfig>,
+    redirected_joins: AtomicU64,
+    unreachable_nodes: Mutex<BTreeSet<usize>>,
+}
+
+impl Federation {
+    /// serves every space if `config` is `None`
+    pub fn new(config: Option<FederationConfig>) -> Sim2hResult<Self> {
+        if let Some(config) = &config {
+            if config.this_node >= config.nodes.len() {
+                return Err(format!(
+                    "federation node index {} is out of range for {} nodes",
+                    config.this_node,
+                    config.nodes.len()
+                )
+                .into());
+            }
+        }
+        Ok(Self {
+            config,
+            redirected_joins: AtomicU64::new(0),
+            unreachable_nodes: Mutex::new(BTreeSet::new()),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// the url of the node serving `space`, `None` if that is this node.
+    /// Every node of the federation gives the same answer, even while
+    /// the owner can't be reached.
+    pub fn owner_of(&self, space: &SpaceHash) -> Option<Lib3hUri> {
+        let config = self.config.as_ref()?;
+        let owner = node_index_for_space(space, config.nodes.len());
+        if owner == config.this_node {
+            None
+        } else {
+            Some(config.nodes[owner].clone())
+        }
+    }
+
+    /// the indexes and urls of the other nodes of the federation
+    pub fn peers(&self) -> Vec<(usize, Lib3hUri)> {
+        match &self.config {
+            None => Vec::new(),
+            Some(config) => config
+                .nodes
+                .iter()
+                .cloned()
+                .enumerate()
+                .filter(|(node, _)| *node != config.this_node)
+                .collect(),
+        }
+    }
+
+    /// records the outcome of checking whether `node` can be reached, for the status,
+    /// returns `true` if that changes which nodes are reachable
+    pub fn set_reachable(&self, node: usize, reachable: bool) -> bool {
+        let mut unreachable = self.unreachable_nodes.lock();
+        if reachable {
+            unreachable.remove(&node)
+        } else {
+            unreachable.insert(node)
+        }
+    }
+
+    /// counts a join sent to another node
+    pub fn redirected(&self) {
+        self.redirected_joins.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn status(&self) -> Option<FederationStatus> {
+        let config = self.config.as_ref()?;
+        Some(FederationStatus {
+            nodes: config.nodes.len(),
+            this_node: config.this_node,
+            redirected_joins: self.redirected_joins.load(Ordering::Relaxed),
+            unreachable_nodes: self.unreachable_nodes.lock().iter().cloned().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(count: usize) -> Vec<Lib3hUri> {
+        (0..count)
+            .map(|i| {
+                url::Url::parse(&format!("ws://node{}:9000", i))
+                    .unwrap()
+                    .into()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn serves_every_space_by_default() {
+        let federation = Federation::new(None).unwrap();
+        assert!(!federation.is_enabled());
+        assert_eq!(None, federation.owner_of(&"QmSpace".into()));
+        assert!(federation.status().is_none());
+    }
+
+    #[test]
+    fn rejects_bad_node_index() {
+        assert!(Federation::new(Some(FederationConfig {
+            nodes: nodes(2),
+            this_node: 2,
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn every_space_has_exactly_one_owner() {
+        let federation: Vec<Federation> = (0..3)
+            .map(|this_node| {
+                Federation::new(Some(FederationConfig {
+                    nodes: nodes(3),
+                    this_node,
+                }))
+                .unwrap()
+            })
+            .collect();
+        let mut owned = vec![0; 3];
+        for i in 0..300 {
+            let space: SpaceHash = format!("QmSpace{}", i).as_str().into();
+            let owner = node_index_for_space(&space, 3);
+            owned[owner] += 1;
+            for (this_node, node) in federation.iter().enumerate() {
+                if this_node == owner {
+                    assert_eq!(None, node.owner_of(&space));
+                } else {
+                    assert_eq!(Some(nodes(3)[owner].clone()), node.owner_of(&space));
+                }
+            }
+        }
+        // the hash ranges spread spaces over all the nodes
+        assert!(owned.iter().all(|count| *count > 50));
+    }
+
+    #[test]
+    fn unreachable_nodes_keep_their_spaces() {
+        let federation = Federation::new(Some(FederationConfig {
+            nodes: nodes(3),
+            this_node: 0,
+        }))
+        .unwrap();
+        let space = (0..)
+            .map(|i| -> SpaceHash { format!("QmSpace{}", i).as_str().into() })
+            .find(|space| node_index_for_space(space, 3) == 1)
+            .unwrap();
+        assert_eq!(Some(nodes(3)[1].clone()), federation.owner_of(&space));
+
+        // taking over the space would split its agents between this node
+        // and the nodes that can still reach node 1
+        assert!(federation.set_reachable(1, false));
+        assert!(!federation.set_reachable(1, false));
+        assert_eq!(vec![1], federation.status().unwrap().unreachable_nodes);
+        federation.set_reachable(2, false);
+        assert_eq!(Some(nodes(3)[1].clone()), federation.owner_of(&space));
+
+        assert!(federation.set_reachable(1, true));
+        assert_eq!(vec![2], federation.status().unwrap().unreachable_nodes);
+    }
+
+    #[test]
+    fn peers_are_the_other_nodes() {
+        let federation = Federation::new(Some(FederationConfig {
+            nodes: nodes(3),
+            this_node: 1,
+        }))
+        .unwrap();
+        assert_eq!(
+            vec![(0, nodes(3)[0].clone()), (2, nodes(3)[2].clone())],
+            federation.peers()
+        );
+        assert!(Federation::new(None).unwrap().peers().is_empty());
+    }
+}
@@ -8,22 +8,39 @@ mod naive_sharding;
 mod schedule;
 #[allow(unused_imports)]
 use schedule::*;
+pub mod admin;
+pub mod admission;
+pub mod clock;
 pub mod connection_state;
 pub mod crypto;
+pub mod dm_queue;
 pub mod error;
+pub mod federation;
 use lib3h_protocol::types::*;
-mod message_log;
+pub mod log_analysis;
+pub mod message_log;
+pub mod rate_limit;
+pub mod transport;
 pub mod websocket;
 pub mod wire_message;
 
 pub use crate::message_log::MESSAGE_LOGGER;
-use crate::{crypto::*, error::*, naive_sharding::entry_location};
+use crate::{
+    admission::Admission,
+    crypto::*,
+    dm_queue::{DmQueue, DmQueueConfig},
+    error::*,
+    federation::{Federation, FederationConfig},
+    naive_sharding::entry_location,
+    rate_limit::{RateLimitConfig, RateLimitDecision, RateLimiter},
+};
 use connection_state::*;
 use lib3h_crypto_api::CryptoSystem;
 use lib3h_protocol::{data_types::*, protocol::*, types::SpaceHash, uri::Lib3hUri};
 
 pub use wire_message::{
-    HelloData, StatusData, WireError, WireMessage, WireMessageVersion, WIRE_VERSION,
+    DirectMessageQueuedData, HelloData, StatusData, WireError, WireMessage, WireMessageVersion,
+    WIRE_VERSION,
 };
 
 use futures::{
@@ -128,6 +145,11 @@ const RETRY_FETCH_MISSING_ASPECTS_INTERVAL_MS: u64 = 30000; // 30 seconds
 /// but it does cause a mutate, so we don't want to spam it too hard
 //const RETRY_FETCH_MISSING_ASPECTS_INTERVAL_MS: u64 = 500; // half second
 
+/// how often the other nodes of a federation are checked for reachability
+const FEDERATION_CHECK_INTERVAL_MS: u64 = 10000; // 10 seconds
+/// how long a federation node gets to accept a websocket connection
+const FEDERATION_CHECK_TIMEOUT_MS: u64 = 5000;
+
 fn open_lifecycle(desc: &str, uuid: &str, uri: &Lib3hUri) {
     debug!("connection event open_conns: {} for {}@{}", desc, uuid, uri);
 }
@@ -138,10 +160,10 @@ struct MetricsTimerGenerator {
 }
 
 impl MetricsTimerGenerator {
-    pub fn new() -> (Self, BoxFuture<'static, ()>) {
+    pub fn new(metric_publisher: MetricPublisherConfig) -> (Self, BoxFuture<'static, ()>) {
         let (sender, mut recv) = tokio::sync::mpsc::unbounded_channel::<(&'static str, f64)>();
         let out = async move {
-            let metric_publisher = MetricPublisherConfig::default().create_metric_publisher();
+            let metric_publisher = metric_publisher.create_metric_publisher();
             'metric_loop: loop {
                 let msg = match recv.next().await {
                     None => break 'metric_loop,
@@ -207,6 +229,8 @@ impl Drop for MetricsTimer {
 //pub(crate) type TcpWss = InStreamWss<InStreamTls<InStreamTcp>>;
 pub(crate) type TcpWssServer = InStreamListenerWss<InStreamListenerTcp>;
 pub type TcpWss = InStreamWss<InStreamTcp>;
+use transport::Sim2hWssServer;
+pub use transport::{Sim2hWss, MEM_SCHEME};
 
 mod connection_mgr;
 use connection_mgr::*;
@@ -235,15 +259,24 @@ struct Sim2hHandle {
     metric_gen: MetricsTimerGenerator,
     connection_mgr: ConnectionMgrHandle,
     connection_count: ConnectionCount,
+    rate_limiter: std::sync::Arc<RateLimiter>,
+    admission: std::sync::Arc<Admission>,
+    dm_queue: std::sync::Arc<DmQueue>,
+    federation: std::sync::Arc<Federation>,
 }
 
 impl Sim2hHandle {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         crypto: Box<dyn CryptoSystem>,
         dht_algorithm: DhtAlgorithm,
         metric_gen: MetricsTimerGenerator,
         connection_mgr: ConnectionMgrHandle,
         connection_count: ConnectionCount,
+        rate_limit: RateLimitConfig,
+        admission: Admission,
+        dm_queue: Option<DmQueueConfig>,
+        federation: Federation,
     ) -> Self {
         let redundancy = match dht_algorithm {
             DhtAlgorithm::FullSync => 0,
@@ -255,6 +288,10 @@ impl Sim2hHandle {
             metric_gen,
             connection_mgr,
             connection_count,
+            rate_limiter: std::sync::Arc::new(RateLimiter::new(rate_limit)),
+            admission: std::sync::Arc::new(admission),
+            dm_queue: std::sync::Arc::new(DmQueue::new(dm_queue)),
+            federation: std::sync::Arc::new(federation),
         }
     }
 
@@ -273,12 +310,58 @@ impl Sim2hHandle {
         &self.connection_mgr
     }
 
+    /// access the rate limits of joined agents
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// access the admission policy for joining spaces
+    pub fn admission(&self) -> &Admission {
+        &self.admission
+    }
+
+    /// access the direct messages waiting for absent agents
+    pub fn dm_queue(&self) -> &DmQueue {
+        &self.dm_queue
+    }
+
+    /// access the partitioning of spaces between federated sim2h nodes
+    pub fn federation(&self) -> &Federation {
+        &self.federation
+    }
+
+    /// the counters reported in a `StatusResponse`
+    pub async fn status(&self) -> StatusData {
+        let state = self.state().get_clone().await;
+        let mut joined_connections = 0_usize;
+        for (_, space) in state.spaces.iter() {
+            joined_connections += space.connections.len();
+        }
+        StatusData {
+            spaces: state.spaces_count(),
+            connections: self.connection_count.get().await,
+            joined_connections,
+            redundant_count: match self.dht_algorithm() {
+                DhtAlgorithm::FullSync => 0,
+                DhtAlgorithm::NaiveSharding { redundant_count } => *redundant_count,
+            },
+            version: WIRE_VERSION,
+            rate_limit: self.rate_limiter().stats(),
+            admission: self.admission().status(),
+            dm_queue: self.dm_queue().stats(),
+            federation: self.federation().status(),
+        }
+    }
+
     /// send a message to another connected agent
     pub fn send(&self, agent: AgentId, uri: Lib3hUri, msg: &WireMessage) {
         debug!(">>OUT>> {} to {}", msg.message_type(), uri);
-        MESSAGE_LOGGER
-            .lock()
-            .log_out(agent, uri.clone(), msg.clone());
+        {
+            let mut logger = MESSAGE_LOGGER.lock();
+            if logger.is_running() {
+                logger.log_out(agent, uri.clone(), msg.clone());
+            }
+        }
         let payload: Opaque = msg.clone().into();
         self.connection_mgr
             .send_data(uri, payload.as_bytes().into());
@@ -302,6 +385,7 @@ impl Sim2hHandle {
         message: WireMessage,
         signer: AgentId,
         receipt: WireMessage,
+        size: usize,
     ) {
         let context = message
             .try_get_span()
@@ -379,6 +463,34 @@ impl Sim2hHandle {
                 return;
             }
 
+            // over-limit messages don't get a receipt, so the client
+            // will send them again after its resend interval
+            match sim2h_handle
+                .rate_limiter()
+                .check(&space_hash, &signer, size)
+            {
+                RateLimitDecision::Allow => (),
+                RateLimitDecision::Limit => {
+                    debug!("rate limiting {} from {}", message.message_type(), signer);
+                    sim2h_handle.send(
+                        signer.clone(),
+                        uri.clone(),
+                        &WireMessage::Err(WireError::RateLimited),
+                    );
+                    return;
+                }
+                RateLimitDecision::Disconnect => {
+                    warn!("disconnecting {} for going over rate limits", signer);
+                    sim2h_handle.send(
+                        signer.clone(),
+                        uri.clone(),
+                        &WireMessage::Err(WireError::RateLimited),
+                    );
+                    sim2h_handle.disconnect(vec![uri.clone()]);
+                    return;
+                }
+            }
+
             sim2h_handle.send_receipt(&receipt, &signer, &uri);
 
             match message {
@@ -411,9 +523,21 @@ impl Sim2hHandle {
     /// disconnect an active connection
     pub fn disconnect(&self, disconnect: Vec<Lib3hUri>) {
         for d in disconnect.iter() {
-            self.state().spawn_drop_connection_by_uri(d.clone());
             self.connection_mgr.disconnect(d.clone());
         }
+        let sim2h_handle = self.clone();
+        tokio::task::spawn(async move {
+            // look up who the connections belonged to before they are dropped from the state
+            let state = sim2h_handle.state().get_clone().await;
+            for uri in disconnect {
+                if let Some((agent_id, space_hash)) = state.get_space_info_from_uri(&uri) {
+                    sim2h_handle
+                        .rate_limiter()
+                        .agent_disconnected(&space_hash, &agent_id);
+                }
+                sim2h_handle.state().spawn_drop_connection_by_uri(uri);
+            }
+        });
     }
 }
 
@@ -527,24 +651,11 @@ fn spawn_handle_message_status(
 ) {
     tokio::task::spawn(async move {
         debug!("Sending StatusResponse in response to Status");
-        let state = sim2h_handle.state().get_clone().await;
-        let mut joined_connections = 0_usize;
-        for (_, space) in state.spaces.iter() {
-            joined_connections += space.connections.len();
-        }
+        let status = sim2h_handle.status().await;
         sim2h_handle.send(
             signer.clone(),
             uri.clone(),
-            &WireMessage::StatusResponse(StatusData {
-                spaces: state.spaces_count(),
-                connections: sim2h_handle.connection_count.get().await,
-                joined_connections,
-                redundant_count: match sim2h_handle.dht_algorithm() {
-                    DhtAlgorithm::FullSync => 0,
-                    DhtAlgorithm::NaiveSharding { redundant_count } => *redundant_count,
-                },
-                version: WIRE_VERSION,
-            }),
+            &WireMessage::StatusResponse(status),
         );
         sim2h_handle.send_receipt(&receipt, &signer, &uri);
     });
@@ -605,6 +716,7 @@ fn spawn_handle_message_hello(
             },
             version: WIRE_VERSION,
             extra: None,
+            redirect: None,
         }),
     );
     sim2h_handle.send_receipt(&receipt, &signer, &uri);
@@ -626,14 +738,81 @@ async fn handle_message_join_space(
     data: SpaceData,
     receipt: WireMessage,
 ) {
-    sim2h_handle
-        .state()
-        .new_connection(
-            data.space_address.clone(),
-            data.agent_id.clone(),
+    if let Some(owner) = sim2h_handle.federation().owner_of(&data.space_address) {
+        debug!(
+            "redirecting join of {} to {} to {}",
+            signer, data.space_address, owner
+        );
+        sim2h_handle.federation().redirected();
+        sim2h_handle.send_receipt(&receipt, &signer, &uri);
+        sim2h_handle.send(
+            signer.clone(),
             uri.clone(),
-        )
-        .await;
+            &WireMessage::HelloResponse(HelloData {
+                redundant_count: match sim2h_handle.dht_algorithm() {
+                    DhtAlgorithm::FullSync => 0,
+                    DhtAlgorithm::NaiveSharding { redundant_count } => *redundant_count,
+                },
+                version: WIRE_VERSION,
+                extra: None,
+                redirect: Some(owner),
+            }),
+        );
+        sim2h_handle.disconnect(vec![uri]);
+        return;
+    }
+
+    if sim2h_handle.admission().is_enabled() {
+        // the policy is checked by the state store, against the state the
+        // connection gets inserted into, so concurrent joins can't overfill a space
+        let check = {
+            let sim2h_handle = sim2h_handle.clone();
+            let space_address = data.space_address.clone();
+            let agent_id = data.agent_id.clone();
+            let signer = signer.clone();
+            sim2h_im_state::JoinCheck(Box::new(move |agents_in_space, already_joined| {
+                sim2h_handle.admission().check_join(
+                    &space_address,
+                    &agent_id,
+                    &signer,
+                    agents_in_space,
+                    already_joined,
+                )
+            }))
+        };
+        let admitted = sim2h_handle
+            .state()
+            .admit_connection(
+                data.space_address.clone(),
+                data.agent_id.clone(),
+                uri.clone(),
+                check,
+            )
+            .await;
+        if let Err(reason) = admitted {
+            warn!(
+                "refusing join of {} to {}: {}",
+                signer, data.space_address, reason
+            );
+            sim2h_handle.send_receipt(&receipt, &signer, &uri);
+            sim2h_handle.send(
+                signer.clone(),
+                uri.clone(),
+                &WireMessage::Err(WireError::JoinRefused(reason)),
+            );
+            sim2h_handle.disconnect(vec![uri]);
+            return;
+        }
+    } else {
+        sim2h_handle
+            .state()
+            .new_connection(
+                data.space_address.clone(),
+                data.agent_id.clone(),
+                uri.clone(),
+            )
+            .await;
+    }
 
     sim2h_handle.send_receipt(&receipt, &signer, &uri);
 
@@ -655,19 +834,27 @@ async fn handle_message_join_space(
 
     sim2h_handle.send(
         data.agent_id.clone(),
-        uri,
+        uri.clone(),
         &WireMessage::Lib3hToClient(
             ht::span_wrap_encode!(
                 Level::INFO,
                 Lib3hToClient::HandleGetAuthoringEntryList(GetListData {
                     request_id: "".into(),
                     space_address: data.space_address.clone(),
-                    provider_agent_id: data.agent_id,
+                    provider_agent_id: data.agent_id.clone(),
                 })
             )
             .into(),
         ),
     );
+
+    // deliver the direct messages that were sent while we were away
+    for message in sim2h_handle
+        .dm_queue()
+        .take(&data.space_address, &data.agent_id)
+    {
+        sim2h_handle.send(data.agent_id.clone(), uri.clone(), &message);
+    }
 }
 
 fn inner_spawn_handle_message_send_dmx(
@@ -700,26 +887,55 @@ fn inner_spawn_handle_message_send_dmx(
 
 fn spawn_handle_message_send_dm(
     sim2h_handle: Sim2hHandle,
-    _uri: Lib3hUri,
-    _signer: AgentId,
+    uri: Lib3hUri,
+    signer: AgentId,
     space_hash: MonoRef<SpaceHash>,
     data: DirectMessageData,
 ) {
-    let to_agent_id = data.to_agent_id.clone();
-    let data_space_hash = data.space_address.clone();
-    inner_spawn_handle_message_send_dmx(
-        sim2h_handle,
-        to_agent_id,
-        data_space_hash,
-        space_hash,
-        WireMessage::Lib3hToClient(
+    if data.space_address != *space_hash {
+        error!(
+            "space mismatch - agent is in {}, message is for {}",
+            *space_hash, data.space_address
+        );
+        return;
+    }
+
+    tokio::task::spawn(async move {
+        let state = sim2h_handle.state().get_clone().await;
+        let to_agent_id = data.to_agent_id.clone();
+        let to_url = state.lookup_joined(&space_hash, &to_agent_id).cloned();
+        let queued = DirectMessageQueuedData {
+            space_address: data.space_address.clone(),
+            request_id: data.request_id.clone(),
+            to_agent_id: data.to_agent_id.clone(),
+            from_agent_id: data.from_agent_id.clone(),
+            ttl_secs: sim2h_handle.dm_queue().ttl().as_secs(),
+        };
+        let size = data.content.as_bytes().len();
+        let message = WireMessage::Lib3hToClient(
             ht::span_wrap_encode!(
                 tracing::Level::INFO,
                 Lib3hToClient::HandleSendDirectMessage(data)
             )
             .into(),
-        ),
-    );
+        );
+        match to_url {
+            Some(to_url) => sim2h_handle.send(to_agent_id, to_url, &message),
+            None if sim2h_handle.dm_queue().is_enabled() => {
+                match sim2h_handle
+                    .dm_queue()
+                    .enqueue(&space_hash, &to_agent_id, message, size)
+                {
+                    Ok(()) => {
+                        debug!("queued direct message for absent agent {}", to_agent_id);
+                        sim2h_handle.send(signer, uri, &WireMessage::DirectMessageQueued(queued));
+                    }
+                    Err(e) => warn!("dropping direct message to absent agent: {}", e),
+                }
+            }
+            None => error!("unvalidated proxy agent {}", &to_agent_id),
+        }
+    });
 }
 
 #[instrument(level = "info", skip(sim2h_handle))]
@@ -1064,15 +1280,50 @@ fn spawn_handle_message_query_entry_result(
     );
 }
 
+/// Optional settings of a sim2h server, see `run_sim2h_with_options`.
+#[derive(Clone, Debug, Default)]
+pub struct Sim2hOptions {
+    /// where the server's timing metrics get published
+    pub metric_publisher: MetricPublisherConfig,
+    /// limits on the messages of joined agents
+    pub rate_limit: RateLimitConfig,
+    /// toml file with the `admission::AdmissionPolicy` for joining spaces,
+    /// re-read on SIGHUP. Anyone can join any space if `None`
+    pub admission_policy_file: Option<std::path::PathBuf>,
+    /// queue direct messages to agents that are not connected, and deliver
+    /// them when they join again. Such messages are dropped if `None`
+    pub dm_queue: Option<DmQueueConfig>,
+    /// the sim2h nodes spaces are partitioned between, and which one we are.
+    /// Every space is served here if `None`
+    pub federation: Option<FederationConfig>,
+    /// the address to serve the `admin` http api at, e.g. `127.0.0.1:9100`.
+    /// No admin api is served if `None`
+    pub admin_bind_address: Option<String>,
+}
+
 /// creates a tokio runtime and executes the Sim2h instance within it
-/// returns the runtime so the user can choose how to manage the main loop
+/// returns the runtime so the user can choose how to manage the main loop,
+/// and a receiver for the bound uri, or the error sim2h failed to start with
 pub fn run_sim2h(
     crypto: Box<dyn CryptoSystem>,
     bind_spec: Lib3hUri,
     dht_algorithm: DhtAlgorithm,
 ) -> (
     tokio::runtime::Runtime,
-    tokio::sync::oneshot::Receiver<Lib3hUri>,
+    tokio::sync::oneshot::Receiver<Sim2hResult<Lib3hUri>>,
+) {
+    run_sim2h_with_options(crypto, bind_spec, dht_algorithm, Sim2hOptions::default())
+}
+
+/// same as `run_sim2h` but with non-default `Sim2hOptions`
+pub fn run_sim2h_with_options(
+    crypto: Box<dyn CryptoSystem>,
+    bind_spec: Lib3hUri,
+    dht_algorithm: DhtAlgorithm,
+    options: Sim2hOptions,
+) -> (
+    tokio::runtime::Runtime,
+    tokio::sync::oneshot::Receiver<Sim2hResult<Lib3hUri>>,
 ) {
     let rt = tokio::runtime::Builder::new()
         .enable_all()
@@ -1085,8 +1336,15 @@ pub fn run_sim2h(
     let (bind_send, bind_recv) = tokio::sync::oneshot::channel();
 
     rt.spawn(async move {
-        let sim2h = Sim2h::new(crypto, bind_spec, dht_algorithm);
-        let _ = bind_send.send(sim2h.bound_uri.clone().unwrap());
+        let sim2h = match Sim2h::new(crypto, bind_spec, dht_algorithm, options) {
+            Ok(sim2h) => sim2h,
+            Err(e) => {
+                error!("could not start sim2h: {}", e);
+                let _ = bind_send.send(Err(e));
+                return;
+            }
+        };
+        let _ = bind_send.send(Ok(sim2h.bound_uri.clone().unwrap()));
 
         /*
         tokio::task::spawn(async move {
@@ -1157,15 +1415,17 @@ pub fn run_sim2h(
 
 /// a Sim2h server instance - manages connections between holochain instances
 pub struct Sim2h {
-    bound_listener: Option<TcpWssServer>,
+    bound_listener: Option<Sim2hWssServer>,
     metric_task: Option<BoxFuture<'static, ()>>,
     pub bound_uri: Option<Lib3hUri>,
-    wss_send: crossbeam_channel::Sender<TcpWss>,
-    wss_recv: crossbeam_channel::Receiver<TcpWss>,
+    wss_send: crossbeam_channel::Sender<Sim2hWss>,
+    wss_recv: crossbeam_channel::Receiver<Sim2hWss>,
     connection_mgr_evt_recv: ConnectionMgrEventRecv,
     num_ticks: u64,
     /// when should we try to resync nodes that are still missing aspect data
     missing_aspects_resync_schedule: Schedule,
+    /// when should we check which federation nodes can be reached
+    federation_check_schedule: Schedule,
     sim2h_handle: Sim2hHandle,
     metric_gen: MetricsTimerGenerator,
 }
@@ -1178,11 +1438,21 @@ impl Sim2h {
         crypto: Box<dyn CryptoSystem>,
         bind_spec: Lib3hUri,
         dht_algorithm: DhtAlgorithm,
-    ) -> Self {
+        options: Sim2hOptions,
+    ) -> Sim2hResult<Self> {
         // make sure if a thread panics, the whole process exits
         assert!(*SET_THREAD_PANIC_FATAL);
 
-        let (metric_gen, metric_task) = MetricsTimerGenerator::new();
+        let (metric_gen, metric_task) = MetricsTimerGenerator::new(options.metric_publisher);
+
+        let admission = match options.admission_policy_file {
+            Some(policy_file) => Admission::from_file(policy_file)
+                .map_err(|e| format!("could not load admission policy: {}", e))?,
+            None => Admission::open(),
+        };
+
+        let federation = Federation::new(options.federation)
+            .map_err(|e| format!("invalid federation config: {}", e))?;
 
         let (connection_mgr, connection_mgr_evt_recv, connection_count) = ConnectionMgr::new();
 
@@ -1193,17 +1463,18 @@ impl Sim2h {
             metric_gen.clone(),
             connection_mgr,
             connection_count,
+            options.rate_limit,
+            admission,
+            options.dm_queue,
+            federation,
         );
 
-        let config = TcpBindConfig::default();
-        //        let config = TlsBindConfig::new(config).dev_certificate();
+        if let Some(bind_address) = options.admin_bind_address {
+            admin::spawn_admin_api(&bind_address, sim2h_handle.clone())
+                .map_err(|e| format!("could not serve admin api: {}", e))?;
+        }
 
-        // if we don't get any messages within a timeframe from a connection,
-        // the connection will throw a timeout error and disconnect.
-        let config = WssBindConfig::new(config)
-            .disconnect_on_slow_pong_ms(Some(NO_MESSAGE_CONNECTION_TIMEOUT_MS));
-        let url = url::Url::from(bind_spec).into();
-        let listen: TcpWssServer = InStreamListenerWss::bind(&url, config).unwrap();
+        let listen = Sim2hWssServer::bind(bind_spec)?;
         let bound_uri = Some(url::Url::from(listen.binding()).into());
 
         let sim2h = Sim2h {
@@ -1219,14 +1490,18 @@ impl Sim2h {
             missing_aspects_resync_schedule: Schedule::new(std::time::Duration::from_millis(
                 RETRY_FETCH_MISSING_ASPECTS_INTERVAL_MS,
             )),
+            federation_check_schedule: Schedule::new(std::time::Duration::from_millis(
+                FEDERATION_CHECK_INTERVAL_MS,
+            )),
             sim2h_handle,
             metric_gen,
         };
 
         // trigger an initial schedule ready event
         let _ = sim2h.missing_aspects_resync_schedule.get_guard();
+        let _ = sim2h.federation_check_schedule.get_guard();
 
-        sim2h
+        Ok(sim2h)
     }
 
     /// if our listening socket has accepted any new connections, set them up
@@ -1363,7 +1638,19 @@ impl Sim2h {
                 Ok((agent_id, wire_message, receipt))
             })() {
                 Ok((source, wire_message, receipt)) => {
-                    sim2h_handle.handle_message(url.clone(), wire_message, source.clone(), receipt);
+                    {
+                        let mut logger = MESSAGE_LOGGER.lock();
+                        if logger.is_running() {
+                            logger.log_in(source.clone(), url.clone(), wire_message.clone());
+                        }
+                    }
+                    sim2h_handle.handle_message(
+                        url.clone(),
+                        wire_message,
+                        source.clone(),
+                        receipt,
+                        payload.as_bytes().len(),
+                    );
                 }
                 Err(error) => {
                     error!(
@@ -1412,6 +1699,25 @@ impl Sim2h {
                 }
             });
 
+            #[cfg(unix)]
+            {
+                // re-read the admission policy file on SIGHUP
+                if self.sim2h_handle.admission().is_enabled() {
+                    let sim2h_handle = self.sim2h_handle.clone();
+                    tokio::task::spawn(async move {
+                        let mut hangups =
+                            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                                .expect("can listen for SIGHUP");
+                        while hangups.recv().await.is_some() {
+                            match sim2h_handle.admission().reload() {
+                                Ok(()) => info!("reloaded admission policy"),
+                                Err(e) => error!("keeping previous admission policy: {}", e),
+                            }
+                        }
+                    });
+                }
+            }
+
             // spawn a task to periodically check for disconnects
             // due to connections being replaced in the sim2h_im_state
             let sim2h_handle = self.sim2h_handle.clone();
@@ -1454,10 +1760,55 @@ impl Sim2h {
             );
         }
 
+        if self.sim2h_handle.federation().is_enabled()
+            && self.federation_check_schedule.should_proceed()
+        {
+            let schedule_guard = self.federation_check_schedule.get_guard();
+            let sim2h_handle = self.sim2h_handle.clone();
+            tokio::task::spawn(check_federation_nodes(sim2h_handle, schedule_guard));
+        }
+
         Ok(did_work)
     }
 }
 
+/// can we open a websocket connection to the sim2h node at `url`
+fn federation_node_reachable(url: Lib3hUri) -> bool {
+    let mut wss = match Sim2hWss::connect(url, FEDERATION_CHECK_TIMEOUT_MS) {
+        Ok(wss) => wss,
+        Err(_) => return false,
+    };
+    let start = std::time::Instant::now();
+    while start.elapsed() < std::time::Duration::from_millis(FEDERATION_CHECK_TIMEOUT_MS) {
+        match wss.check_ready() {
+            Ok(true) => return true,
+            Ok(false) => std::thread::sleep(std::time::Duration::from_millis(10)),
+            Err(_) => return false,
+        }
+    }
+    false
+}
+
+/// marks the other federation nodes as reachable or not in the status.
+/// Their spaces stay theirs: agents keep being redirected to them.
+async fn check_federation_nodes(sim2h_handle: Sim2hHandle, _schedule_guard: ScheduleGuard) {
+    for (node, url) in sim2h_handle.federation().peers() {
+        let reachable = tokio::task::spawn_blocking(move || federation_node_reachable(url))
+            .await
+            .unwrap_or(false);
+        if sim2h_handle.federation().set_reachable(node, reachable) {
+            if reachable {
+                info!("federation node {} is reachable again", node);
+            } else {
+                warn!(
+                    "federation node {} is unreachable, its spaces are unavailable",
+                    node
+                );
+            }
+        }
+    }
+}
+
 async fn missing_aspects_resync(sim2h_handle: Sim2hHandle, _schedule_guard: ScheduleGuard) {
     let gossip_full_start = std::time::Instant::now();
 
@@ -1499,7 +1850,7 @@ async fn missing_aspects_resync(sim2h_handle: Sim2hHandle, _schedule_guard: Sche
 }
 
 struct EntriesAlreadyFetched {
-    entries: std::collections::HashMap<MonoEntryHash, std::time::Instant>,
+    entries: std::collections::HashMap<MonoEntryHash, clock::Instant>,
 }
 
 impl EntriesAlreadyFetched {
@@ -1513,8 +1864,7 @@ impl EntriesAlreadyFetched {
         }
 
         // finally - set
-        self.entries
-            .insert(entry.clone(), std::time::Instant::now());
+        self.entries.insert(entry.clone(), clock::Instant::now());
 
         false
     }
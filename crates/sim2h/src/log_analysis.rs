@@ -0,0 +1,271 @@
+//! analysis of the messages read back from a message log:
+//! per agent timelines, and how long published aspects take to reach other agents
+
+use crate::{
+    message_log::{Direction, MessageLogEntry},
+    WireMessage,
+};
+use chrono::{DateTime, Utc};
+use lib3h_protocol::{
+    data_types::StoreEntryAspectData,
+    protocol::{ClientToLib3h, Lib3hToClient},
+};
+use std::collections::BTreeMap;
+
+/// the messages each agent sent and received, in the order they were logged
+pub fn agent_timelines(entries: &[MessageLogEntry]) -> BTreeMap<String, Vec<&MessageLogEntry>> {
+    let mut timelines: BTreeMap<String, Vec<&MessageLogEntry>> = BTreeMap::new();
+    for entry in entries {
+        timelines
+            .entry(entry.agent.to_string())
+            .or_insert_with(Vec::new)
+            .push(entry);
+    }
+    timelines
+}
+
+/// when an aspect was published and when it reached each other agent
+#[derive(Debug, Clone, PartialEq)]
+pub struct AspectPropagation {
+    pub aspect_address: String,
+    pub publisher: String,
+    pub published: DateTime<Utc>,
+    /// milliseconds from the publish to the first `HandleStoreEntryAspect`, by agent
+    pub stored_after_ms: BTreeMap<String, i64>,
+}
+
+/// propagation latency statistics in milliseconds
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencySummary {
+    pub count: usize,
+    pub mean: f64,
+    pub p50: i64,
+    pub p95: i64,
+    pub max: i64,
+}
+
+/// the propagation of every aspect published in a message log
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PropagationReport {
+    pub aspects: Vec<AspectPropagation>,
+}
+
+impl PropagationReport {
+    /// matches each `PublishEntry` aspect with the `HandleStoreEntryAspect`
+    /// messages sim2h sent for it to the other agents
+    pub fn from_entries(entries: &[MessageLogEntry]) -> Self {
+        let mut aspects: Vec<AspectPropagation> = Vec::new();
+        let mut by_address: BTreeMap<String, usize> = BTreeMap::new();
+        for entry in entries {
+            match (entry.direction, &entry.message) {
+                (Direction::In, WireMessage::ClientToLib3h(span_wrap)) => {
+                    if let ClientToLib3h::PublishEntry(data) = &span_wrap.data {
+                        for aspect in &data.entry.aspect_list {
+                            let aspect_address = aspect.aspect_address.to_string();
+                            // the first publish of an aspect is the one that counts
+                            if !by_address.contains_key(&aspect_address) {
+                                by_address.insert(aspect_address.clone(), aspects.len());
+                                aspects.push(AspectPropagation {
+                                    aspect_address,
+                                    publisher: entry.agent.to_string(),
+                                    published: entry.time,
+                                    stored_after_ms: BTreeMap::new(),
+                                });
+                            }
+                        }
+                    }
+                }
+                (Direction::Out, WireMessage::Lib3hToClient(span_wrap)) => {
+                    Self::stored(&mut aspects, &by_address, entry, &span_wrap.data);
+                }
+                (Direction::Out, WireMessage::MultiSend(messages)) => {
+                    for span_wrap in messages {
+                        Self::stored(&mut aspects, &by_address, entry, &span_wrap.data);
+                    }
+                }
+                _ => (),
+            }
+        }
+        Self { aspects }
+    }
+
+    fn stored(
+        aspects: &mut [AspectPropagation],
+        by_address: &BTreeMap<String, usize>,
+        entry: &MessageLogEntry,
+        message: &Lib3hToClient,
+    ) {
+        if let Lib3hToClient::HandleStoreEntryAspect(StoreEntryAspectData {
+            entry_aspect, ..
+        }) = message
+        {
+            if let Some(index) = by_address.get(&entry_aspect.aspect_address.to_string()) {
+                let aspect = &mut aspects[*index];
+                let agent = entry.agent.to_string();
+                if agent != aspect.publisher {
+                    let latency = (entry.time - aspect.published).num_milliseconds();
+                    aspect.stored_after_ms.entry(agent).or_insert(latency);
+                }
+            }
+        }
+    }
+
+    /// the aspects no other agent was sent
+    pub fn unpropagated(&self) -> impl Iterator<Item = &AspectPropagation> {
+        self.aspects
+            .iter()
+            .filter(|aspect| aspect.stored_after_ms.is_empty())
+    }
+
+    /// statistics over the latency of every aspect reaching every agent
+    pub fn summary(&self) -> LatencySummary {
+        let mut latencies: Vec<i64> = self
+            .aspects
+            .iter()
+            .flat_map(|aspect| aspect.stored_after_ms.values().cloned())
+            .collect();
+        if latencies.is_empty() {
+            return LatencySummary::default();
+        }
+        latencies.sort();
+        let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+        LatencySummary {
+            count: latencies.len(),
+            mean: latencies.iter().sum::<i64>() as f64 / latencies.len() as f64,
+            p50: percentile(50),
+            p95: percentile(95),
+            max: *latencies.last().unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use holochain_tracing as ht;
+    use lib3h_protocol::data_types::{EntryAspectData, EntryData, ProvidedEntryData};
+
+    fn entry(ms: i64, direction: Direction, agent: &str, message: WireMessage) -> MessageLogEntry {
+        MessageLogEntry {
+            time: Utc.ymd(2020, 3, 10).and_hms(12, 0, 0) + Duration::milliseconds(ms),
+            direction,
+            agent: agent.into(),
+            uri: format!("ws://{}", agent),
+            message,
+        }
+    }
+
+    fn aspect(address: &str) -> EntryAspectData {
+        EntryAspectData {
+            aspect_address: address.to_string().into(),
+            type_hint: "".to_string(),
+            aspect: b"aspect".to_vec().into(),
+            publish_ts: 0,
+        }
+    }
+
+    fn publish(ms: i64, agent: &str, addresses: &[&str]) -> MessageLogEntry {
+        entry(
+            ms,
+            Direction::In,
+            agent,
+            WireMessage::ClientToLib3h(ht::test_wrap_enc(ClientToLib3h::PublishEntry(
+                ProvidedEntryData {
+                    space_address: "space".to_string().into(),
+                    provider_agent_id: agent.into(),
+                    entry: EntryData {
+                        entry_address: "entry".to_string().into(),
+                        aspect_list: addresses.iter().map(|address| aspect(address)).collect(),
+                    },
+                },
+            ))),
+        )
+    }
+
+    fn store(address: &str) -> ht::EncodedSpanWrap<Lib3hToClient> {
+        ht::test_wrap_enc(Lib3hToClient::HandleStoreEntryAspect(
+            StoreEntryAspectData {
+                request_id: "".into(),
+                space_address: "space".to_string().into(),
+                provider_agent_id: "alice".into(),
+                entry_address: "entry".to_string().into(),
+                entry_aspect: aspect(address),
+            },
+        ))
+    }
+
+    #[test]
+    fn can_build_agent_timelines() {
+        let entries = vec![
+            entry(0, Direction::In, "alice", WireMessage::Ping),
+            entry(1, Direction::Out, "bob", WireMessage::Pong),
+            entry(2, Direction::Out, "alice", WireMessage::Pong),
+        ];
+        let timelines = agent_timelines(&entries);
+        assert_eq!(2, timelines.len());
+        assert_eq!(vec![&entries[0], &entries[2]], timelines["alice"]);
+    }
+
+    #[test]
+    fn measures_aspect_propagation() {
+        let entries = vec![
+            publish(0, "alice", &["a1", "a2"]),
+            // the publisher being sent its own aspect doesn't count
+            entry(
+                5,
+                Direction::Out,
+                "alice",
+                WireMessage::Lib3hToClient(store("a1")),
+            ),
+            entry(
+                10,
+                Direction::Out,
+                "bob",
+                WireMessage::Lib3hToClient(store("a1")),
+            ),
+            entry(
+                30,
+                Direction::Out,
+                "carol",
+                WireMessage::MultiSend(vec![store("a1"), store("unknown")]),
+            ),
+            // only the first store for an agent counts
+            entry(
+                50,
+                Direction::Out,
+                "bob",
+                WireMessage::Lib3hToClient(store("a1")),
+            ),
+            publish(60, "bob", &["a1"]),
+        ];
+        let report = PropagationReport::from_entries(&entries);
+        assert_eq!(2, report.aspects.len());
+        let a1 = &report.aspects[0];
+        assert_eq!("alice", a1.publisher);
+        assert_eq!(
+            vec![("bob".to_string(), 10), ("carol".to_string(), 30)],
+            a1.stored_after_ms
+                .iter()
+                .map(|(agent, ms)| (agent.clone(), *ms))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["a2"],
+            report
+                .unpropagated()
+                .map(|aspect| aspect.aspect_address.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            LatencySummary {
+                count: 2,
+                mean: 20.0,
+                p50: 10,
+                p95: 10,
+                max: 30,
+            },
+            report.summary()
+        );
+    }
+}
@@ -1,19 +1,42 @@
-use crate::WireMessage;
-use chrono::{DateTime, Utc};
+//! logging of every wire message sim2h receives and sends to a tab separated file,
+//! and reading such files back for analysis and replay
+
+use crate::{error::*, WireMessage};
+use chrono::{DateTime, TimeZone, Utc};
 use holochain_tracing::prelude::*;
 use holochain_tracing_macros::newrelic_autotrace;
 use lazy_static::lazy_static;
 use lib3h_protocol::{types::AgentPubKey, uri::Lib3hUri};
 use parking_lot::Mutex;
 use serde::Serialize;
-use std::{collections::LinkedList, fs::OpenOptions, io::Write, path::PathBuf};
+use std::{
+    collections::LinkedList,
+    convert::TryFrom,
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// how the time of a message is written to the log
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f UTC";
 
-#[derive(Serialize, Debug)]
-enum Direction {
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
     In,
     Out,
 }
 
+impl TryFrom<&str> for Direction {
+    type Error = Sim2hError;
+    fn try_from(direction: &str) -> Sim2hResult<Self> {
+        match direction {
+            "In" => Ok(Direction::In),
+            "Out" => Ok(Direction::Out),
+            _ => Err(format!("unknown message direction: {}", direction).into()),
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct MessageLog {
     time: String,
@@ -55,8 +78,10 @@ impl MessageLogger {
         }
     }
 
+    /// Stops logging, writing out what is left in the buffer.
     pub fn stop(&mut self) {
         self.running = false;
+        self.write_buffer();
     }
 
     pub fn is_running(&self) -> bool {
@@ -75,28 +100,35 @@ impl MessageLogger {
                 if !logger.is_running() {
                     return;
                 }
-                if let Ok(mut file) = OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(logger.file_path())
-                {
-                    let to_append = logger
-                        .buffer
-                        .split_off(0)
-                        .into_iter()
-                        .map(|log| Self::log_to_line(&log))
-                        .collect::<Vec<String>>()
-                        .join("\n");
-                    if let Err(e) = file.write(to_append.as_bytes()) {
-                        error!("Error writing log file: {:?}", e);
-                    }
-                } else {
-                    error!("Could not open log file!")
-                }
+                logger.write_buffer();
             })
             .expect("Could not spawn logger thread");
     }
 
+    /// Takes all items from the buffer and appends them to the log file, one line each.
+    fn write_buffer(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        if let Ok(mut file) = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.file_path())
+        {
+            let to_append = self
+                .buffer
+                .split_off(0)
+                .into_iter()
+                .map(|log| Self::log_to_line(&log) + "\n")
+                .collect::<String>();
+            if let Err(e) = file.write_all(to_append.as_bytes()) {
+                error!("Error writing log file: {:?}", e);
+            }
+        } else {
+            error!("Could not open log file!")
+        }
+    }
+
     /// Serializes a `MessageLog` item to a line that gets added to the log file.
     /// Creates a tab-separated concatenation of the logs elements.
     fn log_to_line(log: &MessageLog) -> String {
@@ -112,7 +144,7 @@ impl MessageLogger {
 
     fn time() -> String {
         let now: DateTime<Utc> = Utc::now();
-        format!("{}", now)
+        now.format(TIME_FORMAT).to_string()
     }
 
     pub fn log_in(&mut self, agent: AgentPubKey, uri: Lib3hUri, message: WireMessage) {
@@ -147,3 +179,98 @@ impl MessageLogger {
         self.file_path.clone()
     }
 }
+
+/// a message read back from a message log file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageLogEntry {
+    pub time: DateTime<Utc>,
+    pub direction: Direction,
+    /// the agent that signed an incoming message, or the recipient of an outgoing one
+    pub agent: AgentPubKey,
+    /// the uri of the connection, unique for every connection
+    pub uri: String,
+    pub message: WireMessage,
+}
+
+impl MessageLogEntry {
+    /// parses a line written by `MessageLogger`
+    pub fn parse_line(line: &str) -> Sim2hResult<Self> {
+        let fields: Vec<&str> = line.splitn(5, '\t').collect();
+        if fields.len() != 5 {
+            return Err(format!("expected 5 tab separated fields, got {}", fields.len()).into());
+        }
+        Ok(MessageLogEntry {
+            time: Utc
+                .datetime_from_str(fields[0], TIME_FORMAT)
+                .map_err(|e| format!("invalid time {}: {}", fields[0], e))?,
+            direction: Direction::try_from(fields[1])?,
+            agent: fields[2].into(),
+            uri: fields[3].to_string(),
+            message: serde_json::from_str(fields[4])
+                .map_err(|e| format!("invalid message: {}", e))?,
+        })
+    }
+}
+
+/// reads all the messages of a message log file, in the order they were logged
+pub fn read_message_log<P: AsRef<Path>>(path: P) -> Sim2hResult<Vec<MessageLogEntry>> {
+    let file = std::fs::File::open(path)?;
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(
+            MessageLogEntry::parse_line(&line).map_err(|e| format!("line {}: {}", index + 1, e))?,
+        );
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_back_logged_lines() {
+        let log = MessageLog {
+            time: MessageLogger::time(),
+            uri: Lib3hUri::with_undefined(),
+            agent: "HcSAlice".into(),
+            direction: Direction::Out,
+            message: WireMessage::Ack(42),
+        };
+        let entry = MessageLogEntry::parse_line(&MessageLogger::log_to_line(&log)).unwrap();
+        assert_eq!(Direction::Out, entry.direction);
+        assert_eq!(AgentPubKey::from("HcSAlice"), entry.agent);
+        assert_eq!(Lib3hUri::with_undefined().to_string(), entry.uri);
+        assert_eq!(WireMessage::Ack(42), entry.message);
+        assert_eq!(log.time, entry.time.format(TIME_FORMAT).to_string());
+    }
+
+    #[test]
+    fn reports_the_line_of_bad_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messages.log");
+        std::fs::write(
+            &path,
+            "2020-03-10 12:00:00.5 UTC\tIn\tHcSAlice\tws://a\t\"Ping\"\n\n\
+             2020-03-10 12:00:01 UTC\tSideways\tHcSAlice\tws://a\t\"Ping\"\n",
+        )
+        .unwrap();
+        let error = read_message_log(&path).unwrap_err().to_string();
+        assert!(error.contains("line 3"));
+        assert!(error.contains("Sideways"));
+
+        std::fs::write(
+            &path,
+            "2020-03-10 12:00:00.5 UTC\tIn\tHcSAlice\tws://a\t\"Ping\"\n",
+        )
+        .unwrap();
+        let entries = read_message_log(&path).unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(WireMessage::Ping, entries[0].message);
+        assert_eq!(500, entries[0].time.timestamp_subsec_millis());
+    }
+}
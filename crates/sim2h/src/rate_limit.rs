@@ -0,0 +1,491 @@
+//! token bucket rate limiting of the messages joined agents send to sim2h
+//! limits apply per agent (within a space) and per space, to both the
+//! number of messages and their size in bytes
+
+use crate::{clock::Instant, MutexExt};
+use holochain_locksmith::Mutex;
+use lib3h_protocol::types::{AgentPubKey, SpaceHash};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+
+/// how often we drop the state of agents and spaces that are back to full buckets
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// a bucket refilling at `rate` tokens per second, holding at most `burst` tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TokenBucketConfig {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+/// limits on message count and byte volume, unlimited if `None`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub messages: Option<TokenBucketConfig>,
+    #[serde(default)]
+    pub bytes: Option<TokenBucketConfig>,
+}
+
+impl QuotaConfig {
+    fn is_unlimited(&self) -> bool {
+        self.messages.is_none() && self.bytes.is_none()
+    }
+}
+
+/// rate limits applied to the messages of joined agents
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub per_agent: QuotaConfig,
+    #[serde(default)]
+    pub per_space: QuotaConfig,
+    /// disconnect agents after this many messages in a row over their own limit.
+    /// messages refused because their space is over its limit don't count
+    #[serde(default)]
+    pub disconnect_after_violations: Option<u32>,
+}
+
+/// rate limiting counters reported in `StatusData`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitStats {
+    /// total messages refused for being over a limit
+    pub limited_messages: u64,
+    /// total agents disconnected for repeatedly going over a limit
+    pub disconnected_agents: u64,
+    /// agents whose last message was over their own limit
+    pub limited_agents: usize,
+}
+
+/// what to do with an incoming message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimitDecision {
+    Allow,
+    /// refuse the message
+    Limit,
+    /// refuse the message and disconnect the agent
+    Disconnect,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    config: TokenBucketConfig,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(config: TokenBucketConfig, now: Instant) -> Self {
+        Self {
+            config,
+            tokens: config.burst,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.config.rate).min(self.config.burst);
+        self.last_refill = now;
+    }
+
+    /// a full bucket lets anything through, so that messages bigger
+    /// than the burst size can't get stuck forever
+    fn allows(&self, amount: f64) -> bool {
+        self.tokens >= amount.min(self.config.burst)
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.tokens -= amount;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.config.burst
+    }
+}
+
+#[derive(Debug)]
+struct Quota {
+    messages: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+}
+
+impl Quota {
+    fn new(config: &QuotaConfig, now: Instant) -> Self {
+        Self {
+            messages: config.messages.map(|c| TokenBucket::new(c, now)),
+            bytes: config.bytes.map(|c| TokenBucket::new(c, now)),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        self.buckets_mut()
+            .for_each(|(bucket, _)| bucket.refill(now));
+    }
+
+    fn allows(&mut self, size: usize) -> bool {
+        self.buckets_mut()
+            .all(|(bucket, is_bytes)| bucket.allows(if is_bytes { size as f64 } else { 1.0 }))
+    }
+
+    fn take(&mut self, size: usize) {
+        self.buckets_mut()
+            .for_each(|(bucket, is_bytes)| bucket.take(if is_bytes { size as f64 } else { 1.0 }));
+    }
+
+    fn is_full(&mut self) -> bool {
+        self.buckets_mut().all(|(bucket, _)| bucket.is_full())
+    }
+
+    fn buckets_mut(&mut self) -> impl Iterator<Item = (&mut TokenBucket, bool)> + '_ {
+        self.messages
+            .iter_mut()
+            .map(|bucket| (bucket, false))
+            .chain(self.bytes.iter_mut().map(|bucket| (bucket, true)))
+    }
+}
+
+#[derive(Debug)]
+struct AgentState {
+    quota: Quota,
+    violations: u32,
+}
+
+#[derive(Debug)]
+struct RateLimiterState {
+    agents: HashMap<(SpaceHash, AgentPubKey), AgentState>,
+    spaces: HashMap<SpaceHash, Quota>,
+    stats: RateLimitStats,
+    last_prune: Instant,
+}
+
+/// keeps the token buckets of every agent and space
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    state: Mutex<RateLimiterState>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(RateLimiterState {
+                agents: HashMap::new(),
+                spaces: HashMap::new(),
+                stats: RateLimitStats::default(),
+                last_prune: Instant::now(),
+            }),
+        }
+    }
+
+    /// false if no limits are configured
+    pub fn is_enabled(&self) -> bool {
+        !(self.config.per_agent.is_unlimited() && self.config.per_space.is_unlimited())
+    }
+
+    /// account for a message of `size` bytes from `agent` in `space`
+    pub fn check(&self, space: &SpaceHash, agent: &AgentPubKey, size: usize) -> RateLimitDecision {
+        self.check_at(space, agent, size, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        space: &SpaceHash,
+        agent: &AgentPubKey,
+        size: usize,
+        now: Instant,
+    ) -> RateLimitDecision {
+        if !self.is_enabled() {
+            return RateLimitDecision::Allow;
+        }
+        let mut state = self.state.f_lock();
+        let state = &mut *state;
+        if now.saturating_duration_since(state.last_prune) >= PRUNE_INTERVAL {
+            Self::prune(state, now);
+        }
+
+        let agent_state = state
+            .agents
+            .entry((space.clone(), agent.clone()))
+            .or_insert_with(|| AgentState {
+                quota: Quota::new(&self.config.per_agent, now),
+                violations: 0,
+            });
+        let space_quota = state
+            .spaces
+            .entry(space.clone())
+            .or_insert_with(|| Quota::new(&self.config.per_space, now));
+        agent_state.quota.refill(now);
+        space_quota.refill(now);
+
+        let agent_allows = agent_state.quota.allows(size);
+        if agent_allows && space_quota.allows(size) {
+            agent_state.quota.take(size);
+            space_quota.take(size);
+            if agent_state.violations > 0 {
+                agent_state.violations = 0;
+                state.stats.limited_agents -= 1;
+            }
+            return RateLimitDecision::Allow;
+        }
+
+        state.stats.limited_messages += 1;
+        if agent_allows {
+            // a busy space is not the fault of the agent that happens to hit its limit
+            return RateLimitDecision::Limit;
+        }
+        if agent_state.violations == 0 {
+            state.stats.limited_agents += 1;
+        }
+        agent_state.violations += 1;
+        match self.config.disconnect_after_violations {
+            Some(max) if agent_state.violations >= max => {
+                // keep the buckets, so that reconnecting doesn't refill them
+                agent_state.violations = 0;
+                state.stats.disconnected_agents += 1;
+                state.stats.limited_agents -= 1;
+                RateLimitDecision::Disconnect
+            }
+            _ => RateLimitDecision::Limit,
+        }
+    }
+
+    /// `agent` disconnected from `space`, so it no longer counts as a limited agent.
+    /// its buckets are kept, reconnecting doesn't refill them.
+    pub fn agent_disconnected(&self, space: &SpaceHash, agent: &AgentPubKey) {
+        let mut state = self.state.f_lock();
+        let state = &mut *state;
+        if let Some(agent_state) = state.agents.get_mut(&(space.clone(), agent.clone())) {
+            if agent_state.violations > 0 {
+                agent_state.violations = 0;
+                state.stats.limited_agents -= 1;
+            }
+        }
+    }
+
+    /// current rate limiting counters
+    pub fn stats(&self) -> RateLimitStats {
+        self.state.f_lock().stats.clone()
+    }
+
+    /// drop the state of agents and spaces that would behave just like new ones
+    fn prune(state: &mut RateLimiterState, now: Instant) {
+        state.agents.retain(|_, agent_state| {
+            agent_state.quota.refill(now);
+            agent_state.violations > 0 || !agent_state.quota.is_full()
+        });
+        state.spaces.retain(|_, quota| {
+            quota.refill(now);
+            !quota.is_full()
+        });
+        state.last_prune = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(config: RateLimitConfig) -> (RateLimiter, SpaceHash, AgentPubKey, AgentPubKey) {
+        (
+            RateLimiter::new(config),
+            SpaceHash::from("space"),
+            AgentPubKey::from("alice"),
+            AgentPubKey::from("bob"),
+        )
+    }
+
+    fn bucket(rate: f64, burst: f64) -> Option<TokenBucketConfig> {
+        Some(TokenBucketConfig { rate, burst })
+    }
+
+    #[test]
+    fn unlimited_by_default() {
+        let (limiter, space, alice, _) = limiter(RateLimitConfig::default());
+        assert!(!limiter.is_enabled());
+        for _ in 0..1000 {
+            assert_eq!(
+                RateLimitDecision::Allow,
+                limiter.check(&space, &alice, 1_000_000)
+            );
+        }
+    }
+
+    #[test]
+    fn limits_messages_per_agent_and_refills() {
+        let (limiter, space, alice, bob) = limiter(RateLimitConfig {
+            per_agent: QuotaConfig {
+                messages: bucket(1.0, 2.0),
+                bytes: None,
+            },
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert_eq!(
+            RateLimitDecision::Allow,
+            limiter.check_at(&space, &alice, 10, now)
+        );
+        assert_eq!(
+            RateLimitDecision::Allow,
+            limiter.check_at(&space, &alice, 10, now)
+        );
+        assert_eq!(
+            RateLimitDecision::Limit,
+            limiter.check_at(&space, &alice, 10, now)
+        );
+        // other agents have their own buckets
+        assert_eq!(
+            RateLimitDecision::Allow,
+            limiter.check_at(&space, &bob, 10, now)
+        );
+        assert_eq!(
+            RateLimitStats {
+                limited_messages: 1,
+                disconnected_agents: 0,
+                limited_agents: 1,
+            },
+            limiter.stats()
+        );
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            RateLimitDecision::Allow,
+            limiter.check_at(&space, &alice, 10, later)
+        );
+        assert_eq!(0, limiter.stats().limited_agents);
+    }
+
+    #[test]
+    fn limits_bytes_per_space() {
+        let (limiter, space, alice, bob) = limiter(RateLimitConfig {
+            per_space: QuotaConfig {
+                messages: None,
+                bytes: bucket(100.0, 1000.0),
+            },
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert_eq!(
+            RateLimitDecision::Allow,
+            limiter.check_at(&space, &alice, 600, now)
+        );
+        assert_eq!(
+            RateLimitDecision::Limit,
+            limiter.check_at(&space, &bob, 600, now)
+        );
+        // a message bigger than the burst still passes once the bucket is full
+        let later = now + Duration::from_secs(10);
+        assert_eq!(
+            RateLimitDecision::Allow,
+            limiter.check_at(&space, &bob, 5000, later)
+        );
+    }
+
+    #[test]
+    fn disconnects_repeat_offenders() {
+        let (limiter, space, alice, _) = limiter(RateLimitConfig {
+            per_agent: QuotaConfig {
+                messages: bucket(1.0, 1.0),
+                bytes: None,
+            },
+            disconnect_after_violations: Some(3),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        assert_eq!(
+            RateLimitDecision::Allow,
+            limiter.check_at(&space, &alice, 1, now)
+        );
+        assert_eq!(
+            RateLimitDecision::Limit,
+            limiter.check_at(&space, &alice, 1, now)
+        );
+        assert_eq!(
+            RateLimitDecision::Limit,
+            limiter.check_at(&space, &alice, 1, now)
+        );
+        assert_eq!(
+            RateLimitDecision::Disconnect,
+            limiter.check_at(&space, &alice, 1, now)
+        );
+        assert_eq!(
+            RateLimitStats {
+                limited_messages: 3,
+                disconnected_agents: 1,
+                limited_agents: 0,
+            },
+            limiter.stats()
+        );
+
+        // reconnecting doesn't refill the buckets, and starts counting violations afresh
+        assert_eq!(
+            RateLimitDecision::Limit,
+            limiter.check_at(&space, &alice, 1, now)
+        );
+        assert_eq!(1, limiter.stats().limited_agents);
+    }
+
+    #[test]
+    fn space_limits_are_not_agent_violations() {
+        let (limiter, space, alice, bob) = limiter(RateLimitConfig {
+            per_agent: QuotaConfig {
+                messages: bucket(1.0, 10.0),
+                bytes: None,
+            },
+            per_space: QuotaConfig {
+                messages: bucket(1.0, 1.0),
+                bytes: None,
+            },
+            disconnect_after_violations: Some(2),
+        });
+        let now = Instant::now();
+        assert_eq!(
+            RateLimitDecision::Allow,
+            limiter.check_at(&space, &alice, 1, now)
+        );
+        for _ in 0..5 {
+            assert_eq!(
+                RateLimitDecision::Limit,
+                limiter.check_at(&space, &bob, 1, now)
+            );
+        }
+        assert_eq!(
+            RateLimitStats {
+                limited_messages: 5,
+                disconnected_agents: 0,
+                limited_agents: 0,
+            },
+            limiter.stats()
+        );
+    }
+
+    #[test]
+    fn disconnected_agents_are_no_longer_limited() {
+        let (limiter, space, alice, bob) = limiter(RateLimitConfig {
+            per_agent: QuotaConfig {
+                messages: bucket(1.0, 1.0),
+                bytes: None,
+            },
+            ..Default::default()
+        });
+        let now = Instant::now();
+        limiter.check_at(&space, &alice, 1, now);
+        assert_eq!(
+            RateLimitDecision::Limit,
+            limiter.check_at(&space, &alice, 1, now)
+        );
+        assert_eq!(1, limiter.stats().limited_agents);
+
+        limiter.agent_disconnected(&space, &alice);
+        limiter.agent_disconnected(&space, &bob);
+        assert_eq!(0, limiter.stats().limited_agents);
+
+        // reconnecting doesn't refill the buckets
+        assert_eq!(
+            RateLimitDecision::Limit,
+            limiter.check_at(&space, &alice, 1, now)
+        );
+        assert_eq!(1, limiter.stats().limited_agents);
+    }
+}
@@ -11,7 +11,7 @@ impl Drop for ScheduleGuard {
 pub struct Schedule {
     sender: tokio::sync::mpsc::Sender<()>,
     receiver: tokio::sync::mpsc::Receiver<()>,
-    last_true: std::time::Instant,
+    last_true: crate::clock::Instant,
     interval: std::time::Duration,
 }
 
@@ -19,7 +19,7 @@ impl Schedule {
     /// create a new Schedule helper instance
     pub fn new(interval: std::time::Duration) -> Self {
         let (sender, receiver) = tokio::sync::mpsc::channel(1);
-        let last_true = std::time::Instant::now();
+        let last_true = crate::clock::Instant::now();
         Self {
             sender,
             receiver,
@@ -34,7 +34,7 @@ impl Schedule {
             return false;
         }
         if let Ok(()) = self.receiver.try_recv() {
-            self.last_true = std::time::Instant::now();
+            self.last_true = crate::clock::Instant::now();
             return true;
         }
         false
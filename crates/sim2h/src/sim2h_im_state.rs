@@ -36,6 +36,16 @@ impl AtomicInc for AtomicU64 {
     }
 }
 
+/// decides whether an agent may join a space, given the number of agents
+/// connected to it and whether this agent is one of them already
+pub struct JoinCheck(pub Box<dyn FnOnce(usize, bool) -> Result<(), String> + Send>);
+
+impl std::fmt::Debug for JoinCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "JoinCheck")
+    }
+}
+
 /// Append-Only-Log Entries for mutating the Sim2h Store
 /// with a list of these, we should be able to reconstruct the store
 /// even if they come out-of-order.
@@ -52,6 +62,18 @@ enum AolEntry {
         uri: Lib3hUri,
     },
 
+    // a NewConnection that only happens if `check` passes, checked against
+    // the same state it gets inserted into, so concurrent joins can't both
+    // take the last place in a space
+    AdmitConnection {
+        aol_idx: u64,
+        space_hash: SpaceHash,
+        agent_id: AgentId,
+        uri: Lib3hUri,
+        check: JoinCheck,
+        response: tokio::sync::oneshot::Sender<Result<(), String>>,
+    },
+
     // we need to be able to drop all connections across spaces based on
     // the uri of the connected socket (i.e. in case of a socket read/write err)
     // - clear all `holding` aspects (to prepare for another connection
@@ -114,11 +136,11 @@ impl CheckGossipData {
 }
 
 #[derive(Clone)]
-struct UpcomingInstant(pub std::time::Instant);
+struct UpcomingInstant(pub clock::Instant);
 
 impl std::fmt::Debug for UpcomingInstant {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let now = std::time::Instant::now();
+        let now = clock::Instant::now();
         let tmp = match self.0.checked_duration_since(now) {
             Some(d) => format!("{:?} ms", d.as_millis()),
             None => "[expired]".to_string(),
@@ -139,14 +161,14 @@ impl Serialize for UpcomingInstant {
 impl UpcomingInstant {
     pub fn new_ms_from_now(ms: u64) -> Self {
         Self(
-            std::time::Instant::now()
+            clock::Instant::now()
                 .checked_add(std::time::Duration::from_millis(ms))
                 .expect("instant"),
         )
     }
 
     pub fn still_pending(&self) -> bool {
-        let now = std::time::Instant::now();
+        let now = clock::Instant::now();
         self.0 > now
     }
 }
@@ -354,6 +376,26 @@ impl Space {
         out
     }
 
+    /// the number of aspects an agent holds, out of those it should hold
+    fn holding_counts(&self, agent_id: &AgentId) -> Option<(usize, usize)> {
+        let agent_count = self.connections.len() as u64;
+        let agent_loc = self.connections.get(agent_id)?.agent_loc;
+
+        let mut held = 0;
+        let mut should_hold = 0;
+        for (_, entry) in self.entry_to_all_aspects.iter() {
+            if should_store(agent_loc, entry.entry_loc, agent_count, self.redundancy) {
+                for (_, holding) in entry.aspects.iter() {
+                    should_hold += 1;
+                    if holding.contains(agent_id) {
+                        held += 1;
+                    }
+                }
+            }
+        }
+        Some((held, should_hold))
+    }
+
     fn check_insert_connection(&mut self, agent_id: &AgentId, uri: Lib3hUri) {
         let agent_id = self.get_mono_agent_id(agent_id);
         let uri: MonoUri = uri.into();
@@ -607,6 +649,14 @@ impl Store {
                 uri,
                 ..
             } => self.new_connection(space_hash, agent_id, uri),
+            AolEntry::AdmitConnection {
+                space_hash,
+                agent_id,
+                uri,
+                check,
+                response,
+                ..
+            } => self.admit_connection(space_hash, agent_id, uri, check, response),
             AolEntry::DropConnectionByUri { uri, .. } => self.drop_connection_by_uri(uri),
             AolEntry::AgentHoldsAspects {
                 space_hash,
@@ -642,6 +692,24 @@ impl Store {
             .check_insert_connection(&agent_id, uri);
     }
 
+    fn admit_connection(
+        &mut self,
+        space_hash: SpaceHash,
+        agent_id: AgentId,
+        uri: Lib3hUri,
+        check: JoinCheck,
+        response: tokio::sync::oneshot::Sender<Result<(), String>>,
+    ) {
+        let already_joined = self.lookup_joined(&space_hash, &agent_id).is_some();
+        let admitted = (check.0)(self.agents_count(&space_hash), already_joined);
+        if admitted.is_ok() {
+            self.new_connection(space_hash, agent_id, uri);
+        }
+        if let Err(e) = response.send(admitted) {
+            error!("Failed to send admit connection response! {:?}", e);
+        }
+    }
+
     fn drop_connection_inner(space: &mut Space, agent_id: MonoAgentId) {
         // - clear all `holding` aspects (to prepare for another connection)
         space.clear_holding(&agent_id);
@@ -748,11 +816,27 @@ impl Store {
         Some(space.get_gossip_aspects_needed_for_agent(agent_id))
     }
 
+    /// how many aspects a joined agent holds, out of those it should hold
+    pub fn agent_holding_counts(
+        &self,
+        space_hash: &SpaceHash,
+        agent_id: &AgentId,
+    ) -> Option<(usize, usize)> {
+        self.get_space(space_hash)?.holding_counts(agent_id)
+    }
+
     /// how many spaces do we currently have registered?
     pub fn spaces_count(&self) -> usize {
         self.spaces.len()
     }
 
+    /// how many agents are currently joined to a space?
+    pub fn agents_count(&self, space_hash: &SpaceHash) -> usize {
+        self.get_space(space_hash)
+            .map(|space| space.connections.len())
+            .unwrap_or(0)
+    }
+
     /// if we have an active connection for an agent_id - get the uri
     pub fn lookup_joined(&self, space_hash: &SpaceHash, agent_id: &AgentId) -> Option<&Lib3hUri> {
         let agent_id: MonoAgentId = agent_id.clone().into();
@@ -923,6 +1007,37 @@ impl StoreHandle {
         .boxed()
     }
 
+    /// like `new_connection`, but only connects if `check` admits the agent
+    #[allow(clippy::redundant_pattern_matching)]
+    pub async fn admit_connection(
+        &self,
+        space_hash: SpaceHash,
+        agent_id: AgentId,
+        uri: Lib3hUri,
+        check: JoinCheck,
+    ) -> Result<(), String> {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let (sender_c, receiver_c) = tokio::sync::oneshot::channel();
+        if let Err(_) = self.send_mut.send(StoreProto::Mutate(
+            AolEntry::AdmitConnection {
+                aol_idx: self.con_incr.inc(),
+                space_hash,
+                agent_id,
+                uri,
+                check,
+                response: sender,
+            },
+            sender_c,
+        )) {
+            error!("failed to send im store message - shutting down?");
+            // we're probably shutting down, prevent panic!s
+            // note this future will never resolve - because it cannot
+            return futures::future::pending().await;
+        }
+        let _ = receiver_c.await;
+        receiver.await.unwrap()
+    }
+
     /*
     #[must_use]
     pub fn drop_connection(&self, space_hash: SpaceHash, agent_id: AgentId) -> BoxFuture<'static, ()> {
@@ -1230,6 +1345,41 @@ mod tests {
         async_run(async_same_aspect_in_differing_entries_test().boxed());
     }
 
+    async fn async_admit_connection_test() {
+        let space_hash: SpaceHash = "abcd".into();
+        let crypto = Box::new(lib3h_sodium::SodiumCryptoSystem::new());
+        let store = Store::new(crypto, 0, None);
+
+        // at most one agent, checked against the state the connection goes into
+        let max_one = || {
+            JoinCheck(Box::new(|agents_in_space, already_joined| {
+                if already_joined || agents_in_space < 1 {
+                    Ok(())
+                } else {
+                    Err("space is full".to_string())
+                }
+            }))
+        };
+        let joins: Vec<_> = (0..5)
+            .map(|i| {
+                let uri: Lib3hUri = url::Url::parse(&format!("ws://yada{}", i)).unwrap().into();
+                store.admit_connection(space_hash.clone(), gen_agent(), uri, max_one())
+            })
+            .collect();
+        let admitted = futures::future::join_all(joins)
+            .await
+            .into_iter()
+            .filter(Result::is_ok)
+            .count();
+        assert_eq!(1, admitted);
+        assert_eq!(1, store.get_clone().await.agents_count(&space_hash));
+    }
+
+    #[test]
+    fn admit_connection_test() {
+        async_run(async_admit_connection_test().boxed());
+    }
+
     async fn async_gossip_test() {
         let aid1 = gen_agent();
         let aid2 = gen_agent();
@@ -0,0 +1,141 @@
+//! the websocket transports sim2h can listen and connect on
+//! besides tcp, sim2h can run over in-process memory streams, bound to
+//! `mem://<name>` urls, so that several sim2h nodes and their clients
+//! can be tested in a single process
+
+use crate::*;
+use std::io::Result;
+
+/// the url scheme of in-process memory sim2h urls
+pub const MEM_SCHEME: &str = "mem";
+
+pub(crate) type MemWssServer = InStreamListenerWss<InStreamListenerMem>;
+pub type MemWss = InStreamWss<InStreamMem>;
+
+/// a sim2h websocket connection over tcp or memory
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Sim2hWss {
+    Tcp(TcpWss),
+    Mem(MemWss),
+}
+
+impl Sim2hWss {
+    /// connect to a `ws://` sim2h over tcp or a `mem://` sim2h in this process
+    pub fn connect(url: Lib3hUri, timeout_ms: u64) -> Result<Self> {
+        let url: Url2 = url::Url::from(url).into();
+        if url.scheme() == MEM_SCHEME {
+            Self::raw_connect(&url, WssConnectConfig::new(MemConnectConfig::default()))
+        } else {
+            //    let config = WssConnectConfig::new(TlsConnectConfig::new(TcpConnectConfig::default()));
+            let config = WssConnectConfig::new(TcpConnectConfig {
+                connect_timeout_ms: Some(timeout_ms),
+            });
+            Self::raw_connect(&url, config)
+        }
+    }
+}
+
+impl InStream<&mut WsFrame, WsFrame> for Sim2hWss {
+    const URL_SCHEME: &'static str = "ws";
+
+    fn raw_connect<C: InStreamConfig>(url: &Url2, config: C) -> Result<Self> {
+        if url.scheme() == MEM_SCHEME {
+            let mut url = url.clone();
+            url.set_scheme("ws").unwrap();
+            Ok(Sim2hWss::Mem(MemWss::raw_connect(&url, config)?))
+        } else {
+            Ok(Sim2hWss::Tcp(TcpWss::raw_connect(url, config)?))
+        }
+    }
+
+    fn remote_url(&self) -> Url2 {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.remote_url(),
+            Sim2hWss::Mem(wss) => wss.remote_url(),
+        }
+    }
+
+    fn check_ready(&mut self) -> Result<bool> {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.check_ready(),
+            Sim2hWss::Mem(wss) => wss.check_ready(),
+        }
+    }
+
+    fn read(&mut self, data: &mut WsFrame) -> Result<usize> {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.read(data),
+            Sim2hWss::Mem(wss) => wss.read(data),
+        }
+    }
+
+    fn write(&mut self, data: WsFrame) -> Result<usize> {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.write(data),
+            Sim2hWss::Mem(wss) => wss.write(data),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.flush(),
+            Sim2hWss::Mem(wss) => wss.flush(),
+        }
+    }
+}
+
+/// the listening side of `Sim2hWss`
+#[derive(Debug)]
+pub(crate) enum Sim2hWssServer {
+    Tcp(TcpWssServer),
+    Mem(MemWssServer),
+}
+
+impl Sim2hWssServer {
+    /// binds to a `ws://` bind spec over tcp, or to a `mem://` one in memory
+    pub fn bind(bind_spec: Lib3hUri) -> Result<Self> {
+        let mut url: Url2 = url::Url::from(bind_spec).into();
+        let is_mem = url.scheme() == MEM_SCHEME;
+        if is_mem {
+            url.set_scheme("ws").unwrap();
+        }
+
+        // if we don't get any messages within a timeframe from a connection,
+        // the connection will throw a timeout error and disconnect.
+        if is_mem {
+            let config = WssBindConfig::new(MemBindConfig::default())
+                .disconnect_on_slow_pong_ms(Some(NO_MESSAGE_CONNECTION_TIMEOUT_MS));
+            Ok(Sim2hWssServer::Mem(InStreamListenerWss::bind(
+                &url, config,
+            )?))
+        } else {
+            let config = TcpBindConfig::default();
+            //        let config = TlsBindConfig::new(config).dev_certificate();
+            let config = WssBindConfig::new(config)
+                .disconnect_on_slow_pong_ms(Some(NO_MESSAGE_CONNECTION_TIMEOUT_MS));
+            Ok(Sim2hWssServer::Tcp(InStreamListenerWss::bind(
+                &url, config,
+            )?))
+        }
+    }
+
+    /// the url clients connect to, `mem://` for memory listeners
+    pub fn binding(&self) -> Url2 {
+        match self {
+            Sim2hWssServer::Tcp(listen) => listen.binding(),
+            Sim2hWssServer::Mem(listen) => {
+                let mut url = listen.binding();
+                url.set_scheme(MEM_SCHEME).unwrap();
+                url
+            }
+        }
+    }
+
+    pub fn accept(&mut self) -> Result<Sim2hWss> {
+        match self {
+            Sim2hWssServer::Tcp(listen) => listen.accept().map(Sim2hWss::Tcp),
+            Sim2hWssServer::Mem(listen) => listen.accept().map(Sim2hWss::Mem),
+        }
+    }
+}
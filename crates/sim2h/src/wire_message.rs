@@ -1,17 +1,30 @@
 //! encapsulates lib3h ghostmessage for sim2h including security challenge
-use crate::error::Sim2hError;
+use crate::{
+    admission::AdmissionStatus, dm_queue::DmQueueStats, error::Sim2hError,
+    federation::FederationStatus, rate_limit::RateLimitStats,
+};
 use holochain_tracing as ht;
 use holochain_tracing_macros::newrelic_autotrace;
-use lib3h_protocol::{data_types::Opaque, protocol::*, types::SpaceHash};
+use lib3h_protocol::{
+    data_types::Opaque,
+    protocol::*,
+    types::{AgentPubKey, SpaceHash},
+    uri::Lib3hUri,
+};
 use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, convert::TryFrom};
 
 pub type WireMessageVersion = u32;
-pub const WIRE_VERSION: WireMessageVersion = 4;
+pub const WIRE_VERSION: WireMessageVersion = 5;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum WireError {
     MessageWhileInLimbo,
+    /// the message was over a rate limit and was not processed, it
+    /// will be handled if sent again once the limit allows it
+    RateLimited,
+    /// the admission policy refused a `JoinSpace`, for the given reason
+    JoinRefused(String),
     Other(String),
 }
 
@@ -22,6 +35,17 @@ pub struct StatusData {
     pub joined_connections: usize,
     pub redundant_count: u64,
     pub version: u32,
+    #[serde(default)]
+    pub rate_limit: RateLimitStats,
+    /// `None` if there is no admission policy
+    #[serde(default)]
+    pub admission: Option<AdmissionStatus>,
+    /// `None` if direct messages to absent agents are not queued
+    #[serde(default)]
+    pub dm_queue: Option<DmQueueStats>,
+    /// `None` if this sim2h is not part of a federation
+    #[serde(default)]
+    pub federation: Option<FederationStatus>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -29,6 +53,22 @@ pub struct HelloData {
     pub redundant_count: u64,
     pub version: u32,
     pub extra: Option<String>,
+    /// the sim2h node to connect to instead, sent in answer to a `JoinSpace`
+    /// for a space this node does not serve
+    #[serde(default)]
+    pub redirect: Option<Lib3hUri>,
+}
+
+/// sent instead of a `SendDirectMessageResult` when the recipient of a
+/// `SendDirectMessage` is not connected and the message was queued for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirectMessageQueuedData {
+    pub space_address: SpaceHash,
+    pub request_id: String,
+    pub to_agent_id: AgentPubKey,
+    pub from_agent_id: AgentPubKey,
+    /// how long the message will wait for its recipient
+    pub ttl_secs: u64,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -48,6 +88,7 @@ pub enum WireMessage {
     Status,
     StatusResponse(StatusData),
     Ack(u64),
+    DirectMessageQueued(DirectMessageQueuedData),
     Debug,
     DebugResponse((BTreeMap<SpaceHash, String>, String)),
 }
@@ -121,6 +162,7 @@ impl WireMessage {
             }
             WireMessage::Err(_) => "[Error] {:?}",
             WireMessage::Ack(_) => "[Ack] {:?}",
+            WireMessage::DirectMessageQueued(_) => "DirectMessageQueued",
         })
     }
     pub fn try_get_span(&self) -> Option<Vec<&ht::EncodedSpanContext>> {
@@ -0,0 +1,172 @@
+//! sim2h-replay reads the message logs sim2h writes with `--message-log-file`
+//! to show what each agent sent and received, to measure how long published
+//! aspects take to reach the other agents, and to replay the logged traffic
+//! against a fresh sim2h.
+
+mod replay;
+
+use replay::{replay, ReplayOptions};
+use sim2h::{
+    error::Sim2hResult,
+    log_analysis::{agent_timelines, PropagationReport},
+    message_log::read_message_log,
+    DhtAlgorithm,
+};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+enum Cli {
+    #[structopt(name = "timeline", about = "Prints the messages of each agent in order")]
+    Timeline {
+        #[structopt(help = "Message log written by sim2h")]
+        log: PathBuf,
+
+        #[structopt(long, help = "Only print the timeline of this agent")]
+        agent: Option<String>,
+    },
+
+    #[structopt(
+        name = "latency",
+        about = "Measures how long published aspects take to be stored by the other agents"
+    )]
+    Latency {
+        #[structopt(help = "Message log written by sim2h")]
+        log: PathBuf,
+
+        #[structopt(long, help = "Also print the latency of every aspect")]
+        verbose: bool,
+    },
+
+    #[structopt(
+        name = "replay",
+        about = "Replays the incoming messages of a log against a fresh in-process sim2h"
+    )]
+    Replay {
+        #[structopt(help = "Message log written by sim2h")]
+        log: PathBuf,
+
+        #[structopt(
+            long,
+            short,
+            help = "Message log for the replayed sim2h to write",
+            default_value = "replayed.log"
+        )]
+        output: PathBuf,
+
+        #[structopt(
+            long,
+            help = "How much faster than logged to send the messages; use 0 for as fast as possible",
+            default_value = "1.0"
+        )]
+        speed: f64,
+
+        #[structopt(
+            long,
+            help = "How long to wait for sim2h after the last message",
+            default_value = "2000"
+        )]
+        settle_ms: u64,
+
+        #[structopt(
+            long,
+            short,
+            help = "Sharding redundancy count; use 0 for fullsync",
+            default_value = "50"
+        )]
+        sharding: u64,
+    },
+}
+
+fn timeline(log: PathBuf, agent: Option<String>) -> Sim2hResult<()> {
+    let entries = read_message_log(log)?;
+    for (logged_agent, timeline) in agent_timelines(&entries) {
+        if agent.as_ref().map_or(false, |agent| *agent != logged_agent) {
+            continue;
+        }
+        println!("{} ({} messages)", logged_agent, timeline.len());
+        let first_time = timeline[0].time;
+        for entry in timeline {
+            println!(
+                "  +{}ms {:?} {} {}",
+                (entry.time - first_time).num_milliseconds(),
+                entry.direction,
+                entry.message.message_type(),
+                entry.uri
+            );
+        }
+    }
+    Ok(())
+}
+
+fn latency(log: PathBuf, verbose: bool) -> Sim2hResult<()> {
+    let entries = read_message_log(log)?;
+    let report = PropagationReport::from_entries(&entries);
+    let summary = report.summary();
+    println!("aspects published: {}", report.aspects.len());
+    println!("aspects stored by other agents: {}", summary.count);
+    println!(
+        "latency ms: mean {:.1}, p50 {}, p95 {}, max {}",
+        summary.mean, summary.p50, summary.p95, summary.max
+    );
+    if verbose {
+        for aspect in &report.aspects {
+            println!("{} published by {}", aspect.aspect_address, aspect.publisher);
+            for (agent, ms) in &aspect.stored_after_ms {
+                println!("  {}ms {}", ms, agent);
+            }
+        }
+    }
+    for aspect in report.unpropagated() {
+        println!(
+            "never stored by another agent: {} published by {}",
+            aspect.aspect_address, aspect.publisher
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    env_logger::init();
+    let result = match Cli::from_args() {
+        Cli::Timeline { log, agent } => timeline(log, agent),
+        Cli::Latency { log, verbose } => latency(log, verbose),
+        Cli::Replay {
+            log,
+            output,
+            speed,
+            settle_ms,
+            sharding,
+        } => read_message_log(log).and_then(|entries| {
+            let dht_algorithm = if sharding == 0 {
+                DhtAlgorithm::FullSync
+            } else {
+                DhtAlgorithm::NaiveSharding {
+                    redundant_count: sharding,
+                }
+            };
+            let stats = replay(
+                &entries,
+                ReplayOptions {
+                    speed,
+                    settle_ms,
+                    output: output.clone(),
+                    dht_algorithm,
+                },
+            )?;
+            println!(
+                "replayed {} messages from {} agents over {} connections, logged to {}",
+                stats.messages,
+                stats.agents,
+                stats.connections,
+                output.display()
+            );
+            Ok(())
+        }),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,374 @@
+//! replays the incoming messages of a message log against a fresh, in-process sim2h
+//!
+//! the original agents' keys are not in the log, so every agent is replaced by one
+//! whose keys are derived from its logged id, and its id is rewritten in every message.
+//! Messages are sent from one thread in the order they were logged, each logged
+//! connection getting a connection of its own, so the same log always produces the
+//! same traffic from the same agents.
+
+use in_stream::*;
+use lib3h_crypto_api::{Buffer, CryptoSystem};
+use lib3h_protocol::{
+    data_types::Opaque,
+    protocol::{ClientToLib3h, Lib3hToClientResponse},
+    types::AgentPubKey,
+    uri::Lib3hUri,
+};
+use lib3h_sodium::SodiumCryptoSystem;
+use log::*;
+use sim2h::{
+    crypto::{Provenance, SignedWireMessage},
+    error::Sim2hResult,
+    message_log::{Direction, MessageLogEntry},
+    run_sim2h_with_options, DhtAlgorithm, Sim2hOptions, Sim2hWss, WireMessage, MESSAGE_LOGGER,
+};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const CONNECT_TIMEOUT_MS: u64 = 5000;
+
+/// mixed into the seeds of the stand-in agents, so their keys differ from
+/// whatever else might be derived from the logged ids
+const SEED_CONTEXT: &str = "sim2h-replay agent seed:";
+
+pub struct ReplayOptions {
+    /// how much faster than logged to send the messages, as fast as possible if 0
+    pub speed: f64,
+    /// how long to keep the connections open after the last message
+    pub settle_ms: u64,
+    /// where the replayed sim2h logs its messages
+    pub output: PathBuf,
+    pub dht_algorithm: DhtAlgorithm,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayStats {
+    pub agents: usize,
+    pub connections: usize,
+    pub messages: usize,
+}
+
+/// a sim2h running on its own tokio runtime, bound to a `mem://` url
+struct Sim2hInstance {
+    bound_uri: Lib3hUri,
+    running: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Sim2hInstance {
+    fn start(dht_algorithm: DhtAlgorithm) -> Self {
+        let url = in_stream_mem::random_url("sim2h-replay");
+        let (snd, rcv) = std::sync::mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let thread = std::thread::spawn(move || {
+            let (mut rt, binding) = run_sim2h_with_options(
+                Box::new(SodiumCryptoSystem::new()),
+                Lib3hUri(url.into()),
+                dht_algorithm,
+                Sim2hOptions::default(),
+            );
+            rt.block_on(async move {
+                snd.send(binding.await.unwrap().expect("could not start sim2h"))
+                    .unwrap();
+                while thread_running.load(Ordering::Relaxed) {
+                    tokio::time::delay_for(Duration::from_millis(1)).await;
+                }
+            });
+        });
+        Self {
+            bound_uri: rcv.recv().expect("sim2h should bind"),
+            running,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Sim2hInstance {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// an agent standing in for a logged one
+struct ReplayAgent {
+    agent_id: String,
+    sec_key: Box<dyn Buffer>,
+}
+
+impl ReplayAgent {
+    /// derives the keys from the logged agent id, so replaying a log
+    /// always uses the same stand-ins
+    fn for_logged_agent(crypto: &dyn CryptoSystem, logged_agent: &str) -> Sim2hResult<Self> {
+        let seed_data: Box<dyn Buffer> =
+            Box::new(format!("{}{}", SEED_CONTEXT, logged_agent).into_bytes());
+        let mut seed = crypto.buf_new_secure(crypto.sign_seed_bytes());
+        crypto
+            .hash_sha256(&mut seed, &seed_data)
+            .map_err(|e| format!("could not derive seed: {:?}", e))?;
+        let mut pub_key = crypto.buf_new_insecure(crypto.sign_public_key_bytes());
+        let mut sec_key = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+        crypto
+            .sign_seed_keypair(&seed, &mut pub_key, &mut sec_key)
+            .map_err(|e| format!("could not generate keys: {:?}", e))?;
+        let enc = hcid::HcidEncoding::with_kind("hcs0")
+            .map_err(|e| format!("could not encode agent id: {:?}", e))?;
+        let agent_id = enc
+            .encode(&*pub_key)
+            .map_err(|e| format!("could not encode agent id: {:?}", e))?;
+        Ok(Self { agent_id, sec_key })
+    }
+
+    fn sign(&self, crypto: &dyn CryptoSystem, message: WireMessage) -> Sim2hResult<Opaque> {
+        let payload: Opaque = message.into();
+        let payload_buf: Box<dyn Buffer> = Box::new(payload.clone().as_bytes());
+        let mut sig = crypto.buf_new_insecure(crypto.sign_bytes());
+        crypto
+            .sign(&mut sig, &payload_buf, &*self.sec_key)
+            .map_err(|e| format!("could not sign: {:?}", e))?;
+        let signature = base64::encode(&*sig.read_lock());
+        Ok(SignedWireMessage {
+            provenance: Provenance::new(self.agent_id.clone().into(), signature.into()),
+            payload,
+        }
+        .into())
+    }
+}
+
+fn rewrite_agent_id(agent_id: &mut AgentPubKey, ids: &BTreeMap<String, String>) {
+    if let Some(replayed) = ids.get(&agent_id.to_string()) {
+        *agent_id = replayed.as_str().into();
+    }
+}
+
+fn rewrite_response_agent_ids(
+    response: &mut Lib3hToClientResponse,
+    ids: &BTreeMap<String, String>,
+) {
+    match response {
+        Lib3hToClientResponse::HandleSendDirectMessageResult(data) => {
+            rewrite_agent_id(&mut data.to_agent_id, ids);
+            rewrite_agent_id(&mut data.from_agent_id, ids);
+        }
+        Lib3hToClientResponse::HandleFetchEntryResult(data) => {
+            rewrite_agent_id(&mut data.provider_agent_id, ids)
+        }
+        Lib3hToClientResponse::HandleQueryEntryResult(data) => {
+            rewrite_agent_id(&mut data.requester_agent_id, ids);
+            rewrite_agent_id(&mut data.responder_agent_id, ids);
+        }
+        Lib3hToClientResponse::HandleGetAuthoringEntryListResult(data)
+        | Lib3hToClientResponse::HandleGetGossipingEntryListResult(data) => {
+            rewrite_agent_id(&mut data.provider_agent_id, ids)
+        }
+        Lib3hToClientResponse::HandleDropEntryResult
+        | Lib3hToClientResponse::HandleStoreEntryAspectResult => (),
+    }
+}
+
+/// replaces the logged agent ids in the agent id fields of a message
+/// with the ids of their stand-ins
+fn rewrite_agent_ids(message: &WireMessage, ids: &BTreeMap<String, String>) -> WireMessage {
+    let mut message = message.clone();
+    match &mut message {
+        WireMessage::ClientToLib3h(span_wrap) => match &mut span_wrap.data {
+            ClientToLib3h::JoinSpace(data) | ClientToLib3h::LeaveSpace(data) => {
+                rewrite_agent_id(&mut data.agent_id, ids)
+            }
+            ClientToLib3h::SendDirectMessage(data) => {
+                rewrite_agent_id(&mut data.to_agent_id, ids);
+                rewrite_agent_id(&mut data.from_agent_id, ids);
+            }
+            ClientToLib3h::FetchEntry(data) => rewrite_agent_id(&mut data.provider_agent_id, ids),
+            ClientToLib3h::PublishEntry(data) => rewrite_agent_id(&mut data.provider_agent_id, ids),
+            ClientToLib3h::QueryEntry(data) => rewrite_agent_id(&mut data.requester_agent_id, ids),
+            ClientToLib3h::Bootstrap(_) => (),
+        },
+        WireMessage::Lib3hToClientResponse(span_wrap) => {
+            rewrite_response_agent_ids(&mut span_wrap.data, ids)
+        }
+        WireMessage::MultiSendResponse(span_wraps) => {
+            for span_wrap in span_wraps.iter_mut() {
+                rewrite_response_agent_ids(&mut span_wrap.data, ids);
+            }
+        }
+        WireMessage::DirectMessageQueued(data) => {
+            rewrite_agent_id(&mut data.to_agent_id, ids);
+            rewrite_agent_id(&mut data.from_agent_id, ids);
+        }
+        _ => (),
+    }
+    message
+}
+
+fn connect(bound_uri: &Lib3hUri) -> Sim2hResult<Sim2hWss> {
+    let mut wss = Sim2hWss::connect(bound_uri.clone(), CONNECT_TIMEOUT_MS)?;
+    let started = Instant::now();
+    while !wss.check_ready()? {
+        if started.elapsed() > Duration::from_millis(CONNECT_TIMEOUT_MS) {
+            return Err(format!("could not connect to {}", bound_uri).into());
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    Ok(wss)
+}
+
+/// reads and drops whatever sim2h sent, it is in the output log,
+/// and forgets the connections sim2h closed
+fn drain(connections: &mut HashMap<String, Sim2hWss>) {
+    let mut frame = WsFrame::default();
+    connections.retain(|uri, wss| loop {
+        match wss.read(&mut frame) {
+            Ok(_) => (),
+            Err(e) if e.would_block() => return true,
+            Err(e) => {
+                debug!("replayed connection {} closed: {:?}", uri, e);
+                return false;
+            }
+        }
+    });
+}
+
+/// sends the incoming messages of `entries` to a new sim2h, which logs
+/// everything to `options.output`
+pub fn replay(entries: &[MessageLogEntry], options: ReplayOptions) -> Sim2hResult<ReplayStats> {
+    let crypto = SodiumCryptoSystem::new();
+    let mut agents: HashMap<String, ReplayAgent> = HashMap::new();
+    for entry in entries {
+        if let Entry::Vacant(vacant) = agents.entry(entry.agent.to_string()) {
+            let agent = ReplayAgent::for_logged_agent(&crypto, vacant.key())?;
+            vacant.insert(agent);
+        }
+    }
+    let ids: BTreeMap<String, String> = agents
+        .iter()
+        .map(|(logged, agent)| (logged.clone(), agent.agent_id.clone()))
+        .collect();
+
+    let sim2h = Sim2hInstance::start(options.dht_algorithm);
+    {
+        let mut logger = MESSAGE_LOGGER.lock();
+        logger.set_logfile(options.output);
+        logger.start();
+    }
+
+    let mut stats = ReplayStats {
+        agents: agents.len(),
+        ..Default::default()
+    };
+    let mut connections: HashMap<String, Sim2hWss> = HashMap::new();
+    let incoming: Vec<&MessageLogEntry> = entries
+        .iter()
+        .filter(|entry| entry.direction == Direction::In)
+        .collect();
+    let first_time = match incoming.first() {
+        Some(entry) => entry.time,
+        None => return Ok(stats),
+    };
+    let started = Instant::now();
+    for entry in incoming {
+        if options.speed > 0.0 {
+            let offset_ms = (entry.time - first_time).num_milliseconds().max(0) as f64;
+            let due = started + Duration::from_millis((offset_ms / options.speed) as u64);
+            while Instant::now() < due {
+                drain(&mut connections);
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        if !connections.contains_key(&entry.uri) {
+            connections.insert(entry.uri.clone(), connect(&sim2h.bound_uri)?);
+            stats.connections += 1;
+        }
+        let agent = &agents[&entry.agent.to_string()];
+        let message = rewrite_agent_ids(&entry.message, &ids);
+        let signed = agent.sign(&crypto, message)?;
+        let wss = connections.get_mut(&entry.uri).expect("connected above");
+        wss.write(signed.as_bytes().into())?;
+        stats.messages += 1;
+        drain(&mut connections);
+    }
+
+    let settled = Instant::now() + Duration::from_millis(options.settle_ms);
+    while Instant::now() < settled {
+        drain(&mut connections);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    MESSAGE_LOGGER.lock().stop();
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_tracing as ht;
+    use lib3h_protocol::data_types::{DirectMessageData, SpaceData};
+
+    fn ids() -> BTreeMap<String, String> {
+        vec![
+            ("HcSAlice".to_string(), "HcSNewAlice".to_string()),
+            ("HcSBob".to_string(), "HcSNewBob".to_string()),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn rewrites_agent_id_fields_of_a_message() {
+        let dm = |from: &str, to: &str, content: &str| {
+            WireMessage::ClientToLib3h(ht::test_wrap_enc(ClientToLib3h::SendDirectMessage(
+                DirectMessageData {
+                    space_address: "space".to_string().into(),
+                    request_id: "request".to_string(),
+                    to_agent_id: to.into(),
+                    from_agent_id: from.into(),
+                    content: content.as_bytes().to_vec().into(),
+                },
+            )))
+        };
+        // only the agent id fields get rewritten, not the content mentioning them
+        assert_eq!(
+            dm("HcSNewAlice", "HcSNewBob", "hi HcSBob"),
+            rewrite_agent_ids(&dm("HcSAlice", "HcSBob", "hi HcSBob"), &ids())
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_agent_ids() {
+        let join = |agent: &str| {
+            WireMessage::ClientToLib3h(ht::test_wrap_enc(ClientToLib3h::JoinSpace(SpaceData {
+                agent_id: agent.into(),
+                request_id: "request".to_string(),
+                space_address: "space".to_string().into(),
+            })))
+        };
+        assert_eq!(
+            join("HcSCarol"),
+            rewrite_agent_ids(&join("HcSCarol"), &ids())
+        );
+    }
+
+    #[test]
+    fn derives_the_same_agent_from_the_same_logged_id() {
+        let crypto = SodiumCryptoSystem::new();
+        let alice = ReplayAgent::for_logged_agent(&crypto, "HcSAlice").unwrap();
+        let bob = ReplayAgent::for_logged_agent(&crypto, "HcSBob").unwrap();
+        assert_eq!(
+            alice.agent_id,
+            ReplayAgent::for_logged_agent(&crypto, "HcSAlice")
+                .unwrap()
+                .agent_id
+        );
+        assert_ne!(alice.agent_id, bob.agent_id);
+    }
+}
@@ -1,10 +1,16 @@
 use holochain_common::new_relic_setup;
+use holochain_metrics::config::{MetricPublisherConfig, PrometheusConfig};
 use holochain_tracing as ht;
 use lib3h_protocol::uri::Builder;
 use lib3h_sodium::SodiumCryptoSystem;
 #[cfg(feature = "newrelic_on")]
 use newrelic::{LogLevel, LogOutput, NewRelicConfig};
-use sim2h::{run_sim2h, DhtAlgorithm, MESSAGE_LOGGER};
+use sim2h::{
+    dm_queue::DmQueueConfig,
+    federation::FederationConfig,
+    rate_limit::{QuotaConfig, RateLimitConfig, TokenBucketConfig},
+    run_sim2h_with_options, DhtAlgorithm, Sim2hOptions, MESSAGE_LOGGER,
+};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -52,6 +58,130 @@ struct Cli {
         default_value = "Log"
     )]
     structured: ht::structured::Output,
+
+    #[structopt(
+        long,
+        help = "Serve Prometheus metrics at http://<address>/metrics, e.g. 0.0.0.0:9187. Metrics are logged if not specified."
+    )]
+    prometheus_bind_address: Option<String>,
+
+    #[structopt(long, help = "Maximum messages per second from each joined agent")]
+    agent_message_rate: Option<f64>,
+
+    #[structopt(long, help = "Maximum bytes per second from each joined agent")]
+    agent_byte_rate: Option<f64>,
+
+    #[structopt(long, help = "Maximum messages per second from all agents of a space")]
+    space_message_rate: Option<f64>,
+
+    #[structopt(long, help = "Maximum bytes per second from all agents of a space")]
+    space_byte_rate: Option<f64>,
+
+    #[structopt(
+        long,
+        help = "How many seconds worth of the above rates can be sent in a burst",
+        default_value = "10"
+    )]
+    rate_limit_burst_secs: f64,
+
+    #[structopt(
+        long,
+        help = "Disconnect agents after this many messages in a row over their own limit"
+    )]
+    disconnect_after_violations: Option<u32>,
+
+    #[structopt(
+        long,
+        help = "Toml file restricting which spaces and agents can join, reloaded on SIGHUP"
+    )]
+    admission_policy_file: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        help = "Queue direct messages to agents that are not connected for this many seconds, delivering them when they join again. Such messages are dropped if not specified."
+    )]
+    dm_queue_ttl_secs: Option<u64>,
+
+    #[structopt(
+        long,
+        help = "Maximum bytes of direct messages queued for a single agent",
+        default_value = "1048576"
+    )]
+    dm_queue_max_bytes: usize,
+
+    #[structopt(
+        long,
+        help = "Maximum bytes of direct messages queued for all agents together",
+        default_value = "104857600"
+    )]
+    dm_queue_max_total_bytes: usize,
+
+    #[structopt(
+        long,
+        use_delimiter = true,
+        requires = "federation-node-index",
+        help = "Comma separated urls of all the sim2h nodes spaces are partitioned between, in the same order on every node. Agents joining a space served by another node are redirected to it."
+    )]
+    federation_nodes: Vec<String>,
+
+    #[structopt(long, help = "The index of this node in --federation-nodes")]
+    federation_node_index: Option<usize>,
+
+    #[structopt(
+        long,
+        help = "Serve the unauthenticated JSON admin API at http://<address>/, e.g. 127.0.0.1:9100. No admin API is served if not specified."
+    )]
+    admin_bind_address: Option<String>,
+}
+
+impl Cli {
+    fn rate_limit(&self) -> RateLimitConfig {
+        let bucket = |rate: Option<f64>| {
+            rate.map(|rate| TokenBucketConfig {
+                rate,
+                burst: rate * self.rate_limit_burst_secs,
+            })
+        };
+        RateLimitConfig {
+            per_agent: QuotaConfig {
+                messages: bucket(self.agent_message_rate),
+                bytes: bucket(self.agent_byte_rate),
+            },
+            per_space: QuotaConfig {
+                messages: bucket(self.space_message_rate),
+                bytes: bucket(self.space_byte_rate),
+            },
+            disconnect_after_violations: self.disconnect_after_violations,
+        }
+    }
+
+    fn dm_queue(&self) -> Option<DmQueueConfig> {
+        self.dm_queue_ttl_secs.map(|ttl_secs| DmQueueConfig {
+            ttl_secs,
+            max_bytes_per_agent: self.dm_queue_max_bytes,
+            max_total_bytes: self.dm_queue_max_total_bytes,
+        })
+    }
+
+    fn federation(&self) -> Option<FederationConfig> {
+        if self.federation_nodes.is_empty() {
+            return None;
+        }
+        Some(FederationConfig {
+            nodes: self
+                .federation_nodes
+                .iter()
+                .map(|node| {
+                    Builder::with_raw_url(node.as_str())
+                        .unwrap_or_else(|e| panic!("invalid federation node {}: {:?}", node, e))
+                        .build()
+                })
+                .collect(),
+            this_node: self
+                .federation_node_index
+                .expect("--federation-node-index is required with --federation-nodes"),
+        })
+    }
 }
 
 new_relic_setup!("NEW_RELIC_LICENSE_KEY");
@@ -72,13 +202,38 @@ fn main() {
         MESSAGE_LOGGER.lock().start();
     }
 
-    let (mut rt, _) = run_sim2h(
+    let mut options = Sim2hOptions {
+        rate_limit: args.rate_limit(),
+        admission_policy_file: args.admission_policy_file.clone(),
+        dm_queue: args.dm_queue(),
+        federation: args.federation(),
+        admin_bind_address: args.admin_bind_address.clone(),
+        ..Default::default()
+    };
+    if let Some(bind_address) = args.prometheus_bind_address {
+        options.metric_publisher =
+            MetricPublisherConfig::Prometheus(PrometheusConfig { bind_address });
+    }
+
+    let (mut rt, binding) = run_sim2h_with_options(
         Box::new(SodiumCryptoSystem::new()),
         uri,
         DhtAlgorithm::NaiveSharding {
             redundant_count: args.sharding,
         },
+        options,
     );
+    match rt.block_on(binding) {
+        Ok(Ok(_)) => (),
+        Ok(Err(e)) => {
+            eprintln!("could not start sim2h: {}", e);
+            std::process::exit(1);
+        }
+        Err(_) => {
+            eprintln!("sim2h stopped before binding its listening socket");
+            std::process::exit(1);
+        }
+    }
 
     // just park the main thread indefinitely...
     rt.block_on(futures::future::pending::<()>());
@@ -0,0 +1,311 @@
+//! an agent in a simulation: a real `Sim2hWorker`, with a minimal stand-in for
+//! core behind it that holds the aspects it is sent and answers sim2h's requests
+
+use holochain_conductor_lib_api::ConductorApi;
+use holochain_locksmith::{Mutex, RwLock};
+use holochain_net::{
+    connection::net_connection::{NetHandler, NetWorker},
+    sim2h_worker::{Sim2hConfig, Sim2hWorker},
+};
+use holochain_tracing as ht;
+use jsonrpc_core::IoHandler;
+use lib3h_crypto_api::{Buffer, CryptoSystem};
+use lib3h_protocol::{
+    data_types::*,
+    protocol_client::Lib3hClientProtocol,
+    protocol_server::Lib3hServerProtocol,
+    types::{AgentPubKey, AspectHash, EntryHash, SpaceHash},
+    uri::Lib3hUri,
+};
+use lib3h_sodium::SodiumCryptoSystem;
+use log::*;
+use sim2h::error::Sim2hResult;
+use std::{collections::HashMap, sync::Arc};
+
+/// aspects by entry and aspect address
+type AspectStore = HashMap<EntryHash, HashMap<AspectHash, EntryAspectData>>;
+
+/// an agent id derived from the simulation's seed and the agent's index,
+/// and a conductor api that signs for it
+fn generate_agent(seed: u64, index: usize) -> Sim2hResult<(String, ConductorApi)> {
+    let crypto = Box::new(SodiumCryptoSystem::new());
+
+    let seed_data: Box<dyn Buffer> =
+        Box::new(format!("sim2h-sim agent seed:{}:{}", seed, index).into_bytes());
+    let mut key_seed = crypto.buf_new_secure(crypto.sign_seed_bytes());
+    crypto
+        .hash_sha256(&mut key_seed, &seed_data)
+        .map_err(|e| format!("could not derive seed: {:?}", e))?;
+    let mut pub_key = crypto.buf_new_insecure(crypto.sign_public_key_bytes());
+    let mut sec_key = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+    crypto
+        .sign_seed_keypair(&key_seed, &mut pub_key, &mut sec_key)
+        .map_err(|e| format!("could not generate keys: {:?}", e))?;
+
+    let enc = hcid::HcidEncoding::with_kind("hcs0")
+        .map_err(|e| format!("could not encode agent id: {:?}", e))?;
+    let agent_id = enc
+        .encode(&*pub_key)
+        .map_err(|e| format!("could not encode agent id: {:?}", e))?;
+
+    let io = Arc::new(RwLock::new(IoHandler::new()));
+    let sec_key = Arc::new(Mutex::new(sec_key));
+    io.write().unwrap().add_method(
+        "agent/sign",
+        move |params: jsonrpc_core::types::params::Params| {
+            let params = match params {
+                jsonrpc_core::types::params::Params::Map(m) => m,
+                _ => return Err(jsonrpc_core::Error::invalid_params("expected a map")),
+            };
+            let payload = params
+                .get("payload")
+                .and_then(|payload| payload.as_str())
+                .and_then(|payload| base64::decode(payload).ok())
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("expected a payload"))?;
+            let mut payload_buf = crypto.buf_new_insecure(payload.len());
+            payload_buf
+                .write(0, &payload)
+                .map_err(|_| jsonrpc_core::Error::internal_error())?;
+
+            let mut sig = crypto.buf_new_insecure(crypto.sign_bytes());
+            crypto
+                .sign(&mut sig, &payload_buf, &*sec_key.lock().unwrap())
+                .map_err(|_| jsonrpc_core::Error::internal_error())?;
+            let signature = base64::encode(&*sig.read_lock());
+            Ok(serde_json::json!({ "signature": signature }))
+        },
+    );
+
+    Ok((agent_id, ConductorApi::new(io)))
+}
+
+/// one simulated holochain node, talking to sim2h through a `Sim2hWorker`
+pub struct SimAgent {
+    agent_id: AgentPubKey,
+    space_address: SpaceHash,
+    worker: Sim2hWorker,
+    from_worker: crossbeam_channel::Receiver<Lib3hServerProtocol>,
+    authored: AspectStore,
+    held: AspectStore,
+    online: bool,
+}
+
+impl SimAgent {
+    /// the agent number `index` of a simulation seeded with `seed`, with a worker
+    /// connected to `sim2h_uri`, joining `space_address`; must be called on the
+    /// simulation's runtime so the worker reads its clock
+    pub(crate) fn new(
+        sim2h_uri: &Lib3hUri,
+        space_address: SpaceHash,
+        seed: u64,
+        index: usize,
+    ) -> Sim2hResult<Self> {
+        let (agent_id, conductor_api) = generate_agent(seed, index)?;
+        let (to_agent, from_worker) = crossbeam_channel::unbounded();
+        let mut worker = Sim2hWorker::new(
+            NetHandler::new(Box::new(move |message| {
+                // only fails when the agent is gone, and then nobody is listening
+                let _ = to_agent.send(message?.data);
+                Ok(())
+            })),
+            Sim2hConfig {
+                sim2h_url: sim2h_uri.as_str().to_string(),
+            },
+            agent_id.clone().into(),
+            conductor_api,
+            None,
+        )
+        .map_err(|e| format!("could not create sim2h worker: {}", e))?;
+        let agent_id: AgentPubKey = agent_id.into();
+        worker
+            .receive(ht::test_wrap_enc(Lib3hClientProtocol::JoinSpace(
+                SpaceData {
+                    agent_id: agent_id.clone(),
+                    request_id: "".to_string(),
+                    space_address: space_address.clone(),
+                },
+            )))
+            .map_err(|e| format!("could not join space: {}", e))?;
+        Ok(Self {
+            agent_id,
+            space_address,
+            worker,
+            from_worker,
+            authored: AspectStore::new(),
+            held: AspectStore::new(),
+            online: true,
+        })
+    }
+
+    pub fn agent_id(&self) -> &AgentPubKey {
+        &self.agent_id
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+
+    /// true if this agent was sent and stored the aspect
+    pub fn holds(&self, entry_address: &EntryHash, aspect_address: &AspectHash) -> bool {
+        self.held
+            .get(entry_address)
+            .map_or(false, |aspects| aspects.contains_key(aspect_address))
+    }
+
+    /// the entry and aspect addresses of everything this agent published
+    pub fn authored(&self) -> impl Iterator<Item = (&EntryHash, &AspectHash)> {
+        self.authored.iter().flat_map(|(entry_address, aspects)| {
+            aspects
+                .keys()
+                .map(move |aspect_address| (entry_address, aspect_address))
+        })
+    }
+
+    /// how many aspects this agent holds
+    pub fn held_count(&self) -> usize {
+        self.held.values().map(|aspects| aspects.len()).sum()
+    }
+
+    pub(crate) fn publish(
+        &mut self,
+        entry_address: EntryHash,
+        aspect_list: Vec<EntryAspectData>,
+    ) -> Sim2hResult<()> {
+        let authored = self
+            .authored
+            .entry(entry_address.clone())
+            .or_insert_with(HashMap::new);
+        for aspect in &aspect_list {
+            authored.insert(aspect.aspect_address.clone(), aspect.clone());
+        }
+        self.send(Lib3hClientProtocol::PublishEntry(ProvidedEntryData {
+            space_address: self.space_address.clone(),
+            provider_agent_id: self.agent_id.clone(),
+            entry: EntryData {
+                entry_address,
+                aspect_list,
+            },
+        }))
+    }
+
+    /// an offline agent's connection is closed, and its worker is not ticked
+    /// until it comes back online, when it reconnects and joins again
+    pub(crate) fn set_online(&mut self, online: bool) {
+        if self.online && !online {
+            self.worker.test_close_connection_cause_reconnect();
+        }
+        self.online = online;
+    }
+
+    /// ticks the worker and answers what it passed on from sim2h,
+    /// returns true if anything happened
+    pub(crate) fn step(&mut self) -> Sim2hResult<bool> {
+        if !self.online {
+            return Ok(false);
+        }
+        let mut did_work = self
+            .worker
+            .tick()
+            .map_err(|e| format!("sim2h worker failed: {}", e))?;
+        while let Ok(message) = self.from_worker.try_recv() {
+            did_work = true;
+            self.handle(message)?;
+        }
+        Ok(did_work)
+    }
+
+    fn send(&mut self, message: Lib3hClientProtocol) -> Sim2hResult<()> {
+        self.worker
+            .receive(ht::test_wrap_enc(message))
+            .map_err(|e| format!("sim2h worker failed: {}", e).into())
+    }
+
+    fn address_map(store: &AspectStore) -> HashMap<EntryHash, Vec<AspectHash>> {
+        store
+            .iter()
+            .map(|(entry_address, aspects)| {
+                (entry_address.clone(), aspects.keys().cloned().collect())
+            })
+            .collect()
+    }
+
+    /// what core would do with the messages the worker hands it
+    fn handle(&mut self, message: Lib3hServerProtocol) -> Sim2hResult<()> {
+        match message {
+            Lib3hServerProtocol::HandleStoreEntryAspect(data) => {
+                self.held
+                    .entry(data.entry_address.clone())
+                    .or_insert_with(HashMap::new)
+                    .insert(
+                        data.entry_aspect.aspect_address.clone(),
+                        data.entry_aspect.clone(),
+                    );
+                // tell sim2h we hold it now, as core does once it has validated an aspect
+                let mut address_map = HashMap::new();
+                address_map.insert(data.entry_address, vec![data.entry_aspect.aspect_address]);
+                self.send(Lib3hClientProtocol::HandleGetGossipingEntryListResult(
+                    EntryListData {
+                        space_address: self.space_address.clone(),
+                        provider_agent_id: self.agent_id.clone(),
+                        request_id: "".to_string(),
+                        address_map,
+                    },
+                ))
+            }
+            Lib3hServerProtocol::HandleFetchEntry(data) => {
+                let mut aspects: HashMap<&AspectHash, &EntryAspectData> = HashMap::new();
+                for store in &[&self.held, &self.authored] {
+                    if let Some(entry_aspects) = store.get(&data.entry_address) {
+                        aspects.extend(entry_aspects.iter());
+                    }
+                }
+                let aspect_list = aspects
+                    .into_iter()
+                    .filter(|(aspect_address, _)| {
+                        data.aspect_address_list
+                            .as_ref()
+                            .map_or(true, |wanted| wanted.contains(*aspect_address))
+                    })
+                    .map(|(_, aspect)| aspect.clone())
+                    .collect();
+                self.send(Lib3hClientProtocol::HandleFetchEntryResult(
+                    FetchEntryResultData {
+                        space_address: data.space_address,
+                        provider_agent_id: self.agent_id.clone(),
+                        request_id: data.request_id,
+                        entry: EntryData {
+                            entry_address: data.entry_address,
+                            aspect_list,
+                        },
+                    },
+                ))
+            }
+            Lib3hServerProtocol::HandleGetAuthoringEntryList(data) => {
+                let address_map = Self::address_map(&self.authored);
+                self.send(Lib3hClientProtocol::HandleGetAuthoringEntryListResult(
+                    EntryListData {
+                        space_address: data.space_address,
+                        provider_agent_id: self.agent_id.clone(),
+                        request_id: data.request_id,
+                        address_map,
+                    },
+                ))
+            }
+            Lib3hServerProtocol::HandleGetGossipingEntryList(data) => {
+                let address_map = Self::address_map(&self.held);
+                self.send(Lib3hClientProtocol::HandleGetGossipingEntryListResult(
+                    EntryListData {
+                        space_address: data.space_address,
+                        provider_agent_id: self.agent_id.clone(),
+                        request_id: data.request_id,
+                        address_map,
+                    },
+                ))
+            }
+            message => {
+                trace!("simulated agent {} ignores {:?}", self.agent_id, message);
+                Ok(())
+            }
+        }
+    }
+}
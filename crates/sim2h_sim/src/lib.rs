@@ -0,0 +1,353 @@
+//! simulations of many agents gossiping through a sim2h, on a virtual clock
+//!
+//! a `Simulation` runs a real `Sim2h` and a real `Sim2hWorker` per agent, connected
+//! over the in-memory `mem://` transport, all on one single threaded tokio runtime
+//! whose clock is paused. Time only passes when the simulation steps, so sim2h's
+//! gossip intervals, the workers' resend and reconnect timers and the expiry of
+//! queued messages play out in virtual time, and an hour of gossip takes as long
+//! as it takes to compute. Behind each worker is a minimal stand-in for core, see
+//! `SimAgent`.
+//!
+//! ```no_run
+//! use lib3h_protocol::data_types::EntryAspectData;
+//! use sim2h_sim::{SimConfig, Simulation};
+//! use std::time::Duration;
+//!
+//! let mut sim = Simulation::new(SimConfig {
+//!     agents: 100,
+//!     ..Default::default()
+//! })
+//! .unwrap();
+//! let aspect = EntryAspectData {
+//!     aspect_address: "aspect".to_string().into(),
+//!     type_hint: "".to_string(),
+//!     aspect: b"content".to_vec().into(),
+//!     publish_ts: 0,
+//! };
+//! sim.publish(0, "entry".to_string().into(), vec![aspect]).unwrap();
+//! let took = sim.run_until_converged(Duration::from_secs(3600)).unwrap();
+//! println!("converged after {:?} of virtual time", took);
+//! ```
+//!
+//! pausing tokio's clock needs tokio's `test-util` feature, which this crate only
+//! turns on with its `virtual-clock` feature. Without it the crate is empty, so
+//! building it with the rest of the workspace leaves tokio as everyone else uses it.
+
+#![cfg(feature = "virtual-clock")]
+
+mod agent;
+
+pub use agent::SimAgent;
+use lib3h_protocol::{
+    data_types::EntryAspectData,
+    types::{AspectHash, EntryHash, SpaceHash},
+    uri::Lib3hUri,
+};
+use lib3h_sodium::SodiumCryptoSystem;
+use log::*;
+use sim2h::{
+    clock::Instant,
+    error::{Sim2hError, Sim2hResult},
+    DhtAlgorithm, Sim2h, Sim2hOptions,
+};
+use std::{collections::HashSet, time::Duration};
+
+/// how many times a step lets sim2h and the agents answer each other
+/// before moving the clock on
+const MAX_ROUNDS_PER_STEP: usize = 100;
+
+/// what to simulate
+pub struct SimConfig {
+    pub agents: usize,
+    /// the space every agent joins
+    pub space_address: String,
+    pub dht_algorithm: DhtAlgorithm,
+    pub sim2h_options: Sim2hOptions,
+    /// how far the clock moves each step; latencies are only as precise as this,
+    /// and a longer step gets through virtual time faster
+    pub step: Duration,
+    /// the agents' keys are derived from this, so the same seed gives the same agents
+    pub seed: u64,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            agents: 10,
+            space_address: "sim2h-sim-space".to_string(),
+            dht_algorithm: DhtAlgorithm::FullSync,
+            sim2h_options: Sim2hOptions::default(),
+            step: Duration::from_millis(10),
+            seed: 0,
+        }
+    }
+}
+
+/// a sim2h and its agents on a paused tokio runtime
+pub struct Simulation {
+    runtime: tokio::runtime::Runtime,
+    sim2h: Sim2h,
+    agents: Vec<SimAgent>,
+    step: Duration,
+    /// how many agents should hold each aspect
+    redundancy: usize,
+    started: Instant,
+}
+
+impl Simulation {
+    /// starts sim2h on a fresh `mem://` url and connects `config.agents` agents to it
+    pub fn new(config: SimConfig) -> Sim2hResult<Self> {
+        let runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("could not build tokio runtime: {}", e))?;
+        runtime.enter(tokio::time::pause);
+
+        let SimConfig {
+            agents,
+            space_address,
+            dht_algorithm,
+            sim2h_options,
+            step,
+            seed,
+        } = config;
+        let redundancy = match dht_algorithm {
+            DhtAlgorithm::FullSync => agents,
+            DhtAlgorithm::NaiveSharding { redundant_count } => agents.min(redundant_count as usize),
+        };
+        let url = in_stream::in_stream_mem::random_url("sim2h-sim");
+        let sim2h = runtime.enter(|| {
+            Sim2h::new(
+                Box::new(SodiumCryptoSystem::new()),
+                Lib3hUri(url.into()),
+                dht_algorithm,
+                sim2h_options,
+            )
+        })?;
+        let bound_uri = sim2h.bound_uri.clone().ok_or("sim2h did not bind")?;
+        let space_address: SpaceHash = space_address.into();
+        let agents = runtime.enter(|| {
+            (0..agents)
+                .map(|index| SimAgent::new(&bound_uri, space_address.clone(), seed, index))
+                .collect::<Sim2hResult<Vec<SimAgent>>>()
+        })?;
+        let started = runtime.enter(Instant::now);
+        Ok(Self {
+            runtime,
+            sim2h,
+            agents,
+            step,
+            redundancy,
+            started,
+        })
+    }
+
+    /// how much virtual time has passed since the simulation started
+    pub fn elapsed(&self) -> Duration {
+        let started = self.started;
+        self.runtime.enter(|| started.elapsed())
+    }
+
+    pub fn agents(&self) -> &[SimAgent] {
+        &self.agents
+    }
+
+    /// `agent` publishes an entry made of `aspect_list`
+    pub fn publish(
+        &mut self,
+        agent: usize,
+        entry_address: EntryHash,
+        aspect_list: Vec<EntryAspectData>,
+    ) -> Sim2hResult<()> {
+        self.agents[agent].publish(entry_address, aspect_list)
+    }
+
+    /// takes `agent` off the network, or brings it back
+    pub fn set_online(&mut self, agent: usize, online: bool) {
+        let agent = &mut self.agents[agent];
+        self.runtime.enter(|| agent.set_online(online));
+    }
+
+    /// lets sim2h and the online agents exchange whatever is pending,
+    /// then moves the clock on by `config.step`. tokio may move it on further,
+    /// up to its next timer, whenever every task is waiting on one
+    pub fn step(&mut self) -> Sim2hResult<()> {
+        let Simulation {
+            runtime,
+            sim2h,
+            agents,
+            step,
+            ..
+        } = self;
+        let step = *step;
+        runtime.block_on(async move {
+            for _ in 0..MAX_ROUNDS_PER_STEP {
+                let mut did_work = match sim2h.process() {
+                    Ok(did_work) => did_work,
+                    Err(e) => {
+                        error!("simulated sim2h: {}", e);
+                        true
+                    }
+                };
+                for agent in agents.iter_mut() {
+                    did_work |= agent.step()?;
+                }
+                // let sim2h's own tasks run
+                tokio::task::yield_now().await;
+                if !did_work {
+                    break;
+                }
+            }
+            tokio::time::advance(step).await;
+            Ok::<(), Sim2hError>(())
+        })
+    }
+
+    /// steps until `duration` of virtual time has passed
+    pub fn run_for(&mut self, duration: Duration) -> Sim2hResult<()> {
+        let until = self.elapsed() + duration;
+        while self.elapsed() < until {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// every aspect published so far
+    fn published(&self) -> HashSet<(EntryHash, AspectHash)> {
+        self.agents
+            .iter()
+            .flat_map(|agent| agent.authored())
+            .map(|(entry_address, aspect_address)| (entry_address.clone(), aspect_address.clone()))
+            .collect()
+    }
+
+    /// how many of the published aspects are held by as many agents as should hold them,
+    /// and how many were published
+    pub fn coverage(&self) -> (usize, usize) {
+        let published = self.published();
+        let covered = published
+            .iter()
+            .filter(|(entry_address, aspect_address)| {
+                self.agents
+                    .iter()
+                    .filter(|agent| agent.holds(entry_address, aspect_address))
+                    .count()
+                    >= self.redundancy
+            })
+            .count();
+        (covered, published.len())
+    }
+
+    /// true once every published aspect is held by as many agents as should hold it:
+    /// all of them with fullsync, the redundant count with sharding
+    pub fn is_converged(&self) -> bool {
+        let (covered, published) = self.coverage();
+        covered == published
+    }
+
+    /// steps until the simulation converges, returning the virtual time that took,
+    /// or fails if it has not after `timeout` of virtual time
+    pub fn run_until_converged(&mut self, timeout: Duration) -> Sim2hResult<Duration> {
+        let started = self.elapsed();
+        let until = started + timeout;
+        loop {
+            if self.is_converged() {
+                return Ok(self.elapsed() - started);
+            }
+            if self.elapsed() >= until {
+                let (covered, published) = self.coverage();
+                return Err(format!(
+                    "not converged after {:?}: {} of {} aspects are held by {} agents",
+                    timeout, covered, published, self.redundancy
+                )
+                .into());
+            }
+            self.step()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib3h_protocol::types::AgentPubKey;
+
+    fn aspect(address: &str) -> EntryAspectData {
+        EntryAspectData {
+            aspect_address: address.to_string().into(),
+            type_hint: "".to_string(),
+            aspect: address.as_bytes().to_vec().into(),
+            publish_ts: 0,
+        }
+    }
+
+    fn publish_one_entry_each(sim: &mut Simulation, agents: std::ops::Range<usize>) {
+        for agent in agents {
+            sim.publish(
+                agent,
+                format!("entry-{}", agent).into(),
+                vec![aspect(&format!("aspect-{}", agent))],
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_agents() {
+        let agent_ids = |seed| {
+            let sim = Simulation::new(SimConfig {
+                agents: 2,
+                seed,
+                ..Default::default()
+            })
+            .unwrap();
+            sim.agents()
+                .iter()
+                .map(|agent| agent.agent_id().clone())
+                .collect::<Vec<AgentPubKey>>()
+        };
+        let agents = agent_ids(1);
+        assert_eq!(agents, agent_ids(1));
+        assert_ne!(agents[0], agents[1]);
+        assert_ne!(agents, agent_ids(2));
+    }
+
+    #[test]
+    fn agents_converge_in_virtual_time() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let mut sim = Simulation::new(SimConfig {
+            agents: 20,
+            ..Default::default()
+        })
+        .unwrap();
+        // let everyone connect and join first
+        sim.run_for(Duration::from_secs(5)).unwrap();
+        publish_one_entry_each(&mut sim, 0..20);
+        sim.run_until_converged(Duration::from_secs(600)).unwrap();
+        assert_eq!((20, 20), sim.coverage());
+        assert!(sim.agents().iter().all(|agent| agent.held_count() == 20));
+    }
+
+    #[test]
+    fn offline_agent_catches_up_after_an_hour() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let mut sim = Simulation::new(SimConfig {
+            agents: 5,
+            step: Duration::from_millis(100),
+            ..Default::default()
+        })
+        .unwrap();
+        sim.run_for(Duration::from_secs(5)).unwrap();
+        sim.set_online(4, false);
+        publish_one_entry_each(&mut sim, 0..4);
+        sim.run_for(Duration::from_secs(3600)).unwrap();
+        assert!(sim.elapsed() >= Duration::from_secs(3600));
+        assert!(!sim.is_converged());
+        assert_eq!(0, sim.agents()[4].held_count());
+
+        sim.set_online(4, true);
+        sim.run_until_converged(Duration::from_secs(600)).unwrap();
+        assert_eq!(4, sim.agents()[4].held_count());
+    }
+}
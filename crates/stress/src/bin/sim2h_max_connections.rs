@@ -205,7 +205,7 @@ pub fn main() {
         DhtAlgorithm::FullSync,
     );
     rt.block_on(async move {
-        let bound_uri = binding.await.unwrap();
+        let bound_uri = binding.await.unwrap().expect("could not start sim2h");
 
         std::thread::spawn(|| loop {
             warn!("1 second tick - hardware");
@@ -619,7 +619,8 @@ impl Suite {
             );
             rt.block_on(async move {
                 tokio::task::spawn(async move {
-                    snd1.send(binding.await.unwrap()).unwrap();
+                    snd1.send(binding.await.unwrap().expect("could not start sim2h"))
+                        .unwrap();
                 });
                 while *sim2h_cont_clone.lock().unwrap() {
                     tokio::time::delay_for(std::time::Duration::from_millis(1)).await;
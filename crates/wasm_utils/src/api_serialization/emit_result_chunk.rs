@@ -0,0 +1,7 @@
+use holochain_json_api::{error::JsonError, json::JsonString};
+
+/// Struct for input data received when invoke_emit_result_chunk is called
+#[derive(Deserialize, Clone, PartialEq, Eq, Hash, Debug, Serialize, DefaultJson)]
+pub struct EmitResultChunkArgs {
+    pub chunk: JsonString,
+}
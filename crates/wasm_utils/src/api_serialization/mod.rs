@@ -12,6 +12,7 @@ mod call;
 pub mod capabilities;
 pub mod commit_entry;
 pub mod crypto;
+pub mod emit_result_chunk;
 pub mod emit_signal;
 pub mod get_entry;
 pub mod get_links;
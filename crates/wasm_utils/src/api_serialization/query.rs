@@ -1,6 +1,8 @@
 use holochain_core_types::{
     chain_header::ChainHeader,
     entry::{entry_type::EntryType, Entry},
+    network::query::SortOrder,
+    time::Iso8601,
 };
 
 use holochain_json_api::{error::JsonError, json::*};
@@ -62,10 +64,52 @@ pub struct QueryArgs {
 pub struct QueryArgsOptions {
     pub start: usize,
     pub limit: usize,
-    // pub ordering: bool,  // forward or reverse (requires doubly-linked chain perhaps)
-    // pub filter_by: ???,
     pub headers: bool,
     pub entries: bool,
+    /// Newest-first (the natural walk of the chain) or oldest-first
+    #[serde(default)]
+    pub ordering: SortOrder,
+    /// Header predicates applied before `start`/`limit`
+    #[serde(default)]
+    pub filter_by: QueryFilter,
+    /// Cursor for pagination: only return results that come after the header with this address
+    #[serde(default)]
+    pub after: Option<Address>,
+}
+
+/// Predicates on ChainHeader fields. Every predicate that is set must match a ChainHeader for
+/// it to be included in the result
+#[derive(Deserialize, Default, Debug, Serialize, Clone, PartialEq)]
+pub struct QueryFilter {
+    /// Only headers with a timestamp at or after this time
+    pub since: Option<Iso8601>,
+    /// Only headers with a timestamp strictly before this time
+    pub until: Option<Iso8601>,
+    /// Only headers with a provenance from this agent address
+    pub provenance: Option<Address>,
+    /// Only headers that update or delete the header with this address
+    pub link_update_delete: Option<Address>,
+}
+
+impl QueryFilter {
+    pub fn matches(&self, header: &ChainHeader) -> bool {
+        self.since
+            .as_ref()
+            .map_or(true, |since| header.timestamp() >= since)
+            && self
+                .until
+                .as_ref()
+                .map_or(true, |until| header.timestamp() < until)
+            && self.provenance.as_ref().map_or(true, |agent| {
+                header
+                    .provenances()
+                    .iter()
+                    .any(|provenance| &provenance.source() == agent)
+            })
+            && self.link_update_delete.as_ref().map_or(true, |replaced| {
+                header.link_update_delete().as_ref() == Some(replaced)
+            })
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize, DefaultJson, Clone, PartialEq)]
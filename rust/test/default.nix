@@ -3,6 +3,8 @@ let
   hc-rust-test = pkgs.writeShellScriptBin "hc-rust-test"
   ''
   hc-rust-wasm-compile && HC_SIMPLE_LOGGER_MUTE=1 RUST_BACKTRACE=1 cargo test --all "$1" -- --test-threads=${holonix.rust.test.threads};
+  # sim2h_sim is empty without its virtual-clock feature, which --all leaves off
+  HC_SIMPLE_LOGGER_MUTE=1 RUST_BACKTRACE=1 cargo test -p sim2h_sim --features virtual-clock "$1" -- --test-threads=${holonix.rust.test.threads};
   '';
 
   hc-rust-coverage-kcov = pkgs.writeShellScriptBin "hc-rust-coverage-kcov"